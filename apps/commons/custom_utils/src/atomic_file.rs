@@ -0,0 +1,156 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Mode of new files before the umask, the one `fs::write` uses
+const DEFAULT_MODE: u32 = 0o666;
+
+/// # Atomic File
+///
+/// Contents written and synced to a file next to the target, which
+/// only replaces the target once `persist` is called. Readers see the
+/// old or the new file, and a crash at any point leaves one of them
+/// on disk. The temporary file is removed if it is never persisted
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    is_persisted: bool,
+}
+
+impl AtomicFile {
+    /// Writes the contents next to `path`, creating its directory
+    pub fn create(path: &Path, contents: &[u8], mode: u32) -> io::Result<Self> {
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name.to_string_lossy(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?} is not a file", path),
+                ))
+            }
+        };
+        // one name per process, the app and its services may write
+        // the same file
+        let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, process::id()));
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let atomic_file = Self {
+            path: path.to_path_buf(),
+            temp_path,
+            is_persisted: false,
+        };
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .open(&atomic_file.temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        Ok(atomic_file)
+    }
+
+    /// Moves the written file over the target
+    pub fn persist(mut self) -> io::Result<()> {
+        fs::rename(&self.temp_path, &self.path)?;
+        self.is_persisted = true;
+        // the rename itself is only durable once the directory is synced
+        if let Some(parent) = self
+            .path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.is_persisted {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Replaces the file at `path` with the contents in one step
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    AtomicFile::create(path, contents.as_ref(), DEFAULT_MODE)?.persist()
+}
+
+/// Same as `write_atomic` for files only the owner may read
+pub fn write_atomic_private(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    AtomicFile::create(path, contents.as_ref(), 0o600)?.persist()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("atomic_file_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn write_creates_the_directory_and_replaces_the_file() {
+        let dir = test_dir("replace");
+        let path = dir.join("nested").join("state.yml");
+
+        write_atomic(&path, "first").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+
+        // only the target is left behind
+        let entries = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(entries, 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unpersisted_file_leaves_the_target_untouched() {
+        let dir = test_dir("drop");
+        let path = dir.join("state.yml");
+        write_atomic(&path, "kept").unwrap();
+
+        let atomic_file = AtomicFile::create(&path, b"dropped", DEFAULT_MODE).unwrap();
+        assert!(atomic_file.temp_path.exists());
+        let temp_path = atomic_file.temp_path.clone();
+        drop(atomic_file);
+
+        assert!(!temp_path.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn private_file_is_only_readable_by_the_owner() {
+        let dir = test_dir("private");
+        let path = dir.join("wg0.conf");
+        write_atomic_private(&path, "[Interface]\n").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn directory_path_is_rejected() {
+        let error = write_atomic(Path::new("/"), "contents").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod atomic_file;
+pub mod local_date;
+pub mod proxy;
+
 use gtk::{gdk, gio};
 use relm4::gtk::{self, prelude::FileExt}; 
 use custom_widgets::gif_paintable::GifPaintable;
@@ -0,0 +1,89 @@
+use std::mem::MaybeUninit;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Date in the local time zone, days start at local midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl LocalDate {
+    pub fn today() -> Self {
+        Self::days_ago(0)
+    }
+
+    /// Steps back in whole days from now, close enough around
+    /// daylight saving changes for a date
+    pub fn days_ago(days: u32) -> Self {
+        let now = unsafe { libc::time(std::ptr::null_mut()) };
+        let time = now - days as libc::time_t * SECONDS_PER_DAY as libc::time_t;
+        let mut tm = MaybeUninit::<libc::tm>::zeroed();
+        // the tm struct is filled in by localtime_r, zeroed is a valid tm otherwise
+        let tm = unsafe {
+            libc::localtime_r(&time, tm.as_mut_ptr());
+            tm.assume_init()
+        };
+        Self {
+            year: tm.tm_year + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+        }
+    }
+
+    /// `YYYY-MM-DD`, sorts the same as the dates
+    pub fn day_key(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// `YYYY-MM`, sorts the same as the months
+    pub fn month_key(&self) -> String {
+        format!("{:04}-{:02}", self.year, self.month)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_zero_padded() {
+        let date = LocalDate {
+            year: 2024,
+            month: 3,
+            day: 7,
+        };
+        assert_eq!(date.day_key(), "2024-03-07");
+        assert_eq!(date.month_key(), "2024-03");
+    }
+
+    #[test]
+    fn keys_sort_like_the_dates() {
+        let dates = [(2023, 12, 31), (2024, 1, 1), (2024, 1, 10), (2024, 10, 2)];
+        let keys: Vec<String> = dates
+            .iter()
+            .map(|(year, month, day)| {
+                LocalDate {
+                    year: *year,
+                    month: *month,
+                    day: *day,
+                }
+                .day_key()
+            })
+            .collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn days_ago_steps_back_one_date_at_a_time() {
+        let today = LocalDate::today();
+        let yesterday = LocalDate::days_ago(1);
+        assert!(yesterday.day_key() < today.day_key());
+        assert!((1..=12).contains(&today.month));
+        assert!((1..=31).contains(&today.day));
+    }
+}
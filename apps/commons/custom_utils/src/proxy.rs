@@ -0,0 +1,368 @@
+use std::{env, fmt, fs, io, path::Path};
+
+use crate::atomic_file::write_atomic;
+
+/// File in the `environment.d` directory of the user, exported to the
+/// session by the systemd user manager at login
+const ENV_FILE_NAME: &str = "environment.d/90-proxy.conf";
+/// No client reads a variable for the PAC URL, it is only kept in the
+/// file to show it again, the network service applies it where it can
+const PAC_URL_COMMENT: &str = "# pac_url=";
+const GENERATED_HEADER: &str = "# Proxy settings, managed by the settings app";
+
+/// How connections find their proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProxyMode {
+    #[default]
+    None,
+    Manual,
+    /// Proxy picked by a PAC script
+    Automatic,
+}
+
+impl fmt::Display for ProxyMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProxyMode::None => write!(f, "None"),
+            ProxyMode::Manual => write!(f, "Manual"),
+            ProxyMode::Automatic => write!(f, "Automatic"),
+        }
+    }
+}
+
+/// # Proxy Config
+///
+/// Proxy used by the whole device, manual servers are `host:port`
+/// and `bypass` lists hosts or domains that are reached directly
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProxyConfig {
+    pub mode: ProxyMode,
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub socks: Option<String>,
+    pub pac_url: Option<String>,
+    pub bypass: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Checks the fields of the selected mode, returns a message to show the user otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        match self.mode {
+            ProxyMode::None => Ok(()),
+            ProxyMode::Manual => {
+                let servers = [
+                    ("HTTP", &self.http),
+                    ("HTTPS", &self.https),
+                    ("SOCKS", &self.socks),
+                ];
+                if servers.iter().all(|(_, server)| server.is_none()) {
+                    return Err("enter at least one proxy server".to_string());
+                }
+                for (name, server) in servers {
+                    if let Some(server) = server {
+                        validate_server(name, server)?;
+                    }
+                }
+                for host in self.bypass.iter() {
+                    if host.chars().any(|c| c.is_whitespace() || c == '/') {
+                        return Err(format!("'{}' is not a valid bypass host", host));
+                    }
+                }
+                Ok(())
+            }
+            ProxyMode::Automatic => match self.pac_url.as_deref() {
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => Ok(()),
+                Some(url) if url.starts_with("file://") => Ok(()),
+                _ => Err("enter the http(s) or file URL of the PAC script".to_string()),
+            },
+        }
+    }
+
+    /// Variables exported to programs, lower case names are the ones
+    /// most clients read, upper case ones are added for the rest
+    pub fn variables(&self) -> Vec<(String, String)> {
+        let mut variables = vec![];
+        match self.mode {
+            ProxyMode::None => (),
+            ProxyMode::Manual => {
+                let servers = [
+                    ("http_proxy", self.http.as_ref().map(|s| format!("http://{}", s))),
+                    ("https_proxy", self.https.as_ref().map(|s| format!("http://{}", s))),
+                    ("all_proxy", self.socks.as_ref().map(|s| format!("socks5://{}", s))),
+                ];
+                for (name, value) in servers {
+                    if let Some(value) = value {
+                        variables.push((name.to_string(), value.clone()));
+                        variables.push((name.to_uppercase(), value));
+                    }
+                }
+                if !self.bypass.is_empty() {
+                    let no_proxy = self.bypass.join(",");
+                    variables.push(("no_proxy".to_string(), no_proxy.clone()));
+                    variables.push(("NO_PROXY".to_string(), no_proxy));
+                }
+            }
+            // HTTP clients only take proxy servers from the environment
+            ProxyMode::Automatic => (),
+        }
+        variables
+    }
+
+    /// Contents of the environment file, `KEY=value` lines that can be
+    /// read by `environment.d`, `pam_env` or sourced by a shell
+    pub fn to_env_file(&self) -> String {
+        let mut contents = format!("{}\n", GENERATED_HEADER);
+        if let (ProxyMode::Automatic, Some(pac_url)) = (self.mode, &self.pac_url) {
+            contents.push_str(&format!("{}{}\n", PAC_URL_COMMENT, pac_url));
+        }
+        for (name, value) in self.variables() {
+            contents.push_str(&format!("{}={}\n", name, value));
+        }
+        contents
+    }
+
+    pub fn from_env_file(contents: &str) -> Self {
+        let lines = contents.lines().map(|line| line.trim());
+        let pac_url = lines
+            .clone()
+            .find_map(|line| line.strip_prefix(PAC_URL_COMMENT))
+            .map(|pac_url| pac_url.trim().to_string())
+            .filter(|pac_url| !pac_url.is_empty());
+        if let Some(pac_url) = pac_url {
+            return ProxyConfig {
+                mode: ProxyMode::Automatic,
+                pac_url: Some(pac_url),
+                ..Default::default()
+            };
+        }
+
+        let variables: Vec<(&str, &str)> = lines
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim(), value.trim().trim_matches('"')))
+            .collect();
+        ProxyConfig::from_variables(&variables)
+    }
+
+    /// Reads the config back from proxy variables, the lower case
+    /// name wins when both are set
+    pub fn from_variables(variables: &[(&str, &str)]) -> Self {
+        let variable = |name: &str| {
+            variables
+                .iter()
+                .find(|(key, _)| *key == name)
+                .or_else(|| variables.iter().find(|(key, _)| *key == name.to_uppercase()))
+                .map(|(_, value)| value.to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let http = variable("http_proxy").map(|server| strip_scheme(&server));
+        let https = variable("https_proxy").map(|server| strip_scheme(&server));
+        let socks = variable("all_proxy").map(|server| strip_scheme(&server));
+        let bypass = variable("no_proxy")
+            .map(|hosts| parse_bypass_hosts(&hosts))
+            .unwrap_or_default();
+
+        let mode = match (&http, &https, &socks) {
+            (None, None, None) => ProxyMode::None,
+            _ => ProxyMode::Manual,
+        };
+        ProxyConfig {
+            mode,
+            http,
+            https,
+            socks,
+            pac_url: None,
+            bypass,
+        }
+    }
+}
+
+/// Splits comma or space separated bypass hosts
+pub fn parse_bypass_hosts(hosts: &str) -> Vec<String> {
+    hosts
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|host| !host.is_empty())
+        .map(|host| host.to_string())
+        .collect()
+}
+
+/// `environment.d` file of the user, the apps run as the user and
+/// cannot write the system wide directory
+pub fn default_env_file() -> String {
+    let config_dir = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .or_else(|| env::var("HOME").ok().map(|home| format!("{}/.config", home)));
+    match config_dir {
+        Some(config_dir) => format!("{}/{}", config_dir, ENV_FILE_NAME),
+        None => format!("/tmp/{}", ENV_FILE_NAME),
+    }
+}
+
+/// Reads the environment file, a missing file means no proxy
+pub fn read_env_file(path: &Path) -> io::Result<ProxyConfig> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(ProxyConfig::from_env_file(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ProxyConfig::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces the environment file, services may read it at any time
+pub fn write_env_file(path: &Path, config: &ProxyConfig) -> io::Result<()> {
+    write_atomic(path, config.to_env_file())
+}
+
+/// Exports the proxy variables to the current process so HTTP clients
+/// started from it use the device proxy, stale variables are cleared.
+/// Must be called before the process starts any thread, `set_var` is
+/// unsound while another thread reads the environment
+pub fn apply_to_process_env(config: &ProxyConfig) {
+    for name in ["http_proxy", "https_proxy", "all_proxy", "no_proxy"] {
+        env::remove_var(name);
+        env::remove_var(name.to_uppercase());
+    }
+    for (name, value) in config.variables() {
+        env::set_var(name, value);
+    }
+}
+
+fn strip_scheme(server: &str) -> String {
+    let server = server
+        .split_once("://")
+        .map(|(_, server)| server)
+        .unwrap_or(server);
+    server.trim_end_matches('/').to_string()
+}
+
+fn validate_server(name: &str, server: &str) -> Result<(), String> {
+    let port = server
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.is_empty() && !host.contains(char::is_whitespace))
+        .and_then(|(_, port)| port.parse::<u16>().ok())
+        .filter(|port| *port != 0);
+    match port {
+        Some(_) => Ok(()),
+        None => Err(format!("{} proxy must be host:port", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    fn manual_config() -> ProxyConfig {
+        ProxyConfig {
+            mode: ProxyMode::Manual,
+            http: Some("proxy.local:3128".to_string()),
+            https: Some("proxy.local:3129".to_string()),
+            socks: None,
+            pac_url: None,
+            bypass: vec!["localhost".to_string(), ".mecha.local".to_string()],
+        }
+    }
+
+    #[test]
+    fn manual_config_round_trips_through_the_env_file() {
+        let config = manual_config();
+        let contents = config.to_env_file();
+        assert!(contents.starts_with(GENERATED_HEADER));
+        assert!(contents.contains("http_proxy=http://proxy.local:3128\n"));
+        assert!(contents.contains("HTTPS_PROXY=http://proxy.local:3129\n"));
+        assert!(contents.contains("no_proxy=localhost,.mecha.local\n"));
+        assert_eq!(ProxyConfig::from_env_file(&contents), config);
+    }
+
+    #[test]
+    fn automatic_config_round_trips_through_the_env_file() {
+        let config = ProxyConfig {
+            mode: ProxyMode::Automatic,
+            pac_url: Some("http://wpad.local/wpad.dat".to_string()),
+            ..Default::default()
+        };
+        // nothing is exported, clients would not read it
+        assert!(config.variables().is_empty());
+        let contents = config.to_env_file();
+        assert!(contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .all(|line| line.is_empty()));
+        assert_eq!(ProxyConfig::from_env_file(&contents), config);
+    }
+
+    #[test]
+    fn upper_case_variables_are_read_when_lower_case_are_missing() {
+        let config = ProxyConfig::from_variables(&[
+            ("HTTP_PROXY", "http://upper:8080/"),
+            ("https_proxy", "lower:8443"),
+            ("HTTPS_PROXY", "upper:8443"),
+        ]);
+        assert_eq!(config.mode, ProxyMode::Manual);
+        assert_eq!(config.http.as_deref(), Some("upper:8080"));
+        assert_eq!(config.https.as_deref(), Some("lower:8443"));
+    }
+
+    #[test]
+    fn quoted_values_and_comments_are_ignored() {
+        let config = ProxyConfig::from_env_file(
+            "# comment\nhttp_proxy=\"http://proxy.local:3128\"\n\nno_proxy = a, b\n",
+        );
+        assert_eq!(config.http.as_deref(), Some("proxy.local:3128"));
+        assert_eq!(config.bypass, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn validate_rejects_incomplete_settings() {
+        assert!(manual_config().validate().is_ok());
+        assert!(ProxyConfig::default().validate().is_ok());
+
+        let no_servers = ProxyConfig {
+            mode: ProxyMode::Manual,
+            ..Default::default()
+        };
+        assert!(no_servers.validate().is_err());
+
+        for server in ["proxy.local", ":3128", "proxy.local:0", "proxy.local:http"] {
+            let config = ProxyConfig {
+                http: Some(server.to_string()),
+                ..manual_config()
+            };
+            assert!(config.validate().is_err(), "{} was accepted", server);
+        }
+
+        let bad_bypass = ProxyConfig {
+            bypass: vec!["example.com/path".to_string()],
+            ..manual_config()
+        };
+        assert!(bad_bypass.validate().is_err());
+
+        let ftp_pac = ProxyConfig {
+            mode: ProxyMode::Automatic,
+            pac_url: Some("ftp://wpad.local/wpad.dat".to_string()),
+            ..Default::default()
+        };
+        assert!(ftp_pac.validate().is_err());
+    }
+
+    #[test]
+    fn bypass_hosts_split_on_commas_and_spaces() {
+        assert_eq!(
+            parse_bypass_hosts("localhost, 10.0.0.0/8 ,,.local"),
+            vec!["localhost", "10.0.0.0/8", ".local"]
+        );
+    }
+
+    #[test]
+    fn env_file_is_written_and_read_back() {
+        let dir = env::temp_dir().join(format!("proxy_env_{}", process::id()));
+        let path = dir.join("proxy.env");
+        assert_eq!(read_env_file(&path).unwrap(), ProxyConfig::default());
+
+        write_env_file(&path, &manual_config()).unwrap();
+        assert_eq!(read_env_file(&path).unwrap(), manual_config());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -60,6 +60,7 @@ pub enum InputMessage {
     InputFocusEnter,
     InputFocusLeave,
     Clear,
+    SetText(String),
 }
 
 #[derive(Debug)]
@@ -243,6 +244,9 @@ impl SimpleComponent for IconInput {
             InputMessage::Clear => {
                 self.input.set_text("");
             }
+            InputMessage::SetText(text) => {
+                self.input.set_text(&text);
+            }
         }
     }
 
@@ -45,6 +45,7 @@ pub enum InputMessage {
     InputChange(String),
     InputFocusEnter,
     InputFocusLeave,
+    SetText(String),
 }
 
 #[derive(Debug)]
@@ -56,6 +57,7 @@ pub struct IconInputPassword {
     settings: InitSettings,
     is_text_visible: bool,
     is_focused: bool,
+    input: gtk::Entry,
 }
 
 pub struct ComponentWidgets {
@@ -178,6 +180,7 @@ impl SimpleComponent for IconInputPassword {
             settings: init,
             is_text_visible: false,
             is_focused: false,
+            input: input.clone(),
         };
 
         let widgets = ComponentWidgets {
@@ -207,6 +210,9 @@ impl SimpleComponent for IconInputPassword {
             InputMessage::InputFocusLeave => {
                 self.is_focused = false;
             }
+            InputMessage::SetText(text) => {
+                self.input.set_text(&text);
+            }
         }
     }
 
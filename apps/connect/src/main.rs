@@ -22,8 +22,9 @@ use relm4::gtk::glib::clone;
 use relm4::{gtk, ComponentController};
 use relm4::{Component, ComponentParts, ComponentSender, Controller, RelmApp, SimpleComponent};
 use settings::ScreenSettings;
-use std::fmt;
-use tracing::info;
+use custom_utils::proxy;
+use std::{fmt, path::Path};
+use tracing::{info, warn};
 
 struct MechaConnectApp {
     current_page: Pages,
@@ -298,7 +299,25 @@ impl SimpleComponent for MechaConnectApp {
     }
 }
 
+/// Exports the device proxy set in the settings app. The app makes no
+/// HTTP requests itself, the cloud calls go through the commands and
+/// libraries it starts, which read `https_proxy` and `no_proxy`
+fn apply_device_proxy() {
+    let settings = match settings::read_settings_yml() {
+        Ok(settings) => settings,
+        Err(_) => ScreenSettings::default(),
+    };
+    match proxy::read_env_file(Path::new(&settings.proxy_env_file)) {
+        Ok(proxy_config) => proxy::apply_to_process_env(&proxy_config),
+        Err(e) => warn!("cannot read proxy settings {:?} - {}", settings.proxy_env_file, e),
+    }
+}
+
 fn main() {
+    // before GTK starts its threads, the environment must not change
+    // while another thread may read it
+    apply_device_proxy();
+
     let app = RelmApp::new("mecha.connect.app");
 
     app.run::<MechaConnectApp>(());
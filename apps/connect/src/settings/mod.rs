@@ -12,6 +12,9 @@ pub struct ScreenSettings {
     pub modules: Modules,
     pub widget_configs: WidgetConfigs,
     pub css: CssConfigs,
+    /// Proxy environment file written by the settings app
+    #[serde(default = "default_proxy_env_file")]
+    pub proxy_env_file: String,
 }
 
 impl Default for ScreenSettings {
@@ -21,10 +24,15 @@ impl Default for ScreenSettings {
             modules: Modules::default(),
             css: CssConfigs::default(),
             widget_configs: WidgetConfigs::default(),
+            proxy_env_file: default_proxy_env_file(),
         }
     }
 }
 
+fn default_proxy_env_file() -> String {
+    custom_utils::proxy::default_env_file()
+}
+
 
 /// # Window Settings
 ///
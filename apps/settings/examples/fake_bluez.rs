@@ -0,0 +1,1033 @@
+//! # Fake BlueZ
+//!
+//! Scripted `org.bluez` service to run the settings app's bluetooth
+//! backend without a Bluetooth controller, set `backends.bluetooth.bus`
+//! in settings.yml to `session` and start both on the same session bus
+//!
+//! `dbus-run-session -- sh -c "cargo run --example fake_bluez & cargo run"`
+//!
+//! The adapter `hci0` starts powered off with two paired devices,
+//! discovery finds the devices below one after another and connecting
+//! a device takes a moment before it reports being connected
+//!
+//! Pairing goes through the registered agent, the speaker asks to
+//! confirm `243 562`, the phone shows `123456` to type in, the mouse
+//! has `482 913` typed on it and the nameless device takes PIN `0000`.
+//! A device paired here is not trusted yet, connecting it asks the
+//! agent to authorize the audio sink service
+//!
+//! The headphones, keyboard and mouse report their battery level, the
+//! headphones drain a percent every few seconds while connected
+//!
+//! The mouse and the nameless device are LE only, the phone speaks
+//! both and the others are classic, a discovery filter on the
+//! transport only finds the matching ones
+//!
+//! A fake obexd runs next to it as `org.bluez.obex`, set
+//! `backends.bluetooth.obex_bus` to `session` too. Once the app
+//! registered its agent the phone offers a file every half minute,
+//! the song breaks off halfway and the notes do not announce a size
+
+use std::{
+    fs,
+    io::Write,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use zbus::{
+    block_on,
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface, fdo,
+    zvariant::{ObjectPath, OwnedObjectPath},
+    SignalContext,
+};
+
+const ADAPTER_PATH: &str = "/org/bluez/hci0";
+
+const DISCOVERY_INTERVAL: Duration = Duration::from_millis(1500);
+const CONNECT_TIME: Duration = Duration::from_millis(800);
+const TYPING_TIME: Duration = Duration::from_millis(1500);
+const BATTERY_DRAIN_INTERVAL: Duration = Duration::from_secs(5);
+const DISCOVERABLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+const AUDIO_SINK_UUID: &str = "0000110b-0000-1000-8000-00805f9b34fb";
+
+const OBEX_SERVER_PATH: &str = "/org/bluez/obex/server";
+const PUSH_INTERVAL: Duration = Duration::from_secs(30);
+const CHUNK_INTERVAL: Duration = Duration::from_millis(250);
+const CHUNKS: u64 = 20;
+
+/// `org.bluez.Error.*` replies
+#[derive(Debug, zbus::DBusError)]
+#[dbus_error(prefix = "org.bluez.Error")]
+enum Error {
+    #[dbus_error(zbus_error)]
+    ZBus(zbus::Error),
+    NotReady(String),
+    InProgress(String),
+    Failed(String),
+    DoesNotExist(String),
+    AlreadyExists(String),
+    AlreadyConnected(String),
+    AuthenticationRejected(String),
+    AuthenticationCanceled(String),
+    AuthenticationFailed(String),
+    InvalidArguments(String),
+}
+
+/// Unique name and path of the agent registered last
+static AGENT: Mutex<Option<(String, OwnedObjectPath)>> = Mutex::new(None);
+
+/// Unique name and path of the obex agent registered last
+static OBEX_AGENT: Mutex<Option<(String, OwnedObjectPath)>> = Mutex::new(None);
+
+/// How a device pairs
+#[derive(Clone, Copy)]
+enum Pairing {
+    JustWorks,
+    Confirm(u32),
+    Passkey(u32),
+    Display(u32),
+    PinCode(&'static str),
+}
+
+struct FakeDevice {
+    address: &'static str,
+    alias: &'static str,
+    icon: &'static str,
+    class: u32,
+    appearance: u16,
+    address_type: &'static str,
+    battery: Option<u8>,
+    rssi: i16,
+    paired: bool,
+    pairing: Pairing,
+}
+
+const PAIRED_DEVICES: [FakeDevice; 2] = [
+    FakeDevice {
+        address: "00:1A:7D:DA:71:01",
+        alias: "Headphones",
+        icon: "audio-headphones",
+        class: 0x240404,
+        appearance: 0,
+        address_type: "public",
+        battery: Some(80),
+        rssi: -48,
+        paired: true,
+        pairing: Pairing::JustWorks,
+    },
+    FakeDevice {
+        address: "00:1A:7D:DA:71:02",
+        alias: "Keyboard",
+        icon: "input-keyboard",
+        class: 0x002540,
+        appearance: 0,
+        address_type: "public",
+        battery: Some(45),
+        rssi: -61,
+        paired: true,
+        pairing: Pairing::JustWorks,
+    },
+];
+
+const DISCOVERED_DEVICES: [FakeDevice; 4] = [
+    FakeDevice {
+        address: "5C:F3:70:8B:12:03",
+        alias: "Speaker",
+        icon: "audio-card",
+        class: 0x240414,
+        appearance: 0,
+        address_type: "public",
+        battery: None,
+        rssi: -55,
+        paired: false,
+        pairing: Pairing::Confirm(243562),
+    },
+    FakeDevice {
+        address: "5C:F3:70:8B:12:04",
+        alias: "Phone",
+        icon: "phone",
+        class: 0x5a020c,
+        appearance: 0x0040,
+        address_type: "public",
+        battery: None,
+        rssi: -67,
+        paired: false,
+        pairing: Pairing::Passkey(123456),
+    },
+    FakeDevice {
+        address: "5C:F3:70:8B:12:05",
+        alias: "Mouse",
+        icon: "input-mouse",
+        class: 0,
+        appearance: 0x03c2,
+        address_type: "random",
+        battery: Some(62),
+        rssi: -74,
+        paired: false,
+        pairing: Pairing::Display(482913),
+    },
+    FakeDevice {
+        address: "5C:F3:70:8B:12:06",
+        alias: "",
+        icon: "",
+        class: 0,
+        appearance: 0,
+        address_type: "random",
+        battery: None,
+        rssi: -88,
+        paired: false,
+        pairing: Pairing::PinCode("0000"),
+    },
+];
+
+/// File the phone pushes, `fails_at` is the chunk the transfer breaks at
+struct FakeFile {
+    name: &'static str,
+    size: u64,
+    announced: bool,
+    fails_at: Option<u64>,
+}
+
+const PUSHED_FILES: [FakeFile; 3] = [
+    FakeFile {
+        name: "holiday.jpg",
+        size: 2_400_000,
+        announced: true,
+        fails_at: None,
+    },
+    FakeFile {
+        name: "notes.txt",
+        size: 12_000,
+        announced: false,
+        fails_at: None,
+    },
+    FakeFile {
+        name: "song.mp3",
+        size: 4_800_000,
+        announced: true,
+        fails_at: Some(CHUNKS / 2),
+    },
+];
+
+fn device_path(address: &str) -> String {
+    format!("{}/dev_{}", ADAPTER_PATH, address.replace(':', "_"))
+}
+
+struct Adapter {
+    alias: String,
+    powered: bool,
+    discovering: bool,
+    discoverable: bool,
+    discoverable_timeout: u32,
+    discoverable_since: Option<Instant>,
+    pairable: bool,
+    transport: String,
+}
+
+#[dbus_interface(name = "org.bluez.Adapter1")]
+impl Adapter {
+    async fn start_discovery(
+        &mut self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> Result<(), Error> {
+        if !self.powered {
+            return Err(Error::NotReady(String::new()));
+        }
+        if self.discovering {
+            return Err(Error::InProgress(String::new()));
+        }
+        println!("discovery started");
+        self.discovering = true;
+        self.discovering_changed(&context).await?;
+        let connection = Connection::from(connection.clone());
+        thread::spawn(move || discover(connection));
+        Ok(())
+    }
+
+    async fn stop_discovery(
+        &mut self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+    ) -> Result<(), Error> {
+        if !self.discovering {
+            return Err(Error::Failed(String::new()));
+        }
+        println!("discovery stopped");
+        self.discovering = false;
+        self.discovering_changed(&context).await?;
+        Ok(())
+    }
+
+    fn set_discovery_filter(
+        &mut self,
+        filter: std::collections::HashMap<String, zbus::zvariant::OwnedValue>,
+    ) -> Result<(), Error> {
+        let transport = match filter.get("Transport") {
+            Some(transport) => match transport.downcast_ref::<str>() {
+                Some(transport @ ("auto" | "bredr" | "le")) => transport.to_string(),
+                _ => return Err(Error::InvalidArguments(String::new())),
+            },
+            None => "auto".to_string(),
+        };
+        println!("discovery transport {}", transport);
+        self.transport = transport;
+        Ok(())
+    }
+
+    async fn remove_device(
+        &self,
+        device: ObjectPath<'_>,
+        #[zbus(object_server)] server: &zbus::ObjectServer,
+    ) -> Result<(), Error> {
+        println!("removing {}", device);
+        let _ = server.remove::<Battery, _>(&device).await;
+        match server.remove::<Device, _>(device).await {
+            Ok(true) => Ok(()),
+            _ => Err(Error::DoesNotExist(String::new())),
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn address(&self) -> String {
+        "00:1B:DC:0F:00:01".to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn alias(&self) -> String {
+        self.alias.clone()
+    }
+
+    /// An empty alias goes back to the host name
+    #[dbus_interface(property)]
+    fn set_alias(&mut self, alias: String) {
+        println!("adapter alias {:?}", alias);
+        self.alias = match alias.is_empty() {
+            true => "Fake Adapter".to_string(),
+            false => alias,
+        };
+    }
+
+    #[dbus_interface(property)]
+    fn powered(&self) -> bool {
+        self.powered
+    }
+
+    #[dbus_interface(property)]
+    fn set_powered(&mut self, powered: bool) {
+        println!("powered {}", powered);
+        self.powered = powered;
+        if !powered {
+            self.discovering = false;
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn discovering(&self) -> bool {
+        self.discovering
+    }
+
+    #[dbus_interface(property)]
+    fn discoverable(&self) -> bool {
+        self.discoverable
+    }
+
+    #[dbus_interface(property)]
+    fn set_discoverable(&mut self, discoverable: bool) -> fdo::Result<()> {
+        if !self.powered {
+            return Err(fdo::Error::Failed("Not Powered".to_string()));
+        }
+        println!("discoverable {}", discoverable);
+        self.discoverable = discoverable;
+        self.discoverable_since = Some(Instant::now()).filter(|_| discoverable);
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn discoverable_timeout(&self) -> u32 {
+        self.discoverable_timeout
+    }
+
+    #[dbus_interface(property)]
+    fn set_discoverable_timeout(&mut self, timeout: u32) {
+        println!("discoverable timeout {}", timeout);
+        self.discoverable_timeout = timeout;
+    }
+
+    #[dbus_interface(property)]
+    fn pairable(&self) -> bool {
+        self.pairable
+    }
+
+    #[dbus_interface(property)]
+    fn set_pairable(&mut self, pairable: bool) {
+        println!("pairable {}", pairable);
+        self.pairable = pairable;
+    }
+}
+
+struct Device {
+    address: String,
+    alias: String,
+    icon: String,
+    class: u32,
+    appearance: u16,
+    address_type: String,
+    rssi: i16,
+    paired: bool,
+    trusted: bool,
+    blocked: bool,
+    connected: bool,
+    pairing: Pairing,
+}
+
+impl Device {
+    fn new(device: &FakeDevice) -> Self {
+        Self {
+            address: device.address.to_string(),
+            alias: match device.alias.is_empty() {
+                true => device.address.replace(':', "-"),
+                false => device.alias.to_string(),
+            },
+            icon: device.icon.to_string(),
+            class: device.class,
+            appearance: device.appearance,
+            address_type: device.address_type.to_string(),
+            rssi: device.rssi,
+            paired: device.paired,
+            trusted: device.paired,
+            blocked: false,
+            connected: false,
+            pairing: device.pairing,
+        }
+    }
+}
+
+#[dbus_interface(name = "org.bluez.Device1")]
+impl Device {
+    async fn pair(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::MessageHeader<'_>,
+    ) -> Result<(), Error> {
+        if self.paired {
+            return Err(Error::AlreadyExists(String::new()));
+        }
+        let path = header.path().ok().flatten().unwrap().to_owned();
+        println!("pairing {}", self.address);
+        let result = ask_agent(connection, &path, self.pairing).await;
+        println!("pairing {} finished {:?}", self.address, result);
+        result?;
+
+        let connection = Connection::from(connection.clone());
+        thread::spawn(move || set_paired(&connection, path.as_str()));
+        Ok(())
+    }
+
+    async fn connect(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::MessageHeader<'_>,
+    ) -> Result<(), Error> {
+        if self.connected {
+            return Err(Error::AlreadyConnected(String::new()));
+        }
+        if self.blocked {
+            return Err(Error::Failed("device is blocked".to_string()));
+        }
+        let path = header.path().ok().flatten().map(|path| path.to_string());
+        println!("connecting {}", self.address);
+        if !self.trusted {
+            let device = header.path().ok().flatten().unwrap();
+            let result =
+                call_agent::<_, ()>(connection, "AuthorizeService", &(device, AUDIO_SINK_UUID))
+                    .await;
+            if let Err(e) = result {
+                println!("connecting {} not authorized {}", self.address, e);
+                return Err(e);
+            }
+        }
+        let connection = Connection::from(connection.clone());
+        thread::spawn(move || {
+            thread::sleep(CONNECT_TIME);
+            if let Some(path) = path {
+                set_connected(&connection, &path, true);
+            }
+        });
+        Ok(())
+    }
+
+    fn disconnect(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::MessageHeader<'_>,
+    ) -> Result<(), Error> {
+        let path = header.path().ok().flatten().map(|path| path.to_string());
+        println!("disconnecting {}", self.address);
+        let connection = Connection::from(connection.clone());
+        thread::spawn(move || {
+            if let Some(path) = path {
+                set_connected(&connection, &path, false);
+            }
+        });
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn alias(&self) -> String {
+        self.alias.clone()
+    }
+
+    /// An empty alias goes back to the name of the device
+    #[dbus_interface(property)]
+    fn set_alias(&mut self, alias: String) {
+        println!("{} alias {:?}", self.address, alias);
+        self.alias = match alias.is_empty() {
+            true => self.address.replace(':', "-"),
+            false => alias,
+        };
+    }
+
+    #[dbus_interface(property)]
+    fn icon(&self) -> String {
+        self.icon.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn class(&self) -> u32 {
+        self.class
+    }
+
+    #[dbus_interface(property)]
+    fn appearance(&self) -> u16 {
+        self.appearance
+    }
+
+    #[dbus_interface(property)]
+    fn address_type(&self) -> String {
+        self.address_type.clone()
+    }
+
+    #[dbus_interface(property, name = "RSSI")]
+    fn rssi(&self) -> i16 {
+        self.rssi
+    }
+
+    #[dbus_interface(property)]
+    fn paired(&self) -> bool {
+        self.paired
+    }
+
+    #[dbus_interface(property)]
+    fn trusted(&self) -> bool {
+        self.trusted
+    }
+
+    #[dbus_interface(property)]
+    fn set_trusted(&mut self, trusted: bool) {
+        println!("{} trusted {}", self.address, trusted);
+        self.trusted = trusted;
+    }
+
+    #[dbus_interface(property)]
+    fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    /// Blocking also drops the connection like BlueZ does, the
+    /// settings app reads every property again on the change
+    #[dbus_interface(property)]
+    fn set_blocked(&mut self, blocked: bool) {
+        println!("{} blocked {}", self.address, blocked);
+        self.blocked = blocked;
+        if blocked {
+            self.connected = false;
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn connected(&self) -> bool {
+        self.connected
+    }
+
+    #[dbus_interface(property)]
+    fn adapter(&self) -> ObjectPath<'_> {
+        ObjectPath::from_static_str_unchecked(ADAPTER_PATH)
+    }
+}
+
+struct Battery {
+    percentage: u8,
+}
+
+#[dbus_interface(name = "org.bluez.Battery1")]
+impl Battery {
+    #[dbus_interface(property)]
+    fn percentage(&self) -> u8 {
+        self.percentage
+    }
+}
+
+async fn call_agent<B, R>(connection: &zbus::Connection, method: &str, body: &B) -> Result<R, Error>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+    R: for<'d> serde::Deserialize<'d> + zbus::zvariant::Type,
+{
+    let (name, path) = match AGENT.lock().unwrap().clone() {
+        Some(agent) => agent,
+        None => return Err(Error::AuthenticationRejected("no agent".to_string())),
+    };
+    let agent = zbus::Proxy::new(connection, name, path, "org.bluez.Agent1").await?;
+    agent.call(method, body).await.map_err(|e| match e {
+        zbus::Error::MethodError(name, _, _) if name.as_str() == "org.bluez.Error.Canceled" => {
+            Error::AuthenticationCanceled(String::new())
+        }
+        e => Error::AuthenticationRejected(e.to_string()),
+    })
+}
+
+/// Walks the agent through the pairing of a device
+async fn ask_agent(
+    connection: &zbus::Connection,
+    device: &ObjectPath<'_>,
+    pairing: Pairing,
+) -> Result<(), Error> {
+    match pairing {
+        Pairing::JustWorks => call_agent(connection, "RequestAuthorization", &(device,)).await,
+        Pairing::Confirm(passkey) => {
+            call_agent(connection, "RequestConfirmation", &(device, passkey)).await
+        }
+        Pairing::Passkey(expected) => {
+            let passkey: u32 = call_agent(connection, "RequestPasskey", &(device,)).await?;
+            match passkey == expected {
+                true => Ok(()),
+                false => Err(Error::AuthenticationFailed(String::new())),
+            }
+        }
+        Pairing::PinCode(expected) => {
+            let pin_code: String = call_agent(connection, "RequestPinCode", &(device,)).await?;
+            match pin_code == expected {
+                true => Ok(()),
+                false => Err(Error::AuthenticationFailed(String::new())),
+            }
+        }
+        Pairing::Display(passkey) => {
+            // someone types the passkey on the device, digit by digit
+            for entered in [0u16, 3, 6] {
+                call_agent::<_, ()>(connection, "DisplayPasskey", &(device, passkey, entered))
+                    .await?;
+                thread::sleep(TYPING_TIME);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn set_paired(connection: &Connection, path: &str) {
+    let device = match connection
+        .object_server()
+        .interface::<_, Device>(path.to_string())
+    {
+        Ok(device) => device,
+        Err(_) => return,
+    };
+    device.get_mut().paired = true;
+    let _ = block_on(device.get().paired_changed(device.signal_context()));
+    println!("{} paired", path);
+}
+
+fn set_connected(connection: &Connection, path: &str, connected: bool) {
+    let device = match connection
+        .object_server()
+        .interface::<_, Device>(path.to_string())
+    {
+        Ok(device) => device,
+        Err(_) => return,
+    };
+    device.get_mut().connected = connected;
+    let _ = block_on(device.get().connected_changed(device.signal_context()));
+    println!("{} connected {}", path, connected);
+}
+
+/// Drains the battery of the connected headphones
+fn drain_battery(connection: Connection) {
+    let path = device_path(PAIRED_DEVICES[0].address);
+    loop {
+        thread::sleep(BATTERY_DRAIN_INTERVAL);
+        let server = connection.object_server();
+        let connected = server
+            .interface::<_, Device>(path.clone())
+            .map(|device| device.get().connected)
+            .unwrap_or(false);
+        let battery = match server.interface::<_, Battery>(path.clone()) {
+            Ok(battery) => battery,
+            Err(_) => continue,
+        };
+        if !connected || battery.get().percentage == 0 {
+            continue;
+        }
+        battery.get_mut().percentage -= 1;
+        let _ = block_on(battery.get().percentage_changed(battery.signal_context()));
+    }
+}
+
+/// Adds a device with its battery, when it has one
+fn add_device(server: &zbus::blocking::ObjectServer, device: &FakeDevice) -> zbus::Result<()> {
+    let path = device_path(device.address);
+    server.at(path.clone(), Device::new(device))?;
+    if let Some(percentage) = device.battery {
+        server.at(path, Battery { percentage })?;
+    }
+    Ok(())
+}
+
+/// Ends discoverability once the timeout of the adapter ran out
+fn expire_discoverable(connection: Connection) {
+    loop {
+        thread::sleep(DISCOVERABLE_CHECK_INTERVAL);
+        let server = connection.object_server();
+        let adapter = match server.interface::<_, Adapter>(ADAPTER_PATH) {
+            Ok(adapter) => adapter,
+            Err(_) => continue,
+        };
+        let expired = {
+            let adapter = adapter.get();
+            adapter.discoverable_timeout > 0
+                && adapter.discoverable_since.is_some_and(|since| {
+                    since.elapsed() >= Duration::from_secs(adapter.discoverable_timeout.into())
+                })
+        };
+        if !expired {
+            continue;
+        }
+        println!("discoverable timed out");
+        adapter.get_mut().discoverable = false;
+        adapter.get_mut().discoverable_since = None;
+        let _ = block_on(adapter.get().discoverable_changed(adapter.signal_context()));
+    }
+}
+
+/// Whether discovery on `transport` finds the device
+fn is_found_with(device: &FakeDevice, transport: &str) -> bool {
+    let is_le = device.appearance != 0 || device.address_type == "random";
+    let is_classic = device.class != 0;
+    match transport {
+        "le" => is_le,
+        "bredr" => is_classic,
+        _ => true,
+    }
+}
+
+/// Adds the discovered devices one after another while discovery runs
+fn discover(connection: Connection) {
+    let server = connection.object_server();
+    for device in DISCOVERED_DEVICES.iter() {
+        thread::sleep(DISCOVERY_INTERVAL);
+        let (discovering, transport) = server
+            .interface::<_, Adapter>(ADAPTER_PATH)
+            .map(|adapter| (adapter.get().discovering, adapter.get().transport.clone()))
+            .unwrap_or_default();
+        if !discovering {
+            return;
+        }
+        if !is_found_with(device, &transport) {
+            continue;
+        }
+        let path = device_path(device.address);
+        if server.interface::<_, Device>(path.clone()).is_ok() {
+            continue;
+        }
+        println!("found {}", device.address);
+        let _ = add_device(&server, device);
+    }
+}
+
+struct AgentManager;
+
+#[dbus_interface(name = "org.bluez.AgentManager1")]
+impl AgentManager {
+    fn register_agent(
+        &self,
+        agent: OwnedObjectPath,
+        capability: String,
+        #[zbus(header)] header: zbus::MessageHeader<'_>,
+    ) -> fdo::Result<()> {
+        let sender = header.sender()?.unwrap().to_string();
+        println!("agent {} {} registered ({})", sender, agent, capability);
+        *AGENT.lock().unwrap() = Some((sender, agent));
+        Ok(())
+    }
+
+    fn request_default_agent(&self, agent: OwnedObjectPath) -> fdo::Result<()> {
+        println!("default agent {}", agent);
+        Ok(())
+    }
+
+    fn unregister_agent(&self, agent: OwnedObjectPath) -> fdo::Result<()> {
+        println!("agent {} unregistered", agent);
+        *AGENT.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+fn main() -> zbus::Result<()> {
+    let connection = ConnectionBuilder::session()?.build()?;
+    // obexd is a service of its own
+    let obex_connection = ConnectionBuilder::session()?.build()?;
+    serve(&connection, obex_connection)?;
+
+    println!("fake bluez listening on {:?}", connection.unique_name());
+    loop {
+        thread::park();
+    }
+}
+
+/// Exports the adapter with its paired devices and runs obexd on the
+/// second connection, also run by the tests of the backend
+pub fn serve(connection: &Connection, obex_connection: Connection) -> zbus::Result<()> {
+    let server = connection.object_server();
+    server.at(
+        ADAPTER_PATH,
+        Adapter {
+            alias: "Fake Adapter".to_string(),
+            powered: false,
+            discovering: false,
+            discoverable: false,
+            discoverable_timeout: 180,
+            discoverable_since: None,
+            pairable: true,
+            transport: "auto".to_string(),
+        },
+    )?;
+    for device in PAIRED_DEVICES.iter() {
+        add_device(&server, device)?;
+    }
+    server.at("/org/bluez", AgentManager)?;
+    server.at("/", fdo::ObjectManager)?;
+    connection.request_name("org.bluez")?;
+
+    obex_connection
+        .object_server()
+        .at("/org/bluez/obex", ObexAgentManager)?;
+    obex_connection.request_name("org.bluez.obex")?;
+    thread::spawn(move || push_files(obex_connection));
+
+    let drain_connection = connection.clone();
+    thread::spawn(move || drain_battery(drain_connection));
+    let discoverable_connection = connection.clone();
+    thread::spawn(move || expire_discoverable(discoverable_connection));
+    Ok(())
+}
+
+struct ObexAgentManager;
+
+#[dbus_interface(name = "org.bluez.obex.AgentManager1")]
+impl ObexAgentManager {
+    fn register_agent(
+        &self,
+        agent: OwnedObjectPath,
+        #[zbus(header)] header: zbus::MessageHeader<'_>,
+    ) -> fdo::Result<()> {
+        let sender = header.sender()?.unwrap().to_string();
+        println!("obex agent {} {} registered", sender, agent);
+        *OBEX_AGENT.lock().unwrap() = Some((sender, agent));
+        Ok(())
+    }
+
+    fn unregister_agent(&self, agent: OwnedObjectPath) -> fdo::Result<()> {
+        println!("obex agent {} unregistered", agent);
+        *OBEX_AGENT.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+struct Session {
+    destination: String,
+}
+
+#[dbus_interface(name = "org.bluez.obex.Session1")]
+impl Session {
+    #[dbus_interface(property)]
+    fn destination(&self) -> String {
+        self.destination.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn target(&self) -> String {
+        "opp".to_string()
+    }
+}
+
+struct Transfer {
+    status: String,
+    name: String,
+    size: u64,
+    transferred: u64,
+    session: OwnedObjectPath,
+    filename: String,
+    cancelled: bool,
+}
+
+#[dbus_interface(name = "org.bluez.obex.Transfer1")]
+impl Transfer {
+    /// Only the agent may stop incoming files
+    fn cancel(&mut self, #[zbus(header)] header: zbus::MessageHeader<'_>) -> fdo::Result<()> {
+        let sender = header.sender()?.map(|sender| sender.to_string());
+        let agent = OBEX_AGENT.lock().unwrap().clone().map(|(name, _)| name);
+        if sender.is_none() || sender != agent {
+            return Err(fdo::Error::AccessDenied("Not Authorized".to_string()));
+        }
+        println!("{} cancelled", self.name);
+        self.cancelled = true;
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// 0 when the sender did not announce it
+    #[dbus_interface(property)]
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[dbus_interface(property)]
+    fn transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    #[dbus_interface(property)]
+    fn session(&self) -> OwnedObjectPath {
+        self.session.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn filename(&self) -> String {
+        self.filename.clone()
+    }
+}
+
+/// Offers the files of the phone one after another to the obex agent
+fn push_files(connection: Connection) {
+    for (number, file) in PUSHED_FILES.iter().cycle().enumerate() {
+        thread::sleep(PUSH_INTERVAL);
+        let (agent_name, agent_path) = match OBEX_AGENT.lock().unwrap().clone() {
+            Some(agent) => agent,
+            None => continue,
+        };
+        if let Err(e) = push_file(&connection, &agent_name, &agent_path, number, file) {
+            println!("pushing {} failed - {}", file.name, e);
+        }
+    }
+}
+
+fn push_file(
+    connection: &Connection,
+    agent_name: &str,
+    agent_path: &OwnedObjectPath,
+    number: usize,
+    file: &FakeFile,
+) -> zbus::Result<()> {
+    let server = connection.object_server();
+    let session_path = format!("{}/session{}", OBEX_SERVER_PATH, number);
+    let transfer_path = format!("{}/transfer{}", session_path, number);
+    server.at(
+        session_path.clone(),
+        Session {
+            destination: DISCOVERED_DEVICES[1].address.to_string(),
+        },
+    )?;
+    server.at(
+        transfer_path.clone(),
+        Transfer {
+            status: "queued".to_string(),
+            name: file.name.to_string(),
+            size: if file.announced { file.size } else { 0 },
+            transferred: 0,
+            session: OwnedObjectPath::try_from(session_path.clone())?,
+            filename: String::new(),
+            cancelled: false,
+        },
+    )?;
+
+    println!("offering {}", file.name);
+    let agent = zbus::blocking::Proxy::new(
+        connection,
+        agent_name,
+        agent_path.as_str(),
+        "org.bluez.obex.Agent1",
+    )?;
+    let transfer = ObjectPath::try_from(transfer_path.as_str())?;
+    let result = match agent.call::<_, _, String>("AuthorizePush", &(transfer,)) {
+        Ok(path) => receive_file(connection, &transfer_path, &path, file),
+        Err(e) => {
+            println!("{} declined - {}", file.name, e);
+            Ok(())
+        }
+    };
+
+    server.remove::<Transfer, _>(transfer_path)?;
+    server.remove::<Session, _>(session_path)?;
+    result
+}
+
+/// Writes the file to `path` chunk by chunk, the way obexd reports
+/// the progress of a transfer
+fn receive_file(
+    connection: &Connection,
+    transfer_path: &str,
+    path: &str,
+    file: &FakeFile,
+) -> zbus::Result<()> {
+    let transfer = connection
+        .object_server()
+        .interface::<_, Transfer>(transfer_path.to_string())?;
+    let set_status = |status: &str| {
+        transfer.get_mut().status = status.to_string();
+        let _ = block_on(transfer.get().status_changed(transfer.signal_context()));
+        println!("{} {}", file.name, status);
+    };
+
+    println!("saving {} to {}", file.name, path);
+    transfer.get_mut().filename = path.to_string();
+    let mut output = match fs::File::create(path) {
+        Ok(output) => output,
+        Err(e) => {
+            set_status("error");
+            return Err(zbus::Error::Failure(e.to_string()));
+        }
+    };
+    set_status("active");
+
+    let chunk = vec![0u8; (file.size / CHUNKS) as usize];
+    for index in 0..CHUNKS {
+        thread::sleep(CHUNK_INTERVAL);
+        if transfer.get().cancelled || file.fails_at == Some(index) {
+            set_status("error");
+            return Ok(());
+        }
+        if let Err(e) = output.write_all(&chunk) {
+            set_status("error");
+            return Err(zbus::Error::Failure(e.to_string()));
+        }
+        transfer.get_mut().transferred += chunk.len() as u64;
+        let _ = block_on(transfer.get().transferred_changed(transfer.signal_context()));
+    }
+    set_status("complete");
+    Ok(())
+}
@@ -0,0 +1,471 @@
+//! # Fake NetworkManager
+//!
+//! Scripted `org.freedesktop.NetworkManager` service to run the
+//! settings app's network backend without a Wi-Fi device, set
+//! `backends.network.bus` in settings.yml to `session` and start both
+//! on the same session bus
+//!
+//! `dbus-run-session -- sh -c "cargo run --example fake_network_manager & cargo run"`
+//!
+//! The Wi-Fi device `wlan0` sees the access points below and is
+//! connected to the saved `Mecha Office` profile, the saved `Home`
+//! profile is out of range. Turning Wi-Fi off drops the connection and
+//! turning it back on joins the office again. Activating the office
+//! profile keeps the psk it was updated with, joining other networks
+//! is not scripted
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use zbus::{
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface, fdo,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+};
+
+const NETWORK_MANAGER_PATH: &str = "/org/freedesktop/NetworkManager";
+const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+const ACTIVE_CONNECTION_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+/// `NM_DEVICE_STATE_DISCONNECTED` and `NM_DEVICE_STATE_ACTIVATED`
+const DEVICE_STATE_DISCONNECTED: u32 = 30;
+const DEVICE_STATE_ACTIVATED: u32 = 100;
+/// `NM_ACTIVE_CONNECTION_STATE_ACTIVATED`
+const ACTIVE_CONNECTION_STATE_ACTIVATED: u32 = 2;
+
+/// `NM_802_11_AP_FLAGS_PRIVACY`
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+/// `NM_802_11_AP_SEC_KEY_MGMT_PSK`
+const AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+/// `NM_802_11_AP_SEC_KEY_MGMT_SAE`
+const AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+
+struct FakeAccessPoint {
+    bssid: &'static str,
+    ssid: &'static str,
+    frequency: u32,
+    strength: u8,
+    flags: u32,
+    rsn_flags: u32,
+}
+
+/// The first one is the access point the device is connected to
+const ACCESS_POINTS: [FakeAccessPoint; 4] = [
+    FakeAccessPoint {
+        bssid: "A0:B1:C2:D3:E4:01",
+        ssid: "Mecha Office",
+        frequency: 5180,
+        strength: 82,
+        flags: AP_FLAGS_PRIVACY,
+        rsn_flags: AP_SEC_KEY_MGMT_PSK,
+    },
+    FakeAccessPoint {
+        bssid: "A0:B1:C2:D3:E4:02",
+        ssid: "Mecha Office",
+        frequency: 2437,
+        strength: 54,
+        flags: AP_FLAGS_PRIVACY,
+        rsn_flags: AP_SEC_KEY_MGMT_PSK,
+    },
+    FakeAccessPoint {
+        bssid: "A0:B1:C2:D3:E4:03",
+        ssid: "Cafe Guest",
+        frequency: 2412,
+        strength: 67,
+        flags: 0,
+        rsn_flags: 0,
+    },
+    FakeAccessPoint {
+        bssid: "A0:B1:C2:D3:E4:04",
+        ssid: "Lab WPA3",
+        frequency: 5745,
+        strength: 48,
+        flags: AP_FLAGS_PRIVACY,
+        rsn_flags: AP_SEC_KEY_MGMT_SAE,
+    },
+];
+
+/// Saved profiles as (ssid, key-mgmt, psk), the first one is the active connection
+const SAVED_CONNECTIONS: [(&str, &str, &str); 2] = [
+    ("Mecha Office", "wpa-psk", "office-password"),
+    ("Home", "wpa-psk", "home-password"),
+];
+
+fn access_point_path(index: usize) -> String {
+    format!("{}/AccessPoint/{}", NETWORK_MANAGER_PATH, index + 1)
+}
+
+fn connection_path(index: usize) -> String {
+    format!("{}/{}", SETTINGS_PATH, index + 1)
+}
+
+fn object_path(path: &str) -> OwnedObjectPath {
+    OwnedObjectPath::try_from(path.to_string()).unwrap()
+}
+
+struct State {
+    wireless_enabled: bool,
+    /// Saved profiles not deleted yet, by index
+    saved: Vec<usize>,
+    /// Secrets of the saved profiles, by index
+    psks: Vec<String>,
+}
+
+impl State {
+    /// The office is joined while Wi-Fi is on and its profile is kept
+    fn is_connected(&self) -> bool {
+        self.wireless_enabled && self.saved.contains(&0)
+    }
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+struct NetworkManager {
+    state: SharedState,
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager")]
+impl NetworkManager {
+    fn get_devices(&self) -> Vec<OwnedObjectPath> {
+        vec![object_path(DEVICE_PATH)]
+    }
+
+    fn activate_connection(
+        &self,
+        connection: OwnedObjectPath,
+        _device: OwnedObjectPath,
+        _specific_object: OwnedObjectPath,
+    ) -> fdo::Result<OwnedObjectPath> {
+        let state = self.state.lock().unwrap();
+        if connection.as_str() != connection_path(0) || !state.is_connected() {
+            return Err(fdo::Error::Failed(format!(
+                "activating {} is not scripted",
+                connection
+            )));
+        }
+        println!("activating {} with psk {:?}", connection, state.psks[0]);
+        Ok(object_path(ACTIVE_CONNECTION_PATH))
+    }
+
+    #[dbus_interface(property)]
+    fn active_connections(&self) -> Vec<OwnedObjectPath> {
+        match self.state.lock().unwrap().is_connected() {
+            true => vec![object_path(ACTIVE_CONNECTION_PATH)],
+            false => vec![],
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn wireless_enabled(&self) -> bool {
+        self.state.lock().unwrap().wireless_enabled
+    }
+
+    #[dbus_interface(property)]
+    fn set_wireless_enabled(&mut self, enabled: bool) {
+        println!("wireless enabled {}", enabled);
+        self.state.lock().unwrap().wireless_enabled = enabled;
+    }
+}
+
+struct Device {
+    state: SharedState,
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.Device")]
+impl Device {
+    #[dbus_interface(property)]
+    fn device_type(&self) -> u32 {
+        DEVICE_TYPE_WIFI
+    }
+
+    #[dbus_interface(property)]
+    fn interface(&self) -> String {
+        "wlan0".to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn state(&self) -> u32 {
+        match self.state.lock().unwrap().is_connected() {
+            true => DEVICE_STATE_ACTIVATED,
+            false => DEVICE_STATE_DISCONNECTED,
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn active_connection(&self) -> OwnedObjectPath {
+        match self.state.lock().unwrap().is_connected() {
+            true => object_path(ACTIVE_CONNECTION_PATH),
+            false => object_path("/"),
+        }
+    }
+}
+
+struct WirelessDevice {
+    state: SharedState,
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.Device.Wireless")]
+impl WirelessDevice {
+    fn get_all_access_points(&self) -> Vec<OwnedObjectPath> {
+        match self.state.lock().unwrap().wireless_enabled {
+            true => (0..ACCESS_POINTS.len())
+                .map(|index| object_path(&access_point_path(index)))
+                .collect(),
+            false => vec![],
+        }
+    }
+
+    fn request_scan(&self, _options: HashMap<String, OwnedValue>) -> fdo::Result<()> {
+        match self.state.lock().unwrap().wireless_enabled {
+            true => {
+                println!("scan requested");
+                Ok(())
+            }
+            false => Err(fdo::Error::Failed("Wi-Fi is disabled".to_string())),
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn active_access_point(&self) -> OwnedObjectPath {
+        match self.state.lock().unwrap().is_connected() {
+            true => object_path(&access_point_path(0)),
+            false => object_path("/"),
+        }
+    }
+}
+
+struct AccessPoint {
+    index: usize,
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.AccessPoint")]
+impl AccessPoint {
+    #[dbus_interface(property)]
+    fn ssid(&self) -> Vec<u8> {
+        ACCESS_POINTS[self.index].ssid.as_bytes().to_vec()
+    }
+
+    #[dbus_interface(property)]
+    fn hw_address(&self) -> String {
+        ACCESS_POINTS[self.index].bssid.to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn strength(&self) -> u8 {
+        ACCESS_POINTS[self.index].strength
+    }
+
+    #[dbus_interface(property)]
+    fn frequency(&self) -> u32 {
+        ACCESS_POINTS[self.index].frequency
+    }
+
+    #[dbus_interface(property)]
+    fn flags(&self) -> u32 {
+        ACCESS_POINTS[self.index].flags
+    }
+
+    #[dbus_interface(property)]
+    fn wpa_flags(&self) -> u32 {
+        0
+    }
+
+    #[dbus_interface(property)]
+    fn rsn_flags(&self) -> u32 {
+        ACCESS_POINTS[self.index].rsn_flags
+    }
+}
+
+struct ActiveConnection;
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+impl ActiveConnection {
+    #[dbus_interface(property)]
+    fn id(&self) -> String {
+        SAVED_CONNECTIONS[0].0.to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn connection(&self) -> OwnedObjectPath {
+        object_path(&connection_path(0))
+    }
+
+    #[dbus_interface(property)]
+    fn state(&self) -> u32 {
+        ACTIVE_CONNECTION_STATE_ACTIVATED
+    }
+}
+
+struct Settings {
+    state: SharedState,
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.Settings")]
+impl Settings {
+    fn list_connections(&self) -> Vec<OwnedObjectPath> {
+        self.state
+            .lock()
+            .unwrap()
+            .saved
+            .iter()
+            .map(|index| object_path(&connection_path(*index)))
+            .collect()
+    }
+}
+
+struct SettingsConnection {
+    index: usize,
+    state: SharedState,
+}
+
+impl SettingsConnection {
+    fn check_exists(&self) -> fdo::Result<()> {
+        match self.state.lock().unwrap().saved.contains(&self.index) {
+            true => Ok(()),
+            false => Err(fdo::Error::UnknownObject(connection_path(self.index))),
+        }
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+impl SettingsConnection {
+    fn get_settings(&self) -> fdo::Result<HashMap<String, HashMap<String, OwnedValue>>> {
+        self.check_exists()?;
+        let (ssid, key_mgmt, _) = SAVED_CONNECTIONS[self.index];
+        let group = |entries: Vec<(&str, Value)>| -> HashMap<String, OwnedValue> {
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), OwnedValue::from(value)))
+                .collect()
+        };
+
+        let mut settings = HashMap::new();
+        settings.insert(
+            "connection".to_string(),
+            group(vec![
+                ("id", Value::from(ssid)),
+                ("type", Value::from("802-11-wireless")),
+                ("autoconnect", Value::from(true)),
+            ]),
+        );
+        settings.insert(
+            "802-11-wireless".to_string(),
+            group(vec![
+                ("ssid", Value::from(ssid.as_bytes().to_vec())),
+                ("mode", Value::from("infrastructure")),
+            ]),
+        );
+        settings.insert(
+            "802-11-wireless-security".to_string(),
+            group(vec![("key-mgmt", Value::from(key_mgmt))]),
+        );
+        Ok(settings)
+    }
+
+    /// Only the psk of the update is kept, the rest is scripted
+    fn update(&self, settings: HashMap<String, HashMap<String, OwnedValue>>) -> fdo::Result<()> {
+        self.check_exists()?;
+        let psk = settings
+            .get("802-11-wireless-security")
+            .and_then(|security| security.get("psk"))
+            .and_then(|psk| psk.downcast_ref::<str>());
+        if let Some(psk) = psk {
+            println!("updating the psk of {}", SAVED_CONNECTIONS[self.index].0);
+            self.state.lock().unwrap().psks[self.index] = psk.to_string();
+        }
+        Ok(())
+    }
+
+    fn get_secrets(
+        &self,
+        setting_name: String,
+    ) -> fdo::Result<HashMap<String, HashMap<String, OwnedValue>>> {
+        self.check_exists()?;
+        let psk = self.state.lock().unwrap().psks[self.index].clone();
+        let secrets = HashMap::from([("psk".to_string(), OwnedValue::from(Value::from(psk)))]);
+        Ok(HashMap::from([(setting_name, secrets)]))
+    }
+
+    fn delete(&self) -> fdo::Result<()> {
+        self.check_exists()?;
+        println!("deleting {}", SAVED_CONNECTIONS[self.index].0);
+        self.state
+            .lock()
+            .unwrap()
+            .saved
+            .retain(|index| *index != self.index);
+        Ok(())
+    }
+}
+
+/// Exports the objects and takes the NetworkManager name on the
+/// connection, also run by the tests of the backend
+pub fn serve(connection: &Connection) -> zbus::Result<()> {
+    let state = Arc::new(Mutex::new(State {
+        wireless_enabled: true,
+        saved: (0..SAVED_CONNECTIONS.len()).collect(),
+        psks: SAVED_CONNECTIONS
+            .iter()
+            .map(|(_, _, psk)| psk.to_string())
+            .collect(),
+    }));
+
+    let server = connection.object_server();
+    server.at(
+        NETWORK_MANAGER_PATH,
+        NetworkManager {
+            state: state.clone(),
+        },
+    )?;
+    server.at(
+        DEVICE_PATH,
+        Device {
+            state: state.clone(),
+        },
+    )?;
+    server.at(
+        DEVICE_PATH,
+        WirelessDevice {
+            state: state.clone(),
+        },
+    )?;
+    server.at(ACTIVE_CONNECTION_PATH, ActiveConnection)?;
+    for index in 0..ACCESS_POINTS.len() {
+        server.at(access_point_path(index), AccessPoint { index })?;
+    }
+    server.at(
+        SETTINGS_PATH,
+        Settings {
+            state: state.clone(),
+        },
+    )?;
+    for index in 0..SAVED_CONNECTIONS.len() {
+        server.at(
+            connection_path(index),
+            SettingsConnection {
+                index,
+                state: state.clone(),
+            },
+        )?;
+    }
+    connection.request_name("org.freedesktop.NetworkManager")?;
+    Ok(())
+}
+
+fn main() -> zbus::Result<()> {
+    let connection = ConnectionBuilder::session()?.build()?;
+    serve(&connection)?;
+    println!(
+        "fake NetworkManager listening on {:?}",
+        connection.unique_name()
+    );
+    loop {
+        thread::park();
+    }
+}
@@ -0,0 +1,360 @@
+//! # Fake wpa_supplicant
+//!
+//! Scripted control socket server to run the settings app's
+//! wpa_supplicant backend without a Wi-Fi device, point
+//! `backends.network.wpa_supplicant.ctrl_interface` in settings.yml
+//! at the socket it creates
+//!
+//! `cargo run --example fake_wpa_supplicant -- /tmp/fake_wpa_supplicant/wlan0`
+//!
+//! The scan always returns the access points below, joining one
+//! goes through association and the handshake before an address is
+//! assigned, a wrong passphrase leaves the network temporarily disabled
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    os::unix::net::UnixDatagram,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_SOCKET_PATH: &str = "/tmp/fake_wpa_supplicant/wlan0";
+
+const ASSOCIATE_TIME: Duration = Duration::from_millis(600);
+const HANDSHAKE_TIME: Duration = Duration::from_millis(1200);
+const DHCP_TIME: Duration = Duration::from_millis(2000);
+
+struct AccessPoint {
+    bssid: &'static str,
+    frequency: u32,
+    signal: i32,
+    flags: &'static str,
+    ssid: &'static str,
+    password: Option<&'static str>,
+}
+
+const ACCESS_POINTS: [AccessPoint; 5] = [
+    AccessPoint {
+        bssid: "a0:b1:c2:d3:e4:01",
+        frequency: 5180,
+        signal: -48,
+        flags: "[WPA2-PSK-CCMP][ESS]",
+        ssid: "Mecha Office",
+        password: Some("mecha1234"),
+    },
+    AccessPoint {
+        bssid: "a0:b1:c2:d3:e4:02",
+        frequency: 2437,
+        signal: -71,
+        flags: "[WPA2-PSK-CCMP][ESS]",
+        ssid: "Mecha Office",
+        password: Some("mecha1234"),
+    },
+    AccessPoint {
+        bssid: "a0:b1:c2:d3:e4:03",
+        frequency: 2412,
+        signal: -60,
+        flags: "[ESS]",
+        ssid: "Cafe Guest",
+        password: None,
+    },
+    AccessPoint {
+        bssid: "a0:b1:c2:d3:e4:04",
+        frequency: 5745,
+        signal: -66,
+        flags: "[WPA2-SAE-CCMP][ESS]",
+        ssid: "Lab WPA3",
+        password: Some("labsecret"),
+    },
+    AccessPoint {
+        bssid: "a0:b1:c2:d3:e4:05",
+        frequency: 2462,
+        signal: -82,
+        flags: "[WPA2-EAP-CCMP][ESS]",
+        ssid: "Corp\\xe2\\x98\\x95",
+        password: None,
+    },
+];
+
+struct Network {
+    id: u32,
+    parameters: HashMap<String, String>,
+    disabled: bool,
+    temp_disabled: bool,
+}
+
+impl Network {
+    fn ssid(&self) -> String {
+        let ssid = self.parameters.get("ssid").cloned().unwrap_or_default();
+        match ssid.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(quoted) => quoted.to_string(),
+            None => {
+                let bytes: Vec<u8> = (0..ssid.len() / 2)
+                    .filter_map(|i| u8::from_str_radix(&ssid[i * 2..i * 2 + 2], 16).ok())
+                    .collect();
+                String::from_utf8_lossy(&bytes).to_string()
+            }
+        }
+    }
+
+    fn password(&self) -> Option<String> {
+        ["psk", "sae_password"]
+            .iter()
+            .filter_map(|key| self.parameters.get(*key))
+            .map(|value| value.trim_matches('"').to_string())
+            .next()
+    }
+}
+
+struct Association {
+    id: u32,
+    started_at: Instant,
+}
+
+#[derive(Default)]
+struct FakeSupplicant {
+    networks: Vec<Network>,
+    next_id: u32,
+    association: Option<Association>,
+}
+
+impl FakeSupplicant {
+    fn access_point(&self, ssid: &str) -> Option<&'static AccessPoint> {
+        ACCESS_POINTS
+            .iter()
+            .filter(|ap| unescape(ap.ssid) == ssid)
+            .max_by_key(|ap| ap.signal)
+    }
+
+    fn network(&mut self, id: &str) -> Option<&mut Network> {
+        let id: u32 = id.parse().ok()?;
+        self.networks.iter_mut().find(|network| network.id == id)
+    }
+
+    /// Advances the scripted association and returns the STATUS lines
+    fn status(&mut self) -> String {
+        let association = match &self.association {
+            Some(association) => association,
+            None => return "wpa_state=DISCONNECTED\n".to_string(),
+        };
+        let elapsed = association.started_at.elapsed();
+        let id = association.id;
+
+        let network = match self.networks.iter_mut().find(|network| network.id == id) {
+            Some(network) => network,
+            None => {
+                self.association = None;
+                return "wpa_state=DISCONNECTED\n".to_string();
+            }
+        };
+        let ssid = network.ssid();
+        let access_point = match ACCESS_POINTS.iter().find(|ap| unescape(ap.ssid) == ssid) {
+            Some(access_point) => access_point,
+            None => return "wpa_state=SCANNING\n".to_string(),
+        };
+
+        if elapsed < ASSOCIATE_TIME {
+            return "wpa_state=ASSOCIATING\n".to_string();
+        }
+        if access_point.password.is_some() && elapsed < HANDSHAKE_TIME {
+            return "wpa_state=4WAY_HANDSHAKE\n".to_string();
+        }
+        if access_point.password.map(|p| p.to_string()) != network.password() {
+            network.temp_disabled = true;
+            self.association = None;
+            return "wpa_state=DISCONNECTED\n".to_string();
+        }
+
+        let mut status = format!(
+            "bssid={}\nfreq={}\nssid={}\nid={}\nmode=station\nkey_mgmt={}\nwpa_state=COMPLETED\n",
+            access_point.bssid,
+            access_point.frequency,
+            access_point.ssid,
+            id,
+            network.parameters.get("key_mgmt").cloned().unwrap_or("NONE".to_string()),
+        );
+        if elapsed > DHCP_TIME {
+            status.push_str("ip_address=192.168.1.50\n");
+        }
+        status
+    }
+
+    fn handle(&mut self, command: &str) -> String {
+        let mut fields = command.splitn(4, ' ');
+        let name = fields.next().unwrap_or_default();
+        let args: Vec<&str> = fields.collect();
+        println!("<- {}", name);
+
+        match (name, args.as_slice()) {
+            ("PING", []) => "PONG\n".to_string(),
+            ("STATUS", []) => self.status(),
+            ("SCAN", []) | ("SAVE_CONFIG", []) => "OK\n".to_string(),
+            ("SCAN_RESULTS", []) => {
+                let mut reply = "bssid / frequency / signal level / flags / ssid\n".to_string();
+                for ap in ACCESS_POINTS.iter() {
+                    reply.push_str(&format!(
+                        "{}\t{}\t{}\t{}\t{}\n",
+                        ap.bssid, ap.frequency, ap.signal, ap.flags, ap.ssid
+                    ));
+                }
+                reply
+            }
+            ("LIST_NETWORKS", []) => {
+                let current = self.association.as_ref().map(|a| a.id);
+                let mut reply = "network id / ssid / bssid / flags\n".to_string();
+                for network in self.networks.iter() {
+                    let mut flags = String::new();
+                    if Some(network.id) == current {
+                        flags.push_str("[CURRENT]");
+                    }
+                    if network.disabled {
+                        flags.push_str("[DISABLED]");
+                    }
+                    if network.temp_disabled {
+                        flags.push_str("[TEMP-DISABLED]");
+                    }
+                    reply.push_str(&format!("{}\t{}\tany\t{}\n", network.id, network.ssid(), flags));
+                }
+                reply
+            }
+            ("ADD_NETWORK", []) => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.networks.push(Network {
+                    id,
+                    parameters: HashMap::new(),
+                    disabled: true,
+                    temp_disabled: false,
+                });
+                format!("{}\n", id)
+            }
+            ("SET_NETWORK", [id, key, value]) => match self.network(id) {
+                Some(network) => {
+                    network.parameters.insert(key.to_string(), value.to_string());
+                    "OK\n".to_string()
+                }
+                None => "FAIL\n".to_string(),
+            },
+            ("GET_NETWORK", [id, key]) => match self.network(id) {
+                Some(network) => match network.parameters.get(*key) {
+                    // secrets are never returned
+                    Some(_) if ["psk", "sae_password", "password"].contains(key) => "*\n".to_string(),
+                    Some(value) => value.clone(),
+                    None => "FAIL\n".to_string(),
+                },
+                None => "FAIL\n".to_string(),
+            },
+            ("SELECT_NETWORK", [id]) => match self.network(id) {
+                Some(network) => {
+                    network.disabled = false;
+                    network.temp_disabled = false;
+                    let id = network.id;
+                    // like wpa_supplicant, every other network is disabled
+                    for network in self.networks.iter_mut().filter(|network| network.id != id) {
+                        network.disabled = true;
+                    }
+                    self.association = Some(Association {
+                        id,
+                        started_at: Instant::now(),
+                    });
+                    "OK\n".to_string()
+                }
+                None => "FAIL\n".to_string(),
+            },
+            ("ENABLE_NETWORK", [id]) | ("DISABLE_NETWORK", [id]) => {
+                let disabled = name == "DISABLE_NETWORK";
+                match self.network(id) {
+                    Some(network) => {
+                        network.disabled = disabled;
+                        "OK\n".to_string()
+                    }
+                    None => "FAIL\n".to_string(),
+                }
+            }
+            ("REMOVE_NETWORK", [id]) => {
+                let id: u32 = id.parse().unwrap_or(u32::MAX);
+                let count = self.networks.len();
+                self.networks.retain(|network| network.id != id);
+                if self.association.as_ref().map(|a| a.id) == Some(id) {
+                    self.association = None;
+                }
+                match self.networks.len() < count {
+                    true => "OK\n".to_string(),
+                    false => "FAIL\n".to_string(),
+                }
+            }
+            ("DISCONNECT", []) => {
+                self.association = None;
+                "OK\n".to_string()
+            }
+            ("RECONNECT", []) => {
+                let enabled = self
+                    .networks
+                    .iter()
+                    .find(|network| !network.disabled && self.access_point(&network.ssid()).is_some())
+                    .map(|network| network.id);
+                if let Some(id) = enabled {
+                    self.association = Some(Association {
+                        id,
+                        started_at: Instant::now(),
+                    });
+                }
+                "OK\n".to_string()
+            }
+            _ => "UNKNOWN COMMAND\n".to_string(),
+        }
+    }
+}
+
+/// Undoes the `\xNN` escaping used in the scripted SSIDs
+fn unescape(ssid: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = ssid;
+    while !rest.is_empty() {
+        match rest.strip_prefix("\\x") {
+            Some(hex) if hex.len() >= 2 => {
+                bytes.push(u8::from_str_radix(&hex[..2], 16).unwrap_or(b'?'));
+                rest = &hex[2..];
+            }
+            _ => {
+                bytes.push(rest.as_bytes()[0]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+fn main() -> std::io::Result<()> {
+    let socket_path = env::args().nth(1).unwrap_or(DEFAULT_SOCKET_PATH.to_string());
+    let socket_path = Path::new(&socket_path);
+    if let Some(parent) = socket_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _ = fs::remove_file(socket_path);
+
+    let socket = UnixDatagram::bind(socket_path)?;
+    println!("fake wpa_supplicant listening on {:?}", socket_path);
+    serve(socket)
+}
+
+/// Answers the commands sent to the socket, also run by the tests
+/// of the backend
+pub fn serve(socket: UnixDatagram) -> std::io::Result<()> {
+    let mut supplicant = FakeSupplicant::default();
+    let mut buffer = vec![0; 4096];
+    loop {
+        let (size, address) = socket.recv_from(&mut buffer)?;
+        let command = String::from_utf8_lossy(&buffer[..size]).to_string();
+        let reply = supplicant.handle(command.trim_end());
+        match address.as_pathname() {
+            Some(path) => {
+                if let Err(e) = socket.send_to(reply.as_bytes(), path) {
+                    println!("cannot reply to {:?} {}", path, e);
+                }
+            }
+            None => println!("dropping reply to unbound client"),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, info, warn};
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, MessageIterator, ProxyBuilder},
+    zvariant::{ObjectPath, OwnedObjectPath},
+    CacheProperties, MatchRule, Message, MessageType,
+};
+
+use super::{connect, map_call_error, proxies::AgentManager1ProxyBlocking, read_device};
+use crate::{
+    backends::bluetooth::{
+        BluetoothDevice, PairingEvent, PairingKind, PairingReply, PairingRequest,
+    },
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::DBusBusType,
+};
+
+const AGENT_PATH: &str = "/org/mecha/settings/bluetooth_agent";
+const AGENT_INTERFACE: &str = "org.bluez.Agent1";
+
+/// The app can show codes and has a keyboard to type them
+const AGENT_CAPABILITY: &str = "KeyboardDisplay";
+
+const REJECTED_ERROR: &str = "org.bluez.Error.Rejected";
+const CANCELED_ERROR: &str = "org.bluez.Error.Canceled";
+
+/// Registers the agent again whenever BlueZ joins the bus
+const WATCH_RULE: &str = "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='org.bluez'";
+
+/// Time before the agent connects again after losing the bus
+const AGENT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Request waiting for the user, with the call to answer
+struct PendingRequest {
+    kind: PairingKind,
+    call: Arc<Message>,
+}
+
+/// # Pairing Agent
+///
+/// Serves `org.bluez.Agent1` on a connection of its own, calls are
+/// answered from the pages once the user decided
+#[derive(Default)]
+pub struct PairingAgent {
+    connection: Option<Connection>,
+    pending: HashMap<u64, PendingRequest>,
+    next_id: u64,
+}
+
+impl PairingAgent {
+    pub fn reply(&mut self, id: u64, reply: PairingReply) -> Result<()> {
+        let request = match self.pending.remove(&id) {
+            Some(request) => request,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendCallError,
+                    "the pairing request is no longer pending".to_string(),
+                ));
+            }
+        };
+        let connection = match self.connection.as_ref() {
+            Some(connection) => connection,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendConnectError,
+                    "the pairing agent is not connected".to_string(),
+                ));
+            }
+        };
+
+        info!(
+            task = "bluetooth_agent",
+            "replying {:?} to request {}", reply, id
+        );
+        let call = request.call.as_ref();
+        let result = match (&request.kind, reply) {
+            (_, PairingReply::Reject) => {
+                connection.reply_error(call, REJECTED_ERROR, &"rejected by the user")
+            }
+            (PairingKind::Passkey, PairingReply::Passkey(passkey)) => {
+                connection.reply(call, &passkey)
+            }
+            (PairingKind::PinCode, PairingReply::PinCode(pin_code)) => {
+                connection.reply(call, &pin_code)
+            }
+            (
+                PairingKind::Confirmation(_)
+                | PairingKind::Authorization
+                | PairingKind::AuthorizeService(_),
+                PairingReply::Accept,
+            ) => connection.reply(call, &()),
+            (kind, reply) => {
+                // keep BlueZ from waiting on an answer that never comes
+                let _ = connection.reply_error(call, REJECTED_ERROR, &"invalid reply");
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendCallError,
+                    format!("{:?} does not answer {:?}", reply, kind),
+                ));
+            }
+        };
+        map_call_error("reply to agent request", result.map(|_| ()))
+    }
+
+    fn request(&mut self, kind: PairingKind, call: Option<Arc<Message>>) -> u64 {
+        self.next_id += 1;
+        if let Some(call) = call {
+            self.pending
+                .insert(self.next_id, PendingRequest { kind, call });
+        }
+        self.next_id
+    }
+
+    /// Drops every pending request, returns their ids
+    fn cancel_all(&mut self) -> Vec<u64> {
+        let ids = self.pending.keys().copied().collect();
+        self.pending.clear();
+        ids
+    }
+}
+
+pub fn run_agent(
+    bus: DBusBusType,
+    agent: Arc<Mutex<PairingAgent>>,
+    on_event: Box<dyn Fn(PairingEvent) + Send + 'static>,
+) {
+    thread::spawn(move || loop {
+        if let Err(e) = serve_agent(bus, &agent, &on_event) {
+            warn!(
+                task = "bluetooth_agent",
+                "error while serving the pairing agent - {}", e
+            );
+        }
+        let mut agent = agent.lock().unwrap();
+        agent.connection = None;
+        for id in agent.cancel_all() {
+            on_event(PairingEvent::Cancelled(id));
+        }
+        drop(agent);
+        thread::sleep(AGENT_RETRY_INTERVAL);
+    });
+}
+
+fn register(connection: &Connection) {
+    let agent_manager = ProxyBuilder::<AgentManager1ProxyBlocking>::new(connection)
+        .cache_properties(CacheProperties::No)
+        .build();
+    let agent_path = ObjectPath::from_static_str_unchecked(AGENT_PATH);
+    let result = agent_manager.and_then(|agent_manager| {
+        agent_manager.register_agent(&agent_path, AGENT_CAPABILITY)?;
+        agent_manager.request_default_agent(&agent_path)
+    });
+    match result {
+        Ok(()) => info!(task = "bluetooth_agent", "pairing agent registered"),
+        // bluez is not running, the agent registers once it starts
+        Err(e) => debug!(
+            task = "bluetooth_agent",
+            "cannot register pairing agent - {}", e
+        ),
+    }
+}
+
+fn serve_agent(
+    bus: DBusBusType,
+    agent: &Arc<Mutex<PairingAgent>>,
+    on_event: &(dyn Fn(PairingEvent) + Send),
+) -> Result<()> {
+    let connection = connect(bus)?;
+    let dbus = map_call_error("build dbus proxy", DBusProxy::new(&connection))?;
+    let rule = map_call_error("parse match rule", MatchRule::try_from(WATCH_RULE))?;
+    map_call_error(
+        "add match rule",
+        dbus.add_match_rule(rule).map_err(zbus::Error::from),
+    )?;
+
+    agent.lock().unwrap().connection = Some(connection.clone());
+    register(&connection);
+
+    for message in MessageIterator::from(&connection) {
+        let message = map_call_error("receive message", message)?;
+        let member = match message.member() {
+            Some(member) => member.to_string(),
+            None => continue,
+        };
+
+        match message.message_type() {
+            MessageType::Signal if member == "NameOwnerChanged" => {
+                let (_, _, new_owner): (String, String, String) =
+                    map_call_error("read NameOwnerChanged", message.body())?;
+                if new_owner.is_empty() {
+                    for id in agent.lock().unwrap().cancel_all() {
+                        on_event(PairingEvent::Cancelled(id));
+                    }
+                } else {
+                    register(&connection);
+                }
+            }
+            MessageType::MethodCall => {
+                let is_agent_call = message.path().is_some_and(|path| path == AGENT_PATH)
+                    && message
+                        .interface()
+                        .is_some_and(|name| name == AGENT_INTERFACE);
+                if !is_agent_call {
+                    let _ = connection.reply_error(
+                        &message,
+                        "org.freedesktop.DBus.Error.UnknownMethod",
+                        &format!("unknown method {}", member),
+                    );
+                    continue;
+                }
+                handle_call(&connection, agent, on_event, message, &member)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn handle_call(
+    connection: &Connection,
+    agent: &Arc<Mutex<PairingAgent>>,
+    on_event: &(dyn Fn(PairingEvent) + Send),
+    call: Arc<Message>,
+    member: &str,
+) -> Result<()> {
+    debug!(task = "bluetooth_agent", "agent call {}", member);
+    let (device_path, kind) = match member {
+        "Release" | "Cancel" => {
+            let _ = connection.reply(&call, &());
+            for id in agent.lock().unwrap().cancel_all() {
+                on_event(PairingEvent::Cancelled(id));
+            }
+            return Ok(());
+        }
+        "RequestPinCode" => {
+            let (device,): (OwnedObjectPath,) = map_call_error("read call", call.body())?;
+            (device, PairingKind::PinCode)
+        }
+        "DisplayPinCode" => {
+            let (device, pin_code): (OwnedObjectPath, String) =
+                map_call_error("read call", call.body())?;
+            (device, PairingKind::DisplayPinCode(pin_code))
+        }
+        "RequestPasskey" => {
+            let (device,): (OwnedObjectPath,) = map_call_error("read call", call.body())?;
+            (device, PairingKind::Passkey)
+        }
+        "DisplayPasskey" => {
+            let (device, passkey, entered): (OwnedObjectPath, u32, u16) =
+                map_call_error("read call", call.body())?;
+            (device, PairingKind::DisplayPasskey(passkey, entered))
+        }
+        "RequestConfirmation" => {
+            let (device, passkey): (OwnedObjectPath, u32) =
+                map_call_error("read call", call.body())?;
+            (device, PairingKind::Confirmation(passkey))
+        }
+        "RequestAuthorization" => {
+            let (device,): (OwnedObjectPath,) = map_call_error("read call", call.body())?;
+            (device, PairingKind::Authorization)
+        }
+        "AuthorizeService" => {
+            let (device, uuid): (OwnedObjectPath, String) =
+                map_call_error("read call", call.body())?;
+            (device, PairingKind::AuthorizeService(uuid))
+        }
+        _ => {
+            let _ = connection.reply_error(
+                &call,
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                &format!("unknown method {}", member),
+            );
+            return Ok(());
+        }
+    };
+
+    let device = match read_device(connection, device_path.as_str()) {
+        Ok(Some(device)) => device,
+        Ok(None) => {
+            let _ = connection.reply_error(&call, CANCELED_ERROR, &"unknown device");
+            return Ok(());
+        }
+        Err(e) => {
+            warn!(
+                task = "bluetooth_agent",
+                "cannot read {} - {}", device_path, e
+            );
+            BluetoothDevice {
+                id: device_path.to_string(),
+                ..Default::default()
+            }
+        }
+    };
+
+    let id = match kind.needs_reply() {
+        true => agent
+            .lock()
+            .unwrap()
+            .request(kind.clone(), Some(call.clone())),
+        false => {
+            let _ = connection.reply(&call, &());
+            agent.lock().unwrap().request(kind.clone(), None)
+        }
+    };
+    info!(
+        task = "bluetooth_agent",
+        "request {} {:?} for {}", id, kind, device.address
+    );
+    on_event(PairingEvent::Requested(PairingRequest { id, device, kind }));
+    Ok(())
+}
@@ -0,0 +1,581 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, info, warn};
+use zbus::{
+    blocking::{
+        fdo::DBusProxy, fdo::ObjectManagerProxy, Connection, MessageIterator, ProxyBuilder,
+    },
+    fdo::{self, ManagedObjects},
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    CacheProperties, MatchRule, MessageType,
+};
+
+use super::{
+    AdapterState, BluetoothBackend, BluetoothDevice, BluetoothEvent, DeviceFilter, PairingEvent,
+    PairingReply, ReceivedFile, TransferEvent,
+};
+use crate::{
+    backends::dbus::{self, BusConnection},
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::{BluetoothBackendSettings, DBusBusType},
+};
+
+mod agent;
+mod obex;
+mod proxies;
+#[cfg(test)]
+mod tests;
+
+use agent::PairingAgent;
+use obex::FileAgent;
+use proxies::{Adapter1ProxyBlocking, Device1ProxyBlocking};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+
+/// Signals of BlueZ itself and of it leaving or joining the bus
+const WATCH_RULES: [&str; 2] = [
+    "type='signal',sender='org.bluez'",
+    "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='org.bluez'",
+];
+
+/// Time before the watcher connects again after losing the bus
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// # BlueZ Backend
+///
+/// Implements the bluetooth backend over the BlueZ D-Bus API for the
+/// configured adapter, the connection to the bus is opened on first
+/// use. Files are received through obexd on `obex_bus`
+pub struct BluezBackend {
+    bus: DBusBusType,
+    obex_bus: DBusBusType,
+    adapter_path: String,
+    connection: BusConnection,
+    agent: Arc<Mutex<PairingAgent>>,
+    file_agent: Arc<Mutex<FileAgent>>,
+}
+
+impl BluezBackend {
+    pub fn new(settings: &BluetoothBackendSettings) -> Self {
+        Self {
+            bus: settings.bus,
+            obex_bus: settings.obex_bus,
+            adapter_path: format!("/org/bluez/{}", settings.adapter),
+            connection: BusConnection::new(
+                settings.bus,
+                "bluetooth_backend",
+                LockScreenErrorCodes::BluetoothBackendConnectError,
+            ),
+            agent: Arc::new(Mutex::new(PairingAgent::default())),
+            file_agent: Arc::new(Mutex::new(FileAgent::new(settings))),
+        }
+    }
+
+    fn adapter_proxy(&self) -> Result<Adapter1ProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<Adapter1ProxyBlocking>::new(&connection)
+            .path(self.adapter_path.clone())
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+        map_call_error("build adapter proxy", proxy)
+    }
+
+    fn device_proxy(&self, id: &str) -> Result<Device1ProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<Device1ProxyBlocking>::new(&connection)
+            .path(id.to_string())
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+        map_call_error("build device proxy", proxy)
+    }
+
+    /// Fails with a clear message when the adapter is missing, the
+    /// bare D-Bus error only names the unknown object
+    fn require_adapter(&self) -> Result<()> {
+        if self.adapter()?.is_none() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::BluetoothBackendCallError,
+                format!("no bluetooth adapter at {}", self.adapter_path),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl BluetoothBackend for BluezBackend {
+    fn adapter(&self) -> Result<Option<AdapterState>> {
+        let objects = managed_objects(&self.connection.get()?)?;
+        Ok(objects.and_then(|objects| adapter_from_objects(&objects, &self.adapter_path)))
+    }
+
+    fn set_powered(&self, powered: bool) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "setting powered {}", powered);
+        map_call_error("set powered", self.adapter_proxy()?.set_powered(powered))
+    }
+
+    fn start_discovery(&self) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "starting discovery");
+        map_call_error("start discovery", self.adapter_proxy()?.start_discovery())
+    }
+
+    fn stop_discovery(&self) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "stopping discovery");
+        map_call_error("stop discovery", self.adapter_proxy()?.stop_discovery())
+    }
+
+    fn set_discovery_filter(&self, filter: DeviceFilter) -> Result<()> {
+        self.require_adapter()?;
+        let transport = match filter {
+            DeviceFilter::All => "auto",
+            DeviceFilter::Classic => "bredr",
+            DeviceFilter::LowEnergy => "le",
+        };
+        info!(task = "bluetooth_backend", "setting discovery transport {}", transport);
+        let filter = HashMap::from([("Transport", Value::from(transport))]);
+        map_call_error(
+            "set discovery filter",
+            self.adapter_proxy()?.set_discovery_filter(filter),
+        )
+    }
+
+    fn set_adapter_alias(&self, alias: &str) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "renaming adapter to {:?}", alias);
+        map_call_error("set adapter alias", self.adapter_proxy()?.set_alias(alias))
+    }
+
+    fn set_discoverable(&self, discoverable: bool) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "setting discoverable {}", discoverable);
+        map_device_error(
+            "set discoverable",
+            self.adapter_proxy()?.set_discoverable(discoverable),
+        )
+    }
+
+    fn set_discoverable_timeout(&self, timeout: u32) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "setting discoverable timeout {}", timeout);
+        map_call_error(
+            "set discoverable timeout",
+            self.adapter_proxy()?.set_discoverable_timeout(timeout),
+        )
+    }
+
+    fn set_pairable(&self, pairable: bool) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "setting pairable {}", pairable);
+        map_device_error("set pairable", self.adapter_proxy()?.set_pairable(pairable))
+    }
+
+    fn devices(&self) -> Result<Vec<BluetoothDevice>> {
+        let objects = match managed_objects(&self.connection.get()?)? {
+            Some(objects) => objects,
+            None => return Ok(vec![]),
+        };
+        Ok(objects
+            .iter()
+            .filter(|(path, _)| is_device_of(path.as_str(), &self.adapter_path))
+            .filter_map(|(path, interfaces)| {
+                let properties = |name: &str| {
+                    interfaces
+                        .iter()
+                        .find(|(interface, _)| interface.as_str() == name)
+                        .map(|(_, properties)| properties)
+                };
+                let mut device = device_from_properties(path.as_str(), properties(DEVICE_INTERFACE)?);
+                device.battery = properties(BATTERY_INTERFACE).and_then(battery_from_properties);
+                Some(device)
+            })
+            .collect())
+    }
+
+    fn watch(&self, on_event: Box<dyn Fn(BluetoothEvent) + Send + 'static>) {
+        let bus = self.bus;
+        let adapter_path = self.adapter_path.clone();
+        thread::spawn(move || loop {
+            if let Err(e) = watch_signals(bus, &adapter_path, &on_event) {
+                warn!(
+                    task = "bluetooth_backend",
+                    "error while watching bluez - {}", e
+                );
+            }
+            thread::sleep(WATCH_RETRY_INTERVAL);
+        });
+    }
+
+    fn pair(&self, id: &str) -> Result<()> {
+        info!(task = "bluetooth_backend", "pairing {}", id);
+        map_device_error("pair", self.device_proxy(id)?.pair())
+    }
+
+    fn register_agent(&self, on_event: Box<dyn Fn(PairingEvent) + Send + 'static>) {
+        agent::run_agent(self.bus, self.agent.clone(), on_event);
+    }
+
+    fn reply_pairing(&self, id: u64, reply: PairingReply) -> Result<()> {
+        self.agent.lock().unwrap().reply(id, reply)
+    }
+
+    fn connect(&self, id: &str) -> Result<()> {
+        info!(task = "bluetooth_backend", "connecting {}", id);
+        map_device_error("connect", self.device_proxy(id)?.connect())
+    }
+
+    fn disconnect(&self, id: &str) -> Result<()> {
+        info!(task = "bluetooth_backend", "disconnecting {}", id);
+        map_device_error("disconnect", self.device_proxy(id)?.disconnect())
+    }
+
+    fn set_alias(&self, id: &str, alias: &str) -> Result<()> {
+        info!(task = "bluetooth_backend", "renaming {} to {:?}", id, alias);
+        map_device_error("set alias", self.device_proxy(id)?.set_alias(alias))
+    }
+
+    fn set_trusted(&self, id: &str, trusted: bool) -> Result<()> {
+        info!(task = "bluetooth_backend", "setting {} trusted {}", id, trusted);
+        map_device_error("set trusted", self.device_proxy(id)?.set_trusted(trusted))
+    }
+
+    fn set_blocked(&self, id: &str, blocked: bool) -> Result<()> {
+        info!(task = "bluetooth_backend", "setting {} blocked {}", id, blocked);
+        map_device_error("set blocked", self.device_proxy(id)?.set_blocked(blocked))
+    }
+
+    fn remove_device(&self, id: &str) -> Result<()> {
+        self.require_adapter()?;
+        info!(task = "bluetooth_backend", "removing {}", id);
+        let path = map_call_error(
+            "parse device path",
+            ObjectPath::try_from(id).map_err(zbus::Error::from),
+        )?;
+        map_device_error("remove device", self.adapter_proxy()?.remove_device(&path))
+    }
+
+    fn register_file_agent(&self, on_event: Box<dyn Fn(TransferEvent) + Send + 'static>) {
+        obex::run_file_agent(
+            self.obex_bus,
+            self.bus,
+            self.adapter_path.clone(),
+            self.file_agent.clone(),
+            on_event,
+        );
+    }
+
+    fn reply_file(&self, id: u64, accept: bool) -> Result<()> {
+        self.file_agent.lock().unwrap().reply(id, accept)
+    }
+
+    fn cancel_transfer(&self, id: u64) -> Result<()> {
+        obex::cancel_transfer(&self.file_agent, id)
+    }
+
+    fn received_files(&self) -> Result<Vec<ReceivedFile>> {
+        self.file_agent.lock().unwrap().received_files()
+    }
+}
+
+fn connect(bus: DBusBusType) -> Result<Connection> {
+    dbus::connect(bus, LockScreenErrorCodes::BluetoothBackendConnectError)
+}
+
+/// Returns every object BlueZ exports, `None` while it is not running
+fn managed_objects(connection: &Connection) -> Result<Option<ManagedObjects>> {
+    let proxy = ObjectManagerProxy::builder(connection)
+        .destination(BLUEZ_SERVICE)
+        .and_then(|builder| builder.path("/"))
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+    let proxy = map_call_error("build object manager proxy", proxy)?;
+
+    match proxy.get_managed_objects() {
+        Ok(objects) => Ok(Some(objects)),
+        Err(fdo::Error::ServiceUnknown(_)) | Err(fdo::Error::NameHasNoOwner(_)) => {
+            debug!(task = "bluetooth_backend", "bluez is not running");
+            Ok(None)
+        }
+        Err(e) => map_call_error("get managed objects", Err(e.into())),
+    }
+}
+
+fn adapter_from_objects(objects: &ManagedObjects, adapter_path: &str) -> Option<AdapterState> {
+    objects
+        .iter()
+        .find(|(path, _)| path.as_str() == adapter_path)
+        .and_then(|(_, interfaces)| {
+            interfaces
+                .iter()
+                .find(|(interface, _)| interface.as_str() == ADAPTER_INTERFACE)
+        })
+        .map(|(_, properties)| adapter_from_properties(properties))
+}
+
+fn adapter_from_properties(properties: &HashMap<String, OwnedValue>) -> AdapterState {
+    AdapterState {
+        address: string_property(properties, "Address").unwrap_or_default(),
+        alias: string_property(properties, "Alias").unwrap_or_default(),
+        powered: bool_property(properties, "Powered").unwrap_or(false),
+        discovering: bool_property(properties, "Discovering").unwrap_or(false),
+        discoverable: bool_property(properties, "Discoverable").unwrap_or(false),
+        discoverable_timeout: properties
+            .get("DiscoverableTimeout")
+            .and_then(|value| value.downcast_ref::<u32>())
+            .copied()
+            .unwrap_or_default(),
+        pairable: bool_property(properties, "Pairable").unwrap_or(false),
+    }
+}
+
+fn device_from_properties(path: &str, properties: &HashMap<String, OwnedValue>) -> BluetoothDevice {
+    BluetoothDevice {
+        id: path.to_string(),
+        address: string_property(properties, "Address").unwrap_or_default(),
+        alias: string_property(properties, "Alias").unwrap_or_default(),
+        icon: string_property(properties, "Icon"),
+        // 0 is the unknown class or appearance, it tells nothing either
+        class: properties
+            .get("Class")
+            .and_then(|value| value.downcast_ref::<u32>())
+            .copied()
+            .filter(|class| *class != 0),
+        appearance: properties
+            .get("Appearance")
+            .and_then(|value| value.downcast_ref::<u16>())
+            .copied()
+            .filter(|appearance| *appearance != 0),
+        random_address: string_property(properties, "AddressType").as_deref() == Some("random"),
+        paired: bool_property(properties, "Paired").unwrap_or(false),
+        trusted: bool_property(properties, "Trusted").unwrap_or(false),
+        blocked: bool_property(properties, "Blocked").unwrap_or(false),
+        connected: bool_property(properties, "Connected").unwrap_or(false),
+        rssi: properties
+            .get("RSSI")
+            .and_then(|value| value.downcast_ref::<i16>())
+            .copied(),
+        battery: None,
+    }
+}
+
+fn battery_from_properties(properties: &HashMap<String, OwnedValue>) -> Option<u8> {
+    properties
+        .get("Percentage")
+        .and_then(|value| value.downcast_ref::<u8>())
+        .copied()
+}
+
+fn string_property(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<String> {
+    properties
+        .get(name)
+        .and_then(|value| value.downcast_ref::<str>())
+        .map(|value| value.to_string())
+}
+
+fn bool_property(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<bool> {
+    properties
+        .get(name)
+        .and_then(|value| value.downcast_ref::<bool>())
+        .copied()
+}
+
+/// Devices are exported below their adapter, `/org/bluez/hci0/dev_..`
+fn is_device_of(path: &str, adapter_path: &str) -> bool {
+    path.strip_prefix(adapter_path)
+        .map(|rest| rest.starts_with("/dev_") && !rest[1..].contains('/'))
+        .unwrap_or(false)
+}
+
+/// Reads a device again after one of its properties changed, a
+/// `PropertiesChanged` signal only carries the changed ones
+fn read_device(connection: &Connection, path: &str) -> Result<Option<BluetoothDevice>> {
+    let proxy = zbus::blocking::fdo::PropertiesProxy::builder(connection)
+        .destination(BLUEZ_SERVICE)
+        .and_then(|builder| builder.path(path.to_string()))
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+    let proxy = map_call_error("build properties proxy", proxy)?;
+
+    let get_all = |name: &'static str| {
+        let interface = zbus::names::InterfaceName::from_static_str_unchecked(name);
+        match proxy.get_all(interface) {
+            Ok(properties) => Ok(Some(properties)),
+            Err(fdo::Error::UnknownObject(_)) | Err(fdo::Error::UnknownInterface(_)) => Ok(None),
+            Err(e) => map_call_error("get device properties", Err(e.into())),
+        }
+    };
+
+    let mut device = match get_all(DEVICE_INTERFACE)? {
+        Some(properties) => device_from_properties(path, &properties),
+        None => return Ok(None),
+    };
+    // most devices do not report a battery
+    device.battery = get_all(BATTERY_INTERFACE)?
+        .as_ref()
+        .and_then(battery_from_properties);
+    Ok(Some(device))
+}
+
+fn read_adapter(connection: &Connection, adapter_path: &str) -> Result<Option<AdapterState>> {
+    let objects = managed_objects(connection)?;
+    Ok(objects.and_then(|objects| adapter_from_objects(&objects, adapter_path)))
+}
+
+/// Forwards BlueZ signals of the adapter and its devices until the
+/// connection breaks
+fn watch_signals(
+    bus: DBusBusType,
+    adapter_path: &str,
+    on_event: &(dyn Fn(BluetoothEvent) + Send),
+) -> Result<()> {
+    let connection = connect(bus)?;
+    let dbus = map_call_error("build dbus proxy", DBusProxy::new(&connection))?;
+    for rule in WATCH_RULES {
+        let rule = map_call_error("parse match rule", MatchRule::try_from(rule))?;
+        map_call_error(
+            "add match rule",
+            dbus.add_match_rule(rule).map_err(zbus::Error::from),
+        )?;
+    }
+
+    // bluez may have changed while the watcher was away
+    on_event(BluetoothEvent::AdapterChanged(read_adapter(
+        &connection,
+        adapter_path,
+    )?));
+
+    for message in MessageIterator::from(&connection) {
+        let message = map_call_error("receive signal", message)?;
+        if message.message_type() != MessageType::Signal {
+            continue;
+        }
+        let path = match message.path() {
+            Some(path) => path.to_string(),
+            None => continue,
+        };
+        let member = match message.member() {
+            Some(member) => member.to_string(),
+            None => continue,
+        };
+
+        match member.as_str() {
+            "NameOwnerChanged" => {
+                let (_, _, new_owner): (String, String, String) =
+                    map_call_error("read NameOwnerChanged", message.body())?;
+                debug!(
+                    task = "bluetooth_backend",
+                    "bluez owner is now {:?}", new_owner
+                );
+                on_event(BluetoothEvent::AdapterChanged(match new_owner.is_empty() {
+                    true => None,
+                    false => read_adapter(&connection, adapter_path)?,
+                }));
+            }
+            "InterfacesAdded" => {
+                let (object_path, interfaces): (
+                    OwnedObjectPath,
+                    HashMap<String, HashMap<String, OwnedValue>>,
+                ) = map_call_error("read InterfacesAdded", message.body())?;
+                let object_path = object_path.as_str();
+                if object_path == adapter_path && interfaces.contains_key(ADAPTER_INTERFACE) {
+                    on_event(BluetoothEvent::AdapterChanged(Some(
+                        adapter_from_properties(&interfaces[ADAPTER_INTERFACE]),
+                    )));
+                } else if is_device_of(object_path, adapter_path) {
+                    if let Some(properties) = interfaces.get(DEVICE_INTERFACE) {
+                        let mut device = device_from_properties(object_path, properties);
+                        device.battery = interfaces
+                            .get(BATTERY_INTERFACE)
+                            .and_then(battery_from_properties);
+                        on_event(BluetoothEvent::DeviceChanged(device));
+                    } else if interfaces.contains_key(BATTERY_INTERFACE) {
+                        // the battery of a known device showed up
+                        if let Some(device) = read_device(&connection, object_path)? {
+                            on_event(BluetoothEvent::DeviceChanged(device));
+                        }
+                    }
+                }
+            }
+            "InterfacesRemoved" => {
+                let (object_path, interfaces): (OwnedObjectPath, Vec<String>) =
+                    map_call_error("read InterfacesRemoved", message.body())?;
+                let object_path = object_path.as_str();
+                let interface_removed = |name: &str| interfaces.iter().any(|i| i == name);
+                if object_path == adapter_path && interface_removed(ADAPTER_INTERFACE) {
+                    on_event(BluetoothEvent::AdapterChanged(None));
+                } else if is_device_of(object_path, adapter_path)
+                    && interface_removed(DEVICE_INTERFACE)
+                {
+                    on_event(BluetoothEvent::DeviceRemoved(object_path.to_string()));
+                } else if is_device_of(object_path, adapter_path)
+                    && interface_removed(BATTERY_INTERFACE)
+                {
+                    if let Some(device) = read_device(&connection, object_path)? {
+                        on_event(BluetoothEvent::DeviceChanged(device));
+                    }
+                }
+            }
+            "PropertiesChanged" => {
+                let (interface, _, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                    map_call_error("read PropertiesChanged", message.body())?;
+                if path == adapter_path && interface == ADAPTER_INTERFACE {
+                    on_event(BluetoothEvent::AdapterChanged(read_adapter(
+                        &connection,
+                        adapter_path,
+                    )?));
+                } else if is_device_of(&path, adapter_path)
+                    && (interface == DEVICE_INTERFACE || interface == BATTERY_INTERFACE)
+                {
+                    if let Some(device) = read_device(&connection, &path)? {
+                        on_event(BluetoothEvent::DeviceChanged(device));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Names the common BlueZ errors of device calls, the others keep
+/// the bare D-Bus error
+fn map_device_error<T>(call: &str, result: zbus::Result<T>) -> Result<T> {
+    let name = match &result {
+        Err(zbus::Error::MethodError(name, _, _)) => name.as_str(),
+        _ => return map_call_error(call, result),
+    };
+    let message = match name {
+        "org.bluez.Error.AuthenticationCanceled" => "pairing was cancelled",
+        "org.bluez.Error.AuthenticationRejected" => "pairing was rejected",
+        "org.bluez.Error.AuthenticationFailed" => "pairing failed, check the code",
+        "org.bluez.Error.AuthenticationTimeout" => "the device did not answer in time",
+        "org.bluez.Error.AlreadyExists" => "the device is already paired",
+        "org.bluez.Error.AlreadyConnected" => "the device is already connected",
+        "org.bluez.Error.NotConnected" => "the device is not connected",
+        "org.bluez.Error.ConnectionAttemptFailed" | "org.bluez.Error.PageTimeout" => {
+            "the device is out of reach"
+        }
+        "org.bluez.Error.NotReady" => "bluetooth is turned off",
+        "org.bluez.Error.DoesNotExist" => "the device is no longer known",
+        _ => return map_call_error(call, result),
+    };
+    bail!(LockScreenError::new(
+        LockScreenErrorCodes::BluetoothBackendCallError,
+        message.to_string(),
+    ));
+}
+
+fn map_call_error<T>(call: &str, result: zbus::Result<T>) -> Result<T> {
+    dbus::map_call_error(
+        LockScreenErrorCodes::BluetoothBackendCallError,
+        "bluez",
+        call,
+        result,
+    )
+}
@@ -0,0 +1,624 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use custom_utils::{atomic_file::write_atomic, local_date::LocalDate};
+use tracing::{debug, info, warn};
+use zbus::{
+    blocking::{fdo::DBusProxy, fdo::PropertiesProxy, Connection, MessageIterator, ProxyBuilder},
+    names::InterfaceName,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue},
+    CacheProperties, MatchRule, Message, MessageType,
+};
+
+use super::{
+    connect, map_call_error,
+    proxies::{ObexAgentManager1ProxyBlocking, Session1ProxyBlocking, Transfer1ProxyBlocking},
+    read_device,
+};
+use crate::{
+    backends::bluetooth::{IncomingFile, ReceivedFile, TransferEvent},
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::{BluetoothBackendSettings, DBusBusType},
+};
+
+const OBEX_SERVICE: &str = "org.bluez.obex";
+const AGENT_PATH: &str = "/org/mecha/settings/obex_agent";
+const AGENT_INTERFACE: &str = "org.bluez.obex.Agent1";
+const TRANSFER_INTERFACE: &str = "org.bluez.obex.Transfer1";
+
+const REJECTED_ERROR: &str = "org.bluez.obex.Error.Rejected";
+const CANCELED_ERROR: &str = "org.bluez.obex.Error.Canceled";
+
+/// Progress of transfers and obexd leaving or joining the bus
+const WATCH_RULES: [&str; 2] = [
+    "type='signal',sender='org.bluez.obex',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged'",
+    "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='org.bluez.obex'",
+];
+
+/// Time before the agent connects again after losing the bus
+const AGENT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Entries kept in the history of received files
+const HISTORY_LENGTH: usize = 100;
+
+/// Offer waiting for the user, with the call to answer
+struct PendingFile {
+    file: IncomingFile,
+    transfer: String,
+    call: Arc<Message>,
+}
+
+/// Accepted file obexd is writing to `path`
+struct ActiveTransfer {
+    file: IncomingFile,
+    transfer: String,
+    path: PathBuf,
+    cancelled: bool,
+}
+
+/// Where the sender of a file is looked up by its address
+struct DeviceLookup {
+    bus: DBusBusType,
+    adapter_path: String,
+}
+
+/// # File Agent
+///
+/// Serves `org.bluez.obex.Agent1` on a connection of its own to
+/// obexd, offered files are answered from the pages and saved in
+/// the download directory
+pub struct FileAgent {
+    download_dir: PathBuf,
+    history_path: PathBuf,
+    connection: Option<Connection>,
+    pending: HashMap<u64, PendingFile>,
+    active: HashMap<u64, ActiveTransfer>,
+    next_id: u64,
+}
+
+impl FileAgent {
+    pub fn new(settings: &BluetoothBackendSettings) -> Self {
+        Self {
+            download_dir: PathBuf::from(&settings.download_dir),
+            history_path: PathBuf::from(&settings.received_files_path),
+            connection: None,
+            pending: HashMap::new(),
+            active: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn reply(&mut self, id: u64, accept: bool) -> Result<()> {
+        let pending = match self.pending.remove(&id) {
+            Some(pending) => pending,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendCallError,
+                    "the file is no longer offered".to_string(),
+                ));
+            }
+        };
+        let connection = match self.connection.as_ref() {
+            Some(connection) => connection,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendConnectError,
+                    "the file transfer agent is not connected".to_string(),
+                ));
+            }
+        };
+
+        let call = pending.call.as_ref();
+        if !accept {
+            info!(task = "obex_agent", "declining file {}", id);
+            return map_call_error(
+                "reply to agent request",
+                connection
+                    .reply_error(call, REJECTED_ERROR, &"declined by the user")
+                    .map(|_| ()),
+            );
+        }
+
+        if let Err(e) = fs::create_dir_all(&self.download_dir) {
+            // obexd waits for an answer either way
+            let _ = connection.reply_error(call, REJECTED_ERROR, &"cannot save the file");
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::BluetoothFileReceiveError,
+                format!("cannot create {:?} - {}", self.download_dir, e),
+            ));
+        }
+        let path = unique_path(&self.download_dir, &pending.file.filename);
+        info!(task = "obex_agent", "saving file {} to {:?}", id, path);
+        map_call_error(
+            "reply to agent request",
+            connection
+                .reply(call, &path.to_string_lossy().as_ref())
+                .map(|_| ()),
+        )?;
+        self.active.insert(
+            id,
+            ActiveTransfer {
+                file: pending.file,
+                transfer: pending.transfer,
+                path,
+                cancelled: false,
+            },
+        );
+        Ok(())
+    }
+
+    /// Marks an accepted file as cancelled, returns the connection
+    /// and transfer to cancel it on. Only the agent may cancel
+    /// incoming transfers so it goes over the connection of the agent
+    pub fn cancel(&mut self, id: u64) -> Result<(Connection, String)> {
+        let transfer = match self.active.get_mut(&id) {
+            Some(transfer) => transfer,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendCallError,
+                    "the file is no longer being received".to_string(),
+                ));
+            }
+        };
+        let connection = match self.connection.as_ref() {
+            Some(connection) => connection.clone(),
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::BluetoothBackendConnectError,
+                    "the file transfer agent is not connected".to_string(),
+                ));
+            }
+        };
+        transfer.cancelled = true;
+        Ok((connection, transfer.transfer.clone()))
+    }
+
+    pub fn received_files(&self) -> Result<Vec<ReceivedFile>> {
+        read_history(&self.history_path)
+    }
+
+    fn request(&mut self, file: IncomingFile, transfer: String, call: Arc<Message>) -> u64 {
+        self.next_id += 1;
+        let file = IncomingFile {
+            id: self.next_id,
+            ..file
+        };
+        self.pending
+            .insert(self.next_id, PendingFile { file, transfer, call });
+        self.next_id
+    }
+
+    /// Drops every pending offer
+    fn cancel_pending(&mut self) -> Vec<TransferEvent> {
+        let events = self
+            .pending
+            .keys()
+            .map(|id| TransferEvent::Cancelled(*id))
+            .collect();
+        self.pending.clear();
+        events
+    }
+
+    /// Drops every offer and fails every transfer, obexd went away
+    fn cancel_all(&mut self) -> Vec<TransferEvent> {
+        let mut events = self.cancel_pending();
+        for (id, transfer) in self.active.drain() {
+            let _ = fs::remove_file(&transfer.path);
+            events.push(TransferEvent::Finished(
+                id,
+                Some("the connection to obexd was lost".to_string()),
+            ));
+        }
+        events
+    }
+
+    /// Follows the status of an accepted file, a finished one is
+    /// added to the history
+    fn transfer_changed(
+        &mut self,
+        transfer: &str,
+        properties: &HashMap<String, OwnedValue>,
+    ) -> Option<TransferEvent> {
+        let id = self
+            .active
+            .iter()
+            .find(|(_, active)| active.transfer == transfer)
+            .map(|(id, _)| *id)?;
+
+        let status = properties
+            .get("Status")
+            .and_then(|value| value.downcast_ref::<str>());
+        match status {
+            Some("complete") => {
+                let active = self.active.remove(&id)?;
+                let size = fs::metadata(&active.path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(active.file.size.unwrap_or_default());
+                info!(task = "obex_agent", "received {:?}", active.path);
+                let received = ReceivedFile {
+                    sender: active.file.sender,
+                    filename: active.file.filename,
+                    path: active.path.to_string_lossy().to_string(),
+                    size,
+                    received_on: LocalDate::today().day_key(),
+                };
+                let error = add_to_history(&self.history_path, received)
+                    .err()
+                    .map(|e| e.to_string());
+                Some(TransferEvent::Finished(id, error))
+            }
+            Some("error") => {
+                let active = self.active.remove(&id)?;
+                // obexd leaves what it got so far behind
+                let _ = fs::remove_file(&active.path);
+                let error = match active.cancelled {
+                    true => "the transfer was cancelled",
+                    false => "the transfer was interrupted",
+                };
+                Some(TransferEvent::Finished(id, Some(error.to_string())))
+            }
+            _ => properties
+                .get("Transferred")
+                .and_then(|value| value.downcast_ref::<u64>())
+                .map(|transferred| TransferEvent::Progress(id, *transferred)),
+        }
+    }
+}
+
+pub fn run_file_agent(
+    obex_bus: DBusBusType,
+    bus: DBusBusType,
+    adapter_path: String,
+    agent: Arc<Mutex<FileAgent>>,
+    on_event: Box<dyn Fn(TransferEvent) + Send + 'static>,
+) {
+    let lookup = DeviceLookup { bus, adapter_path };
+    thread::spawn(move || loop {
+        if let Err(e) = serve_agent(obex_bus, &lookup, &agent, &on_event) {
+            warn!(
+                task = "obex_agent",
+                "error while serving the file transfer agent - {}", e
+            );
+        }
+        let mut agent = agent.lock().unwrap();
+        agent.connection = None;
+        let events = agent.cancel_all();
+        drop(agent);
+        for event in events {
+            on_event(event);
+        }
+        thread::sleep(AGENT_RETRY_INTERVAL);
+    });
+}
+
+/// Stops an accepted file, obexd reports the end as an error status
+pub fn cancel_transfer(agent: &Mutex<FileAgent>, id: u64) -> Result<()> {
+    // not held during the call, the agent thread takes it for the status
+    let (connection, transfer) = agent.lock().unwrap().cancel(id)?;
+    info!(task = "obex_agent", "cancelling file {}", id);
+    let proxy = ProxyBuilder::<Transfer1ProxyBlocking>::new(&connection)
+        .path(transfer)
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+    map_call_error("build transfer proxy", proxy)
+        .and_then(|proxy| map_call_error("cancel transfer", proxy.cancel()))
+}
+
+fn register(connection: &Connection) {
+    let agent_manager = ProxyBuilder::<ObexAgentManager1ProxyBlocking>::new(connection)
+        .cache_properties(CacheProperties::No)
+        .build();
+    let agent_path = ObjectPath::from_static_str_unchecked(AGENT_PATH);
+    match agent_manager.and_then(|agent_manager| agent_manager.register_agent(&agent_path)) {
+        Ok(()) => info!(task = "obex_agent", "file transfer agent registered"),
+        // obexd is not running, the agent registers once it starts
+        Err(e) => debug!(
+            task = "obex_agent",
+            "cannot register file transfer agent - {}", e
+        ),
+    }
+}
+
+fn serve_agent(
+    obex_bus: DBusBusType,
+    lookup: &DeviceLookup,
+    agent: &Arc<Mutex<FileAgent>>,
+    on_event: &(dyn Fn(TransferEvent) + Send),
+) -> Result<()> {
+    let connection = connect(obex_bus)?;
+    let dbus = map_call_error("build dbus proxy", DBusProxy::new(&connection))?;
+    for rule in WATCH_RULES {
+        let rule = map_call_error("parse match rule", MatchRule::try_from(rule))?;
+        map_call_error(
+            "add match rule",
+            dbus.add_match_rule(rule).map_err(zbus::Error::from),
+        )?;
+    }
+
+    agent.lock().unwrap().connection = Some(connection.clone());
+    register(&connection);
+
+    for message in MessageIterator::from(&connection) {
+        let message = map_call_error("receive message", message)?;
+        let member = match message.member() {
+            Some(member) => member.to_string(),
+            None => continue,
+        };
+
+        match message.message_type() {
+            MessageType::Signal if member == "NameOwnerChanged" => {
+                let (_, _, new_owner): (String, String, String) =
+                    map_call_error("read NameOwnerChanged", message.body())?;
+                if new_owner.is_empty() {
+                    let events = agent.lock().unwrap().cancel_all();
+                    for event in events {
+                        on_event(event);
+                    }
+                } else {
+                    register(&connection);
+                }
+            }
+            MessageType::Signal if member == "PropertiesChanged" => {
+                let path = match message.path() {
+                    Some(path) => path.to_string(),
+                    None => continue,
+                };
+                let (interface, properties, _): (
+                    String,
+                    HashMap<String, OwnedValue>,
+                    Vec<String>,
+                ) = map_call_error("read PropertiesChanged", message.body())?;
+                if interface != TRANSFER_INTERFACE {
+                    continue;
+                }
+                let event = agent.lock().unwrap().transfer_changed(&path, &properties);
+                if let Some(event) = event {
+                    on_event(event);
+                }
+            }
+            MessageType::MethodCall => {
+                let is_agent_call = message.path().is_some_and(|path| path == AGENT_PATH)
+                    && message
+                        .interface()
+                        .is_some_and(|name| name == AGENT_INTERFACE);
+                if !is_agent_call {
+                    let _ = connection.reply_error(
+                        &message,
+                        "org.freedesktop.DBus.Error.UnknownMethod",
+                        &format!("unknown method {}", member),
+                    );
+                    continue;
+                }
+                handle_call(&connection, lookup, agent, on_event, message, &member)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn handle_call(
+    connection: &Connection,
+    lookup: &DeviceLookup,
+    agent: &Arc<Mutex<FileAgent>>,
+    on_event: &(dyn Fn(TransferEvent) + Send),
+    call: Arc<Message>,
+    member: &str,
+) -> Result<()> {
+    debug!(task = "obex_agent", "agent call {}", member);
+    match member {
+        "Release" | "Cancel" => {
+            let _ = connection.reply(&call, &());
+            let events = agent.lock().unwrap().cancel_pending();
+            for event in events {
+                on_event(event);
+            }
+        }
+        "AuthorizePush" => {
+            let (transfer,): (OwnedObjectPath,) = map_call_error("read call", call.body())?;
+            let file = match read_offer(connection, lookup, transfer.as_str()) {
+                Ok(file) => file,
+                Err(e) => {
+                    warn!(
+                        task = "obex_agent",
+                        "cannot read {} - {}", transfer, e
+                    );
+                    let _ = connection.reply_error(&call, CANCELED_ERROR, &"unknown transfer");
+                    return Ok(());
+                }
+            };
+            let id = agent
+                .lock()
+                .unwrap()
+                .request(file.clone(), transfer.to_string(), call.clone());
+            info!(
+                task = "obex_agent",
+                "file {} {:?} offered by {}", id, file.filename, file.sender
+            );
+            on_event(TransferEvent::Requested(IncomingFile { id, ..file }));
+        }
+        _ => {
+            let _ = connection.reply_error(
+                &call,
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                &format!("unknown method {}", member),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Reads the file a transfer offers and who sends it, the sender
+/// is named after the device when BlueZ knows it
+fn read_offer(connection: &Connection, lookup: &DeviceLookup, transfer: &str) -> Result<IncomingFile> {
+    let proxy = PropertiesProxy::builder(connection)
+        .destination(OBEX_SERVICE)
+        .and_then(|builder| builder.path(transfer.to_string()))
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+    let proxy = map_call_error("build properties proxy", proxy)?;
+    let properties = proxy
+        .get_all(InterfaceName::from_static_str_unchecked(TRANSFER_INTERFACE))
+        .map_err(zbus::Error::from);
+    let properties = map_call_error("get transfer properties", properties)?;
+
+    // both are optional, senders do not always announce them
+    let filename = properties
+        .get("Name")
+        .and_then(|value| value.downcast_ref::<str>())
+        .unwrap_or_default()
+        .to_string();
+    let size = properties
+        .get("Size")
+        .and_then(|value| value.downcast_ref::<u64>())
+        .copied()
+        .filter(|size| *size > 0);
+
+    let session = ProxyBuilder::<Transfer1ProxyBlocking>::new(connection)
+        .path(transfer.to_string())
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build())
+        .and_then(|proxy| proxy.session());
+    let session = map_call_error("get transfer session", session)?;
+    let address = ProxyBuilder::<Session1ProxyBlocking>::new(connection)
+        .path(session)
+        .and_then(|builder| builder.cache_properties(CacheProperties::No).build())
+        .and_then(|proxy| proxy.destination());
+    let address = map_call_error("get session destination", address)?;
+
+    Ok(IncomingFile {
+        id: 0,
+        sender: sender_name(lookup, &address),
+        filename,
+        size,
+    })
+}
+
+fn sender_name(lookup: &DeviceLookup, address: &str) -> String {
+    let device_path = format!("{}/dev_{}", lookup.adapter_path, address.replace(':', "_"));
+    let device = connect(lookup.bus).and_then(|connection| read_device(&connection, &device_path));
+    match device {
+        Ok(Some(device)) => device.to_string(),
+        Ok(None) => address.to_string(),
+        Err(e) => {
+            debug!(task = "obex_agent", "cannot read {} - {}", device_path, e);
+            address.to_string()
+        }
+    }
+}
+
+/// Path in `dir` for a file named by the sender, only the last
+/// component of the name is kept and taken names get a number
+fn unique_path(dir: &Path, filename: &str) -> PathBuf {
+    let name = Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| String::from("file"));
+    let path = dir.join(&name);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem.to_string(), format!(".{}", extension)),
+        _ => (name.clone(), String::new()),
+    };
+    (1..)
+        .map(|number| dir.join(format!("{} ({}){}", stem, number, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn read_history(history_path: &Path) -> Result<Vec<ReceivedFile>> {
+    let contents = match fs::read_to_string(history_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::BluetoothFileReceiveError,
+                format!("cannot read received files from {:?} - {}", history_path, e),
+            ));
+        }
+    };
+    match serde_yaml::from_str::<Option<Vec<ReceivedFile>>>(&contents) {
+        Ok(history) => Ok(history.unwrap_or_default()),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::BluetoothFileReceiveError,
+                format!("cannot parse received files in {:?} - {}", history_path, e),
+            ));
+        }
+    }
+}
+
+fn add_to_history(history_path: &Path, received: ReceivedFile) -> Result<()> {
+    let mut history = read_history(history_path)?;
+    history.insert(0, received);
+    history.truncate(HISTORY_LENGTH);
+
+    let result = serde_yaml::to_string(&history)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| write_atomic(history_path, contents).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::BluetoothFileReceiveError,
+            format!("cannot write received files to {:?} - {}", history_path, e),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn taken_names_get_a_number() {
+        let dir = env::temp_dir().join(format!("obex_unique_path_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(unique_path(&dir, "../../etc/passwd"), dir.join("passwd"));
+        assert_eq!(unique_path(&dir, " "), dir.join("file"));
+        fs::write(dir.join("holiday.jpg"), "").unwrap();
+        fs::write(dir.join("holiday (1).jpg"), "").unwrap();
+        assert_eq!(
+            unique_path(&dir, "holiday.jpg"),
+            dir.join("holiday (2).jpg")
+        );
+        fs::write(dir.join(".profile"), "").unwrap();
+        assert_eq!(unique_path(&dir, ".profile"), dir.join(".profile (1)"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn history_keeps_the_most_recent_first() {
+        let dir = env::temp_dir().join(format!("obex_history_{}", process::id()));
+        let history_path = dir.join("received_files.yml");
+        assert_eq!(read_history(&history_path).unwrap(), vec![]);
+
+        for number in 0..HISTORY_LENGTH + 1 {
+            let received = ReceivedFile {
+                filename: format!("{}.txt", number),
+                ..Default::default()
+            };
+            add_to_history(&history_path, received).unwrap();
+        }
+        let history = read_history(&history_path).unwrap();
+        assert_eq!(history.len(), HISTORY_LENGTH);
+        assert_eq!(history[0].filename, format!("{}.txt", HISTORY_LENGTH));
+        assert_eq!(history[HISTORY_LENGTH - 1].filename, "1.txt");
+
+        fs::write(&history_path, "not: [a list").unwrap();
+        assert!(read_history(&history_path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
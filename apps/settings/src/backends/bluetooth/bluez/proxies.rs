@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use zbus::{
+    dbus_proxy,
+    zvariant::{ObjectPath, OwnedObjectPath, Value},
+};
+
+/// Local controller, `/org/bluez/<adapter>`
+#[dbus_proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+trait Adapter1 {
+    fn start_discovery(&self) -> zbus::Result<()>;
+
+    fn stop_discovery(&self) -> zbus::Result<()>;
+
+    fn remove_device(&self, device: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    fn set_discovery_filter(&self, filter: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn address(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn alias(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn set_alias(&self, value: &str) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn powered(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_powered(&self, value: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn discovering(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn discoverable(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_discoverable(&self, value: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn discoverable_timeout(&self) -> zbus::Result<u32>;
+
+    #[dbus_proxy(property)]
+    fn set_discoverable_timeout(&self, value: u32) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn pairable(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_pairable(&self, value: bool) -> zbus::Result<()>;
+}
+
+/// Remote device, `/org/bluez/<adapter>/dev_<address>`
+#[dbus_proxy(interface = "org.bluez.Device1", default_service = "org.bluez")]
+trait Device1 {
+    fn pair(&self) -> zbus::Result<()>;
+
+    fn cancel_pairing(&self) -> zbus::Result<()>;
+
+    fn connect(&self) -> zbus::Result<()>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn alias(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn set_alias(&self, value: &str) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn trusted(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_trusted(&self, value: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn blocked(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn set_blocked(&self, value: bool) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.bluez.AgentManager1",
+    default_service = "org.bluez",
+    default_path = "/org/bluez"
+)]
+trait AgentManager1 {
+    fn register_agent(&self, agent: &ObjectPath<'_>, capability: &str) -> zbus::Result<()>;
+
+    fn request_default_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    fn unregister_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.bluez.obex.AgentManager1",
+    default_service = "org.bluez.obex",
+    default_path = "/org/bluez/obex"
+)]
+trait ObexAgentManager1 {
+    fn register_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    fn unregister_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+/// File sent or received by obexd, `/org/bluez/obex/server/session<n>/transfer<n>`
+#[dbus_proxy(interface = "org.bluez.obex.Transfer1", default_service = "org.bluez.obex")]
+trait Transfer1 {
+    fn cancel(&self) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn session(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// Connection of a remote device to obexd
+#[dbus_proxy(interface = "org.bluez.obex.Session1", default_service = "org.bluez.obex")]
+trait Session1 {
+    /// Address of the remote device
+    #[dbus_proxy(property)]
+    fn destination(&self) -> zbus::Result<String>;
+}
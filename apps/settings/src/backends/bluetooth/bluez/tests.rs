@@ -0,0 +1,145 @@
+use std::time::Instant;
+
+use super::*;
+use crate::backends::{
+    bluetooth::{sort_devices, DeviceTransport},
+    test_bus::TestBus,
+};
+
+#[allow(dead_code)]
+#[path = "../../../../examples/fake_bluez.rs"]
+mod fake_bluez;
+
+const HEADPHONES: &str = "/org/bluez/hci0/dev_00_1A_7D_DA_71_01";
+
+/// Starts the scripted BlueZ on a bus of its own and returns a
+/// backend connected to it, the fake leaves the bus once its
+/// connection is dropped
+fn start_fake() -> (BluezBackend, Connection, TestBus) {
+    let bus = TestBus::start();
+    let fake_connection = bus.connection();
+    fake_bluez::serve(&fake_connection, bus.connection()).unwrap();
+
+    let backend = BluezBackend::new(&BluetoothBackendSettings {
+        bus: DBusBusType::Session,
+        ..Default::default()
+    });
+    backend.connection.set(bus.connection());
+    (backend, fake_connection, bus)
+}
+
+/// Polls the devices until one matches, the fake changes them from
+/// threads of its own
+fn wait_for_device(
+    backend: &BluezBackend,
+    timeout: Duration,
+    matches: impl Fn(&BluetoothDevice) -> bool,
+) -> Option<BluetoothDevice> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let devices = backend.devices().unwrap();
+        if let Some(device) = devices.into_iter().find(|device| matches(device)) {
+            return Some(device);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    None
+}
+
+#[test]
+fn device_paths_belong_to_their_adapter() {
+    assert!(is_device_of(HEADPHONES, "/org/bluez/hci0"));
+    assert!(!is_device_of(HEADPHONES, "/org/bluez/hci1"));
+    assert!(!is_device_of("/org/bluez/hci0", "/org/bluez/hci0"));
+    assert!(!is_device_of(
+        "/org/bluez/hci0/dev_00_1A_7D_DA_71_01/sep1",
+        "/org/bluez/hci0"
+    ));
+}
+
+#[test]
+fn adapter_is_read_and_powered() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    let adapter = backend.adapter().unwrap().unwrap();
+    assert_eq!(adapter.address, "00:1B:DC:0F:00:01");
+    assert_eq!(adapter.alias, "Fake Adapter");
+    assert!(!adapter.powered);
+    assert!(backend.start_discovery().is_err());
+
+    backend.set_powered(true).unwrap();
+    backend.set_adapter_alias("Desk").unwrap();
+    let adapter = backend.adapter().unwrap().unwrap();
+    assert!(adapter.powered);
+    assert_eq!(adapter.alias, "Desk");
+}
+
+#[test]
+fn paired_devices_are_listed_with_their_battery() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    let mut devices = backend.devices().unwrap();
+    sort_devices(&mut devices);
+
+    let aliases: Vec<&str> = devices.iter().map(|d| d.alias.as_str()).collect();
+    assert_eq!(aliases, vec!["Headphones", "Keyboard"]);
+    assert_eq!(devices[0].id, HEADPHONES);
+    assert_eq!(devices[0].battery, Some(80));
+    assert_eq!(devices[0].category(), "Audio");
+    assert!(devices.iter().all(|d| d.paired && d.trusted));
+}
+
+#[test]
+fn discovery_finds_devices_of_the_filtered_transport() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    backend.set_powered(true).unwrap();
+    backend
+        .set_discovery_filter(DeviceFilter::LowEnergy)
+        .unwrap();
+    backend.start_discovery().unwrap();
+    assert!(backend.adapter().unwrap().unwrap().discovering);
+
+    // the classic speaker is skipped, the phone is dual mode
+    let phone = wait_for_device(&backend, Duration::from_secs(5), |d| d.alias == "Phone").unwrap();
+    assert_eq!(phone.transport(), DeviceTransport::Dual);
+    assert!(!phone.paired);
+    assert!(backend
+        .devices()
+        .unwrap()
+        .iter()
+        .all(|d| d.alias != "Speaker"));
+
+    backend.stop_discovery().unwrap();
+    assert!(!backend.adapter().unwrap().unwrap().discovering);
+}
+
+#[test]
+fn trusted_device_connects_and_is_removed() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    backend.set_powered(true).unwrap();
+    backend.connect(HEADPHONES).unwrap();
+    assert!(
+        wait_for_device(&backend, Duration::from_secs(3), |d| d.id == HEADPHONES
+            && d.connected)
+        .is_some()
+    );
+    let error = backend.connect(HEADPHONES).unwrap_err();
+    assert!(error.to_string().contains("already connected"));
+
+    backend.remove_device(HEADPHONES).unwrap();
+    assert!(backend
+        .devices()
+        .unwrap()
+        .iter()
+        .all(|d| d.id != HEADPHONES));
+    let error = backend.remove_device(HEADPHONES).unwrap_err();
+    assert!(error.to_string().contains("no longer known"));
+}
+
+#[test]
+fn missing_bluez_has_no_adapter() {
+    let bus = TestBus::start();
+    let backend = BluezBackend::new(&BluetoothBackendSettings::default());
+    backend.connection.set(bus.connection());
+    assert_eq!(backend.adapter().unwrap(), None);
+    assert!(backend.devices().unwrap().is_empty());
+    assert!(backend.set_powered(true).is_err());
+}
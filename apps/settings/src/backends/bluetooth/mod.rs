@@ -0,0 +1,507 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::BluetoothBackendSettings;
+
+mod bluez;
+
+pub use bluez::BluezBackend;
+
+/// # Adapter State
+///
+/// Local Bluetooth controller, devices are only listed and
+/// discovered while it is `powered`. Others only find it while it
+/// is `discoverable`, for `discoverable_timeout` seconds or for as
+/// long as it is on when the timeout is 0
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AdapterState {
+    pub address: String,
+    pub alias: String,
+    pub powered: bool,
+    pub discovering: bool,
+    pub discoverable: bool,
+    pub discoverable_timeout: u32,
+    pub pairable: bool,
+}
+
+/// # Bluetooth Device
+///
+/// Remote device known to the adapter, either paired or seen while
+/// discovering. `id` is the object path the backend addresses it by
+/// and `rssi` is only set while the device is in range of a scan,
+/// `class` is only known for classic devices, `appearance` only for
+/// LE ones and `battery` only for devices reporting their charge
+/// in percent
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BluetoothDevice {
+    pub id: String,
+    pub address: String,
+    pub alias: String,
+    pub icon: Option<String>,
+    pub class: Option<u32>,
+    pub appearance: Option<u16>,
+    /// LE devices may hide behind random addresses, classic ones cannot
+    pub random_address: bool,
+    pub paired: bool,
+    pub trusted: bool,
+    pub blocked: bool,
+    pub connected: bool,
+    pub rssi: Option<i16>,
+    pub battery: Option<u8>,
+}
+
+impl BluetoothDevice {
+    /// How the device talks to the adapter, BlueZ does not say
+    /// directly so it is told from the properties each one brings
+    pub fn transport(&self) -> DeviceTransport {
+        let is_le = self.appearance.is_some() || self.random_address;
+        match (self.class.is_some(), is_le) {
+            (true, true) => DeviceTransport::Dual,
+            (false, true) => DeviceTransport::LowEnergy,
+            _ => DeviceTransport::Classic,
+        }
+    }
+
+    /// Kind of device, from the major class of classic devices or
+    /// the icon BlueZ picked from the appearance of LE ones
+    pub fn category(&self) -> &'static str {
+        if let Some(class) = self.class {
+            let category = match (class >> 8) & 0x1f {
+                1 => "Computer",
+                2 => "Phone",
+                3 => "Network Access Point",
+                4 => "Audio",
+                5 => match class & 0xc0 {
+                    0x40 => "Keyboard",
+                    0x80 => "Mouse",
+                    _ => "Input Device",
+                },
+                6 => "Imaging",
+                7 => "Wearable",
+                8 => "Toy",
+                9 => "Health",
+                _ => "",
+            };
+            if !category.is_empty() {
+                return category;
+            }
+        }
+
+        match self.icon.as_deref().unwrap_or_default() {
+            "computer" => "Computer",
+            "phone" => "Phone",
+            "modem" | "network-wireless" => "Network Access Point",
+            "audio-card" | "audio-headphones" | "audio-headset" | "audio-input-microphone" => {
+                "Audio"
+            }
+            "input-keyboard" => "Keyboard",
+            "input-mouse" => "Mouse",
+            "input-gaming" => "Game Controller",
+            "input-tablet" => "Tablet",
+            "camera-photo" | "camera-video" | "printer" | "scanner" => "Imaging",
+            "video-display" => "Display",
+            _ => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for BluetoothDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.alias.is_empty() {
+            true => write!(f, "{}", self.address),
+            false => write!(f, "{}", self.alias),
+        }
+    }
+}
+
+/// Radio a device is reached over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceTransport {
+    /// BR/EDR, the Bluetooth of headsets and keyboards
+    #[default]
+    Classic,
+    LowEnergy,
+    /// Both, like most phones
+    Dual,
+}
+
+/// Devices the lists show and discovery looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceFilter {
+    #[default]
+    All,
+    Classic,
+    LowEnergy,
+}
+
+impl DeviceFilter {
+    pub fn matches(&self, device: &BluetoothDevice) -> bool {
+        match (self, device.transport()) {
+            (DeviceFilter::All, _) | (_, DeviceTransport::Dual) => true,
+            (DeviceFilter::Classic, transport) => transport == DeviceTransport::Classic,
+            (DeviceFilter::LowEnergy, transport) => transport == DeviceTransport::LowEnergy,
+        }
+    }
+}
+
+impl fmt::Display for DeviceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceFilter::All => write!(f, "All Devices"),
+            DeviceFilter::Classic => write!(f, "Classic"),
+            DeviceFilter::LowEnergy => write!(f, "Low Energy (BLE)"),
+        }
+    }
+}
+
+/// Change reported while watching the adapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BluetoothEvent {
+    /// The adapter changed, appeared or went away (`None`)
+    AdapterChanged(Option<AdapterState>),
+    /// A device was found or one of its properties changed
+    DeviceChanged(BluetoothDevice),
+    /// A device went out of range or was removed, by `id`
+    DeviceRemoved(String),
+}
+
+/// What BlueZ asks of the user while pairing a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairingKind {
+    /// Confirm the passkey matches the one the device shows
+    Confirmation(u32),
+    /// Type the passkey the device shows
+    Passkey,
+    /// Type the PIN code of the device
+    PinCode,
+    /// Show the passkey to type on the device, with the digits typed so far
+    DisplayPasskey(u32, u16),
+    /// Show the PIN code to type on the device
+    DisplayPinCode(String),
+    /// Allow pairing without a code
+    Authorization,
+    /// Allow the device to use a service, by UUID
+    AuthorizeService(String),
+}
+
+impl PairingKind {
+    /// Displayed codes are only shown, BlueZ does not wait for them
+    pub fn needs_reply(&self) -> bool {
+        !matches!(
+            self,
+            PairingKind::DisplayPasskey(..) | PairingKind::DisplayPinCode(_)
+        )
+    }
+}
+
+/// # Pairing Request
+///
+/// Request of the pairing agent, answered with `reply_pairing`
+/// under its `id` unless it only displays a code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingRequest {
+    pub id: u64,
+    pub device: BluetoothDevice,
+    pub kind: PairingKind,
+}
+
+/// Change reported by the pairing agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairingEvent {
+    Requested(PairingRequest),
+    /// BlueZ gave up on the request, by `id`
+    Cancelled(u64),
+}
+
+/// Answer of the user to a pairing request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairingReply {
+    Accept,
+    Reject,
+    Passkey(u32),
+    PinCode(String),
+}
+
+/// # Incoming File
+///
+/// File a device offers to send, answered with `reply_file` under
+/// its `id`. `size` is only known when the sender announced it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingFile {
+    pub id: u64,
+    pub sender: String,
+    pub filename: String,
+    pub size: Option<u64>,
+}
+
+/// # Received File
+///
+/// Entry of the history of received files, `path` is where the file
+/// was saved and `received_on` the local date, `YYYY-MM-DD`
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ReceivedFile {
+    pub sender: String,
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    pub received_on: String,
+}
+
+/// Change reported by the file transfer agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferEvent {
+    Requested(IncomingFile),
+    /// The sender gave up before the user answered, by `id`
+    Cancelled(u64),
+    /// Bytes received so far of an accepted file, by `id`
+    Progress(u64, u64),
+    /// An accepted file was saved or failed with the error, by `id`
+    Finished(u64, Option<String>),
+}
+
+/// Largest passkey, passkeys have six digits
+pub const MAX_PASSKEY: u32 = 999_999;
+
+/// Formats a passkey the way devices show it, `243 562`
+pub fn format_passkey(passkey: u32) -> String {
+    let digits = format!("{:06}", passkey);
+    format!("{} {}", &digits[..3], &digits[3..])
+}
+
+/// Returns the name of a well known service UUID
+pub fn service_name(uuid: &str) -> Option<&'static str> {
+    let short = uuid
+        .strip_suffix("-0000-1000-8000-00805f9b34fb")
+        .and_then(|uuid| uuid.strip_prefix("0000"))?;
+    let name = match short.to_lowercase().as_str() {
+        "1101" => "Serial Port",
+        "1105" => "File Transfer (OBEX Push)",
+        "1106" => "File Transfer",
+        "1108" | "1112" => "Headset",
+        "110a" => "Audio Source",
+        "110b" => "Audio Sink",
+        "110c" | "110e" => "Remote Control",
+        "111e" | "111f" => "Hands-Free",
+        "1115" | "1116" => "Network Access",
+        "1124" => "Input Device",
+        "112f" => "Phone Book Access",
+        "1132" => "Message Access",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// # Bluetooth Backend
+///
+/// Abstraction over the Bluetooth stack, every call is blocking
+/// and should be made off the main thread
+pub trait BluetoothBackend: Send + Sync {
+    /// Returns the adapter, `None` when there is none
+    fn adapter(&self) -> Result<Option<AdapterState>>;
+
+    /// Powers the adapter on or off
+    fn set_powered(&self, powered: bool) -> Result<()>;
+
+    /// Starts looking for devices, found devices are reported by `watch`
+    fn start_discovery(&self) -> Result<()>;
+
+    /// Stops looking for devices
+    fn stop_discovery(&self) -> Result<()>;
+
+    /// Limits discovery to the devices of a transport, applies from
+    /// the next discovery on
+    fn set_discovery_filter(&self, filter: DeviceFilter) -> Result<()>;
+
+    /// Renames the adapter as others see it, an empty alias goes back
+    /// to the host name
+    fn set_adapter_alias(&self, alias: &str) -> Result<()>;
+
+    /// Lets others find the adapter while discovering
+    fn set_discoverable(&self, discoverable: bool) -> Result<()>;
+
+    /// Seconds the adapter stays discoverable, 0 never stops
+    fn set_discoverable_timeout(&self, timeout: u32) -> Result<()>;
+
+    /// Lets others ask to pair with the adapter
+    fn set_pairable(&self, pairable: bool) -> Result<()>;
+
+    /// Returns the devices known to the adapter
+    fn devices(&self) -> Result<Vec<BluetoothDevice>>;
+
+    /// Reports changes of the adapter and its devices until the
+    /// process exits, the stack is watched from a thread of its own
+    fn watch(&self, on_event: Box<dyn Fn(BluetoothEvent) + Send + 'static>);
+
+    /// Pairs with a device, returns once pairing finished, the codes
+    /// asked for on the way come through the pairing agent
+    fn pair(&self, id: &str) -> Result<()>;
+
+    /// Registers the pairing agent and reports its requests until the
+    /// process exits, the agent is registered again when BlueZ restarts
+    fn register_agent(&self, on_event: Box<dyn Fn(PairingEvent) + Send + 'static>);
+
+    /// Answers a pairing request of the agent
+    fn reply_pairing(&self, id: u64, reply: PairingReply) -> Result<()>;
+
+    /// Connects a device, returns once its profiles are connected
+    fn connect(&self, id: &str) -> Result<()>;
+
+    /// Disconnects every profile of a device
+    fn disconnect(&self, id: &str) -> Result<()>;
+
+    /// Renames a device, an empty alias goes back to the name the
+    /// device gives itself
+    fn set_alias(&self, id: &str, alias: &str) -> Result<()>;
+
+    /// Trusted devices connect without asking the pairing agent
+    fn set_trusted(&self, id: &str, trusted: bool) -> Result<()>;
+
+    /// Blocked devices are disconnected and cannot connect again
+    fn set_blocked(&self, id: &str, blocked: bool) -> Result<()>;
+
+    /// Removes a device along with its pairing
+    fn remove_device(&self, id: &str) -> Result<()>;
+
+    /// Accepts files pushed from other devices, offers are reported
+    /// to `on_event` and answered with `reply_file`
+    fn register_file_agent(&self, on_event: Box<dyn Fn(TransferEvent) + Send + 'static>);
+
+    /// Saves the offered file in the download directory or declines it
+    fn reply_file(&self, id: u64, accept: bool) -> Result<()>;
+
+    /// Stops receiving an accepted file, the partial file is removed
+    fn cancel_transfer(&self, id: u64) -> Result<()>;
+
+    /// History of received files, most recent first
+    fn received_files(&self) -> Result<Vec<ReceivedFile>>;
+}
+
+/// Orders devices for the lists, connected first then by name
+pub fn sort_devices(devices: &mut [BluetoothDevice]) {
+    devices.sort_by(|a, b| {
+        b.connected.cmp(&a.connected).then(
+            a.to_string()
+                .to_lowercase()
+                .cmp(&b.to_string().to_lowercase()),
+        )
+    });
+}
+
+/// Orders devices for hunting one down, strongest signal first and
+/// devices out of range last
+pub fn sort_devices_by_signal(devices: &mut [BluetoothDevice]) {
+    devices.sort_by(|a, b| {
+        let signal = |device: &BluetoothDevice| device.rssi.unwrap_or(i16::MIN);
+        signal(b).cmp(&signal(a)).then(
+            a.to_string()
+                .to_lowercase()
+                .cmp(&b.to_string().to_lowercase()),
+        )
+    });
+}
+
+pub type BluetoothBackendHandle = Arc<dyn BluetoothBackend>;
+
+/// # Initializes the bluetooth backend
+///
+/// Creates the bluetooth backend as configured in the settings.yml
+pub fn init_bluetooth_backend(settings: &BluetoothBackendSettings) -> BluetoothBackendHandle {
+    Arc::new(BluezBackend::new(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(alias: &str, connected: bool, rssi: Option<i16>) -> BluetoothDevice {
+        BluetoothDevice {
+            alias: alias.to_string(),
+            connected,
+            rssi,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transport_follows_the_properties() {
+        let classic = BluetoothDevice {
+            class: Some(0x240404),
+            ..Default::default()
+        };
+        let le = BluetoothDevice {
+            random_address: true,
+            ..Default::default()
+        };
+        let dual = BluetoothDevice {
+            class: Some(0x5a020c),
+            appearance: Some(0x0040),
+            ..Default::default()
+        };
+        assert_eq!(classic.transport(), DeviceTransport::Classic);
+        assert_eq!(le.transport(), DeviceTransport::LowEnergy);
+        assert_eq!(dual.transport(), DeviceTransport::Dual);
+
+        assert!(DeviceFilter::Classic.matches(&classic));
+        assert!(!DeviceFilter::Classic.matches(&le));
+        assert!(DeviceFilter::LowEnergy.matches(&le));
+        assert!(DeviceFilter::LowEnergy.matches(&dual));
+        assert!(DeviceFilter::All.matches(&classic));
+    }
+
+    #[test]
+    fn category_comes_from_the_class_then_the_icon() {
+        let keyboard = BluetoothDevice {
+            class: Some(0x002540),
+            ..Default::default()
+        };
+        let mouse = BluetoothDevice {
+            icon: Some("input-mouse".to_string()),
+            ..Default::default()
+        };
+        // uncategorized class falls back to the icon
+        let phone = BluetoothDevice {
+            class: Some(0x1f00),
+            icon: Some("phone".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(keyboard.category(), "Keyboard");
+        assert_eq!(mouse.category(), "Mouse");
+        assert_eq!(phone.category(), "Phone");
+        assert_eq!(BluetoothDevice::default().category(), "Unknown");
+    }
+
+    #[test]
+    fn devices_are_sorted_for_the_lists() {
+        let mut devices = vec![
+            device("speaker", false, Some(-80)),
+            device("Headphones", true, None),
+            device("", false, Some(-40)),
+            device("Keyboard", false, None),
+        ];
+        devices[2].address = "5C:F3:70:8B:12:06".to_string();
+
+        sort_devices(&mut devices);
+        let names: Vec<String> = devices.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            names,
+            vec!["Headphones", "5C:F3:70:8B:12:06", "Keyboard", "speaker"]
+        );
+
+        sort_devices_by_signal(&mut devices);
+        let names: Vec<String> = devices.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            names,
+            vec!["5C:F3:70:8B:12:06", "speaker", "Headphones", "Keyboard"]
+        );
+    }
+
+    #[test]
+    fn displayed_codes_need_no_reply() {
+        assert!(PairingKind::Confirmation(243562).needs_reply());
+        assert!(PairingKind::PinCode.needs_reply());
+        assert!(!PairingKind::DisplayPasskey(482913, 2).needs_reply());
+        assert!(!PairingKind::DisplayPinCode("0000".to_string()).needs_reply());
+    }
+}
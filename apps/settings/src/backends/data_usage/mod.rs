@@ -0,0 +1,491 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io,
+    os::unix::io::AsRawFd,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use custom_utils::{atomic_file::write_atomic, local_date::LocalDate};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::DataUsageBackendSettings,
+};
+
+mod proc_net_dev;
+
+use proc_net_dev::{read_boot_id, read_counters, InterfaceCounters};
+
+/// Days of daily totals kept, enough for the graph and the month before
+const DAILY_RETENTION: usize = 62;
+/// Months of monthly totals kept
+const MONTHLY_RETENTION: usize = 24;
+/// Days shown in the usage graph
+pub const GRAPH_DAYS: u32 = 30;
+
+/// Bytes received and sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl UsageTotals {
+    pub fn total(&self) -> u64 {
+        self.rx_bytes.saturating_add(self.tx_bytes)
+    }
+
+    fn add(&mut self, rx_bytes: u64, tx_bytes: u64) {
+        self.rx_bytes = self.rx_bytes.saturating_add(rx_bytes);
+        self.tx_bytes = self.tx_bytes.saturating_add(tx_bytes);
+    }
+}
+
+/// # Interface Usage
+///
+/// Totals of an interface for display, `daily` holds the last
+/// `GRAPH_DAYS` days oldest first with zeros for days without traffic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceUsage {
+    pub interface: String,
+    pub today: UsageTotals,
+    pub month: UsageTotals,
+    pub daily: Vec<(LocalDate, UsageTotals)>,
+    /// Monthly total in bytes the user wants to be warned at
+    pub warning_threshold: Option<u64>,
+}
+
+impl InterfaceUsage {
+    pub fn over_threshold(&self) -> bool {
+        match self.warning_threshold {
+            Some(threshold) => self.month.total() >= threshold,
+            None => false,
+        }
+    }
+}
+
+/// Counters and totals of an interface as stored on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredInterface {
+    /// Kernel counters at the last sample, the next delta starts here
+    rx_counter: u64,
+    tx_counter: u64,
+    #[serde(default)]
+    warning_threshold: Option<u64>,
+    #[serde(default)]
+    daily: BTreeMap<String, UsageTotals>,
+    #[serde(default)]
+    monthly: BTreeMap<String, UsageTotals>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UsageStore {
+    #[serde(default)]
+    boot_id: Option<String>,
+    #[serde(default)]
+    interfaces: BTreeMap<String, StoredInterface>,
+}
+
+impl UsageStore {
+    /// Adds the traffic since the last sample to today and this month.
+    /// Counters start from zero after a reboot or when a driver is
+    /// reloaded, everything counted since is new traffic then
+    fn record(
+        &mut self,
+        boot_id: Option<String>,
+        counters: &[InterfaceCounters],
+        today: LocalDate,
+    ) {
+        let rebooted = boot_id.is_some() && boot_id != self.boot_id;
+        self.boot_id = boot_id;
+
+        for counter in counters {
+            let is_new = !self.interfaces.contains_key(&counter.interface);
+            let stored = self
+                .interfaces
+                .entry(counter.interface.clone())
+                .or_default();
+
+            // the traffic before the first sample is not attributed to today
+            let (rx_bytes, tx_bytes) = match is_new {
+                true => (0, 0),
+                false => (
+                    counter_delta(stored.rx_counter, counter.rx_bytes, rebooted),
+                    counter_delta(stored.tx_counter, counter.tx_bytes, rebooted),
+                ),
+            };
+            stored.rx_counter = counter.rx_bytes;
+            stored.tx_counter = counter.tx_bytes;
+
+            if rx_bytes > 0 || tx_bytes > 0 {
+                stored
+                    .daily
+                    .entry(today.day_key())
+                    .or_default()
+                    .add(rx_bytes, tx_bytes);
+                stored
+                    .monthly
+                    .entry(today.month_key())
+                    .or_default()
+                    .add(rx_bytes, tx_bytes);
+            }
+            trim_oldest(&mut stored.daily, DAILY_RETENTION);
+            trim_oldest(&mut stored.monthly, MONTHLY_RETENTION);
+        }
+
+        // interfaces that went away keep their totals, their counters
+        // start over when they come back
+        let present: HashSet<&String> = counters.iter().map(|c| &c.interface).collect();
+        for (interface, stored) in self.interfaces.iter_mut() {
+            if !present.contains(interface) {
+                stored.rx_counter = 0;
+                stored.tx_counter = 0;
+            }
+        }
+    }
+
+    fn usage(&self) -> Vec<InterfaceUsage> {
+        let today = LocalDate::today();
+        let days: Vec<LocalDate> = (0..GRAPH_DAYS).rev().map(LocalDate::days_ago).collect();
+
+        self.interfaces
+            .iter()
+            .map(|(interface, stored)| InterfaceUsage {
+                interface: interface.clone(),
+                today: stored
+                    .daily
+                    .get(&today.day_key())
+                    .copied()
+                    .unwrap_or_default(),
+                month: stored
+                    .monthly
+                    .get(&today.month_key())
+                    .copied()
+                    .unwrap_or_default(),
+                daily: days
+                    .iter()
+                    .map(|day| {
+                        let totals = stored.daily.get(&day.day_key()).copied();
+                        (*day, totals.unwrap_or_default())
+                    })
+                    .collect(),
+                warning_threshold: stored.warning_threshold,
+            })
+            .collect()
+    }
+}
+
+/// A counter below the stored one was reset, all of it is new traffic
+fn counter_delta(previous: u64, current: u64, rebooted: bool) -> u64 {
+    match rebooted || current < previous {
+        true => current,
+        false => current - previous,
+    }
+}
+
+/// Keys are dates, the smallest ones are the oldest
+fn trim_oldest(totals: &mut BTreeMap<String, UsageTotals>, keep: usize) {
+    while totals.len() > keep {
+        let oldest = totals.keys().next().cloned();
+        if let Some(oldest) = oldest {
+            totals.remove(&oldest);
+        }
+    }
+}
+
+/// # Data Usage Backend
+///
+/// Samples the kernel interface counters and keeps daily and monthly
+/// totals in a file, so the collector and the app can both sample
+/// without counting the same traffic twice. Updates of the store hold
+/// a lock on a file next to it, the collector runs in another process
+pub struct DataUsageBackend {
+    procfs_root: PathBuf,
+    store_path: PathBuf,
+    lock_path: PathBuf,
+    sample_interval: u32,
+}
+
+pub type DataUsageBackendHandle = Arc<DataUsageBackend>;
+
+impl DataUsageBackend {
+    pub fn new(settings: &DataUsageBackendSettings) -> Self {
+        Self {
+            procfs_root: PathBuf::from(&settings.procfs_root),
+            store_path: PathBuf::from(&settings.store_path),
+            lock_path: PathBuf::from(format!("{}.lock", settings.store_path)),
+            sample_interval: settings.sample_interval,
+        }
+    }
+
+    /// Locks the store until the file is dropped, the store itself is
+    /// replaced on every write so the lock is taken on another file
+    fn lock_store(&self) -> Result<File> {
+        let result = self
+            .lock_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(&self.lock_path)
+            })
+            .and_then(
+                |file| match unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } {
+                    0 => Ok(file),
+                    _ => Err(io::Error::last_os_error()),
+                },
+            );
+        match result {
+            Ok(file) => Ok(file),
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DataUsageWriteError,
+                    format!("cannot lock {:?} - {}", self.lock_path, e),
+                ));
+            }
+        }
+    }
+
+    pub fn sample(&self) -> Result<()> {
+        let counters = match read_counters(&self.procfs_root) {
+            Ok(counters) => counters,
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DataUsageReadError,
+                    format!("cannot read interface counters - {}", e),
+                ));
+            }
+        };
+        let boot_id = read_boot_id(&self.procfs_root);
+
+        let _lock = self.lock_store()?;
+        let mut store = self.read_store()?;
+        store.record(boot_id, &counters, LocalDate::today());
+        self.write_store(&store)
+    }
+
+    pub fn usage(&self) -> Result<Vec<InterfaceUsage>> {
+        Ok(self.read_store()?.usage())
+    }
+
+    pub fn set_warning_threshold(&self, interface: &str, threshold: Option<u64>) -> Result<()> {
+        let _lock = self.lock_store()?;
+        let mut store = self.read_store()?;
+        store
+            .interfaces
+            .entry(interface.to_string())
+            .or_default()
+            .warning_threshold = threshold;
+        self.write_store(&store)
+    }
+
+    /// Samples every `sample_interval` seconds until the process exits,
+    /// run as a service so traffic is counted while the app is closed
+    pub fn run_collector(&self) {
+        info!(
+            task = "data_usage_collector",
+            "collecting data usage into {:?} every {}s", self.store_path, self.sample_interval
+        );
+        loop {
+            if let Err(e) = self.sample() {
+                error!("error while sampling data usage {}", e);
+            }
+            thread::sleep(Duration::from_secs(self.sample_interval as u64));
+        }
+    }
+
+    fn read_store(&self) -> Result<UsageStore> {
+        let contents = match fs::read_to_string(&self.store_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!(
+                    task = "data_usage",
+                    "no data usage store at {:?} yet", self.store_path
+                );
+                return Ok(UsageStore::default());
+            }
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DataUsageReadError,
+                    format!("cannot read data usage from {:?} - {}", self.store_path, e),
+                ));
+            }
+        };
+        match serde_yaml::from_str(&contents) {
+            Ok(store) => Ok(store),
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DataUsageReadError,
+                    format!("cannot parse data usage in {:?} - {}", self.store_path, e),
+                ));
+            }
+        }
+    }
+
+    fn write_store(&self, store: &UsageStore) -> Result<()> {
+        let result = serde_yaml::to_string(store)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                write_atomic(&self.store_path, contents).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DataUsageWriteError,
+                format!("cannot write data usage to {:?} - {}", self.store_path, e),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes with a decimal unit, the way carriers bill data
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+pub fn init_data_usage_backend(settings: &DataUsageBackendSettings) -> DataUsageBackendHandle {
+    Arc::new(DataUsageBackend::new(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn counters(rx_bytes: u64, tx_bytes: u64) -> Vec<InterfaceCounters> {
+        vec![InterfaceCounters {
+            interface: "wlan0".to_string(),
+            rx_bytes,
+            tx_bytes,
+        }]
+    }
+
+    fn write_counters(procfs_root: &std::path::Path, rx_bytes: u64, tx_bytes: u64) {
+        fs::create_dir_all(procfs_root.join("net")).unwrap();
+        fs::write(
+            procfs_root.join("net/dev"),
+            format!(
+                "Inter-| header\n face | header\n wlan0: {} 0 0 0 0 0 0 0 {} 0 0 0 0 0 0 0\n",
+                rx_bytes, tx_bytes
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reset_counters_count_as_new_traffic() {
+        assert_eq!(counter_delta(100, 150, false), 50);
+        assert_eq!(counter_delta(100, 30, false), 30);
+        assert_eq!(counter_delta(100, 150, true), 150);
+    }
+
+    #[test]
+    fn traffic_is_added_to_today_and_the_month() {
+        let today = LocalDate::today();
+        let boot = Some("boot-1".to_string());
+        let mut store = UsageStore::default();
+
+        // counted before the first sample, not today's
+        store.record(boot.clone(), &counters(1000, 500), today);
+        store.record(boot.clone(), &counters(1600, 700), today);
+        // the interface went away and came back with fresh counters
+        store.record(boot.clone(), &[], today);
+        store.record(boot.clone(), &counters(50, 10), today);
+        // a reboot counts everything since
+        store.record(Some("boot-2".to_string()), &counters(2000, 5000), today);
+
+        let usage = store.usage();
+        assert_eq!(usage.len(), 1);
+        let expected = UsageTotals {
+            rx_bytes: 600 + 50 + 2000,
+            tx_bytes: 200 + 10 + 5000,
+        };
+        assert_eq!(usage[0].today, expected);
+        assert_eq!(usage[0].month, expected);
+        assert_eq!(usage[0].daily.len(), GRAPH_DAYS as usize);
+        assert_eq!(usage[0].daily.last(), Some(&(today, expected)));
+    }
+
+    #[test]
+    fn oldest_totals_are_dropped() {
+        let mut totals: BTreeMap<String, UsageTotals> = (0..5)
+            .map(|days| (LocalDate::days_ago(days).day_key(), UsageTotals::default()))
+            .collect();
+        trim_oldest(&mut totals, 3);
+        let keys: Vec<String> = totals.keys().cloned().collect();
+        let expected: Vec<String> = (0..3)
+            .rev()
+            .map(|days| LocalDate::days_ago(days).day_key())
+            .collect();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn samples_of_both_processes_are_counted_once() {
+        let root = env::temp_dir().join(format!("data_usage_{}", process::id()));
+        let settings = DataUsageBackendSettings {
+            procfs_root: root.join("proc").to_string_lossy().to_string(),
+            store_path: root
+                .join("store/data-usage.yml")
+                .to_string_lossy()
+                .to_string(),
+            sample_interval: 60,
+        };
+        let backend = Arc::new(DataUsageBackend::new(&settings));
+        write_counters(&root.join("proc"), 1000, 1000);
+        backend.sample().unwrap();
+
+        write_counters(&root.join("proc"), 4000, 2000);
+        let samplers: Vec<_> = (0..4)
+            .map(|_| {
+                let backend = backend.clone();
+                thread::spawn(move || backend.sample().unwrap())
+            })
+            .collect();
+        for sampler in samplers {
+            sampler.join().unwrap();
+        }
+        backend.set_warning_threshold("wlan0", Some(4000)).unwrap();
+
+        let usage = backend.usage().unwrap();
+        assert_eq!(
+            usage[0].today,
+            UsageTotals {
+                rx_bytes: 3000,
+                tx_bytes: 1000,
+            }
+        );
+        assert!(usage[0].over_threshold());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn bytes_use_decimal_units() {
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1500), "1.5 KB");
+        assert_eq!(format_bytes(2_400_000_000), "2.4 GB");
+        assert_eq!(format_bytes(u64::MAX), "18446744.1 TB");
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fs, io, path::Path};
+
+/// Byte counters of an interface since its counters were last reset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Reads `<procfs_root>/net/dev`, the loopback interface is left out
+pub fn read_counters(procfs_root: &Path) -> io::Result<Vec<InterfaceCounters>> {
+    let contents = fs::read_to_string(procfs_root.join("net/dev"))?;
+    Ok(parse_counters(&contents))
+}
+
+/// The two header lines are followed by `<iface>: <rx fields> <tx fields>`,
+/// eight receive fields come first with bytes leading both groups
+fn parse_counters(contents: &str) -> Vec<InterfaceCounters> {
+    contents
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (interface, fields) = line.split_once(':')?;
+            let interface = interface.trim();
+            if interface == "lo" {
+                return None;
+            }
+            let fields: Vec<u64> = fields
+                .split_whitespace()
+                .filter_map(|field| field.parse::<u64>().ok())
+                .collect();
+            Some(InterfaceCounters {
+                interface: interface.to_string(),
+                rx_bytes: *fields.first()?,
+                tx_bytes: *fields.get(8)?,
+            })
+        })
+        .collect()
+}
+
+/// Changes on every boot, counters restart from zero with it
+pub fn read_boot_id(procfs_root: &Path) -> Option<String> {
+    fs::read_to_string(procfs_root.join("sys/kernel/random/boot_id"))
+        .ok()
+        .map(|boot_id| boot_id.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_and_headers_are_skipped() {
+        let contents = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  104857     120    0    0    0     0          0         0   104857     120    0    0    0     0       0          0
+  eth0: 9876543   12000    0    0    0     0          0        12  1234567    8000    0    0    0     0       0          0
+wlan0:12 1 0 0 0 0 0 0 34 1 0 0 0 0 0 0
+ short: 1 2 3
+";
+        assert_eq!(
+            parse_counters(contents),
+            vec![
+                InterfaceCounters {
+                    interface: "eth0".to_string(),
+                    rx_bytes: 9876543,
+                    tx_bytes: 1234567,
+                },
+                InterfaceCounters {
+                    interface: "wlan0".to_string(),
+                    rx_bytes: 12,
+                    tx_bytes: 34,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+use tracing::info;
+use zbus::blocking::Connection;
+
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::DBusBusType,
+};
+
+/// # Bus Connection
+///
+/// Connection of a backend, made on first use and shared by every
+/// call after it. A failed attempt is made again on the next call,
+/// the bus may come up after the app
+pub struct BusConnection {
+    bus: DBusBusType,
+    task: &'static str,
+    error_code: LockScreenErrorCodes,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl BusConnection {
+    /// `error_code` is returned when the bus cannot be reached
+    pub fn new(bus: DBusBusType, task: &'static str, error_code: LockScreenErrorCodes) -> Self {
+        Self {
+            bus,
+            task,
+            error_code,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Result<Connection> {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+
+        info!(task = self.task, "connecting to {:?} bus", self.bus);
+        let new_connection = connect(self.bus, self.error_code)?;
+        *connection = Some(new_connection.clone());
+        Ok(new_connection)
+    }
+
+    /// Uses a connection to the bus of the test instead
+    #[cfg(test)]
+    pub fn set(&self, connection: Connection) {
+        *self.connection.lock().unwrap() = Some(connection);
+    }
+}
+
+/// Opens a connection of its own, for listeners that must not share
+/// the connection of the backend
+pub fn connect(bus: DBusBusType, error_code: LockScreenErrorCodes) -> Result<Connection> {
+    let connection = match bus {
+        DBusBusType::System => Connection::system(),
+        DBusBusType::Session => Connection::session(),
+    };
+
+    match connection {
+        Ok(connection) => Ok(connection),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                error_code,
+                format!("cannot connect to the {:?} bus - {}", bus, e),
+            ));
+        }
+    }
+}
+
+/// Turns a failed call to `service` into an error of the backend
+pub fn map_call_error<T>(
+    error_code: LockScreenErrorCodes,
+    service: &str,
+    call: &str,
+    result: zbus::Result<T>,
+) -> Result<T> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                error_code,
+                format!("{} call failed - {} - {}", service, call, e),
+            ));
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    fmt, fs,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic;
+use tracing::info;
+
+use crate::{
+    backends::network::{default_gateway, default_route_interface_in},
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::DiagnosticsBackendSettings,
+};
+
+/// # Diagnostic Check
+///
+/// Steps run in order, each one needs the ones before it to tell
+/// the user anything useful
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCheck {
+    Link,
+    IpAddress,
+    Gateway,
+    Dns,
+    Https,
+}
+
+pub const DIAGNOSTIC_CHECKS: [DiagnosticCheck; 5] = [
+    DiagnosticCheck::Link,
+    DiagnosticCheck::IpAddress,
+    DiagnosticCheck::Gateway,
+    DiagnosticCheck::Dns,
+    DiagnosticCheck::Https,
+];
+
+impl fmt::Display for DiagnosticCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticCheck::Link => write!(f, "Link"),
+            DiagnosticCheck::IpAddress => write!(f, "IP Address"),
+            DiagnosticCheck::Gateway => write!(f, "Gateway"),
+            DiagnosticCheck::Dns => write!(f, "DNS"),
+            DiagnosticCheck::Https => write!(f, "HTTPS"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticStatus {
+    Passed,
+    Failed,
+    /// Not run because an earlier step failed
+    Skipped,
+}
+
+impl fmt::Display for DiagnosticStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticStatus::Passed => write!(f, "OK"),
+            DiagnosticStatus::Failed => write!(f, "Failed"),
+            DiagnosticStatus::Skipped => write!(f, "Skipped"),
+        }
+    }
+}
+
+/// Outcome of one check, `detail` says what was tested or what went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticStep {
+    pub check: DiagnosticCheck,
+    pub status: DiagnosticStatus,
+    pub detail: String,
+    pub latency: Option<Duration>,
+}
+
+impl DiagnosticStep {
+    fn passed(check: DiagnosticCheck, detail: String, latency: Option<Duration>) -> Self {
+        Self {
+            check,
+            status: DiagnosticStatus::Passed,
+            detail,
+            latency,
+        }
+    }
+
+    fn failed(check: DiagnosticCheck, detail: String, latency: Option<Duration>) -> Self {
+        Self {
+            check,
+            status: DiagnosticStatus::Failed,
+            detail,
+            latency,
+        }
+    }
+
+    fn skipped(check: DiagnosticCheck) -> Self {
+        Self {
+            check,
+            status: DiagnosticStatus::Skipped,
+            detail: String::new(),
+            latency: None,
+        }
+    }
+}
+
+/// Latency in milliseconds as shown to the user
+pub fn format_latency(latency: Duration) -> String {
+    format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+}
+
+/// # Diagnostics Report
+///
+/// Plain text summary of a run that techs can paste into a ticket
+pub fn report_text(interface: Option<&str>, steps: &[DiagnosticStep]) -> String {
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    let mut report = String::from("Network diagnostics\n");
+    report.push_str(&format!("Device: {}\n", hostname.trim()));
+    report.push_str(&format!("Interface: {}\n\n", interface.unwrap_or("none")));
+    for step in steps {
+        let latency = step.latency.map(format_latency).unwrap_or_default();
+        report.push_str(&format!(
+            "{:<12}{:<9}{:<11}{}\n",
+            step.check.to_string(),
+            step.status.to_string(),
+            latency,
+            step.detail
+        ));
+    }
+    report
+}
+
+/// # Diagnostics Backend
+///
+/// Checks the path from the link to an HTTPS endpoint with the tools
+/// found on the image: sysfs for the link, `ip` for addresses, `ping`
+/// for the gateway, the system resolver and `curl`
+pub struct DiagnosticsBackend {
+    sysfs_root: PathBuf,
+    procfs_root: PathBuf,
+    gateway: Option<String>,
+    dns_hostname: String,
+    https_url: String,
+    timeout: Duration,
+    report_path: PathBuf,
+}
+
+pub type DiagnosticsBackendHandle = Arc<DiagnosticsBackend>;
+
+impl DiagnosticsBackend {
+    pub fn new(settings: &DiagnosticsBackendSettings, sysfs_root: &str) -> Self {
+        Self {
+            sysfs_root: PathBuf::from(sysfs_root),
+            procfs_root: PathBuf::from(&settings.procfs_root),
+            gateway: settings.gateway.clone(),
+            dns_hostname: settings.dns_hostname.clone(),
+            https_url: settings.https_url.clone(),
+            timeout: Duration::from_secs(settings.timeout as u64),
+            report_path: PathBuf::from(&settings.report_path),
+        }
+    }
+
+    /// Interface the checks run on, the one with the default route
+    pub fn interface(&self) -> Option<String> {
+        default_route_interface_in(&self.procfs_root).or_else(|| self.first_link_up())
+    }
+
+    /// Runs every check in order, `on_step` is called as each one finishes
+    pub fn run<F>(&self, on_step: F) -> Vec<DiagnosticStep>
+    where
+        F: Fn(&DiagnosticStep),
+    {
+        let interface = self.interface();
+        info!(
+            task = "diagnostics",
+            "running network diagnostics on {:?}", interface
+        );
+
+        let mut steps: Vec<DiagnosticStep> = vec![];
+        for check in DIAGNOSTIC_CHECKS {
+            // without a link or an address nothing further can pass
+            let blocked = steps.iter().any(|step| {
+                step.status != DiagnosticStatus::Passed
+                    && matches!(
+                        step.check,
+                        DiagnosticCheck::Link | DiagnosticCheck::IpAddress
+                    )
+            });
+            let step = match blocked {
+                true => DiagnosticStep::skipped(check),
+                false => self.run_check(check, interface.as_deref()),
+            };
+            on_step(&step);
+            steps.push(step);
+        }
+        steps
+    }
+
+    /// Saves the report so it can be pulled off the device later, a
+    /// failed save leaves the previous report whole
+    pub fn save_report(&self, report: &str) -> Result<PathBuf> {
+        if let Err(e) = write_atomic(&self.report_path, report) {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DiagnosticsError,
+                format!("cannot save report to {:?} - {}", self.report_path, e),
+            ));
+        }
+        Ok(self.report_path.clone())
+    }
+
+    fn run_check(&self, check: DiagnosticCheck, interface: Option<&str>) -> DiagnosticStep {
+        match check {
+            DiagnosticCheck::Link => self.check_link(interface),
+            DiagnosticCheck::IpAddress => self.check_ip_address(interface),
+            DiagnosticCheck::Gateway => self.check_gateway(interface),
+            DiagnosticCheck::Dns => self.check_dns(),
+            DiagnosticCheck::Https => self.check_https(),
+        }
+    }
+
+    fn check_link(&self, interface: Option<&str>) -> DiagnosticStep {
+        let check = DiagnosticCheck::Link;
+        let interface = match interface {
+            Some(interface) => interface,
+            None => return DiagnosticStep::failed(check, "no interface is up".to_string(), None),
+        };
+        let operstate = read_attribute(&self.interface_path(interface), "operstate");
+        match operstate.as_deref() {
+            Some("up") => DiagnosticStep::passed(check, format!("{} is up", interface), None),
+            Some(state) => {
+                DiagnosticStep::failed(check, format!("{} is {}", interface, state), None)
+            }
+            None => DiagnosticStep::failed(check, format!("{} not found", interface), None),
+        }
+    }
+
+    fn check_ip_address(&self, interface: Option<&str>) -> DiagnosticStep {
+        let check = DiagnosticCheck::IpAddress;
+        let interface = interface.unwrap_or_default();
+        let output = Command::new("ip")
+            .args(["-o", "addr", "show", "dev", interface, "scope", "global"])
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                let reason = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                return DiagnosticStep::failed(check, reason, None);
+            }
+            Err(e) => return DiagnosticStep::failed(check, format!("cannot run ip - {}", e), None),
+        };
+
+        // `<index>: <iface> inet <address>/<prefix> ...`
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let addresses: Vec<&str> = stdout
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let family = fields.iter().position(|f| *f == "inet" || *f == "inet6")?;
+                fields.get(family + 1).copied()
+            })
+            .collect();
+        match addresses.is_empty() {
+            true => DiagnosticStep::failed(check, "no address assigned".to_string(), None),
+            false => DiagnosticStep::passed(check, addresses.join(", "), None),
+        }
+    }
+
+    fn check_gateway(&self, interface: Option<&str>) -> DiagnosticStep {
+        let check = DiagnosticCheck::Gateway;
+        let gateway = self
+            .gateway
+            .clone()
+            .or_else(|| default_gateway(&self.procfs_root, interface?));
+        let gateway = match gateway {
+            Some(gateway) => gateway,
+            None => return DiagnosticStep::failed(check, "no default gateway".to_string(), None),
+        };
+
+        let timeout = self.timeout.as_secs().max(1).to_string();
+        let started = Instant::now();
+        let output = Command::new("ping")
+            .args(["-c", "1", "-W", &timeout, &gateway])
+            .output();
+        let elapsed = started.elapsed();
+        match output {
+            Ok(output) if output.status.success() => {
+                // the round trip ping measured, without the process start
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let latency = parse_ping_time(&stdout).unwrap_or(elapsed);
+                DiagnosticStep::passed(check, gateway, Some(latency))
+            }
+            Ok(_) => DiagnosticStep::failed(check, format!("{} did not answer", gateway), None),
+            Err(e) => DiagnosticStep::failed(check, format!("cannot run ping - {}", e), None),
+        }
+    }
+
+    fn check_dns(&self) -> DiagnosticStep {
+        let check = DiagnosticCheck::Dns;
+        let hostname = self.dns_hostname.clone();
+
+        // the resolver has no timeout of its own, a hung lookup is left behind
+        let (result_sender, result_receiver) = mpsc::channel();
+        let started = Instant::now();
+        let lookup_hostname = hostname.clone();
+        thread::spawn(move || {
+            let result = (lookup_hostname.as_str(), 0)
+                .to_socket_addrs()
+                .map(|addresses| addresses.map(|address| address.ip()).collect::<Vec<_>>());
+            let _ = result_sender.send(result);
+        });
+
+        match result_receiver.recv_timeout(self.timeout) {
+            Ok(Ok(addresses)) if !addresses.is_empty() => {
+                let latency = started.elapsed();
+                let detail = format!("{} is {}", hostname, addresses[0]);
+                DiagnosticStep::passed(check, detail, Some(latency))
+            }
+            Ok(Ok(_)) => {
+                DiagnosticStep::failed(check, format!("{} has no address", hostname), None)
+            }
+            Ok(Err(e)) => {
+                DiagnosticStep::failed(check, format!("cannot resolve {} - {}", hostname, e), None)
+            }
+            Err(_) => DiagnosticStep::failed(check, format!("{} timed out", hostname), None),
+        }
+    }
+
+    fn check_https(&self) -> DiagnosticStep {
+        let check = DiagnosticCheck::Https;
+        let timeout = self.timeout.as_secs().max(1).to_string();
+        let started = Instant::now();
+        let output = Command::new("curl")
+            .args([
+                "--silent",
+                "--show-error",
+                "--output",
+                "/dev/null",
+                "--write-out",
+                "%{http_code}",
+                "--max-time",
+                &timeout,
+                &self.https_url,
+            ])
+            .output();
+        let elapsed = started.elapsed();
+
+        match output {
+            // any HTTP answer means the endpoint was reached through TLS
+            Ok(output) if output.status.success() => {
+                let status_code = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                let detail = format!("{} answered {}", self.https_url, status_code);
+                DiagnosticStep::passed(check, detail, Some(elapsed))
+            }
+            Ok(output) => {
+                let reason = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                DiagnosticStep::failed(check, reason, None)
+            }
+            Err(e) => DiagnosticStep::failed(check, format!("cannot run curl - {}", e), None),
+        }
+    }
+
+    fn interface_path(&self, interface: &str) -> PathBuf {
+        self.sysfs_root.join("class/net").join(interface)
+    }
+
+    /// Without a default route the first interface that is up is checked
+    fn first_link_up(&self) -> Option<String> {
+        let mut interfaces: Vec<String> = fs::read_dir(self.sysfs_root.join("class/net"))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|interface| interface != "lo")
+            .collect();
+        interfaces.sort();
+        interfaces
+            .iter()
+            .find(|interface| {
+                read_attribute(&self.interface_path(interface), "operstate").as_deref()
+                    == Some("up")
+            })
+            .or(interfaces.first())
+            .cloned()
+    }
+}
+
+/// `time=<ms> ms` from the reply line of ping
+fn parse_ping_time(output: &str) -> Option<Duration> {
+    let (_, rest) = output.split_once("time=")?;
+    let milliseconds = rest.split_whitespace().next()?.parse::<f64>().ok()?;
+    Some(Duration::from_secs_f64(milliseconds / 1000.0))
+}
+
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+pub fn init_diagnostics_backend(
+    settings: &DiagnosticsBackendSettings,
+    sysfs_root: &str,
+) -> DiagnosticsBackendHandle {
+    Arc::new(DiagnosticsBackend::new(settings, sysfs_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        io::{Read, Write},
+        net::TcpListener,
+        process,
+    };
+
+    use super::*;
+
+    fn backend(name: &str, https_url: String) -> (PathBuf, DiagnosticsBackend) {
+        let root = env::temp_dir().join(format!("{}_{}", name, process::id()));
+        for (interface, operstate) in [("eth0", "down"), ("lo", "unknown"), ("wlan0", "up")] {
+            let dir = root.join("class/net").join(interface);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("operstate"), format!("{}\n", operstate)).unwrap();
+        }
+        let settings = DiagnosticsBackendSettings {
+            gateway: None,
+            dns_hostname: "localhost".to_string(),
+            https_url,
+            timeout: 2,
+            report_path: root
+                .join("reports/diagnostics.txt")
+                .to_string_lossy()
+                .to_string(),
+            procfs_root: root.to_string_lossy().to_string(),
+        };
+        let backend = DiagnosticsBackend::new(&settings, &root.to_string_lossy());
+        (root, backend)
+    }
+
+    /// Answers one request with `204 No Content`
+    fn serve_once() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+            }
+        });
+        format!("http://{}/generate_204", address)
+    }
+
+    #[test]
+    fn ping_time_is_read() {
+        let output = "64 bytes from 192.168.1.1: icmp_seq=1 ttl=64 time=2.41 ms\n";
+        assert_eq!(parse_ping_time(output), Some(Duration::from_micros(2410)));
+        assert_eq!(parse_ping_time("1 packets transmitted, 0 received"), None);
+        assert_eq!(format_latency(Duration::from_micros(2410)), "2.4 ms");
+    }
+
+    #[test]
+    fn link_of_the_interface_that_is_up_is_checked() {
+        let (root, backend) = backend("diagnostics_link", String::new());
+
+        assert_eq!(backend.first_link_up(), Some("wlan0".to_string()));
+        let step = backend.check_link(Some("wlan0"));
+        assert_eq!(step.status, DiagnosticStatus::Passed);
+        let step = backend.check_link(Some("eth0"));
+        assert_eq!(step.detail, "eth0 is down");
+        assert_eq!(backend.check_link(Some("usb0")).detail, "usb0 not found");
+        assert_eq!(backend.check_link(None).status, DiagnosticStatus::Failed);
+
+        // only down links, the first one is checked
+        fs::remove_dir_all(root.join("class/net/wlan0")).unwrap();
+        assert_eq!(backend.first_link_up(), Some("eth0".to_string()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn default_route_is_read_from_the_procfs_root() {
+        let (root, backend) = backend("diagnostics_route", String::new());
+        // no routes, the first link that is up
+        assert_eq!(backend.interface(), Some("wlan0".to_string()));
+
+        fs::create_dir_all(root.join("net")).unwrap();
+        fs::write(
+            root.join("net/route"),
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+",
+        )
+        .unwrap();
+        assert_eq!(backend.interface(), Some("eth0".to_string()));
+        assert_eq!(
+            default_gateway(&backend.procfs_root, "eth0").as_deref(),
+            Some("192.168.1.1")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn configured_endpoints_are_probed() {
+        if Command::new("curl").arg("--version").output().is_err() {
+            println!("skipping, curl is not installed");
+            return;
+        }
+        let (root, backend) = backend("diagnostics_probes", serve_once());
+
+        let step = backend.check_dns();
+        assert_eq!(step.status, DiagnosticStatus::Passed, "{}", step.detail);
+        assert!(step.latency.is_some());
+
+        let step = backend.check_https();
+        assert_eq!(step.status, DiagnosticStatus::Passed, "{}", step.detail);
+        assert!(step.detail.ends_with("answered 204"));
+
+        // nothing listens any more
+        let step = backend.check_https();
+        assert_eq!(step.status, DiagnosticStatus::Failed);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn report_lists_every_step() {
+        let (root, backend) = backend("diagnostics_report", String::new());
+        let steps = vec![
+            DiagnosticStep::passed(DiagnosticCheck::Link, "wlan0 is up".to_string(), None),
+            DiagnosticStep::failed(
+                DiagnosticCheck::IpAddress,
+                "no address assigned".to_string(),
+                None,
+            ),
+            DiagnosticStep::skipped(DiagnosticCheck::Gateway),
+        ];
+        let report = report_text(Some("wlan0"), &steps);
+        assert!(report.contains("Interface: wlan0\n"));
+        assert!(report.contains("IP Address  Failed              no address assigned\n"));
+        assert!(report.contains("Gateway     Skipped"));
+
+        let path = backend.save_report(&report).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), report);
+        // replaced whole, nothing of the longer report is left
+        backend.save_report("Interface: eth0\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Interface: eth0\n");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
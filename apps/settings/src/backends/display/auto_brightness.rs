@@ -0,0 +1,205 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::BrightnessCurvePoint,
+};
+
+/// Weight of a new reading in the smoothed light level, a reading a
+/// second takes a few seconds to follow a change of the light
+const SMOOTHING: f64 = 0.25;
+
+/// Change of the smoothed light level, as a ratio, before the
+/// backlight follows. Keeps it still under flickering lights
+const HYSTERESIS: f64 = 1.3;
+
+/// Furthest the slider moves the curve, in points of the scale
+pub const MAX_CURVE_OFFSET: f64 = 50.0;
+
+/// # Auto Brightness
+///
+/// Whether the backlight follows the light sensor, and how far the
+/// user moved the curve with the slider. Kept in a file so the app
+/// and the `--auto-brightness` service share it
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AutoBrightness {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+/// Level of the brightness scale for the light, between the points of
+/// the curve the level follows the log of the light like the eye does
+pub fn curve_brightness(curve: &[BrightnessCurvePoint], lux: f64) -> f64 {
+    let level = |lux: f64| (lux.max(0.0) + 1.0).ln();
+    let mut points: Vec<&BrightnessCurvePoint> = curve.iter().collect();
+    points.sort_by(|a, b| a.lux.total_cmp(&b.lux));
+
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 100.0,
+    };
+    if lux <= first.lux {
+        return first.brightness;
+    }
+    if lux >= last.lux {
+        return last.brightness;
+    }
+    for pair in points.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        if lux <= high.lux {
+            let span = level(high.lux) - level(low.lux);
+            if span <= 0.0 {
+                return high.brightness;
+            }
+            let fraction = (level(lux) - level(low.lux)) / span;
+            return low.brightness + fraction * (high.brightness - low.brightness);
+        }
+    }
+    last.brightness
+}
+
+/// Level of the brightness scale for the light with the user's offset
+pub fn target_brightness(curve: &[BrightnessCurvePoint], lux: f64, offset: f64) -> f64 {
+    (curve_brightness(curve, lux) + offset).clamp(0.0, 100.0)
+}
+
+/// # Lux Filter
+///
+/// Smooths the readings of the sensor and tells when the light
+/// changed enough for the backlight to follow
+#[derive(Debug, Default)]
+pub struct LuxFilter {
+    smoothed: Option<f64>,
+    applied: Option<f64>,
+}
+
+impl LuxFilter {
+    /// Adds a reading, the smoothed light level in lux
+    pub fn add(&mut self, lux: f64) -> f64 {
+        // smoothed as the log, a lamp in a dark room is a large step
+        let level = (lux.max(0.0) + 1.0).ln();
+        let smoothed = match self.smoothed {
+            Some(smoothed) => smoothed + SMOOTHING * (level - smoothed),
+            None => level,
+        };
+        self.smoothed = Some(smoothed);
+        smoothed.exp() - 1.0
+    }
+
+    /// Whether the smoothed light moved out of the band around the
+    /// light the backlight was last set for
+    pub fn should_apply(&self) -> bool {
+        match (self.smoothed, self.applied) {
+            (Some(smoothed), Some(applied)) => (smoothed - applied).abs() > HYSTERESIS.ln(),
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+
+    /// Marks the backlight set for the smoothed light
+    pub fn applied(&mut self) {
+        self.applied = self.smoothed;
+    }
+
+    pub fn reset(&mut self) {
+        self.smoothed = None;
+        self.applied = None;
+    }
+}
+
+pub fn read_auto_brightness(path: &Path) -> Result<AutoBrightness> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(AutoBrightness::default());
+        }
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendReadError,
+                format!("cannot read auto brightness from {:?} - {}", path, e),
+            ));
+        }
+    };
+    match serde_yaml::from_str(&contents) {
+        Ok(auto_brightness) => Ok(auto_brightness),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendReadError,
+                format!("cannot parse auto brightness in {:?} - {}", path, e),
+            ));
+        }
+    }
+}
+
+/// Replaces the file, the service may read it at any time
+pub fn write_auto_brightness(path: &Path, auto_brightness: &AutoBrightness) -> Result<()> {
+    let result = serde_yaml::to_string(auto_brightness)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| write_atomic(path, contents).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::DisplayBackendWriteError,
+            format!("cannot write auto brightness to {:?} - {}", path, e),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve() -> Vec<BrightnessCurvePoint> {
+        [(1000.0, 70.0), (0.0, 5.0), (10.0, 20.0), (100.0, 40.0)]
+            .iter()
+            .map(|(lux, brightness)| BrightnessCurvePoint {
+                lux: *lux,
+                brightness: *brightness,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn curve_follows_the_log_of_the_light() {
+        let curve = curve();
+        assert_eq!(curve_brightness(&curve, -5.0), 5.0);
+        assert_eq!(curve_brightness(&curve, 10.0), 20.0);
+        assert_eq!(curve_brightness(&curve, 5000.0), 70.0);
+        assert_eq!(curve_brightness(&[], 50.0), 100.0);
+        // halfway on the log scale, not on the lux scale
+        let middle = ((11.0f64 * 101.0).sqrt()) - 1.0;
+        assert!((curve_brightness(&curve, middle) - 30.0).abs() < 1e-9);
+        assert!(curve_brightness(&curve, 55.0) > 30.0);
+
+        assert_eq!(target_brightness(&curve, 5000.0, 40.0), 100.0);
+        assert_eq!(target_brightness(&curve, 0.0, -20.0), 0.0);
+    }
+
+    #[test]
+    fn small_changes_of_the_light_are_ignored() {
+        let mut filter = LuxFilter::default();
+        assert!(!filter.should_apply());
+        assert!((filter.add(100.0) - 100.0).abs() < 1e-9);
+        assert!(filter.should_apply());
+        filter.applied();
+
+        filter.add(110.0);
+        assert!(!filter.should_apply());
+        // a lamp turned on is followed within a few readings
+        let mut readings = 0;
+        while !filter.should_apply() {
+            filter.add(1000.0);
+            readings += 1;
+        }
+        assert!(readings <= 3);
+
+        filter.reset();
+        assert!(!filter.should_apply());
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    fmt::Display,
+    fs,
+    os::unix::io::AsRawFd,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use wayland_client::{
+    backend::WaylandError,
+    delegate_noop,
+    protocol::{
+        wl_output::WlOutput,
+        wl_registry::{self, WlRegistry},
+        wl_seat::WlSeat,
+    },
+    Connection, Dispatch, EventQueue, QueueHandle,
+};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+use wayland_protocols_wlr::output_power_management::v1::client::{
+    zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+};
+
+use super::DisplayBackend;
+use crate::errors::{LockScreenError, LockScreenErrorCodes};
+
+/// Screen off timeouts offered on the screen timeout page, in seconds
+pub const SCREEN_TIMEOUT_OPTIONS: [u32; 6] = [10, 30, 60, 300, 900, 1800];
+
+/// Longest screen off timeout, a day, which also keeps the
+/// milliseconds given to the compositor within a `u32`
+const MAX_SCREEN_TIMEOUT: u32 = 24 * 60 * 60;
+
+/// Seconds the screen is dimmed before it turns off, at most half
+/// of the timeout
+const DIM_BEFORE_OFF: u32 = 10;
+
+/// Part of the brightness kept while the screen is dimmed
+const DIM_FACTOR: f64 = 0.4;
+
+/// Longest wait for the compositor, the timeout file is read again
+/// in between so a new choice applies without a restart
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Time before the daemon connects to the compositor again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// # Screen Timeout
+///
+/// Seconds without input before the screen turns off, kept in a
+/// file the `--idle-daemon` service reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenTimeout {
+    pub timeout: u32,
+}
+
+impl Default for ScreenTimeout {
+    fn default() -> Self {
+        Self { timeout: 30 }
+    }
+}
+
+impl ScreenTimeout {
+    /// Checks the timeout is between a second and a day, the file may
+    /// have been edited by hand
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        match (1..=MAX_SCREEN_TIMEOUT).contains(&self.timeout) {
+            true => Ok(()),
+            false => Err(format!(
+                "screen timeout must be 1 to {} seconds, not {}",
+                MAX_SCREEN_TIMEOUT, self.timeout
+            )),
+        }
+    }
+}
+
+pub fn read_screen_timeout(path: &Path) -> Result<ScreenTimeout> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(ScreenTimeout::default());
+        }
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendReadError,
+                format!("cannot read screen timeout from {:?} - {}", path, e),
+            ));
+        }
+    };
+    let screen_timeout: ScreenTimeout = match serde_yaml::from_str(&contents) {
+        Ok(screen_timeout) => screen_timeout,
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendReadError,
+                format!("cannot parse screen timeout in {:?} - {}", path, e),
+            ));
+        }
+    };
+    if let Err(e) = screen_timeout.validate() {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::DisplayBackendReadError,
+            format!("invalid screen timeout in {:?} - {}", path, e),
+        ));
+    }
+    Ok(screen_timeout)
+}
+
+/// Replaces the file, the idle daemon may read it at any time
+pub fn write_screen_timeout(path: &Path, screen_timeout: &ScreenTimeout) -> Result<()> {
+    let result = screen_timeout
+        .validate()
+        .and_then(|_| serde_yaml::to_string(screen_timeout).map_err(|e| e.to_string()))
+        .and_then(|contents| write_atomic(path, contents).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::DisplayBackendWriteError,
+            format!("cannot write screen timeout to {:?} - {}", path, e),
+        ));
+    }
+    Ok(())
+}
+
+/// Timeout as listed on the pages, minutes past the first one
+pub fn format_timeout(timeout: u32) -> String {
+    match (timeout / 60, timeout % 60) {
+        (minutes, 0) if minutes > 1 => format!("{}m", minutes),
+        _ => format!("{}s", timeout),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdleStage {
+    Dim,
+    Off,
+}
+
+struct Output {
+    name: u32,
+    output: WlOutput,
+    power: Option<ZwlrOutputPowerV1>,
+}
+
+/// Globals of the compositor and the idle events not handled yet
+#[derive(Default)]
+struct IdleState {
+    seat: Option<WlSeat>,
+    notifier: Option<ExtIdleNotifierV1>,
+    power_manager: Option<ZwlrOutputPowerManagerV1>,
+    outputs: Vec<Output>,
+    events: Vec<(IdleStage, bool)>,
+    is_off: bool,
+}
+
+impl IdleState {
+    /// Turns the outputs off or back on, outputs plugged in while
+    /// the screen is off are turned off too
+    fn set_outputs_on(&mut self, on: bool, queue_handle: &QueueHandle<Self>) {
+        self.is_off = !on;
+        let power_manager = match &self.power_manager {
+            Some(power_manager) => power_manager,
+            None => return,
+        };
+        for output in self.outputs.iter_mut() {
+            let power = output.power.get_or_insert_with(|| {
+                power_manager.get_output_power(&output.output, queue_handle, ())
+            });
+            power.set_mode(if on { Mode::On } else { Mode::Off });
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for IdleState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        queue_handle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name, interface, ..
+            } => match interface.as_str() {
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, 1, queue_handle, ()));
+                }
+                "ext_idle_notifier_v1" => {
+                    state.notifier = Some(registry.bind(name, 1, queue_handle, ()));
+                }
+                "zwlr_output_power_manager_v1" => {
+                    state.power_manager = Some(registry.bind(name, 1, queue_handle, ()));
+                }
+                "wl_output" => {
+                    state.outputs.push(Output {
+                        name,
+                        output: registry.bind(name, 1, queue_handle, ()),
+                        power: None,
+                    });
+                    if state.is_off {
+                        state.set_outputs_on(false, queue_handle);
+                    }
+                }
+                _ => {}
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                state.outputs.retain(|output| {
+                    if output.name != name {
+                        return true;
+                    }
+                    if let Some(power) = &output.power {
+                        power.destroy();
+                    }
+                    false
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, IdleStage> for IdleState {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        stage: &IdleStage,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.events.push((*stage, true)),
+            ext_idle_notification_v1::Event::Resumed => state.events.push((*stage, false)),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, ()> for IdleState {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // another client holds the output power, or it went away
+        if let zwlr_output_power_v1::Event::Failed = event {
+            warn!(task = "idle_daemon", "cannot set the power mode of an output");
+        }
+    }
+}
+
+delegate_noop!(IdleState: ignore WlSeat);
+delegate_noop!(IdleState: ignore WlOutput);
+delegate_noop!(IdleState: ExtIdleNotifierV1);
+delegate_noop!(IdleState: ZwlrOutputPowerManagerV1);
+
+/// Dims the screen and then turns the outputs off when the user
+/// is idle for the screen off timeout, until the process exits
+pub fn run_idle_daemon(display_backend: &DisplayBackend) {
+    loop {
+        if let Err(e) = run_idle_session(display_backend) {
+            error!("error while watching for idle {}", e);
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+fn idle_error(message: &str, e: impl Display) -> LockScreenError {
+    LockScreenError::new(
+        LockScreenErrorCodes::IdleDaemonError,
+        format!("{} - {}", message, e),
+    )
+}
+
+/// One connection to the compositor, ends with an error when the
+/// compositor goes away
+fn run_idle_session(display_backend: &DisplayBackend) -> Result<()> {
+    let connection = match Connection::connect_to_env() {
+        Ok(connection) => connection,
+        Err(e) => bail!(idle_error("cannot connect to the compositor", e)),
+    };
+    let mut queue: EventQueue<IdleState> = connection.new_event_queue();
+    let queue_handle = queue.handle();
+    connection.display().get_registry(&queue_handle, ());
+
+    let mut state = IdleState::default();
+    if let Err(e) = queue.roundtrip(&mut state) {
+        bail!(idle_error("cannot list the globals of the compositor", e));
+    }
+    let (seat, notifier) = match (state.seat.clone(), state.notifier.clone()) {
+        (Some(seat), Some(notifier)) => (seat, notifier),
+        _ => bail!(idle_error(
+            "cannot watch for idle",
+            "the compositor has no seat or no ext_idle_notifier_v1"
+        )),
+    };
+    if state.power_manager.is_none() {
+        warn!(
+            task = "idle_daemon",
+            "the compositor has no zwlr_output_power_manager_v1, the screen is only dimmed"
+        );
+    }
+
+    let mut timeout = match display_backend.screen_timeout() {
+        Ok(timeout) => timeout,
+        Err(e) => {
+            error!("error while reading screen timeout {}", e);
+            ScreenTimeout::default().timeout
+        }
+    };
+    let mut notifications = Vec::new();
+    let mut dimmed_from: Option<f64> = None;
+    let mut last_read = Instant::now();
+    info!(task = "idle_daemon", "turning the screen off after {}s", timeout);
+
+    loop {
+        if notifications.is_empty() {
+            // idle inhibitors of the compositor hold these back, a
+            // playing video keeps the screen on
+            let dim_after = timeout - DIM_BEFORE_OFF.min(timeout / 2);
+            notifications.push(notifier.get_idle_notification(
+                millis(dim_after),
+                &seat,
+                &queue_handle,
+                IdleStage::Dim,
+            ));
+            notifications.push(notifier.get_idle_notification(
+                millis(timeout),
+                &seat,
+                &queue_handle,
+                IdleStage::Off,
+            ));
+        }
+
+        wait_for_events(&connection, &mut queue, &mut state)?;
+
+        for (stage, is_idle) in state.events.drain(..).collect::<Vec<_>>() {
+            debug!(task = "idle_daemon", "{:?} idle {}", stage, is_idle);
+            match (stage, is_idle) {
+                (IdleStage::Dim, true) => {
+                    dimmed_from = dim_screen(display_backend);
+                }
+                (IdleStage::Off, true) => {
+                    info!(task = "idle_daemon", "turning the screen off");
+                    state.set_outputs_on(false, &queue_handle);
+                }
+                (_, false) => {
+                    if state.is_off {
+                        info!(task = "idle_daemon", "turning the screen on");
+                        state.set_outputs_on(true, &queue_handle);
+                    }
+                    if let Some(brightness) = dimmed_from.take() {
+                        restore_screen(display_backend, brightness);
+                    }
+                }
+            }
+        }
+
+        if last_read.elapsed() >= POLL_INTERVAL {
+            last_read = Instant::now();
+            let new_timeout = match display_backend.screen_timeout() {
+                Ok(new_timeout) => new_timeout,
+                Err(e) => {
+                    error!("error while reading screen timeout {}", e);
+                    timeout
+                }
+            };
+            // the notifications are made again for the new timeout
+            if new_timeout != timeout {
+                info!(task = "idle_daemon", "turning the screen off after {}s", new_timeout);
+                timeout = new_timeout;
+                for notification in notifications.drain(..) {
+                    notification.destroy();
+                }
+            }
+        }
+    }
+}
+
+/// Idle time the compositor takes, the timeout is validated to fit
+fn millis(seconds: u32) -> u32 {
+    seconds.saturating_mul(1000)
+}
+
+/// Waits up to `POLL_INTERVAL` for events of the compositor and
+/// dispatches them
+fn wait_for_events(
+    connection: &Connection,
+    queue: &mut EventQueue<IdleState>,
+    state: &mut IdleState,
+) -> Result<()> {
+    if let Err(e) = connection.flush() {
+        bail!(idle_error("cannot write to the compositor", e));
+    }
+    if let Err(e) = queue.dispatch_pending(state) {
+        bail!(idle_error("cannot dispatch events of the compositor", e));
+    }
+    if let Some(guard) = queue.prepare_read() {
+        let mut poll_fd = libc::pollfd {
+            fd: guard.connection_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = unsafe {
+            libc::poll(&mut poll_fd, 1, POLL_INTERVAL.as_millis() as libc::c_int)
+        };
+        if ready > 0 {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => bail!(idle_error("cannot read from the compositor", e)),
+            }
+        }
+    }
+    if let Err(e) = queue.dispatch_pending(state) {
+        bail!(idle_error("cannot dispatch events of the compositor", e));
+    }
+    Ok(())
+}
+
+/// Dims the backlight, with the level to restore
+fn dim_screen(display_backend: &DisplayBackend) -> Option<f64> {
+    let brightness = match display_backend.backlight() {
+        Ok(Some(backlight)) => backlight.percent(),
+        Ok(None) => return None,
+        Err(e) => {
+            error!("error while reading the backlight {}", e);
+            return None;
+        }
+    };
+    info!(task = "idle_daemon", "dimming the screen");
+    if let Err(e) = display_backend.set_percent(brightness * DIM_FACTOR) {
+        error!("error while dimming the screen {}", e);
+        return None;
+    }
+    Some(brightness)
+}
+
+fn restore_screen(display_backend: &DisplayBackend, brightness: f64) {
+    if let Err(e) = display_backend.set_percent(brightness) {
+        error!("error while restoring the brightness {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn whole_minutes_are_listed_as_minutes() {
+        let labels: Vec<String> = SCREEN_TIMEOUT_OPTIONS
+            .iter()
+            .map(|t| format_timeout(*t))
+            .collect();
+        assert_eq!(labels, vec!["10s", "30s", "60s", "5m", "15m", "30m"]);
+        assert_eq!(format_timeout(90), "90s");
+    }
+
+    #[test]
+    fn timeouts_outside_a_day_are_refused() {
+        let path = env::temp_dir().join(format!("screen_timeout_{}.yml", process::id()));
+        for timeout in SCREEN_TIMEOUT_OPTIONS {
+            assert!(ScreenTimeout { timeout }.validate().is_ok());
+        }
+        for timeout in [0, MAX_SCREEN_TIMEOUT + 1, u32::MAX] {
+            assert!(ScreenTimeout { timeout }.validate().is_err());
+            assert!(write_screen_timeout(&path, &ScreenTimeout { timeout }).is_err());
+        }
+        assert!(!path.exists());
+
+        // would overflow the milliseconds of the compositor
+        fs::write(&path, "timeout: 4294968").unwrap();
+        assert!(read_screen_timeout(&path).is_err());
+        assert_eq!(millis(MAX_SCREEN_TIMEOUT), 86_400_000);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
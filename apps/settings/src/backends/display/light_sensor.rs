@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// # Light Sensor
+///
+/// IIO ambient light sensor, `/sys/bus/iio/devices/<name>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightSensor {
+    pub name: String,
+    dir: PathBuf,
+    /// Lux per step of the raw value
+    scale: f64,
+    /// Added to the raw value before it is scaled
+    offset: f64,
+}
+
+impl LightSensor {
+    /// Reads the light falling on the sensor in lux
+    pub fn read_lux(&self) -> Option<f64> {
+        let raw: f64 = read_value(&self.dir.join("in_illuminance_raw"))?;
+        Some(((raw + self.offset) * self.scale).max(0.0))
+    }
+}
+
+fn read_value(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_light_sensor(dir: &Path) -> Option<LightSensor> {
+    if !dir.join("in_illuminance_raw").exists() {
+        return None;
+    }
+    // scale and offset are left out by drivers that report lux
+    Some(LightSensor {
+        name: dir.file_name()?.to_string_lossy().to_string(),
+        dir: dir.to_path_buf(),
+        scale: read_value(&dir.join("in_illuminance_scale")).unwrap_or(1.0),
+        offset: read_value(&dir.join("in_illuminance_offset")).unwrap_or(0.0),
+    })
+}
+
+/// The configured sensor, or the first IIO device that measures
+/// illuminance when none is configured
+pub fn find_light_sensor(sysfs_root: &Path, device: Option<&str>) -> Option<LightSensor> {
+    let dir = sysfs_root.join("bus/iio/devices");
+    if let Some(device) = device {
+        return read_light_sensor(&dir.join(device));
+    }
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    dirs.iter().find_map(|dir| read_light_sensor(dir))
+}
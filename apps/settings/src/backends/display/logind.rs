@@ -0,0 +1,25 @@
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath};
+
+/// User of the caller, `self` resolves by the caller's uid and works
+/// from a user service that belongs to no session
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.User",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/user/self"
+)]
+trait User {
+    /// Graphical session of the user, the path is `/` without one
+    #[dbus_proxy(property)]
+    fn display(&self) -> zbus::Result<(String, OwnedObjectPath)>;
+}
+
+/// Session of the user, looked up through `User.Display`
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Session {
+    /// Sets `/sys/class/<subsystem>/<name>/brightness`, allowed for
+    /// the active session only
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+}
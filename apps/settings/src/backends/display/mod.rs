@@ -0,0 +1,624 @@
+use std::{
+    fs::{self, File},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, error, info, warn};
+use zbus::CacheProperties;
+
+use crate::{
+    backends::dbus::BusConnection,
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::{BrightnessCurvePoint, BrightnessWriter, DBusBusType, DisplayBackendSettings},
+};
+
+mod auto_brightness;
+mod idle;
+mod light_sensor;
+mod logind;
+
+pub use auto_brightness::AutoBrightness;
+pub use idle::{format_timeout, ScreenTimeout, SCREEN_TIMEOUT_OPTIONS};
+use idle::{read_screen_timeout, write_screen_timeout};
+pub use light_sensor::LightSensor;
+use auto_brightness::{
+    curve_brightness, read_auto_brightness, target_brightness, write_auto_brightness, LuxFilter,
+    MAX_CURVE_OFFSET,
+};
+use light_sensor::find_light_sensor;
+use logind::{SessionProxyBlocking, UserProxyBlocking};
+
+/// Exponent of the brightness curve, the eye sees steps at low
+/// levels far more than the same steps near the top
+const BRIGHTNESS_GAMMA: f64 = 2.2;
+
+/// Longest wait between reads of the backlight, the kernel wakes the
+/// watcher earlier when it changes the level itself
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time before the watcher looks for a backlight again
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time between readings of the light sensor
+const LIGHT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Kind of backlight control, from `type`. Firmware and platform
+/// controls are preferred, raw ones may skip the vendor's tuning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BacklightType {
+    #[default]
+    Raw,
+    Platform,
+    Firmware,
+}
+
+impl BacklightType {
+    fn parse(backlight_type: &str) -> Self {
+        match backlight_type {
+            "firmware" => BacklightType::Firmware,
+            "platform" => BacklightType::Platform,
+            _ => BacklightType::Raw,
+        }
+    }
+}
+
+/// # Backlight
+///
+/// Backlight of the display, `brightness` is the raw level of
+/// `/sys/class/backlight/<name>` between 0 and `max_brightness`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Backlight {
+    pub name: String,
+    pub backlight_type: BacklightType,
+    pub brightness: u32,
+    pub max_brightness: u32,
+}
+
+impl Backlight {
+    /// Level as shown on the brightness scale, from 0 to 100
+    pub fn percent(&self) -> f64 {
+        brightness_to_percent(self.brightness, self.max_brightness)
+    }
+}
+
+/// Maps a raw level onto the brightness scale
+pub fn brightness_to_percent(brightness: u32, max_brightness: u32) -> f64 {
+    if max_brightness == 0 {
+        return 0.0;
+    }
+    let fraction = (brightness.min(max_brightness) as f64) / (max_brightness as f64);
+    100.0 * fraction.powf(1.0 / BRIGHTNESS_GAMMA)
+}
+
+/// Maps the brightness scale onto a raw level, 0 maps to the lowest
+/// lit level so the screen never goes dark
+pub fn percent_to_brightness(percent: f64, max_brightness: u32) -> u32 {
+    let fraction = (percent.clamp(0.0, 100.0) / 100.0).powf(BRIGHTNESS_GAMMA);
+    let brightness = (fraction * max_brightness as f64).round() as u32;
+    brightness.clamp(max_brightness.min(1), max_brightness)
+}
+
+/// # Display Backend
+///
+/// Reads the backlight from the sysfs tree under `sysfs_root` and
+/// sets it through logind, which lets the user of the session change
+/// it without write access to sysfs. `writer: sysfs` writes the tree
+/// directly, for a fake tree or a device without logind
+pub struct DisplayBackend {
+    sysfs_root: PathBuf,
+    device: Option<String>,
+    writer: BrightnessWriter,
+    light_sensor: Option<String>,
+    brightness_curve: Vec<BrightnessCurvePoint>,
+    auto_brightness_path: PathBuf,
+    screen_timeout_path: PathBuf,
+    connection: BusConnection,
+    /// Serializes the read, update and write of the auto brightness file
+    auto_brightness_lock: Mutex<()>,
+}
+
+pub type DisplayBackendHandle = Arc<DisplayBackend>;
+
+impl DisplayBackend {
+    pub fn new(settings: &DisplayBackendSettings) -> Self {
+        Self {
+            sysfs_root: PathBuf::from(&settings.sysfs_root),
+            device: settings.device.clone(),
+            writer: settings.writer,
+            light_sensor: settings.light_sensor.clone(),
+            brightness_curve: settings.brightness_curve.clone(),
+            auto_brightness_path: PathBuf::from(&settings.auto_brightness_path),
+            screen_timeout_path: PathBuf::from(&settings.screen_timeout_path),
+            connection: BusConnection::new(
+                DBusBusType::System,
+                "display_backend",
+                LockScreenErrorCodes::DisplayBackendWriteError,
+            ),
+            auto_brightness_lock: Mutex::new(()),
+        }
+    }
+
+    /// The configured backlight, or the preferred one of the device
+    /// when none is configured. `None` when there is no backlight
+    pub fn backlight(&self) -> Result<Option<Backlight>> {
+        find_backlight(&self.sysfs_root, self.device.as_deref())
+    }
+
+    /// Sets the backlight to a level of the brightness scale
+    pub fn set_percent(&self, percent: f64) -> Result<()> {
+        let backlight = match self.backlight()? {
+            Some(backlight) => backlight,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DisplayBackendWriteError,
+                    "there is no backlight to adjust".to_string(),
+                ));
+            }
+        };
+        let brightness = percent_to_brightness(percent, backlight.max_brightness);
+        debug!(
+            task = "display_backend",
+            "setting {} brightness {} ({:.0}%)", backlight.name, brightness, percent
+        );
+        match self.writer {
+            BrightnessWriter::Logind => self.set_brightness_logind(&backlight.name, brightness),
+            BrightnessWriter::Sysfs => self.set_brightness_sysfs(&backlight.name, brightness),
+        }
+    }
+
+    /// Calls `on_change` with the backlight whenever its level changes,
+    /// also when hardware keys or other tools changed it
+    pub fn watch<F>(&self, on_change: F)
+    where
+        F: Fn(Option<Backlight>) + Send + 'static,
+    {
+        let sysfs_root = self.sysfs_root.clone();
+        let device = self.device.clone();
+        thread::spawn(move || {
+            let mut last = None;
+            loop {
+                if let Err(e) = watch_backlight(&sysfs_root, device.as_deref(), &mut last, &on_change) {
+                    warn!(
+                        task = "display_backend",
+                        "error while watching the backlight - {}", e
+                    );
+                }
+                thread::sleep(WATCH_RETRY_INTERVAL);
+            }
+        });
+    }
+
+    /// The ambient light sensor, automatic brightness needs one
+    pub fn light_sensor(&self) -> Option<LightSensor> {
+        find_light_sensor(&self.sysfs_root, self.light_sensor.as_deref())
+    }
+
+    pub fn auto_brightness(&self) -> Result<AutoBrightness> {
+        let _guard = self.auto_brightness_lock.lock().unwrap();
+        read_auto_brightness(&self.auto_brightness_path)
+    }
+
+    pub fn set_auto_brightness(&self, enabled: bool) -> Result<()> {
+        let _guard = self.auto_brightness_lock.lock().unwrap();
+        let mut auto_brightness = read_auto_brightness(&self.auto_brightness_path)?;
+        auto_brightness.enabled = enabled;
+        write_auto_brightness(&self.auto_brightness_path, &auto_brightness)
+    }
+
+    /// Moves the curve so the light falling on the sensor now maps to
+    /// the level the user picked with the slider
+    pub fn move_curve_to(&self, percent: f64) -> Result<()> {
+        let lux = match self.light_sensor().and_then(|sensor| sensor.read_lux()) {
+            Some(lux) => lux,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DisplayBackendReadError,
+                    "cannot read the light sensor".to_string(),
+                ));
+            }
+        };
+        let offset = (percent - curve_brightness(&self.brightness_curve, lux))
+            .clamp(-MAX_CURVE_OFFSET, MAX_CURVE_OFFSET);
+        debug!(
+            task = "auto_brightness",
+            "moving curve by {:.1} at {:.1} lux", offset, lux
+        );
+
+        let _guard = self.auto_brightness_lock.lock().unwrap();
+        let mut auto_brightness = read_auto_brightness(&self.auto_brightness_path)?;
+        auto_brightness.offset = offset;
+        write_auto_brightness(&self.auto_brightness_path, &auto_brightness)
+    }
+
+    /// Sets the backlight along the curve for the light falling on the
+    /// sensor while automatic brightness is on, until the process exits.
+    /// Runs as the `--auto-brightness` service only, the app writes the
+    /// switch and the curve it reads
+    pub fn run_auto_brightness(&self) {
+        info!(
+            task = "auto_brightness",
+            "following the light sensor when {:?} turns it on", self.auto_brightness_path
+        );
+        let mut filter = LuxFilter::default();
+        let mut sensor: Option<LightSensor> = None;
+        let mut applied: Option<AutoBrightness> = None;
+        loop {
+            thread::sleep(LIGHT_SAMPLE_INTERVAL);
+            let auto_brightness = match self.auto_brightness() {
+                Ok(auto_brightness) => auto_brightness,
+                Err(e) => {
+                    error!("error while reading auto brightness {}", e);
+                    continue;
+                }
+            };
+            if !auto_brightness.enabled {
+                filter.reset();
+                applied = None;
+                continue;
+            }
+
+            if sensor.is_none() {
+                sensor = self.light_sensor();
+                info!(task = "auto_brightness", "using light sensor {:?}", sensor);
+            }
+            let lux = match sensor.as_ref().and_then(|sensor| sensor.read_lux()) {
+                Some(lux) => lux,
+                None => {
+                    // looked up again, the driver may have been reloaded
+                    sensor = None;
+                    continue;
+                }
+            };
+            let lux = filter.add(lux);
+
+            // the slider moved the curve or the switch was just turned on
+            if !filter.should_apply() && applied == Some(auto_brightness) {
+                continue;
+            }
+            let brightness = target_brightness(&self.brightness_curve, lux, auto_brightness.offset);
+            debug!(
+                task = "auto_brightness",
+                "{:.1} lux, setting brightness {:.0}%", lux, brightness
+            );
+            match self.set_percent(brightness) {
+                Ok(()) => {
+                    filter.applied();
+                    applied = Some(auto_brightness);
+                }
+                Err(e) => warn!("error while setting auto brightness {}", e),
+            }
+        }
+    }
+
+    /// Seconds without input before the screen turns off
+    pub fn screen_timeout(&self) -> Result<u32> {
+        Ok(read_screen_timeout(&self.screen_timeout_path)?.timeout)
+    }
+
+    pub fn set_screen_timeout(&self, timeout: u32) -> Result<()> {
+        write_screen_timeout(&self.screen_timeout_path, &ScreenTimeout { timeout })
+    }
+
+    /// Dims and then turns the screen off when the user is idle for the
+    /// screen off timeout, until the process exits. Runs as the
+    /// `--idle-daemon` service in the session of the compositor
+    pub fn run_idle_daemon(&self) {
+        idle::run_idle_daemon(self)
+    }
+
+    /// Graphical session of the user, the services run outside of it
+    /// as `session/auto` would need
+    fn logind_session(&self) -> Result<SessionProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let display = UserProxyBlocking::builder(&connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .and_then(|user| user.display());
+        let session_path = match display {
+            Ok((_, session_path)) if session_path.as_str() != "/" => session_path,
+            Ok(_) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DisplayBackendWriteError,
+                    "the user has no graphical session to set the brightness in".to_string(),
+                ));
+            }
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DisplayBackendWriteError,
+                    format!("cannot find the session of the user - {}", e),
+                ));
+            }
+        };
+        let session = SessionProxyBlocking::builder(&connection)
+            .path(session_path)
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+        match session {
+            Ok(session) => Ok(session),
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DisplayBackendWriteError,
+                    format!("cannot build the session proxy - {}", e),
+                ));
+            }
+        }
+    }
+
+    fn set_brightness_logind(&self, name: &str, brightness: u32) -> Result<()> {
+        let session = self.logind_session()?;
+        if let Err(e) = session.set_brightness("backlight", name, brightness) {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendWriteError,
+                format!("cannot set brightness of {} through logind - {}", name, e),
+            ));
+        }
+        Ok(())
+    }
+
+    fn set_brightness_sysfs(&self, name: &str, brightness: u32) -> Result<()> {
+        let path = backlight_dir(&self.sysfs_root).join(name).join("brightness");
+        if let Err(e) = fs::write(&path, brightness.to_string()) {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendWriteError,
+                format!("cannot write {:?} - {}", path, e),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn backlight_dir(sysfs_root: &Path) -> PathBuf {
+    sysfs_root.join("class/backlight")
+}
+
+fn read_number(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_backlight(dir: &Path) -> Option<Backlight> {
+    let name = dir.file_name()?.to_string_lossy().to_string();
+    let backlight_type = fs::read_to_string(dir.join("type"))
+        .map(|backlight_type| BacklightType::parse(backlight_type.trim()))
+        .unwrap_or_default();
+    Some(Backlight {
+        name,
+        backlight_type,
+        brightness: read_number(&dir.join("brightness"))?,
+        max_brightness: read_number(&dir.join("max_brightness"))?,
+    })
+}
+
+/// Lists `/sys/class/backlight/*`, a device without a display
+/// has no such class
+fn find_backlight(sysfs_root: &Path, device: Option<&str>) -> Result<Option<Backlight>> {
+    let dir = backlight_dir(sysfs_root);
+    if let Some(device) = device {
+        return Ok(read_backlight(&dir.join(device)));
+    }
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DisplayBackendReadError,
+                format!("cannot list {:?} - {}", dir, e),
+            ));
+        }
+    };
+    let mut backlights: Vec<Backlight> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| read_backlight(&entry.path()))
+        .filter(|backlight| backlight.max_brightness > 0)
+        .collect();
+    // firmware before platform before raw, then the finest control
+    backlights.sort_by(|a, b| {
+        b.backlight_type
+            .cmp(&a.backlight_type)
+            .then(b.max_brightness.cmp(&a.max_brightness))
+            .then(a.name.cmp(&b.name))
+    });
+    Ok(backlights.into_iter().next())
+}
+
+/// Reports the backlight until it goes away. The kernel notifies
+/// `actual_brightness` when it changes the level, otherwise the
+/// level is read again every `WATCH_INTERVAL`
+fn watch_backlight(
+    sysfs_root: &Path,
+    device: Option<&str>,
+    last: &mut Option<Option<Backlight>>,
+    on_change: &(dyn Fn(Option<Backlight>) + Send),
+) -> Result<()> {
+    let backlight = find_backlight(sysfs_root, device)?;
+    if last.as_ref() != Some(&backlight) {
+        on_change(backlight.clone());
+        *last = Some(backlight.clone());
+    }
+    let backlight = match backlight {
+        Some(backlight) => backlight,
+        None => return Ok(()),
+    };
+
+    let dir = backlight_dir(sysfs_root).join(&backlight.name);
+    info!(task = "display_backend", "watching backlight {:?}", dir);
+    // a fake tree has no actual_brightness, it is only read
+    let notify_file = File::open(dir.join("actual_brightness")).ok();
+    loop {
+        match &notify_file {
+            Some(file) => wait_for_notify(file),
+            None => thread::sleep(WATCH_INTERVAL),
+        }
+        let backlight = read_backlight(&dir);
+        // caught half written, as a fake tree can be
+        if backlight.is_none() && dir.exists() {
+            continue;
+        }
+        if last.as_ref() != Some(&backlight) {
+            debug!(task = "display_backend", "backlight changed {:?}", backlight);
+            on_change(backlight.clone());
+            *last = Some(backlight.clone());
+        }
+        if backlight.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Waits up to `WATCH_INTERVAL` for sysfs to notify a change of the
+/// attribute, which needs it read after every wake up
+fn wait_for_notify(file: &File) {
+    let mut buffer = [0u8; 32];
+    // the read re-arms the notification
+    unsafe {
+        libc::pread(
+            file.as_raw_fd(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+            0,
+        );
+    }
+    let mut poll_fd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLPRI | libc::POLLERR,
+        revents: 0,
+    };
+    unsafe {
+        libc::poll(&mut poll_fd, 1, WATCH_INTERVAL.as_millis() as libc::c_int);
+    }
+}
+
+pub fn init_display_backend(settings: &DisplayBackendSettings) -> DisplayBackendHandle {
+    Arc::new(DisplayBackend::new(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// Fake sysfs tree with a raw and a firmware backlight and a light
+    /// sensor reading 120 lux
+    fn fake_tree(name: &str) -> (PathBuf, DisplayBackend) {
+        let root = env::temp_dir().join(format!("{}_{}", name, process::id()));
+        for (name, backlight_type, brightness, max_brightness) in [
+            ("acpi_video0", "raw", "10", "15"),
+            ("intel_backlight", "firmware", "24000", "96000"),
+            ("broken", "firmware", "", "0"),
+        ] {
+            let dir = root.join("class/backlight").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("type"), format!("{}\n", backlight_type)).unwrap();
+            fs::write(dir.join("brightness"), brightness).unwrap();
+            fs::write(dir.join("max_brightness"), max_brightness).unwrap();
+        }
+        let sensor = root.join("bus/iio/devices/iio:device0");
+        fs::create_dir_all(&sensor).unwrap();
+        fs::write(sensor.join("in_illuminance_raw"), "250\n").unwrap();
+        fs::write(sensor.join("in_illuminance_scale"), "0.5").unwrap();
+        fs::write(sensor.join("in_illuminance_offset"), "-10").unwrap();
+        fs::create_dir_all(root.join("bus/iio/devices/iio:device1")).unwrap();
+
+        let backend = DisplayBackend::new(&DisplayBackendSettings {
+            sysfs_root: root.to_string_lossy().to_string(),
+            writer: BrightnessWriter::Sysfs,
+            auto_brightness_path: root
+                .join("auto-brightness.yml")
+                .to_string_lossy()
+                .to_string(),
+            screen_timeout_path: root
+                .join("screen-timeout.yml")
+                .to_string_lossy()
+                .to_string(),
+            ..Default::default()
+        });
+        (root, backend)
+    }
+
+    #[test]
+    fn brightness_scale_round_trips() {
+        assert_eq!(brightness_to_percent(0, 0), 0.0);
+        assert_eq!(brightness_to_percent(255, 255), 100.0);
+        assert_eq!(brightness_to_percent(300, 255), 100.0);
+        // a quarter of the raw level is well past a quarter of the scale
+        assert!(brightness_to_percent(24000, 96000) > 50.0);
+
+        assert_eq!(percent_to_brightness(0.0, 255), 1);
+        assert_eq!(percent_to_brightness(0.0, 0), 0);
+        assert_eq!(percent_to_brightness(150.0, 255), 255);
+        for brightness in [1, 24000, 50000, 96000] {
+            let percent = brightness_to_percent(brightness, 96000);
+            assert_eq!(percent_to_brightness(percent, 96000), brightness);
+        }
+    }
+
+    #[test]
+    fn firmware_backlight_is_preferred_and_set() {
+        let (root, backend) = fake_tree("display_backlight");
+
+        let backlight = backend.backlight().unwrap().unwrap();
+        assert_eq!(backlight.name, "intel_backlight");
+        assert_eq!(backlight.backlight_type, BacklightType::Firmware);
+        assert_eq!(backlight.brightness, 24000);
+
+        backend.set_percent(100.0).unwrap();
+        assert_eq!(backend.backlight().unwrap().unwrap().brightness, 96000);
+
+        let configured = DisplayBackend::new(&DisplayBackendSettings {
+            sysfs_root: root.to_string_lossy().to_string(),
+            device: Some("acpi_video0".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(configured.backlight().unwrap().unwrap().max_brightness, 15);
+
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(backend.backlight().unwrap(), None);
+        assert!(backend.set_percent(50.0).is_err());
+    }
+
+    #[test]
+    fn curve_is_moved_to_the_slider() {
+        let (root, backend) = fake_tree("display_curve");
+
+        let sensor = backend.light_sensor().unwrap();
+        assert_eq!(sensor.name, "iio:device0");
+        assert_eq!(sensor.read_lux(), Some(120.0));
+        assert_eq!(
+            backend.auto_brightness().unwrap(),
+            AutoBrightness::default()
+        );
+
+        backend.set_auto_brightness(true).unwrap();
+        let curve = curve_brightness(&backend.brightness_curve, 120.0);
+        backend.move_curve_to(curve + 10.0).unwrap();
+        let auto_brightness = backend.auto_brightness().unwrap();
+        assert!(auto_brightness.enabled);
+        assert!((auto_brightness.offset - 10.0).abs() < 1e-9);
+
+        // the slider only moves the curve so far
+        backend.move_curve_to(curve - 100.0).unwrap();
+        assert_eq!(backend.auto_brightness().unwrap().offset, -MAX_CURVE_OFFSET);
+
+        fs::remove_dir_all(root.join("bus")).unwrap();
+        assert!(backend.move_curve_to(50.0).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn screen_timeout_is_kept() {
+        let (root, backend) = fake_tree("display_timeout");
+
+        assert_eq!(backend.screen_timeout().unwrap(), 30);
+        backend.set_screen_timeout(300).unwrap();
+        assert_eq!(backend.screen_timeout().unwrap(), 300);
+        fs::write(&backend.screen_timeout_path, "timeout: soon").unwrap();
+        assert!(backend.screen_timeout().is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
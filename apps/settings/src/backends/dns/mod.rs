@@ -0,0 +1,257 @@
+use std::{
+    fmt,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use tracing::warn;
+
+use crate::settings::{DnsBackendSettings, DnsServiceType};
+
+mod resolv_conf;
+mod resolved;
+
+pub use resolv_conf::ResolvConfBackend;
+pub use resolved::ResolvedBackend;
+
+/// Where the DNS servers come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DnsMode {
+    /// Servers handed out by DHCP or router advertisements
+    #[default]
+    Auto,
+    Manual,
+}
+
+impl fmt::Display for DnsMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnsMode::Auto => write!(f, "Auto"),
+            DnsMode::Manual => write!(f, "Manual"),
+        }
+    }
+}
+
+/// # DNS over TLS
+///
+/// Encryption of queries, `Opportunistic` falls back to plain DNS
+/// when a server does not offer TLS, `Strict` fails instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DnsOverTls {
+    #[default]
+    Off,
+    Opportunistic,
+    Strict,
+}
+
+impl fmt::Display for DnsOverTls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnsOverTls::Off => write!(f, "Off"),
+            DnsOverTls::Opportunistic => write!(f, "Opportunistic"),
+            DnsOverTls::Strict => write!(f, "Strict"),
+        }
+    }
+}
+
+/// # DNS Config
+///
+/// Resolver configuration of the device, `servers` are tried in order
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DnsConfig {
+    pub mode: DnsMode,
+    pub servers: Vec<IpAddr>,
+    pub search_domains: Vec<String>,
+    pub dns_over_tls: DnsOverTls,
+}
+
+impl DnsConfig {
+    /// Checks a manual configuration has servers, returns a message to show the user otherwise
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.mode == DnsMode::Manual && self.servers.is_empty() {
+            return Err("add at least one DNS server".to_string());
+        }
+        for server in self.servers.iter() {
+            if server.is_unspecified() || server.is_multicast() {
+                return Err(format!("{} cannot be used as a DNS server", server));
+            }
+        }
+        for domain in self.search_domains.iter() {
+            validate_domain(domain)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a server entered by the user
+pub fn parse_dns_server(server: &str) -> std::result::Result<IpAddr, String> {
+    server
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(|_| format!("'{}' is not a valid IPv4 or IPv6 address", server.trim()))
+}
+
+/// Splits comma or space separated search domains
+pub fn parse_search_domains(domains: &str) -> Vec<String> {
+    domains
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| domain.trim_end_matches('.').to_lowercase())
+        .collect()
+}
+
+fn validate_domain(domain: &str) -> std::result::Result<(), String> {
+    let is_valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    match domain.len() <= 253 && domain.split('.').all(is_valid_label) {
+        true => Ok(()),
+        false => Err(format!("'{}' is not a valid search domain", domain)),
+    }
+}
+
+/// # DNS Backend
+///
+/// Abstraction over the service that resolves names on the device,
+/// every call is blocking and should be made off the main thread
+pub trait DnsBackend: Send + Sync {
+    /// Name of the service shown on the DNS page
+    fn name(&self) -> &'static str;
+
+    fn dns_config(&self) -> Result<DnsConfig>;
+
+    fn set_dns_config(&self, config: &DnsConfig) -> Result<()>;
+}
+
+pub type DnsBackendHandle = Arc<dyn DnsBackend>;
+
+/// Uses systemd-resolved when it is running, images without it
+/// get the resolv.conf writer. Called on the main thread, so the bus
+/// is only asked for resolved on first use of the handle
+pub fn init_dns_backend(settings: &DnsBackendSettings) -> DnsBackendHandle {
+    match settings.service {
+        DnsServiceType::ResolvConf => Arc::new(resolv_conf_backend(settings)),
+        DnsServiceType::Resolved => Arc::new(ProbedDnsBackend {
+            resolved: resolved_backend(settings),
+            resolv_conf: resolv_conf_backend(settings),
+            resolv_conf_path: settings.resolv_conf_path.clone(),
+            uses_resolved: Mutex::new(None),
+        }),
+    }
+}
+
+/// Applies the manual servers kept by the app to the link again, the
+/// network service replaced them when the link came up. Blocking, runs
+/// at session start as `--restore-dns` and off the main thread in the app
+pub fn restore_manual_dns(settings: &DnsBackendSettings) {
+    // resolv.conf keeps what was written to it
+    if settings.service != DnsServiceType::Resolved {
+        return;
+    }
+    let resolved = resolved_backend(settings);
+    if resolved.is_available() {
+        resolved.restore_manual_dns();
+    }
+}
+
+fn resolv_conf_backend(settings: &DnsBackendSettings) -> ResolvConfBackend {
+    ResolvConfBackend::new(&settings.resolv_conf_path, settings.interface.clone())
+}
+
+fn resolved_backend(settings: &DnsBackendSettings) -> ResolvedBackend {
+    ResolvedBackend::new(
+        settings.bus,
+        settings.interface.clone(),
+        &settings.resolved_drop_in_path,
+    )
+}
+
+/// # Probed DNS Backend
+///
+/// systemd-resolved when it owns its name on the bus, the resolv.conf
+/// writer otherwise, decided by the first call
+struct ProbedDnsBackend {
+    resolved: ResolvedBackend,
+    resolv_conf: ResolvConfBackend,
+    resolv_conf_path: String,
+    uses_resolved: Mutex<Option<bool>>,
+}
+
+impl ProbedDnsBackend {
+    fn backend(&self) -> &dyn DnsBackend {
+        let mut uses_resolved = self.uses_resolved.lock().unwrap();
+        let uses_resolved = *uses_resolved.get_or_insert_with(|| {
+            let is_available = self.resolved.is_available();
+            if !is_available {
+                warn!(
+                    task = "dns_backend",
+                    "systemd-resolved is not running, writing {}", self.resolv_conf_path
+                );
+            }
+            is_available
+        });
+        match uses_resolved {
+            true => &self.resolved,
+            false => &self.resolv_conf,
+        }
+    }
+}
+
+impl DnsBackend for ProbedDnsBackend {
+    fn name(&self) -> &'static str {
+        self.backend().name()
+    }
+
+    fn dns_config(&self) -> Result<DnsConfig> {
+        self.backend().dns_config()
+    }
+
+    fn set_dns_config(&self, config: &DnsConfig) -> Result<()> {
+        self.backend().set_dns_config(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_config_needs_usable_servers() {
+        let mut config = DnsConfig {
+            mode: DnsMode::Manual,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(DnsConfig::default().validate().is_ok());
+
+        config.servers = vec![parse_dns_server(" 1.1.1.1 ").unwrap()];
+        config.search_domains = parse_search_domains("Office.Example.com., lab_1.local home");
+        assert_eq!(
+            config.search_domains,
+            vec!["office.example.com", "lab_1.local", "home"]
+        );
+        assert!(config.validate().is_ok());
+
+        config.servers.push(parse_dns_server("::").unwrap());
+        assert!(config.validate().is_err());
+        config.servers = vec![parse_dns_server("224.0.0.251").unwrap()];
+        assert!(config.validate().is_err());
+        assert!(parse_dns_server("1.1.1").is_err());
+    }
+
+    #[test]
+    fn invalid_search_domains_are_refused() {
+        assert!(validate_domain("example.com").is_ok());
+        for domain in ["-lab.local", "lab-.local", "a..b", "", "lab.lo cal"] {
+            assert!(validate_domain(domain).is_err(), "{:?}", domain);
+        }
+        assert!(validate_domain(&"a".repeat(64)).is_err());
+        assert!(validate_domain(&vec!["a".repeat(63); 4].join(".")).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::AtomicFile;
+use tracing::info;
+
+use super::{DnsBackend, DnsConfig, DnsMode, DnsOverTls};
+use crate::{
+    backends::network::{default_route_interface, is_ipv6_link_local},
+    errors::{LockScreenError, LockScreenErrorCodes},
+};
+
+/// First line of a resolv.conf written by this backend
+const GENERATED_HEADER: &str = "# Generated by the settings app";
+/// glibc only queries the first `MAXNS` servers
+const MAX_SERVERS: usize = 3;
+
+/// # resolv.conf Backend
+///
+/// Writes the servers straight to resolv.conf for images without
+/// systemd-resolved. The file found before switching to manual is
+/// moved aside and put back when switching to auto, which keeps a
+/// symlink managed by another service intact. Link-local IPv6
+/// servers need the `%interface` they are reached through, it is kept
+/// from the current file or taken from `interface`
+pub struct ResolvConfBackend {
+    path: PathBuf,
+    backup_path: PathBuf,
+    interface: Option<String>,
+}
+
+impl ResolvConfBackend {
+    pub fn new(path: &str, interface: Option<String>) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            backup_path: PathBuf::from(format!("{}.settings-backup", path)),
+            interface,
+        }
+    }
+
+    fn is_generated(&self) -> bool {
+        fs::read_to_string(&self.path)
+            .map(|contents| contents.starts_with(GENERATED_HEADER))
+            .unwrap_or(false)
+    }
+
+    /// `%interface` scopes of the servers in the current file
+    fn scopes(&self) -> HashMap<IpAddr, String> {
+        let contents = fs::read_to_string(&self.path).unwrap_or_default();
+        contents
+            .lines()
+            .filter_map(parse_nameserver)
+            .filter_map(|(server, scope)| Some((server, scope?.to_string())))
+            .collect()
+    }
+
+    fn write(&self, config: &DnsConfig) -> Result<()> {
+        let scopes = self.scopes();
+        let mut contents = format!("{}\n", GENERATED_HEADER);
+        for server in config.servers.iter() {
+            if !is_link_local(server) {
+                contents.push_str(&format!("nameserver {}\n", server));
+                continue;
+            }
+            let scope = scopes
+                .get(server)
+                .cloned()
+                .or_else(|| self.interface.clone())
+                .or_else(default_route_interface);
+            match scope {
+                Some(scope) => contents.push_str(&format!("nameserver {}%{}\n", server, scope)),
+                None => {
+                    bail!(LockScreenError::new(
+                        LockScreenErrorCodes::DnsBackendCallError,
+                        format!("{} is link-local, set the interface to reach it", server),
+                    ));
+                }
+            }
+        }
+        if !config.search_domains.is_empty() {
+            contents.push_str(&format!("search {}\n", config.search_domains.join(" ")));
+        }
+
+        // the new file is on disk before the original is moved aside,
+        // a failed write leaves the original in place
+        let file = match AtomicFile::create(&self.path, contents.as_bytes(), 0o644) {
+            Ok(file) => file,
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DnsBackendCallError,
+                    format!("cannot write {:?} - {}", self.path, e),
+                ));
+            }
+        };
+        let is_backed_up = !self.is_generated() && fs::symlink_metadata(&self.path).is_ok();
+        if is_backed_up {
+            rename(&self.path, &self.backup_path)?;
+        }
+        if let Err(e) = file.persist() {
+            if is_backed_up {
+                rename(&self.backup_path, &self.path)?;
+            }
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DnsBackendCallError,
+                format!("cannot write {:?} - {}", self.path, e),
+            ));
+        }
+        Ok(())
+    }
+
+    fn restore(&self) -> Result<()> {
+        // a dangling symlink still counts as the previous file
+        match fs::symlink_metadata(&self.backup_path).is_ok() {
+            true => rename(&self.backup_path, &self.path),
+            // nothing to go back to, leave the servers in place
+            false => Ok(()),
+        }
+    }
+}
+
+impl DnsBackend for ResolvConfBackend {
+    fn name(&self) -> &'static str {
+        "resolv.conf"
+    }
+
+    fn dns_config(&self) -> Result<DnsConfig> {
+        let contents = fs::read_to_string(&self.path).unwrap_or_default();
+        let mut config = DnsConfig {
+            mode: match self.is_generated() {
+                true => DnsMode::Manual,
+                false => DnsMode::Auto,
+            },
+            ..Default::default()
+        };
+
+        for line in contents.lines() {
+            if let Some((server, _)) = parse_nameserver(line) {
+                config.servers.push(server);
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                // the last `search` or `domain` line wins
+                Some("search") | Some("domain") => {
+                    config.search_domains = fields.map(|domain| domain.to_string()).collect();
+                }
+                _ => (),
+            }
+        }
+        Ok(config)
+    }
+
+    fn set_dns_config(&self, config: &DnsConfig) -> Result<()> {
+        if config.dns_over_tls != DnsOverTls::Off {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DnsBackendCallError,
+                "DNS over TLS needs systemd-resolved".to_string(),
+            ));
+        }
+
+        info!(
+            task = "dns_backend",
+            "setting {} dns in {:?}", config.mode, self.path
+        );
+        match config.mode {
+            DnsMode::Auto => self.restore(),
+            DnsMode::Manual => {
+                if config.servers.len() > MAX_SERVERS {
+                    bail!(LockScreenError::new(
+                        LockScreenErrorCodes::DnsBackendCallError,
+                        format!("resolv.conf supports at most {} servers", MAX_SERVERS),
+                    ));
+                }
+                self.write(config)
+            }
+        }
+    }
+}
+
+/// Server of a `nameserver` line, link-local servers carry a
+/// `%interface` suffix
+fn parse_nameserver(line: &str) -> Option<(IpAddr, Option<&str>)> {
+    let mut fields = line.split_whitespace();
+    if fields.next() != Some("nameserver") {
+        return None;
+    }
+    let mut server = fields.next()?.splitn(2, '%');
+    let address = server.next()?.parse::<IpAddr>().ok()?;
+    Some((address, server.next()))
+}
+
+/// Whether a server is reachable on one link only and needs a scope
+fn is_link_local(server: &IpAddr) -> bool {
+    match server {
+        IpAddr::V4(_) => false,
+        IpAddr::V6(server) => is_ipv6_link_local(*server),
+    }
+}
+
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    if let Err(e) = fs::rename(from, to) {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::DnsBackendCallError,
+            format!("cannot move {:?} to {:?} - {}", from, to, e),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix::fs::symlink, process};
+
+    use super::*;
+
+    fn manual(servers: &[&str]) -> DnsConfig {
+        DnsConfig {
+            mode: DnsMode::Manual,
+            servers: servers.iter().map(|s| s.parse().unwrap()).collect(),
+            search_domains: vec!["lab.local".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn original_file_is_put_back_for_auto() {
+        let dir = env::temp_dir().join(format!("resolv_conf_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("resolv.conf");
+        fs::write(dir.join("stub-resolv.conf"), "nameserver 127.0.0.53\n").unwrap();
+        symlink("stub-resolv.conf", &path).unwrap();
+        let backend = ResolvConfBackend::new(&path.to_string_lossy(), Some("wlan0".to_string()));
+
+        let config = backend.dns_config().unwrap();
+        assert_eq!(config.mode, DnsMode::Auto);
+        assert_eq!(
+            config.servers,
+            vec!["127.0.0.53".parse::<IpAddr>().unwrap()]
+        );
+
+        backend
+            .set_dns_config(&manual(&["1.1.1.1", "fe80::1"]))
+            .unwrap();
+        // written again, the backup is still the original
+        backend
+            .set_dns_config(&manual(&["9.9.9.9", "fe80::1"]))
+            .unwrap();
+        assert_eq!(
+            backend.dns_config().unwrap(),
+            manual(&["9.9.9.9", "fe80::1"])
+        );
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("nameserver fe80::1%wlan0\n"));
+
+        backend.set_dns_config(&DnsConfig::default()).unwrap();
+        assert!(fs::symlink_metadata(&path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(backend.dns_config().unwrap().mode, DnsMode::Auto);
+        // nothing left to restore, the file stays
+        backend.set_dns_config(&DnsConfig::default()).unwrap();
+        assert!(path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unsupported_configs_are_refused() {
+        let dir = env::temp_dir().join(format!("resolv_conf_refused_{}", process::id()));
+        let path = dir.join("resolv.conf");
+        let backend = ResolvConfBackend::new(&path.to_string_lossy(), None);
+
+        let too_many = manual(&["1.1.1.1", "1.0.0.1", "9.9.9.9", "8.8.8.8"]);
+        assert!(backend.set_dns_config(&too_many).is_err());
+        let dns_over_tls = DnsConfig {
+            dns_over_tls: DnsOverTls::Strict,
+            ..manual(&["1.1.1.1"])
+        };
+        assert!(backend.set_dns_config(&dns_over_tls).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn link_local_servers_keep_their_interface() {
+        let dir = env::temp_dir().join(format!("resolv_conf_scope_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("resolv.conf");
+        fs::write(&path, "nameserver fe80::1%eth1\nnameserver 192.168.1.1\n").unwrap();
+        let backend = ResolvConfBackend::new(&path.to_string_lossy(), Some("wlan0".to_string()));
+
+        let mut config = backend.dns_config().unwrap();
+        assert_eq!(config.servers[0], "fe80::1".parse::<IpAddr>().unwrap());
+        // the scope of the file wins, a new server gets the configured one
+        config.mode = DnsMode::Manual;
+        config.servers.push("fe80::2".parse().unwrap());
+        backend.set_dns_config(&config).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("nameserver fe80::1%eth1\n"));
+        assert!(contents.contains("nameserver 192.168.1.1\n"));
+        assert!(contents.contains("nameserver fe80::2%wlan0\n"));
+        assert_eq!(backend.dns_config().unwrap().servers, config.servers);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nameservers_are_parsed_with_their_scope() {
+        let fe80 = "fe80::1".parse::<IpAddr>().unwrap();
+        assert_eq!(parse_nameserver("nameserver fe80::1%wlan0"), Some((fe80, Some("wlan0"))));
+        assert_eq!(parse_nameserver("nameserver fe80::1"), Some((fe80, None)));
+        assert_eq!(parse_nameserver("nameserver bogus"), None);
+        assert_eq!(parse_nameserver("search fe80::1"), None);
+        assert!(is_link_local(&fe80));
+        assert!(is_link_local(&"febf::1".parse().unwrap()));
+        assert!(!is_link_local(&"fec0::1".parse().unwrap()));
+        assert!(!is_link_local(&"169.254.1.1".parse().unwrap()));
+    }
+}
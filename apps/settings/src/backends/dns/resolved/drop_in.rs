@@ -0,0 +1,129 @@
+use std::{fs, io, path::Path};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic;
+
+use super::{dns_over_tls_value, parse_dns_over_tls};
+use crate::{
+    backends::dns::{DnsConfig, DnsMode, DnsOverTls},
+    errors::{LockScreenError, LockScreenErrorCodes},
+};
+
+/// First line of a drop-in written by this backend
+const GENERATED_HEADER: &str = "# Generated by the settings app";
+
+/// Reads the settings kept in the drop-in, `None` without one.
+/// Manual servers make the mode manual
+pub fn read_drop_in(path: &Path) -> Result<Option<DnsConfig>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::DnsBackendCallError,
+                format!("cannot read {:?} - {}", path, e),
+            ));
+        }
+    };
+
+    let mut config = DnsConfig::default();
+    for line in contents.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "DNS" => {
+                config.servers = value
+                    .split_whitespace()
+                    .filter_map(|server| server.parse().ok())
+                    .collect()
+            }
+            "Domains" => {
+                config.search_domains = value.split_whitespace().map(String::from).collect()
+            }
+            "DNSOverTLS" => config.dns_over_tls = parse_dns_over_tls(value),
+            _ => (),
+        }
+    }
+    if !config.servers.is_empty() {
+        config.mode = DnsMode::Manual;
+    }
+    Ok(Some(config))
+}
+
+/// Keeps the manual servers and DNS over TLS across reboots and
+/// link changes, resolved reads the drop-in when it starts. Nothing
+/// is kept for the automatic mode without DNS over TLS
+pub fn write_drop_in(path: &Path, config: &DnsConfig) -> Result<()> {
+    let result = match (config.mode, config.dns_over_tls) {
+        (DnsMode::Auto, DnsOverTls::Off) => match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+        _ => write_atomic(path, to_drop_in(config)),
+    };
+    if let Err(e) = result {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::DnsBackendCallError,
+            format!("cannot write {:?} - {}", path, e),
+        ));
+    }
+    Ok(())
+}
+
+fn to_drop_in(config: &DnsConfig) -> String {
+    let mut contents = format!("{}\n[Resolve]\n", GENERATED_HEADER);
+    if config.mode == DnsMode::Manual {
+        let servers: Vec<String> = config.servers.iter().map(|s| s.to_string()).collect();
+        contents.push_str(&format!("DNS={}\n", servers.join(" ")));
+        if !config.search_domains.is_empty() {
+            contents.push_str(&format!("Domains={}\n", config.search_domains.join(" ")));
+        }
+    }
+    contents.push_str(&format!(
+        "DNSOverTLS={}\n",
+        dns_over_tls_value(config.dns_over_tls)
+    ));
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn drop_in_is_read_back() {
+        let dir = env::temp_dir().join(format!("resolved_drop_in_{}", process::id()));
+        let path = dir.join("resolved.conf.d/50-settings.conf");
+        assert_eq!(read_drop_in(&path).unwrap(), None);
+
+        let manual = DnsConfig {
+            mode: DnsMode::Manual,
+            servers: vec![
+                "1.1.1.1".parse().unwrap(),
+                "2606:4700::1111".parse().unwrap(),
+            ],
+            search_domains: vec!["lab.local".to_string()],
+            dns_over_tls: DnsOverTls::Strict,
+        };
+        write_drop_in(&path, &manual).unwrap();
+        assert_eq!(read_drop_in(&path).unwrap(), Some(manual));
+
+        let opportunistic = DnsConfig {
+            dns_over_tls: DnsOverTls::Opportunistic,
+            ..Default::default()
+        };
+        write_drop_in(&path, &opportunistic).unwrap();
+        assert_eq!(read_drop_in(&path).unwrap(), Some(opportunistic));
+
+        // nothing to keep for the defaults
+        write_drop_in(&path, &DnsConfig::default()).unwrap();
+        assert!(!path.exists());
+        write_drop_in(&path, &DnsConfig::default()).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
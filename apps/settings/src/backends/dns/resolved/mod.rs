@@ -0,0 +1,262 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use tracing::{error, info};
+use zbus::{
+    blocking::{fdo::DBusProxy, ProxyBuilder},
+    names::BusName,
+    zvariant::OwnedObjectPath,
+    CacheProperties,
+};
+
+use super::{DnsBackend, DnsConfig, DnsMode, DnsOverTls};
+use crate::{
+    backends::{
+        dbus::{self, BusConnection},
+        network::default_route_interface,
+    },
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::DBusBusType,
+};
+
+mod drop_in;
+mod proxies;
+
+use drop_in::{read_drop_in, write_drop_in};
+use proxies::{LinkProxyBlocking, ManagerProxyBlocking};
+
+const RESOLVED_SERVICE: &str = "org.freedesktop.resolve1";
+
+/// `AF_INET` and `AF_INET6` as used in resolved's address tuples
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+
+/// # systemd-resolved Backend
+///
+/// Sets per-link DNS through the resolve1 D-Bus API on the interface
+/// of the default route, or the configured one. resolved keeps these
+/// settings until the link goes down, the network service then pushes
+/// its own servers again, so the manual settings are also kept in a
+/// resolved.conf.d drop-in and applied to the link again on startup
+pub struct ResolvedBackend {
+    interface: Option<String>,
+    drop_in_path: PathBuf,
+    connection: BusConnection,
+}
+
+impl ResolvedBackend {
+    pub fn new(bus: DBusBusType, interface: Option<String>, drop_in_path: &str) -> Self {
+        Self {
+            interface,
+            drop_in_path: PathBuf::from(drop_in_path),
+            connection: BusConnection::new(
+                bus,
+                "dns_backend",
+                LockScreenErrorCodes::DnsBackendConnectError,
+            ),
+        }
+    }
+
+    /// Applies the manual settings kept in the drop-in to the link,
+    /// the network service replaced them when the link came up
+    pub fn restore_manual_dns(&self) {
+        let config = match read_drop_in(&self.drop_in_path) {
+            Ok(Some(config)) if config.mode == DnsMode::Manual => config,
+            Ok(_) => return,
+            Err(e) => {
+                error!("error while reading manual dns {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.set_link_dns_config(&config) {
+            error!("error while restoring manual dns {}", e);
+        }
+    }
+
+    /// Whether resolved owns its name on the bus
+    pub fn is_available(&self) -> bool {
+        let connection = match self.connection.get() {
+            Ok(connection) => connection,
+            Err(_) => return false,
+        };
+        let proxy = match DBusProxy::new(&connection) {
+            Ok(proxy) => proxy,
+            Err(_) => return false,
+        };
+        match BusName::try_from(RESOLVED_SERVICE) {
+            Ok(name) => proxy.name_has_owner(name).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    fn manager(&self) -> Result<ManagerProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<ManagerProxyBlocking>::new(&connection)
+            .cache_properties(CacheProperties::No)
+            .build();
+        map_call_error("build manager proxy", proxy)
+    }
+
+    fn link(&self, path: OwnedObjectPath) -> Result<LinkProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<LinkProxyBlocking>::new(&connection)
+            .path(path)
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+        map_call_error("build link proxy", proxy)
+    }
+
+    /// Kernel index of the interface DNS is configured on
+    fn ifindex(&self) -> Result<i32> {
+        let interface = match self.interface.clone().or_else(default_route_interface) {
+            Some(interface) => interface,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DnsBackendCallError,
+                    "no interface has a default route".to_string(),
+                ));
+            }
+        };
+
+        let ifindex = fs::read_to_string(format!("/sys/class/net/{}/ifindex", interface))
+            .ok()
+            .and_then(|ifindex| ifindex.trim().parse::<i32>().ok());
+        match ifindex {
+            Some(ifindex) => Ok(ifindex),
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::DnsBackendCallError,
+                    format!("cannot find the index of {}", interface),
+                ));
+            }
+        }
+    }
+
+    fn set_link_dns_config(&self, config: &DnsConfig) -> Result<()> {
+        let manager = self.manager()?;
+        let ifindex = self.ifindex()?;
+        info!(
+            task = "dns_backend",
+            "setting {} dns on link {}", config.mode, ifindex
+        );
+
+        match config.mode {
+            DnsMode::Auto => {
+                map_call_error("revert link", manager.revert_link(ifindex))?;
+            }
+            DnsMode::Manual => {
+                let addresses: Vec<(i32, Vec<u8>)> =
+                    config.servers.iter().map(dns_address).collect();
+                let domains: Vec<(&str, bool)> = config
+                    .search_domains
+                    .iter()
+                    .map(|domain| (domain.as_str(), false))
+                    .collect();
+                map_call_error("set link dns", manager.set_link_dns(ifindex, &addresses))?;
+                map_call_error(
+                    "set link domains",
+                    manager.set_link_domains(ifindex, &domains),
+                )?;
+            }
+        }
+
+        map_call_error(
+            "set link dns over tls",
+            manager.set_link_dns_over_tls(ifindex, dns_over_tls_value(config.dns_over_tls)),
+        )?;
+        Ok(())
+    }
+}
+
+impl DnsBackend for ResolvedBackend {
+    fn name(&self) -> &'static str {
+        "systemd-resolved"
+    }
+
+    fn dns_config(&self) -> Result<DnsConfig> {
+        let manager = self.manager()?;
+        let link_path = map_call_error("get link", manager.get_link(self.ifindex()?))?;
+        let link = self.link(link_path)?;
+
+        let servers = map_call_error("get link dns", link.dns())?
+            .into_iter()
+            .filter_map(|(family, address)| ip_address(family, &address))
+            .collect();
+        // route-only domains are for split DNS and are not searched
+        let search_domains = map_call_error("get link domains", link.domains())?
+            .into_iter()
+            .filter(|(_, route_only)| !route_only)
+            .map(|(domain, _)| domain)
+            .collect();
+
+        // an empty link setting follows the global one from resolved.conf
+        let mut dns_over_tls = map_call_error("get link dns over tls", link.dns_over_tls())?;
+        if dns_over_tls.is_empty() {
+            dns_over_tls = map_call_error("get dns over tls", manager.dns_over_tls())?;
+        }
+
+        // the drop-in holds what the user entered, the link may only
+        // have it again once it is restored
+        match read_drop_in(&self.drop_in_path)? {
+            Some(config) if config.mode == DnsMode::Manual => Ok(config),
+            _ => Ok(DnsConfig {
+                mode: DnsMode::Auto,
+                servers,
+                search_domains,
+                dns_over_tls: parse_dns_over_tls(&dns_over_tls),
+            }),
+        }
+    }
+
+    fn set_dns_config(&self, config: &DnsConfig) -> Result<()> {
+        self.set_link_dns_config(config)?;
+        write_drop_in(&self.drop_in_path, config)
+    }
+}
+
+fn dns_address(address: &IpAddr) -> (i32, Vec<u8>) {
+    match address {
+        IpAddr::V4(address) => (AF_INET, address.octets().to_vec()),
+        IpAddr::V6(address) => (AF_INET6, address.octets().to_vec()),
+    }
+}
+
+fn ip_address(family: i32, address: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => <[u8; 4]>::try_from(address)
+            .ok()
+            .map(|octets| IpAddr::V4(Ipv4Addr::from(octets))),
+        AF_INET6 => <[u8; 16]>::try_from(address)
+            .ok()
+            .map(|octets| IpAddr::V6(Ipv6Addr::from(octets))),
+        _ => None,
+    }
+}
+
+fn dns_over_tls_value(dns_over_tls: DnsOverTls) -> &'static str {
+    match dns_over_tls {
+        DnsOverTls::Off => "no",
+        DnsOverTls::Opportunistic => "opportunistic",
+        DnsOverTls::Strict => "yes",
+    }
+}
+
+fn parse_dns_over_tls(mode: &str) -> DnsOverTls {
+    match mode {
+        "yes" => DnsOverTls::Strict,
+        "opportunistic" => DnsOverTls::Opportunistic,
+        _ => DnsOverTls::Off,
+    }
+}
+
+fn map_call_error<T>(call: &str, result: zbus::Result<T>) -> Result<T> {
+    dbus::map_call_error(
+        LockScreenErrorCodes::DnsBackendCallError,
+        "systemd-resolved",
+        call,
+        result,
+    )
+}
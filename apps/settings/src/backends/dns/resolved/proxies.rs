@@ -0,0 +1,39 @@
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath};
+
+/// Root object of systemd-resolved, `/org/freedesktop/resolve1`
+#[dbus_proxy(
+    interface = "org.freedesktop.resolve1.Manager",
+    default_service = "org.freedesktop.resolve1",
+    default_path = "/org/freedesktop/resolve1"
+)]
+trait Manager {
+    fn get_link(&self, ifindex: i32) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(name = "SetLinkDNS")]
+    fn set_link_dns(&self, ifindex: i32, addresses: &[(i32, Vec<u8>)]) -> zbus::Result<()>;
+
+    fn set_link_domains(&self, ifindex: i32, domains: &[(&str, bool)]) -> zbus::Result<()>;
+
+    #[dbus_proxy(name = "SetLinkDNSOverTLS")]
+    fn set_link_dns_over_tls(&self, ifindex: i32, mode: &str) -> zbus::Result<()>;
+
+    fn revert_link(&self, ifindex: i32) -> zbus::Result<()>;
+
+    #[dbus_proxy(property, name = "DNSOverTLS")]
+    fn dns_over_tls(&self) -> zbus::Result<String>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.resolve1.Link",
+    default_service = "org.freedesktop.resolve1"
+)]
+trait Link {
+    #[dbus_proxy(property, name = "DNS")]
+    fn dns(&self) -> zbus::Result<Vec<(i32, Vec<u8>)>>;
+
+    #[dbus_proxy(property)]
+    fn domains(&self) -> zbus::Result<Vec<(String, bool)>>;
+
+    #[dbus_proxy(property, name = "DNSOverTLS")]
+    fn dns_over_tls(&self) -> zbus::Result<String>;
+}
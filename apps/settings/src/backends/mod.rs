@@ -0,0 +1,11 @@
+pub mod bluetooth;
+pub mod data_usage;
+mod dbus;
+pub mod diagnostics;
+pub mod display;
+pub mod dns;
+pub mod network;
+pub mod rfkill;
+
+#[cfg(test)]
+mod test_bus;
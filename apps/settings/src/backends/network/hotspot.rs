@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// Channels usable in most regulatory domains, the driver still
+/// refuses the ones the configured country does not allow
+const CHANNELS_2_4GHZ: [u32; 11] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const CHANNELS_5GHZ: [u32; 9] = [36, 40, 44, 48, 149, 153, 157, 161, 165];
+
+/// Name of the hotspot until the user picks one
+const DEFAULT_SSID: &str = "Mecha Hotspot";
+
+/// Frequency band the hotspot is started on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HotspotBand {
+    #[default]
+    Band2_4GHz,
+    Band5GHz,
+}
+
+impl HotspotBand {
+    pub fn channels(&self) -> &'static [u32] {
+        match self {
+            HotspotBand::Band2_4GHz => &CHANNELS_2_4GHZ,
+            HotspotBand::Band5GHz => &CHANNELS_5GHZ,
+        }
+    }
+
+    /// Band of a frequency in MHz
+    pub fn from_frequency(frequency: u32) -> Self {
+        match frequency > 4000 {
+            true => HotspotBand::Band5GHz,
+            false => HotspotBand::Band2_4GHz,
+        }
+    }
+}
+
+impl fmt::Display for HotspotBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HotspotBand::Band2_4GHz => write!(f, "2.4 GHz"),
+            HotspotBand::Band5GHz => write!(f, "5 GHz"),
+        }
+    }
+}
+
+/// # Hotspot Config
+///
+/// Access point shared by the device, always WPA2 protected,
+/// `channel` is picked by the driver when not set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotspotConfig {
+    pub ssid: String,
+    pub password: String,
+    pub band: HotspotBand,
+    pub channel: Option<u32>,
+}
+
+impl Default for HotspotConfig {
+    fn default() -> Self {
+        Self {
+            ssid: DEFAULT_SSID.to_string(),
+            password: String::new(),
+            band: HotspotBand::default(),
+            channel: None,
+        }
+    }
+}
+
+impl HotspotConfig {
+    /// Checks the config can be started, returns a message to show the user otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ssid.is_empty() || self.ssid.len() > 32 {
+            return Err("network name must be 1 to 32 bytes long".to_string());
+        }
+        if !(8..=63).contains(&self.password.len()) || !self.password.is_ascii() {
+            return Err("password must be 8 to 63 ASCII characters".to_string());
+        }
+        if let Some(channel) = self.channel {
+            if !self.band.channels().contains(&channel) {
+                return Err(format!(
+                    "channel {} is not in the {} band",
+                    channel, self.band
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Device connected to the hotspot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotspotClient {
+    pub mac_address: String,
+    pub ip_address: Option<String>,
+}
+
+/// Whether the hotspot is running and who is connected to it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HotspotStatus {
+    pub active: bool,
+    pub clients: Vec<HotspotClient>,
+}
@@ -0,0 +1,412 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// # IP Connection
+///
+/// Connection whose IP configuration is read or changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpConnection {
+    /// Saved profile of a Wi-Fi network, by SSID
+    Wifi(String),
+    /// Profile of the first wired device
+    Ethernet,
+}
+
+impl fmt::Display for IpConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpConnection::Wifi(ssid) => write!(f, "{}", ssid),
+            IpConnection::Ethernet => write!(f, "Ethernet"),
+        }
+    }
+}
+
+/// Address family shown by the protocol pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpFamily {
+    #[default]
+    V4,
+    V6,
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpFamily::V4 => write!(f, "IPv4"),
+            IpFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Manually assigned IPv4 address of a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticIpv4 {
+    pub address: Ipv4Addr,
+    pub prefix: u32,
+    pub gateway: Ipv4Addr,
+    pub dns: Vec<Ipv4Addr>,
+}
+
+impl StaticIpv4 {
+    /// Parses the fields entered by the user, the netmask can be given as
+    /// `255.255.255.0`, `24` or `/24` and DNS servers are comma or space separated
+    pub fn parse(address: &str, netmask: &str, gateway: &str, dns: &str) -> Result<Self, String> {
+        let address = parse_ipv4(address, "IP address")?;
+        let prefix = parse_prefix(netmask)?;
+        let gateway = parse_ipv4(gateway, "gateway")?;
+        let dns = dns
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|server| !server.is_empty())
+            .map(|server| parse_ipv4(server, "DNS server"))
+            .collect::<Result<Vec<Ipv4Addr>, String>>()?;
+
+        let config = Self {
+            address,
+            prefix,
+            gateway,
+            dns,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the address and gateway are usable hosts of the same subnet
+    pub fn validate(&self) -> Result<(), String> {
+        // /31 and /32 have no room for a gateway next to the address
+        if !(1..=30).contains(&self.prefix) {
+            return Err("prefix must be between 1 and 30".to_string());
+        }
+        check_host(self.address, "IP address")?;
+        check_host(self.gateway, "gateway")?;
+
+        let network = self.network();
+        let broadcast = self.broadcast();
+        if self.address == network || self.address == broadcast {
+            return Err(format!(
+                "{} is the network or broadcast address of the subnet",
+                self.address
+            ));
+        }
+        if u32::from(self.gateway) & self.mask() != u32::from(network) {
+            return Err(format!("gateway {} is outside the subnet", self.gateway));
+        }
+        if self.gateway == network || self.gateway == broadcast {
+            return Err(format!(
+                "gateway {} is the network or broadcast address of the subnet",
+                self.gateway
+            ));
+        }
+        if self.gateway == self.address {
+            return Err("gateway must differ from the IP address".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn mask(&self) -> u32 {
+        prefix_to_mask(self.prefix)
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.mask())
+    }
+
+    fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) & self.mask())
+    }
+
+    fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) | !self.mask())
+    }
+}
+
+/// IPv4 configuration method of a connection
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Ipv4Config {
+    #[default]
+    Auto,
+    Static(StaticIpv4),
+}
+
+impl fmt::Display for Ipv4Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ipv4Config::Auto => write!(f, "Auto [ DHCP ]"),
+            Ipv4Config::Static(_) => write!(f, "Static"),
+        }
+    }
+}
+
+/// Manually assigned IPv6 address of a connection, routers are
+/// often only reachable through their link-local address so the
+/// gateway may be outside the prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticIpv6 {
+    pub address: Ipv6Addr,
+    pub prefix: u32,
+    pub gateway: Option<Ipv6Addr>,
+    pub dns: Vec<Ipv6Addr>,
+}
+
+impl StaticIpv6 {
+    /// Parses the fields entered by the user, the prefix can be given as
+    /// `64` or `/64`, the gateway may be left empty and DNS servers are
+    /// comma or space separated
+    pub fn parse(address: &str, prefix: &str, gateway: &str, dns: &str) -> Result<Self, String> {
+        let address = parse_ipv6(address, "IPv6 address")?;
+        let prefix = prefix
+            .trim()
+            .trim_start_matches('/')
+            .parse::<u32>()
+            .map_err(|_| format!("prefix length '{}' is not a number", prefix.trim()))?;
+        let gateway = match gateway.trim().is_empty() {
+            true => None,
+            false => Some(parse_ipv6(gateway, "gateway")?),
+        };
+        let dns = dns
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|server| !server.is_empty())
+            .map(|server| parse_ipv6(server, "DNS server"))
+            .collect::<Result<Vec<Ipv6Addr>, String>>()?;
+
+        let config = Self {
+            address,
+            prefix,
+            gateway,
+            dns,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the address is a global unicast host address and the
+    /// gateway is either link-local or inside the prefix
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=128).contains(&self.prefix) {
+            return Err("prefix length must be between 1 and 128".to_string());
+        }
+        check_ipv6_host(self.address, "IPv6 address")?;
+        if is_ipv6_link_local(self.address) {
+            return Err("link-local addresses are assigned automatically".to_string());
+        }
+        // the all-zeros host of a prefix is the subnet-router anycast address
+        if self.prefix < 127 && u128::from(self.address) & !self.mask() == 0 {
+            return Err(format!(
+                "{} is the subnet-router anycast address of the prefix",
+                self.address
+            ));
+        }
+
+        if let Some(gateway) = self.gateway {
+            check_ipv6_host(gateway, "gateway")?;
+            let same_prefix =
+                u128::from(gateway) & self.mask() == u128::from(self.address) & self.mask();
+            if !is_ipv6_link_local(gateway) && !same_prefix {
+                return Err(format!(
+                    "gateway {} is neither link-local nor inside the prefix",
+                    gateway
+                ));
+            }
+            if gateway == self.address {
+                return Err("gateway must differ from the IPv6 address".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn mask(&self) -> u128 {
+        match self.prefix {
+            0 => 0,
+            prefix => u128::MAX << (128 - prefix.min(128)),
+        }
+    }
+}
+
+/// IPv6 configuration method of a connection
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Ipv6Config {
+    /// Stateless autoconfiguration from router advertisements
+    #[default]
+    Auto,
+    Dhcp,
+    Static(StaticIpv6),
+    LinkLocal,
+    Disabled,
+}
+
+impl fmt::Display for Ipv6Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ipv6Config::Auto => write!(f, "Auto [ SLAAC ]"),
+            Ipv6Config::Dhcp => write!(f, "DHCPv6"),
+            Ipv6Config::Static(_) => write!(f, "Static"),
+            Ipv6Config::LinkLocal => write!(f, "Link-local only"),
+            Ipv6Config::Disabled => write!(f, "Disabled"),
+        }
+    }
+}
+
+/// Whether an address is in `fe80::/10`
+pub fn is_ipv6_link_local(address: Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+fn prefix_to_mask(prefix: u32) -> u32 {
+    match prefix {
+        0 => 0,
+        prefix => u32::MAX << (32 - prefix.min(32)),
+    }
+}
+
+fn parse_ipv4(value: &str, field: &str) -> Result<Ipv4Addr, String> {
+    value
+        .trim()
+        .parse::<Ipv4Addr>()
+        .map_err(|_| format!("{} '{}' is not a valid IPv4 address", field, value.trim()))
+}
+
+fn parse_prefix(netmask: &str) -> Result<u32, String> {
+    let netmask = netmask.trim();
+    if let Ok(prefix) = netmask.trim_start_matches('/').parse::<u32>() {
+        return match prefix <= 32 {
+            true => Ok(prefix),
+            false => Err(format!("prefix /{} is out of range", prefix)),
+        };
+    }
+
+    let mask = u32::from(parse_ipv4(netmask, "subnet mask")?);
+    // a mask is a run of ones followed by zeros
+    if mask.leading_ones() + mask.trailing_zeros() != 32 {
+        return Err(format!("subnet mask {} is not contiguous", netmask));
+    }
+    Ok(mask.leading_ones())
+}
+
+fn check_host(address: Ipv4Addr, field: &str) -> Result<(), String> {
+    if address.is_unspecified()
+        || address.is_loopback()
+        || address.is_multicast()
+        || address.is_broadcast()
+        || address.octets()[0] >= 240
+    {
+        return Err(format!(
+            "{} {} cannot be assigned to a host",
+            field, address
+        ));
+    }
+    Ok(())
+}
+
+fn parse_ipv6(value: &str, field: &str) -> Result<Ipv6Addr, String> {
+    value
+        .trim()
+        .parse::<Ipv6Addr>()
+        .map_err(|_| format!("{} '{}' is not a valid IPv6 address", field, value.trim()))
+}
+
+fn check_ipv6_host(address: Ipv6Addr, field: &str) -> Result<(), String> {
+    if address.is_unspecified() || address.is_loopback() || address.is_multicast() {
+        return Err(format!(
+            "{} {} cannot be assigned to a host",
+            field, address
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netmask_is_accepted_in_every_notation() {
+        for netmask in ["255.255.255.0", "24", "/24", " 24 "] {
+            let config = StaticIpv4::parse("192.168.1.50", netmask, "192.168.1.1", "").unwrap();
+            assert_eq!(config.prefix, 24);
+            assert_eq!(config.netmask(), Ipv4Addr::new(255, 255, 255, 0));
+        }
+    }
+
+    #[test]
+    fn dns_servers_split_on_commas_and_spaces() {
+        let config =
+            StaticIpv4::parse("10.0.0.5", "8", "10.0.0.1", "1.1.1.1, 8.8.8.8 9.9.9.9").unwrap();
+        assert_eq!(
+            config.dns,
+            vec![
+                Ipv4Addr::new(1, 1, 1, 1),
+                Ipv4Addr::new(8, 8, 8, 8),
+                Ipv4Addr::new(9, 9, 9, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn unusable_ipv4_settings_are_rejected() {
+        let cases = [
+            ("192.168.1.300", "24", "192.168.1.1"),
+            ("192.168.1.50", "255.0.255.0", "192.168.1.1"),
+            ("192.168.1.50", "/33", "192.168.1.1"),
+            ("192.168.1.50", "31", "192.168.1.51"),
+            ("192.168.1.0", "24", "192.168.1.1"),
+            ("192.168.1.255", "24", "192.168.1.1"),
+            ("192.168.1.50", "24", "192.168.2.1"),
+            ("192.168.1.50", "24", "192.168.1.50"),
+            ("127.0.0.2", "8", "127.0.0.1"),
+            ("224.0.0.5", "24", "224.0.0.1"),
+        ];
+        for (address, netmask, gateway) in cases {
+            assert!(
+                StaticIpv4::parse(address, netmask, gateway, "").is_err(),
+                "{}/{} via {} was accepted",
+                address,
+                netmask,
+                gateway
+            );
+        }
+        assert!(StaticIpv4::parse("192.168.1.50", "24", "192.168.1.1", "dns.local").is_err());
+    }
+
+    #[test]
+    fn ipv6_gateway_may_be_link_local_or_missing() {
+        let config = StaticIpv6::parse("2001:db8::10", "/64", "fe80::1", "2001:db8::53").unwrap();
+        assert_eq!(config.prefix, 64);
+        assert_eq!(config.gateway, Some("fe80::1".parse().unwrap()));
+
+        let config = StaticIpv6::parse("2001:db8::10", "64", "", "").unwrap();
+        assert_eq!(config.gateway, None);
+        assert!(config.dns.is_empty());
+    }
+
+    #[test]
+    fn unusable_ipv6_settings_are_rejected() {
+        let cases = [
+            ("2001:db8::10", "0", ""),
+            ("2001:db8::10", "129", ""),
+            ("fe80::10", "64", ""),
+            ("2001:db8::", "64", ""),
+            ("::1", "128", ""),
+            ("ff02::1", "64", ""),
+            ("2001:db8::10", "64", "2001:db9::1"),
+            ("2001:db8::10", "64", "2001:db8::10"),
+        ];
+        for (address, prefix, gateway) in cases {
+            assert!(
+                StaticIpv6::parse(address, prefix, gateway, "").is_err(),
+                "{}/{} via {:?} was accepted",
+                address,
+                prefix,
+                gateway
+            );
+        }
+    }
+
+    #[test]
+    fn link_local_range_is_fe80_slash_10() {
+        assert!(is_ipv6_link_local("fe80::1".parse().unwrap()));
+        assert!(is_ipv6_link_local("febf::1".parse().unwrap()));
+        assert!(!is_ipv6_link_local("fec0::1".parse().unwrap()));
+        assert!(!is_ipv6_link_local("2001:db8::1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,925 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{bail, Result};
+use tracing::{error, warn};
+
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::{NetworkBackendSettings, NetworkServiceType},
+};
+
+use custom_utils::proxy;
+pub use custom_utils::proxy::{parse_bypass_hosts, ProxyConfig, ProxyMode};
+
+mod hotspot;
+mod ip_config;
+mod network_manager;
+mod procfs;
+mod sysfs;
+mod wireguard;
+mod wpa_supplicant;
+
+pub use hotspot::{HotspotBand, HotspotClient, HotspotConfig, HotspotStatus};
+pub use ip_config::{
+    is_ipv6_link_local, IpConnection, IpFamily, Ipv4Config, Ipv6Config, StaticIpv4, StaticIpv6,
+};
+pub use network_manager::NetworkManagerBackend;
+pub use procfs::{default_gateway, default_route_interface, default_route_interface_in};
+pub use sysfs::{EthernetLink, OperState};
+pub use wireguard::{
+    WireguardConfig, WireguardPeer, WireguardProfile, WireguardStatus, WireguardTable,
+};
+pub use wpa_supplicant::WpaSupplicantBackend;
+
+/// # Connection State
+///
+/// State of an active connection, mirrors `NMActiveConnectionState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionState {
+    #[default]
+    Unknown,
+    Activating,
+    Activated,
+    Deactivating,
+    Deactivated,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionState::Unknown => write!(f, "Unknown"),
+            ConnectionState::Activating => write!(f, "Connecting"),
+            ConnectionState::Activated => write!(f, "Connected"),
+            ConnectionState::Deactivating => write!(f, "Disconnecting"),
+            ConnectionState::Deactivated => write!(f, "Disconnected"),
+        }
+    }
+}
+
+/// Wi-Fi network the device is currently using
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveWifi {
+    pub ssid: String,
+    pub state: ConnectionState,
+}
+
+/// # Wi-Fi Security
+///
+/// Key management used by a Wi-Fi network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WifiSecurity {
+    #[default]
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa3Sae,
+    Enterprise,
+}
+
+impl WifiSecurity {
+    pub fn is_private(&self) -> bool {
+        *self != WifiSecurity::Open
+    }
+}
+
+impl fmt::Display for WifiSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WifiSecurity::Open => write!(f, "None"),
+            WifiSecurity::Wep => write!(f, "WEP"),
+            WifiSecurity::WpaPsk => write!(f, "WPA2"),
+            WifiSecurity::Wpa3Sae => write!(f, "WPA3"),
+            WifiSecurity::Enterprise => write!(f, "Enterprise"),
+        }
+    }
+}
+
+/// Wi-Fi network as seen in the scan results, `strength` is in percent
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub bssid: String,
+    pub strength: u8,
+    pub frequency: u32,
+    pub security: WifiSecurity,
+    pub is_connected: bool,
+}
+
+/// Saved Wi-Fi connection profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownWifi {
+    pub id: String,
+    pub ssid: String,
+    pub security: WifiSecurity,
+}
+
+/// Wi-Fi networks split into saved and not yet saved ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WifiNetworkList {
+    pub known: Vec<WifiNetwork>,
+    pub available: Vec<WifiNetwork>,
+}
+
+/// # EAP Method
+///
+/// Outer authentication of an enterprise (802.1X) network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EapMethod {
+    #[default]
+    Peap,
+    Ttls,
+    Tls,
+}
+
+impl fmt::Display for EapMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EapMethod::Peap => write!(f, "PEAP"),
+            EapMethod::Ttls => write!(f, "TTLS"),
+            EapMethod::Tls => write!(f, "TLS"),
+        }
+    }
+}
+
+/// # Phase 2 Auth
+///
+/// Inner authentication of a PEAP or TTLS tunnel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase2Auth {
+    #[default]
+    Mschapv2,
+    Gtc,
+    Pap,
+    Chap,
+}
+
+impl fmt::Display for Phase2Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase2Auth::Mschapv2 => write!(f, "MSCHAPv2"),
+            Phase2Auth::Gtc => write!(f, "GTC"),
+            Phase2Auth::Pap => write!(f, "PAP"),
+            Phase2Auth::Chap => write!(f, "CHAP"),
+        }
+    }
+}
+
+/// 802.1X credentials of an enterprise network, certificates are file paths
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnterpriseSettings {
+    pub eap: EapMethod,
+    pub phase2_auth: Phase2Auth,
+    pub identity: String,
+    pub anonymous_identity: Option<String>,
+    pub password: Option<String>,
+    pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub private_key: Option<String>,
+    pub private_key_password: Option<String>,
+}
+
+/// # Wi-Fi Profile
+///
+/// Saved connection profile, `password` is the pre-shared key of
+/// WPA2/WPA3 networks and `enterprise` is required for 802.1X ones
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WifiProfile {
+    pub ssid: String,
+    pub hidden: bool,
+    pub security: WifiSecurity,
+    pub password: Option<String>,
+    pub enterprise: Option<EnterpriseSettings>,
+}
+
+impl WifiProfile {
+    /// Checks the profile is complete, returns a message to show the user otherwise
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.ssid.is_empty() || self.ssid.len() > 32 {
+            return Err("network name must be 1 to 32 characters".to_string());
+        }
+
+        match self.security {
+            WifiSecurity::Open => Ok(()),
+            WifiSecurity::Wep => Err("WEP networks are not supported".to_string()),
+            WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae => {
+                let password_len = self.password.as_deref().unwrap_or_default().len();
+                match (8..=63).contains(&password_len) {
+                    true => Ok(()),
+                    false => Err("password must be 8 to 63 characters".to_string()),
+                }
+            }
+            WifiSecurity::Enterprise => {
+                let enterprise = match &self.enterprise {
+                    Some(enterprise) => enterprise,
+                    None => return Err("enterprise settings are missing".to_string()),
+                };
+                if enterprise.identity.is_empty() {
+                    return Err("identity is required".to_string());
+                }
+                match enterprise.eap {
+                    EapMethod::Tls => {
+                        if enterprise.client_cert.is_none() || enterprise.private_key.is_none() {
+                            return Err(
+                                "TLS needs a client certificate and private key".to_string()
+                            );
+                        }
+                    }
+                    EapMethod::Peap | EapMethod::Ttls => {
+                        if enterprise
+                            .password
+                            .as_deref()
+                            .unwrap_or_default()
+                            .is_empty()
+                        {
+                            return Err("password is required".to_string());
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&WifiConnectRequest> for WifiProfile {
+    fn from(request: &WifiConnectRequest) -> Self {
+        Self {
+            ssid: request.ssid.clone(),
+            hidden: false,
+            security: request.security,
+            password: request.password.clone(),
+            enterprise: None,
+        }
+    }
+}
+
+/// Request to join a Wi-Fi network, `password` is not needed
+/// for open networks or networks with a saved profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiConnectRequest {
+    pub ssid: String,
+    pub security: WifiSecurity,
+    pub password: Option<String>,
+}
+
+/// # Wi-Fi Connect Failure
+///
+/// Reason a connection attempt did not succeed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifiConnectFailure {
+    WrongPassword,
+    Timeout,
+    DhcpFailed,
+    NetworkNotFound,
+    Other(String),
+}
+
+impl fmt::Display for WifiConnectFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WifiConnectFailure::WrongPassword => write!(f, "Wrong password"),
+            WifiConnectFailure::Timeout => write!(f, "Connection timed out"),
+            WifiConnectFailure::DhcpFailed => write!(f, "Could not obtain an IP address"),
+            WifiConnectFailure::NetworkNotFound => write!(f, "Network not found"),
+            WifiConnectFailure::Other(reason) => write!(f, "Could not connect - {}", reason),
+        }
+    }
+}
+
+/// # Wi-Fi Connect State
+///
+/// Progress of a connection attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifiConnectState {
+    Associating,
+    Authenticating,
+    ObtainingIp,
+    Connected,
+    Failed(WifiConnectFailure),
+}
+
+impl fmt::Display for WifiConnectState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WifiConnectState::Associating => write!(f, "Associating..."),
+            WifiConnectState::Authenticating => write!(f, "Authenticating..."),
+            WifiConnectState::ObtainingIp => write!(f, "Obtaining IP address..."),
+            WifiConnectState::Connected => write!(f, "Connected"),
+            WifiConnectState::Failed(failure) => write!(f, "{}", failure),
+        }
+    }
+}
+
+/// Address assigned to an interface with its prefix length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpAddress {
+    pub address: String,
+    pub prefix: u32,
+}
+
+impl IpAddress {
+    /// Dotted subnet mask of an IPv4 address, `None` for IPv6
+    pub fn subnet_mask(&self) -> Option<String> {
+        if self.address.parse::<Ipv4Addr>().is_err() || self.prefix > 32 {
+            return None;
+        }
+        let mask = match self.prefix {
+            0 => 0,
+            prefix => u32::MAX << (32 - prefix),
+        };
+        Some(Ipv4Addr::from(mask).to_string())
+    }
+
+    /// Whether this is an IPv6 link-local (`fe80::/10`) address
+    pub fn is_link_local(&self) -> bool {
+        self.address
+            .parse::<Ipv6Addr>()
+            .map(is_ipv6_link_local)
+            .unwrap_or(false)
+    }
+}
+
+impl fmt::Display for IpAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// IP configuration of a connected interface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpDetails {
+    pub ipv4_addresses: Vec<IpAddress>,
+    pub ipv4_gateway: Option<String>,
+    pub ipv6_addresses: Vec<IpAddress>,
+    pub ipv6_gateway: Option<String>,
+    pub dns: Vec<String>,
+}
+
+/// # MAC Address Mode
+///
+/// Hardware address the device uses on a network, `Stable` is a
+/// random address that stays the same for each network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MacAddressMode {
+    #[default]
+    Device,
+    Random,
+    Stable,
+}
+
+impl fmt::Display for MacAddressMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacAddressMode::Device => write!(f, "Device address"),
+            MacAddressMode::Random => write!(f, "Random"),
+            MacAddressMode::Stable => write!(f, "Stable per network"),
+        }
+    }
+}
+
+/// # Wi-Fi Details
+///
+/// Live state of a network, `ip` is only set while connected and
+/// `autoconnect` is only meaningful for networks with a saved profile.
+/// `mac_address_mode` and `metered` are unset when the service cannot
+/// store them on the profile
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WifiDetails {
+    pub network: WifiNetwork,
+    pub is_known: bool,
+    pub autoconnect: bool,
+    pub mac_address_mode: Option<MacAddressMode>,
+    pub metered: Option<bool>,
+    pub ip: Option<IpDetails>,
+}
+
+/// Returns the Wi-Fi channel of a frequency in MHz
+pub fn wifi_channel(frequency: u32) -> Option<u32> {
+    match frequency {
+        2484 => Some(14),
+        2412..=2472 => Some((frequency - 2407) / 5),
+        5160..=5885 => Some((frequency - 5000) / 5),
+        5955..=7115 => Some((frequency - 5950) / 5),
+        _ => None,
+    }
+}
+
+/// Snapshot of the Wi-Fi radio and its active network
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WifiStatus {
+    pub enabled: bool,
+    pub active: Option<ActiveWifi>,
+}
+
+/// # Network Backend
+///
+/// Abstraction over the system service that manages network
+/// connections, every call is blocking and should be made
+/// off the main thread
+pub trait NetworkBackend: Send + Sync {
+    /// Returns if the Wi-Fi radio is enabled
+    fn wifi_enabled(&self) -> Result<bool>;
+
+    /// Enables or disables the Wi-Fi radio
+    fn set_wifi_enabled(&self, enabled: bool) -> Result<()>;
+
+    /// Returns the active Wi-Fi network, if any
+    fn active_wifi(&self) -> Result<Option<ActiveWifi>>;
+
+    /// Asks the Wi-Fi device to scan, results are available
+    /// from `wifi_networks` once the scan completes
+    fn request_wifi_scan(&self) -> Result<()>;
+
+    /// Returns the networks from the last scan, one entry per BSSID
+    fn wifi_networks(&self) -> Result<Vec<WifiNetwork>>;
+
+    /// Returns the saved Wi-Fi connection profiles
+    fn known_wifi_networks(&self) -> Result<Vec<KnownWifi>>;
+
+    /// Joins a Wi-Fi network, every step is reported through `on_state`
+    /// and the call returns once the connection is up or has failed
+    fn connect_wifi(
+        &self,
+        request: &WifiConnectRequest,
+        on_state: &dyn Fn(WifiConnectState),
+    ) -> Result<()>;
+
+    /// Saves a Wi-Fi connection profile without connecting to it
+    fn add_wifi_profile(&self, profile: &WifiProfile) -> Result<()>;
+
+    /// Returns the live state of a network by SSID
+    fn wifi_details(&self, ssid: &str) -> Result<WifiDetails>;
+
+    /// Deletes every saved profile of a network, disconnecting it if active
+    fn forget_wifi(&self, ssid: &str) -> Result<()>;
+
+    /// Sets if the saved profile of a network is joined automatically
+    fn set_wifi_autoconnect(&self, ssid: &str, autoconnect: bool) -> Result<()>;
+
+    /// Sets the MAC address the saved profile of a network uses, an
+    /// active connection is reapplied to pick up the new address
+    fn set_wifi_mac_address_mode(&self, ssid: &str, mode: MacAddressMode) -> Result<()>;
+
+    /// Marks the saved profile of a network as metered, so background
+    /// downloads like updates hold off while it is the default route
+    fn set_wifi_metered(&self, ssid: &str, metered: bool) -> Result<()>;
+
+    /// Returns the saved IPv4 configuration of a connection
+    fn ipv4_config(&self, connection: &IpConnection) -> Result<Ipv4Config>;
+
+    /// Saves the IPv4 configuration of a connection, an active connection
+    /// is reapplied and the call fails if it does not come back up
+    fn set_ipv4_config(&self, connection: &IpConnection, config: &Ipv4Config) -> Result<()>;
+
+    /// Applies an IPv4 configuration, restoring the previous one on failure
+    fn apply_ipv4_config(&self, connection: &IpConnection, config: &Ipv4Config) -> Result<()> {
+        let previous_config = self.ipv4_config(connection)?;
+        apply_or_revert(connection, config, &previous_config, |config| {
+            self.set_ipv4_config(connection, config)
+        })
+    }
+
+    /// Returns the saved IPv6 configuration of a connection
+    fn ipv6_config(&self, connection: &IpConnection) -> Result<Ipv6Config>;
+
+    /// Saves the IPv6 configuration of a connection, an active connection
+    /// is reapplied and the call fails if it does not come back up
+    fn set_ipv6_config(&self, connection: &IpConnection, config: &Ipv6Config) -> Result<()>;
+
+    /// Applies an IPv6 configuration, restoring the previous one on failure
+    fn apply_ipv6_config(&self, connection: &IpConnection, config: &Ipv6Config) -> Result<()> {
+        let previous_config = self.ipv6_config(connection)?;
+        apply_or_revert(connection, config, &previous_config, |config| {
+            self.set_ipv6_config(connection, config)
+        })
+    }
+
+    /// Returns the wired interfaces with their link state
+    fn ethernet_links(&self) -> Result<Vec<EthernetLink>>;
+
+    /// Brings a wired interface up or down
+    fn set_ethernet_enabled(&self, interface: &str, enabled: bool) -> Result<()>;
+
+    /// Returns the saved hotspot settings, defaults when none are saved
+    fn hotspot_config(&self) -> Result<HotspotConfig>;
+
+    /// Returns if the hotspot is running and the devices connected to it
+    fn hotspot_status(&self) -> Result<HotspotStatus>;
+
+    /// Saves the hotspot settings and starts sharing the uplink over
+    /// Wi-Fi, the Wi-Fi client connection is dropped while it runs
+    fn start_hotspot(&self, config: &HotspotConfig) -> Result<()>;
+
+    /// Stops the hotspot, the Wi-Fi client connection can be used again
+    fn stop_hotspot(&self) -> Result<()>;
+
+    /// Returns the proxy used by the device
+    fn proxy_config(&self) -> Result<ProxyConfig>;
+
+    /// Saves the device proxy, the session gets the proxy servers at the
+    /// next login and the connect app when it starts
+    fn set_proxy_config(&self, config: &ProxyConfig) -> Result<()>;
+
+    /// Returns the saved WireGuard profiles and whether their tunnel is up
+    fn wireguard_profiles(&self) -> Result<Vec<WireguardProfile>>;
+
+    /// Saves a WireGuard profile, an existing profile with the same name is replaced
+    fn import_wireguard_profile(&self, config: &WireguardConfig) -> Result<()>;
+
+    /// Brings the tunnel of a saved WireGuard profile up or down
+    fn set_wireguard_active(&self, name: &str, active: bool) -> Result<()>;
+
+    /// Returns the handshake and transfer counters of a WireGuard tunnel
+    fn wireguard_status(&self, name: &str) -> Result<WireguardStatus>;
+
+    /// Deletes a saved WireGuard profile, bringing its tunnel down first
+    fn remove_wireguard_profile(&self, name: &str) -> Result<()>;
+
+    fn wifi_network_list(&self) -> Result<WifiNetworkList> {
+        let networks = collapse_by_ssid(self.wifi_networks()?);
+        let known_networks = self.known_wifi_networks()?;
+        Ok(split_known_networks(networks, known_networks))
+    }
+
+    fn wifi_status(&self) -> Result<WifiStatus> {
+        let enabled = self.wifi_enabled()?;
+        let active = match enabled {
+            true => self.active_wifi()?,
+            false => None,
+        };
+        Ok(WifiStatus { enabled, active })
+    }
+}
+
+fn read_proxy_env(path: &Path) -> Result<ProxyConfig> {
+    match proxy::read_env_file(path) {
+        Ok(config) => Ok(config),
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot read {:?} - {}", path, e),
+            ));
+        }
+    }
+}
+
+fn write_proxy_env(path: &Path, config: &ProxyConfig) -> Result<()> {
+    if let Err(reason) = config.validate() {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::NetworkBackendCallError,
+            format!("invalid proxy settings - {}", reason),
+        ));
+    }
+    if let Err(e) = proxy::write_env_file(path, config) {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::NetworkBackendCallError,
+            format!("cannot write {:?} - {}", path, e),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_wireguard_config(config: &WireguardConfig) -> Result<()> {
+    if let Err(reason) = config.validate() {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::NetworkBackendCallError,
+            format!("invalid WireGuard profile - {}", reason),
+        ));
+    }
+    Ok(())
+}
+
+/// The settings app runs as the user, commands that change links
+/// need `CAP_NET_ADMIN`
+fn missing_privilege(action: &str) -> LockScreenError {
+    LockScreenError::new(
+        LockScreenErrorCodes::NetworkBackendPermissionError,
+        format!("{} needs administrator rights", action),
+    )
+}
+
+/// Text shown on a page for a failed call, a missing privilege is
+/// told plainly rather than as a call failure
+pub fn failure_reason(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<LockScreenError>() {
+        Some(LockScreenError {
+            code: LockScreenErrorCodes::NetworkBackendPermissionError,
+            message,
+        }) => message.clone(),
+        _ => e.to_string(),
+    }
+}
+
+/// Whether a command failed for lack of privileges, as reported by
+/// `ip`, `wg` and `wg-quick`
+fn is_permission_error(stderr: &str) -> bool {
+    ["Operation not permitted", "Permission denied", "must be run as root"]
+        .iter()
+        .any(|reason| stderr.contains(reason))
+}
+
+fn no_wireguard_profile(name: &str) -> LockScreenError {
+    LockScreenError::new(
+        LockScreenErrorCodes::NetworkBackendCallError,
+        format!("no WireGuard profile named {}", name),
+    )
+}
+
+/// Sets a configuration, putting back the previous one if that fails
+fn apply_or_revert<T: fmt::Display>(
+    connection: &IpConnection,
+    config: &T,
+    previous_config: &T,
+    set: impl Fn(&T) -> Result<()>,
+) -> Result<()> {
+    if let Err(e) = set(config) {
+        warn!(
+            task = "network_backend",
+            "reverting {} of {} to {} {}", config, connection, previous_config, e
+        );
+        if let Err(revert_error) = set(previous_config) {
+            error!(
+                task = "network_backend",
+                "cannot revert {} {}", connection, revert_error
+            );
+            bail!("{} - previous settings could not be restored", e);
+        }
+        bail!("{} - previous settings were restored", e);
+    }
+    Ok(())
+}
+
+/// Collapses scan results to one entry per SSID, keeping the connected
+/// or strongest BSSID, hidden networks are dropped
+pub fn collapse_by_ssid(networks: Vec<WifiNetwork>) -> Vec<WifiNetwork> {
+    let mut by_ssid: HashMap<String, WifiNetwork> = HashMap::new();
+    for network in networks {
+        if network.ssid.is_empty() {
+            continue;
+        }
+        match by_ssid.get(&network.ssid) {
+            Some(existing)
+                if existing.is_connected
+                    || (!network.is_connected && existing.strength >= network.strength) => {}
+            _ => {
+                by_ssid.insert(network.ssid.clone(), network);
+            }
+        }
+    }
+
+    let mut networks: Vec<WifiNetwork> = by_ssid.into_values().collect();
+    networks.sort_by(|a, b| {
+        b.is_connected
+            .cmp(&a.is_connected)
+            .then(b.strength.cmp(&a.strength))
+            .then(a.ssid.cmp(&b.ssid))
+    });
+    networks
+}
+
+/// Splits collapsed scan results into saved and available networks,
+/// saved networks out of range are listed with no strength
+pub fn split_known_networks(
+    networks: Vec<WifiNetwork>,
+    known_networks: Vec<KnownWifi>,
+) -> WifiNetworkList {
+    let mut list = WifiNetworkList::default();
+    for known_network in known_networks.iter() {
+        if list.known.iter().any(|n| n.ssid == known_network.ssid) {
+            continue;
+        }
+        let network = match networks.iter().find(|n| n.ssid == known_network.ssid) {
+            Some(network) => network.clone(),
+            None => WifiNetwork {
+                ssid: known_network.ssid.clone(),
+                security: known_network.security,
+                ..Default::default()
+            },
+        };
+        list.known.push(network);
+    }
+    list.known.sort_by(|a, b| {
+        b.is_connected
+            .cmp(&a.is_connected)
+            .then(b.strength.cmp(&a.strength))
+    });
+
+    list.available = networks
+        .into_iter()
+        .filter(|n| !known_networks.iter().any(|k| k.ssid == n.ssid))
+        .collect();
+    list
+}
+
+pub type NetworkBackendHandle = Arc<dyn NetworkBackend>;
+
+/// # Initializes the network backend
+///
+/// Creates the network backend as configured in the settings.yml
+pub fn init_network_backend(settings: &NetworkBackendSettings) -> NetworkBackendHandle {
+    match settings.service {
+        NetworkServiceType::NetworkManager => Arc::new(NetworkManagerBackend::new(settings)),
+        NetworkServiceType::WpaSupplicant => Arc::new(WpaSupplicantBackend::new(settings)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn network(ssid: &str, bssid: &str, strength: u8, is_connected: bool) -> WifiNetwork {
+        WifiNetwork {
+            ssid: ssid.to_string(),
+            bssid: bssid.to_string(),
+            strength,
+            frequency: 2412,
+            security: WifiSecurity::WpaPsk,
+            is_connected,
+        }
+    }
+
+    fn known(ssid: &str) -> KnownWifi {
+        KnownWifi {
+            id: ssid.to_string(),
+            ssid: ssid.to_string(),
+            security: WifiSecurity::WpaPsk,
+        }
+    }
+
+    #[test]
+    fn scan_results_collapse_to_the_connected_or_strongest_bssid() {
+        let networks = collapse_by_ssid(vec![
+            network("Office", "01", 80, false),
+            network("Office", "02", 40, true),
+            network("Cafe", "03", 30, false),
+            network("Cafe", "04", 60, false),
+            network("", "05", 99, false),
+            network("Lab", "06", 60, false),
+        ]);
+        let summary: Vec<(&str, &str)> = networks
+            .iter()
+            .map(|n| (n.ssid.as_str(), n.bssid.as_str()))
+            .collect();
+        // connected first, then by strength and name
+        assert_eq!(
+            summary,
+            vec![("Office", "02"), ("Cafe", "04"), ("Lab", "06")]
+        );
+    }
+
+    #[test]
+    fn saved_networks_are_split_from_available_ones() {
+        let networks = vec![
+            network("Office", "01", 40, false),
+            network("Cafe", "02", 70, false),
+            network("Lab", "03", 90, true),
+        ];
+        let list = split_known_networks(
+            networks,
+            vec![
+                known("Office"),
+                known("Home"),
+                known("Lab"),
+                known("Office"),
+            ],
+        );
+
+        let known_ssids: Vec<&str> = list.known.iter().map(|n| n.ssid.as_str()).collect();
+        assert_eq!(known_ssids, vec!["Lab", "Office", "Home"]);
+        // out of range networks have no strength
+        assert_eq!(list.known[2].strength, 0);
+        let available_ssids: Vec<&str> = list.available.iter().map(|n| n.ssid.as_str()).collect();
+        assert_eq!(available_ssids, vec!["Cafe"]);
+    }
+
+    #[test]
+    fn profiles_need_the_secrets_of_their_security() {
+        let profile = |security, password: Option<&str>| WifiProfile {
+            ssid: "Office".to_string(),
+            security,
+            password: password.map(String::from),
+            ..Default::default()
+        };
+        assert!(profile(WifiSecurity::Open, None).validate().is_ok());
+        assert!(profile(WifiSecurity::WpaPsk, Some("mecha1234"))
+            .validate()
+            .is_ok());
+        assert!(profile(WifiSecurity::WpaPsk, Some("short"))
+            .validate()
+            .is_err());
+        assert!(profile(WifiSecurity::Wpa3Sae, None).validate().is_err());
+        assert!(profile(WifiSecurity::Wep, Some("abcde"))
+            .validate()
+            .is_err());
+        assert!(profile(WifiSecurity::Enterprise, None).validate().is_err());
+
+        let long_ssid = WifiProfile {
+            ssid: "x".repeat(33),
+            ..Default::default()
+        };
+        assert!(long_ssid.validate().is_err());
+
+        let enterprise = |eap, password: Option<&str>| WifiProfile {
+            enterprise: Some(EnterpriseSettings {
+                eap,
+                identity: "tech".to_string(),
+                password: password.map(String::from),
+                ..Default::default()
+            }),
+            ..profile(WifiSecurity::Enterprise, None)
+        };
+        assert!(enterprise(EapMethod::Peap, Some("secret"))
+            .validate()
+            .is_ok());
+        assert!(enterprise(EapMethod::Ttls, Some("")).validate().is_err());
+        assert!(enterprise(EapMethod::Tls, None).validate().is_err());
+    }
+
+    #[test]
+    fn subnet_mask_is_only_given_for_ipv4() {
+        let address = |address: &str, prefix| IpAddress {
+            address: address.to_string(),
+            prefix,
+        };
+        assert_eq!(
+            address("192.168.1.50", 24).subnet_mask().as_deref(),
+            Some("255.255.255.0")
+        );
+        assert_eq!(
+            address("10.0.0.1", 0).subnet_mask().as_deref(),
+            Some("0.0.0.0")
+        );
+        assert_eq!(address("10.0.0.1", 33).subnet_mask(), None);
+        assert_eq!(address("fe80::1", 64).subnet_mask(), None);
+        assert!(address("fe80::1", 64).is_link_local());
+    }
+
+    #[test]
+    fn channels_follow_the_band_plans() {
+        assert_eq!(wifi_channel(2412), Some(1));
+        assert_eq!(wifi_channel(2484), Some(14));
+        assert_eq!(wifi_channel(5180), Some(36));
+        assert_eq!(wifi_channel(5955), Some(1));
+        assert_eq!(wifi_channel(900), None);
+    }
+
+    #[test]
+    fn missing_privilege_is_reported_plainly() {
+        let e = anyhow::anyhow!(missing_privilege("Bringing eth0 up"));
+        assert_eq!(
+            failure_reason(&e),
+            "Bringing eth0 up needs administrator rights"
+        );
+
+        let e = anyhow::anyhow!(LockScreenError::new(
+            LockScreenErrorCodes::NetworkBackendCallError,
+            "link is gone".to_string(),
+        ));
+        assert_eq!(failure_reason(&e), e.to_string());
+
+        assert!(is_permission_error(
+            "RTNETLINK answers: Operation not permitted"
+        ));
+        assert!(is_permission_error("wg-quick must be run as root"));
+        assert!(!is_permission_error("Cannot find device \"eth9\""));
+    }
+
+    #[test]
+    fn failed_configuration_is_reverted() {
+        let applied = RefCell::new(vec![]);
+        let result = apply_or_revert(
+            &IpConnection::Ethernet,
+            &Ipv4Config::Auto,
+            &Ipv4Config::Auto,
+            |config| {
+                applied.borrow_mut().push(config.clone());
+                match applied.borrow().len() {
+                    1 => bail!("link did not come back"),
+                    _ => Ok(()),
+                }
+            },
+        );
+        let e = result.unwrap_err();
+        assert!(e.to_string().contains("previous settings were restored"));
+        assert_eq!(applied.borrow().len(), 2);
+
+        let result = apply_or_revert(
+            &IpConnection::Ethernet,
+            &Ipv4Config::Auto,
+            &Ipv4Config::Auto,
+            |_| bail!("no device"),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("could not be restored"));
+    }
+}
@@ -0,0 +1,1790 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, info, warn};
+use zbus::{
+    blocking::ProxyBuilder,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    CacheProperties, ProxyDefault,
+};
+
+use super::{
+    collapse_by_ssid, no_wireguard_profile, procfs, read_proxy_env, sysfs,
+    validate_wireguard_config, wireguard, write_proxy_env, ActiveWifi, ConnectionState, EapMethod,
+    EnterpriseSettings, EthernetLink, HotspotBand, HotspotConfig, HotspotStatus, IpAddress,
+    IpConnection, IpDetails, Ipv4Config, Ipv6Config, KnownWifi, MacAddressMode, NetworkBackend,
+    Phase2Auth, ProxyConfig, ProxyMode, StaticIpv4, StaticIpv6, WifiConnectFailure,
+    WifiConnectRequest, WifiConnectState, WifiDetails, WifiNetwork, WifiProfile, WifiSecurity,
+    WireguardConfig, WireguardProfile, WireguardStatus, WireguardTable,
+};
+use crate::{
+    backends::dbus::{self, BusConnection},
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::NetworkBackendSettings,
+};
+
+mod proxies;
+
+#[cfg(test)]
+mod tests;
+
+use proxies::{
+    AccessPointProxyBlocking, ActiveConnectionProxyBlocking, DeviceProxyBlocking,
+    IP4ConfigProxyBlocking, IP6ConfigProxyBlocking, NetworkManagerProxyBlocking,
+    SettingsConnectionProxyBlocking, SettingsProxyBlocking, WirelessDeviceProxyBlocking,
+};
+
+/// `NM_DEVICE_TYPE_ETHERNET`
+const DEVICE_TYPE_ETHERNET: u32 = 1;
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+
+/// `NM_802_11_AP_FLAGS_PRIVACY`
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+/// `NM_802_11_AP_SEC_KEY_MGMT_PSK`
+const AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+/// `NM_802_11_AP_SEC_KEY_MGMT_802_1X`
+const AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+/// `NM_802_11_AP_SEC_KEY_MGMT_SAE`
+const AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+
+/// `NM_DEVICE_STATE_*` values reported while connecting
+const DEVICE_STATE_PREPARE: u32 = 40;
+const DEVICE_STATE_CONFIG: u32 = 50;
+const DEVICE_STATE_NEED_AUTH: u32 = 60;
+const DEVICE_STATE_IP_CONFIG: u32 = 70;
+const DEVICE_STATE_IP_CHECK: u32 = 80;
+const DEVICE_STATE_SECONDARIES: u32 = 90;
+const DEVICE_STATE_FAILED: u32 = 120;
+
+/// `NM_ACTIVE_CONNECTION_STATE_*`
+const ACTIVE_CONNECTION_STATE_ACTIVATED: u32 = 2;
+const ACTIVE_CONNECTION_STATE_DEACTIVATED: u32 = 4;
+
+/// `NM_SETTING_PROXY_METHOD_*`
+const PROXY_METHOD_NONE: i32 = 0;
+const PROXY_METHOD_AUTO: i32 = 1;
+
+/// `NM_METERED_*` of `connection.metered`
+const METERED_YES: i32 = 1;
+const METERED_NO: i32 = 2;
+
+/// `connection.id` of the profile created for the hotspot
+const HOTSPOT_CONNECTION_ID: &str = "Hotspot";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(45);
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+type NewConnectionSettings<'a> = HashMap<&'static str, HashMap<&'static str, Value<'a>>>;
+type UpdatedConnectionSettings<'a> = HashMap<&'a str, HashMap<&'a str, Value<'a>>>;
+
+/// # NetworkManager Backend
+///
+/// Implements the network backend over the NetworkManager D-Bus API,
+/// the connection to the bus is opened on first use
+pub struct NetworkManagerBackend {
+    sysfs_root: PathBuf,
+    proxy_env_file: PathBuf,
+    connection: BusConnection,
+}
+
+impl NetworkManagerBackend {
+    pub fn new(settings: &NetworkBackendSettings) -> Self {
+        Self {
+            sysfs_root: PathBuf::from(&settings.sysfs_root),
+            proxy_env_file: PathBuf::from(&settings.proxy_env_file),
+            connection: BusConnection::new(
+                settings.bus,
+                "network_backend",
+                LockScreenErrorCodes::NetworkBackendConnectError,
+            ),
+        }
+    }
+
+    fn proxy<P>(&self, path: OwnedObjectPath) -> Result<P>
+    where
+        P: ProxyDefault + From<zbus::Proxy<'static>>,
+    {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<P>::new(&connection)
+            .path(path)
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build());
+        map_call_error("build proxy", proxy)
+    }
+
+    fn network_manager(&self) -> Result<NetworkManagerProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        let proxy = ProxyBuilder::<NetworkManagerProxyBlocking>::new(&connection)
+            .cache_properties(CacheProperties::No)
+            .build();
+        map_call_error("build network manager proxy", proxy)
+    }
+
+    /// Returns the path of the first Wi-Fi device
+    fn wifi_device(&self) -> Result<Option<OwnedObjectPath>> {
+        self.device_of_type(DEVICE_TYPE_WIFI)
+    }
+
+    fn device_of_type(&self, device_type: u32) -> Result<Option<OwnedObjectPath>> {
+        let devices = map_call_error("get devices", self.network_manager()?.get_devices())?;
+        for path in devices {
+            let device: DeviceProxyBlocking = self.proxy(path.clone())?;
+            if map_call_error("get device type", device.device_type())? == device_type {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the saved profile of a connection and the device it runs on
+    fn ip_connection_profile(
+        &self,
+        connection: &IpConnection,
+    ) -> Result<(OwnedObjectPath, OwnedObjectPath)> {
+        let (device_path, profile_path) = match connection {
+            IpConnection::Wifi(ssid) => (self.wifi_device()?, self.known_connection(ssid)?),
+            IpConnection::Ethernet => {
+                let device_path = self.device_of_type(DEVICE_TYPE_ETHERNET)?;
+                let profile_path = match &device_path {
+                    Some(device_path) => self.ethernet_profile(device_path.clone())?,
+                    None => None,
+                };
+                (device_path, profile_path)
+            }
+        };
+
+        match (device_path, profile_path) {
+            (Some(device_path), Some(profile_path)) => Ok((profile_path, device_path)),
+            _ => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("no saved profile for {}", connection),
+                ));
+            }
+        }
+    }
+
+    fn ip_profile_settings(&self, connection: &IpConnection) -> Result<ConnectionSettings> {
+        let (profile_path, _) = self.ip_connection_profile(connection)?;
+        let settings_connection: SettingsConnectionProxyBlocking = self.proxy(profile_path)?;
+        map_call_error(
+            "get connection settings",
+            settings_connection.get_settings(),
+        )
+    }
+
+    /// Returns the profile active on a wired device, or the first saved wired profile
+    fn ethernet_profile(&self, device_path: OwnedObjectPath) -> Result<Option<OwnedObjectPath>> {
+        if let Some(profile_path) = self.active_profile(device_path)? {
+            return Ok(Some(profile_path));
+        }
+        let profile_path = self
+            .connection_settings()?
+            .into_iter()
+            .find(|(_, connection_settings)| {
+                setting_str(connection_settings, "connection", "type").as_deref()
+                    == Some("802-3-ethernet")
+            })
+            .map(|(path, _)| path);
+        Ok(profile_path)
+    }
+
+    /// Returns the profile of the connection active on a device
+    fn active_profile(&self, device_path: OwnedObjectPath) -> Result<Option<OwnedObjectPath>> {
+        let device: DeviceProxyBlocking = self.proxy(device_path)?;
+        let active_connection_path =
+            map_call_error("get active connection", device.active_connection())?;
+        if is_empty_path(&active_connection_path) {
+            return Ok(None);
+        }
+        let active_connection: ActiveConnectionProxyBlocking =
+            self.proxy(active_connection_path)?;
+        let profile_path = map_call_error(
+            "get active connection profile",
+            active_connection.connection(),
+        )?;
+        Ok(Some(profile_path))
+    }
+
+    /// Activates a profile again so changed settings take effect,
+    /// profiles that are not active are left alone
+    fn reactivate_if_active(
+        &self,
+        profile_path: OwnedObjectPath,
+        device_path: OwnedObjectPath,
+    ) -> Result<()> {
+        if self.active_profile(device_path.clone())? != Some(profile_path.clone()) {
+            return Ok(());
+        }
+
+        let active_connection_path = map_call_error(
+            "activate connection",
+            self.network_manager()?.activate_connection(
+                &profile_path,
+                &device_path,
+                &ObjectPath::from_static_str_unchecked("/"),
+            ),
+        )?;
+
+        match self.wait_for_activation(device_path, active_connection_path, &|_| {})? {
+            None => Ok(()),
+            Some(failure) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("connection did not come back up - {}", failure),
+                ));
+            }
+        }
+    }
+
+    fn wireless_device(&self) -> Result<WirelessDeviceProxyBlocking<'static>> {
+        match self.wifi_device()? {
+            Some(path) => self.proxy(path),
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    "no wifi device found".to_string(),
+                ));
+            }
+        }
+    }
+
+    fn access_point(&self, path: OwnedObjectPath, is_connected: bool) -> Result<WifiNetwork> {
+        let access_point: AccessPointProxyBlocking = self.proxy(path)?;
+        let ssid = map_call_error("get ssid", access_point.ssid())?;
+        Ok(WifiNetwork {
+            ssid: String::from_utf8_lossy(&ssid).to_string(),
+            bssid: map_call_error("get hw address", access_point.hw_address())?,
+            strength: map_call_error("get strength", access_point.strength())?,
+            frequency: map_call_error("get frequency", access_point.frequency())?,
+            security: security_from_ap_flags(
+                map_call_error("get flags", access_point.flags())?,
+                map_call_error("get wpa flags", access_point.wpa_flags())?,
+                map_call_error("get rsn flags", access_point.rsn_flags())?,
+            ),
+            is_connected,
+        })
+    }
+
+    /// Returns the settings of every saved connection
+    fn connection_settings(&self) -> Result<Vec<(OwnedObjectPath, ConnectionSettings)>> {
+        let settings = self.settings()?;
+        let mut connections = vec![];
+        for path in map_call_error("list connections", settings.list_connections())? {
+            let settings_connection: SettingsConnectionProxyBlocking = self.proxy(path.clone())?;
+            let connection_settings = map_call_error(
+                "get connection settings",
+                settings_connection.get_settings(),
+            )?;
+            connections.push((path, connection_settings));
+        }
+        Ok(connections)
+    }
+
+    fn settings(&self) -> Result<SettingsProxyBlocking<'static>> {
+        let connection = self.connection.get()?;
+        map_call_error(
+            "build settings proxy",
+            ProxyBuilder::<SettingsProxyBlocking>::new(&connection)
+                .cache_properties(CacheProperties::No)
+                .build(),
+        )
+    }
+
+    /// Returns the saved Wi-Fi profile for a SSID
+    fn known_connection(&self, ssid: &str) -> Result<Option<OwnedObjectPath>> {
+        let known_connection = self
+            .known_connections(ssid)?
+            .into_iter()
+            .next()
+            .map(|(path, _)| path);
+        Ok(known_connection)
+    }
+
+    /// Returns every saved Wi-Fi profile for a SSID
+    fn known_connections(&self, ssid: &str) -> Result<Vec<(OwnedObjectPath, ConnectionSettings)>> {
+        let known_connections = self
+            .connection_settings()?
+            .into_iter()
+            .filter(|(_, connection_settings)| {
+                setting_bytes(connection_settings, "802-11-wireless", "ssid") == ssid.as_bytes()
+            })
+            .collect();
+        Ok(known_connections)
+    }
+
+    /// Rewrites a saved profile with the changes made by `modify`,
+    /// secrets are not returned by `GetSettings` and NetworkManager
+    /// keeps the stored ones when an update leaves them out
+    fn update_connection<F>(&self, path: OwnedObjectPath, modify: F) -> Result<()>
+    where
+        F: for<'a> FnOnce(&mut UpdatedConnectionSettings<'a>),
+    {
+        let settings_connection: SettingsConnectionProxyBlocking = self.proxy(path)?;
+        let connection_settings = map_call_error(
+            "get connection settings",
+            settings_connection.get_settings(),
+        )?;
+
+        let mut updated_settings: UpdatedConnectionSettings = connection_settings
+            .iter()
+            .map(|(group, values)| {
+                let values = values
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), Value::from(value.clone())))
+                    .collect();
+                (group.as_str(), values)
+            })
+            .collect();
+        modify(&mut updated_settings);
+
+        map_call_error(
+            "update connection",
+            settings_connection.update(updated_settings),
+        )
+    }
+
+    /// Returns the saved access point profile, at most one is kept
+    fn hotspot_profile(&self) -> Result<Option<(OwnedObjectPath, ConnectionSettings)>> {
+        let hotspot_profile = self
+            .connection_settings()?
+            .into_iter()
+            .find(|(_, connection_settings)| is_access_point(connection_settings));
+        Ok(hotspot_profile)
+    }
+
+    /// Returns the active connection of the Wi-Fi device when it runs the hotspot
+    fn active_hotspot(&self) -> Result<Option<OwnedObjectPath>> {
+        let (device_path, (profile_path, _)) = match (self.wifi_device()?, self.hotspot_profile()?)
+        {
+            (Some(device_path), Some(profile)) => (device_path, profile),
+            _ => return Ok(None),
+        };
+        if self.active_profile(device_path.clone())? != Some(profile_path) {
+            return Ok(None);
+        }
+
+        let device: DeviceProxyBlocking = self.proxy(device_path)?;
+        let active_connection_path =
+            map_call_error("get active connection", device.active_connection())?;
+        Ok(Some(active_connection_path))
+    }
+
+    /// Returns the saved WireGuard profiles by `connection.id`
+    fn wireguard_connections(&self) -> Result<Vec<(String, OwnedObjectPath)>> {
+        let wireguard_connections = self
+            .connection_settings()?
+            .into_iter()
+            .filter(|(_, connection_settings)| {
+                setting_str(connection_settings, "connection", "type").as_deref()
+                    == Some("wireguard")
+            })
+            .filter_map(|(path, connection_settings)| {
+                Some((setting_str(&connection_settings, "connection", "id")?, path))
+            })
+            .collect();
+        Ok(wireguard_connections)
+    }
+
+    fn wireguard_connection(&self, name: &str) -> Result<OwnedObjectPath> {
+        match self
+            .wireguard_connections()?
+            .into_iter()
+            .find(|(id, _)| id == name)
+        {
+            Some((_, path)) => Ok(path),
+            None => bail!(no_wireguard_profile(name)),
+        }
+    }
+
+    /// Returns the active connections with the profile each one runs
+    fn active_connections(&self) -> Result<Vec<(OwnedObjectPath, OwnedObjectPath)>> {
+        let mut active_connections = vec![];
+        let paths = map_call_error(
+            "get active connections",
+            self.network_manager()?.active_connections(),
+        )?;
+        for active_connection_path in paths {
+            let active_connection: ActiveConnectionProxyBlocking =
+                self.proxy(active_connection_path.clone())?;
+            // connections can go away while they are walked
+            if let Ok(profile_path) = active_connection.connection() {
+                active_connections.push((active_connection_path, profile_path));
+            }
+        }
+        Ok(active_connections)
+    }
+
+    /// Reads the IPv4 and IPv6 configuration applied to a device
+    fn ip_details(&self, device_path: OwnedObjectPath) -> Result<IpDetails> {
+        let device: DeviceProxyBlocking = self.proxy(device_path)?;
+        let mut details = IpDetails::default();
+
+        let ip4_config_path = map_call_error("get ip4 config", device.ip4_config())?;
+        if !is_empty_path(&ip4_config_path) {
+            let ip4_config: IP4ConfigProxyBlocking = self.proxy(ip4_config_path)?;
+            details.ipv4_addresses = ip_addresses(map_call_error(
+                "get ip4 addresses",
+                ip4_config.address_data(),
+            )?);
+            details.ipv4_gateway =
+                non_empty(map_call_error("get ip4 gateway", ip4_config.gateway())?);
+            for nameserver in map_call_error("get ip4 nameservers", ip4_config.nameserver_data())? {
+                if let Some(address) = dict_str(&nameserver, "address") {
+                    details.dns.push(address);
+                }
+            }
+        }
+
+        let ip6_config_path = map_call_error("get ip6 config", device.ip6_config())?;
+        if !is_empty_path(&ip6_config_path) {
+            let ip6_config: IP6ConfigProxyBlocking = self.proxy(ip6_config_path)?;
+            details.ipv6_addresses = ip_addresses(map_call_error(
+                "get ip6 addresses",
+                ip6_config.address_data(),
+            )?);
+            details.ipv6_gateway =
+                non_empty(map_call_error("get ip6 gateway", ip6_config.gateway())?);
+            for nameserver in map_call_error("get ip6 nameservers", ip6_config.nameservers())? {
+                if let Ok(octets) = <[u8; 16]>::try_from(nameserver.as_slice()) {
+                    details.dns.push(Ipv6Addr::from(octets).to_string());
+                }
+            }
+        }
+
+        Ok(details)
+    }
+
+    /// Polls the device until the activation completes,
+    /// returns the failure if the connection did not come up
+    fn wait_for_activation(
+        &self,
+        device_path: OwnedObjectPath,
+        active_connection_path: OwnedObjectPath,
+        on_state: &dyn Fn(WifiConnectState),
+    ) -> Result<Option<WifiConnectFailure>> {
+        let device: DeviceProxyBlocking = self.proxy(device_path)?;
+        let active_connection: ActiveConnectionProxyBlocking =
+            self.proxy(active_connection_path)?;
+
+        let started_at = Instant::now();
+        let mut last_state: Option<WifiConnectState> = None;
+        let mut last_reason = 0;
+        loop {
+            if started_at.elapsed() > CONNECT_TIMEOUT {
+                return Ok(Some(WifiConnectFailure::Timeout));
+            }
+
+            let device_state = map_call_error("get device state", device.state())?;
+            // the reason is reset when the device leaves the failed state, keep the last one
+            if let Ok((_, reason)) = device.state_reason() {
+                if reason != 0 {
+                    last_reason = reason;
+                }
+            }
+
+            if let Some(state) = connect_state_from_device_state(device_state) {
+                if last_state.as_ref() != Some(&state) {
+                    on_state(state.clone());
+                    last_state = Some(state);
+                }
+            }
+
+            // the active connection is removed once the attempt fails
+            let active_state = active_connection
+                .state()
+                .unwrap_or(ACTIVE_CONNECTION_STATE_DEACTIVATED);
+            if active_state == ACTIVE_CONNECTION_STATE_ACTIVATED {
+                return Ok(None);
+            }
+            if active_state == ACTIVE_CONNECTION_STATE_DEACTIVATED
+                || device_state == DEVICE_STATE_FAILED
+            {
+                return Ok(Some(connect_failure_from_reason(last_reason)));
+            }
+
+            thread::sleep(CONNECT_POLL_INTERVAL);
+        }
+    }
+}
+
+impl NetworkBackend for NetworkManagerBackend {
+    fn wifi_enabled(&self) -> Result<bool> {
+        map_call_error(
+            "get wireless enabled",
+            self.network_manager()?.wireless_enabled(),
+        )
+    }
+
+    fn set_wifi_enabled(&self, enabled: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting wifi enabled to {}", enabled
+        );
+        map_call_error(
+            "set wireless enabled",
+            self.network_manager()?.set_wireless_enabled(enabled),
+        )
+    }
+
+    fn active_wifi(&self) -> Result<Option<ActiveWifi>> {
+        let device_path = match self.wifi_device()? {
+            Some(path) => path,
+            None => {
+                debug!(task = "network_backend", "no wifi device found");
+                return Ok(None);
+            }
+        };
+
+        let device: DeviceProxyBlocking = self.proxy(device_path.clone())?;
+        let active_connection_path =
+            map_call_error("get active connection", device.active_connection())?;
+        if is_empty_path(&active_connection_path) {
+            return Ok(None);
+        }
+
+        let active_connection: ActiveConnectionProxyBlocking =
+            self.proxy(active_connection_path)?;
+        let state = connection_state_from_nm(map_call_error(
+            "get active connection state",
+            active_connection.state(),
+        )?);
+
+        // prefer the ssid of the access point, the connection id can be renamed
+        let wireless_device: WirelessDeviceProxyBlocking = self.proxy(device_path)?;
+        let access_point_path = map_call_error(
+            "get active access point",
+            wireless_device.active_access_point(),
+        )?;
+        let ssid = match is_empty_path(&access_point_path) {
+            true => map_call_error("get active connection id", active_connection.id())?,
+            false => {
+                let access_point: AccessPointProxyBlocking = self.proxy(access_point_path)?;
+                let ssid = map_call_error("get ssid", access_point.ssid())?;
+                String::from_utf8_lossy(&ssid).to_string()
+            }
+        };
+
+        Ok(Some(ActiveWifi { ssid, state }))
+    }
+
+    fn request_wifi_scan(&self) -> Result<()> {
+        info!(task = "network_backend", "requesting wifi scan");
+        map_call_error(
+            "request scan",
+            self.wireless_device()?.request_scan(HashMap::new()),
+        )
+    }
+
+    fn wifi_networks(&self) -> Result<Vec<WifiNetwork>> {
+        let wireless_device = self.wireless_device()?;
+        let active_access_point = map_call_error(
+            "get active access point",
+            wireless_device.active_access_point(),
+        )?;
+        let access_points =
+            map_call_error("get access points", wireless_device.get_all_access_points())?;
+
+        let mut networks = vec![];
+        for path in access_points {
+            let is_connected = path == active_access_point;
+            // access points can vanish between listing and reading them
+            match self.access_point(path, is_connected) {
+                Ok(network) => networks.push(network),
+                Err(e) => debug!(task = "network_backend", "skipping access point {}", e),
+            }
+        }
+        Ok(networks)
+    }
+
+    fn known_wifi_networks(&self) -> Result<Vec<KnownWifi>> {
+        let mut known_networks = vec![];
+        for (_, connection_settings) in self.connection_settings()? {
+            if setting_str(&connection_settings, "connection", "type").as_deref()
+                != Some("802-11-wireless")
+            {
+                continue;
+            }
+            // the hotspot profile is not a network to join
+            if is_access_point(&connection_settings) {
+                continue;
+            }
+
+            let ssid = setting_bytes(&connection_settings, "802-11-wireless", "ssid");
+            known_networks.push(KnownWifi {
+                id: setting_str(&connection_settings, "connection", "id").unwrap_or_default(),
+                ssid: String::from_utf8_lossy(&ssid).to_string(),
+                security: security_from_key_mgmt(
+                    setting_str(&connection_settings, "802-11-wireless-security", "key-mgmt")
+                        .as_deref(),
+                ),
+            });
+        }
+        Ok(known_networks)
+    }
+
+    fn connect_wifi(
+        &self,
+        request: &WifiConnectRequest,
+        on_state: &dyn Fn(WifiConnectState),
+    ) -> Result<()> {
+        info!(task = "network_backend", "connecting to {}", request.ssid);
+        let device_path = match self.wifi_device()? {
+            Some(path) => path,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    "no wifi device found".to_string(),
+                ));
+            }
+        };
+
+        let mut access_point_path = None;
+        let mut access_point_strength = 0;
+        let wireless_device: WirelessDeviceProxyBlocking = self.proxy(device_path.clone())?;
+        for path in map_call_error("get access points", wireless_device.get_all_access_points())? {
+            let access_point: AccessPointProxyBlocking = self.proxy(path.clone())?;
+            let ssid = access_point.ssid().unwrap_or_default();
+            let strength = access_point.strength().unwrap_or_default();
+            if ssid == request.ssid.as_bytes()
+                && (access_point_path.is_none() || strength > access_point_strength)
+            {
+                access_point_path = Some(path);
+                access_point_strength = strength;
+            }
+        }
+        let access_point_path = match access_point_path {
+            Some(path) => path,
+            None => {
+                on_state(WifiConnectState::Failed(
+                    WifiConnectFailure::NetworkNotFound,
+                ));
+                return Ok(());
+            }
+        };
+
+        on_state(WifiConnectState::Associating);
+        let network_manager = self.network_manager()?;
+        let known_connection = self.known_connections(&request.ssid)?.into_iter().next();
+        let (connection_path, active_connection_path, is_new_connection) =
+            match known_connection {
+                Some((connection_path, connection_settings)) => {
+                    // a password entered again, after a failure or a change
+                    // on the router, replaces the saved one
+                    let security = security_from_key_mgmt(
+                        setting_str(&connection_settings, "802-11-wireless-security", "key-mgmt")
+                            .as_deref(),
+                    );
+                    let uses_psk = matches!(security, WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae);
+                    match &request.password {
+                        Some(password) if uses_psk && !password.is_empty() => {
+                            let profile = WifiProfile {
+                                security,
+                                ..WifiProfile::from(request)
+                            };
+                            if let Err(reason) = profile.validate() {
+                                let failure = WifiConnectFailure::Other(reason);
+                                on_state(WifiConnectState::Failed(failure));
+                                return Ok(());
+                            }
+                            // SAE passwords are kept as the psk as well
+                            let password = password.clone();
+                            self.update_connection(connection_path.clone(), |settings| {
+                                settings
+                                    .entry("802-11-wireless-security")
+                                    .or_default()
+                                    .insert("psk", Value::from(password));
+                            })?;
+                        }
+                        _ => (),
+                    }
+                    let active_connection_path = map_call_error(
+                        "activate connection",
+                        network_manager.activate_connection(
+                            &connection_path,
+                            &device_path,
+                            &access_point_path,
+                        ),
+                    )?;
+                    (connection_path, active_connection_path, false)
+                }
+                None => {
+                    let profile = WifiProfile::from(request);
+                    if request.security == WifiSecurity::Enterprise {
+                        on_state(WifiConnectState::Failed(WifiConnectFailure::Other(
+                            "enterprise networks must be added from Add Network".to_string(),
+                        )));
+                        return Ok(());
+                    }
+                    if let Err(reason) = profile.validate() {
+                        on_state(WifiConnectState::Failed(WifiConnectFailure::Other(reason)));
+                        return Ok(());
+                    }
+                    let connection_settings = wifi_connection_settings(&profile);
+                    let (connection_path, active_connection_path) = map_call_error(
+                        "add and activate connection",
+                        network_manager.add_and_activate_connection(
+                            connection_settings,
+                            &device_path,
+                            &access_point_path,
+                        ),
+                    )?;
+                    (connection_path, active_connection_path, true)
+                }
+            };
+
+        match self.wait_for_activation(device_path, active_connection_path, on_state)? {
+            None => on_state(WifiConnectState::Connected),
+            Some(failure) => {
+                // do not keep a profile with a wrong password around
+                if is_new_connection {
+                    let settings_connection: SettingsConnectionProxyBlocking =
+                        self.proxy(connection_path)?;
+                    if let Err(e) = settings_connection.delete() {
+                        warn!(
+                            task = "network_backend",
+                            "cannot delete failed connection {}", e
+                        );
+                    }
+                }
+                on_state(WifiConnectState::Failed(failure));
+            }
+        }
+        Ok(())
+    }
+
+    fn wifi_details(&self, ssid: &str) -> Result<WifiDetails> {
+        let known_connections = self.known_connections(ssid)?;
+        let network = collapse_by_ssid(self.wifi_networks()?)
+            .into_iter()
+            .find(|network| network.ssid == ssid);
+
+        // out of range networks are described by their saved profile
+        let network = match network {
+            Some(network) => network,
+            None => WifiNetwork {
+                ssid: ssid.to_string(),
+                security: known_connections
+                    .first()
+                    .map(|(_, connection_settings)| {
+                        security_from_key_mgmt(
+                            setting_str(
+                                connection_settings,
+                                "802-11-wireless-security",
+                                "key-mgmt",
+                            )
+                            .as_deref(),
+                        )
+                    })
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+        };
+
+        let ip = match (network.is_connected, self.wifi_device()?) {
+            (true, Some(device_path)) => Some(self.ip_details(device_path)?),
+            _ => None,
+        };
+
+        // autoconnect defaults to true when the profile does not set it
+        let autoconnect = known_connections.iter().any(|(_, connection_settings)| {
+            setting_bool(connection_settings, "connection", "autoconnect").unwrap_or(true)
+        });
+
+        let known_settings = known_connections.first().map(|(_, settings)| settings);
+        let mac_address_mode = known_settings.map(|connection_settings| {
+            match setting_str(connection_settings, "802-11-wireless", "cloned-mac-address")
+                .as_deref()
+            {
+                Some("random") => MacAddressMode::Random,
+                Some("stable") => MacAddressMode::Stable,
+                _ => MacAddressMode::Device,
+            }
+        });
+        // an unset flag is guessed by NetworkManager, only `yes` is shown as metered
+        let metered = known_settings.map(|connection_settings| {
+            setting_value::<i32>(connection_settings, "connection", "metered") == Some(METERED_YES)
+        });
+
+        Ok(WifiDetails {
+            network,
+            is_known: !known_connections.is_empty(),
+            autoconnect,
+            mac_address_mode,
+            metered,
+            ip,
+        })
+    }
+
+    fn forget_wifi(&self, ssid: &str) -> Result<()> {
+        info!(task = "network_backend", "forgetting wifi {}", ssid);
+        for (path, _) in self.known_connections(ssid)? {
+            let settings_connection: SettingsConnectionProxyBlocking = self.proxy(path)?;
+            map_call_error("delete connection", settings_connection.delete())?;
+        }
+        Ok(())
+    }
+
+    fn set_wifi_autoconnect(&self, ssid: &str, autoconnect: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting autoconnect of {} to {}", ssid, autoconnect
+        );
+        let known_connections = self.known_connections(ssid)?;
+        if known_connections.is_empty() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no saved profile for {}", ssid),
+            ));
+        }
+
+        for (path, _) in known_connections {
+            self.update_connection(path, |connection_settings| {
+                connection_settings
+                    .entry("connection")
+                    .or_default()
+                    .insert("autoconnect", Value::from(autoconnect));
+            })?;
+        }
+        Ok(())
+    }
+
+    fn set_wifi_mac_address_mode(&self, ssid: &str, mode: MacAddressMode) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting mac address of {} to {}", ssid, mode
+        );
+        let known_connections = self.known_connections(ssid)?;
+        if known_connections.is_empty() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no saved profile for {}", ssid),
+            ));
+        }
+
+        let cloned_mac_address = match mode {
+            MacAddressMode::Device => "permanent",
+            MacAddressMode::Random => "random",
+            MacAddressMode::Stable => "stable",
+        };
+        for (path, _) in known_connections.iter() {
+            self.update_connection(path.clone(), |connection_settings| {
+                connection_settings
+                    .entry("802-11-wireless")
+                    .or_default()
+                    .insert("cloned-mac-address", Value::from(cloned_mac_address));
+            })?;
+        }
+
+        // the address is only set when the device associates
+        if let Some(device_path) = self.wifi_device()? {
+            for (path, _) in known_connections {
+                self.reactivate_if_active(path, device_path.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_wifi_metered(&self, ssid: &str, metered: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting metered of {} to {}", ssid, metered
+        );
+        let known_connections = self.known_connections(ssid)?;
+        if known_connections.is_empty() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no saved profile for {}", ssid),
+            ));
+        }
+
+        let value = match metered {
+            true => METERED_YES,
+            false => METERED_NO,
+        };
+        for (path, _) in known_connections {
+            self.update_connection(path, |connection_settings| {
+                connection_settings
+                    .entry("connection")
+                    .or_default()
+                    .insert("metered", Value::from(value));
+            })?;
+        }
+        Ok(())
+    }
+
+    fn ipv4_config(&self, connection: &IpConnection) -> Result<Ipv4Config> {
+        let connection_settings = self.ip_profile_settings(connection)?;
+        Ok(ipv4_config_from_settings(&connection_settings))
+    }
+
+    fn set_ipv4_config(&self, connection: &IpConnection, config: &Ipv4Config) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting ipv4 config of {} to {}", connection, config
+        );
+        let (profile_path, device_path) = self.ip_connection_profile(connection)?;
+        self.update_connection(profile_path.clone(), |connection_settings| {
+            set_ipv4_settings(connection_settings, config);
+        })?;
+        self.reactivate_if_active(profile_path, device_path)
+    }
+
+    fn ipv6_config(&self, connection: &IpConnection) -> Result<Ipv6Config> {
+        let connection_settings = self.ip_profile_settings(connection)?;
+        Ok(ipv6_config_from_settings(&connection_settings))
+    }
+
+    fn set_ipv6_config(&self, connection: &IpConnection, config: &Ipv6Config) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting ipv6 config of {} to {}", connection, config
+        );
+        let (profile_path, device_path) = self.ip_connection_profile(connection)?;
+        self.update_connection(profile_path.clone(), |connection_settings| {
+            set_ipv6_settings(connection_settings, config);
+        })?;
+        self.reactivate_if_active(profile_path, device_path)
+    }
+
+    fn add_wifi_profile(&self, profile: &WifiProfile) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "adding wifi profile {}", profile.ssid
+        );
+        if let Err(reason) = profile.validate() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("invalid wifi profile - {}", reason),
+            ));
+        }
+
+        map_call_error(
+            "add connection",
+            self.settings()?
+                .add_connection(wifi_connection_settings(profile)),
+        )?;
+        Ok(())
+    }
+
+    fn ethernet_links(&self) -> Result<Vec<EthernetLink>> {
+        Ok(sysfs::ethernet_links(&self.sysfs_root))
+    }
+
+    fn hotspot_config(&self) -> Result<HotspotConfig> {
+        let (profile_path, connection_settings) = match self.hotspot_profile()? {
+            Some(profile) => profile,
+            None => return Ok(HotspotConfig::default()),
+        };
+
+        // secrets are only handed to privileged callers, the password is asked again otherwise
+        let settings_connection: SettingsConnectionProxyBlocking = self.proxy(profile_path)?;
+        let password = settings_connection
+            .get_secrets("802-11-wireless-security")
+            .ok()
+            .and_then(|secrets| setting_str(&secrets, "802-11-wireless-security", "psk"))
+            .unwrap_or_default();
+
+        let ssid = setting_bytes(&connection_settings, "802-11-wireless", "ssid");
+        Ok(HotspotConfig {
+            ssid: String::from_utf8_lossy(&ssid).to_string(),
+            password,
+            band: match setting_str(&connection_settings, "802-11-wireless", "band").as_deref() {
+                Some("a") => HotspotBand::Band5GHz,
+                _ => HotspotBand::Band2_4GHz,
+            },
+            channel: setting_value::<u32>(&connection_settings, "802-11-wireless", "channel")
+                .filter(|channel| *channel != 0),
+        })
+    }
+
+    fn hotspot_status(&self) -> Result<HotspotStatus> {
+        if self.active_hotspot()?.is_none() {
+            return Ok(HotspotStatus::default());
+        }
+
+        // NetworkManager does not list stations, the shared network's neighbours are used
+        let clients = match self.wifi_device()? {
+            Some(device_path) => {
+                let device: DeviceProxyBlocking = self.proxy(device_path)?;
+                procfs::arp_clients(&map_call_error("get interface", device.interface())?)
+            }
+            None => vec![],
+        };
+        Ok(HotspotStatus {
+            active: true,
+            clients,
+        })
+    }
+
+    fn start_hotspot(&self, config: &HotspotConfig) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "starting hotspot {} on {}", config.ssid, config.band
+        );
+        if let Err(reason) = config.validate() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("invalid hotspot settings - {}", reason),
+            ));
+        }
+
+        let device_path = match self.wifi_device()? {
+            Some(path) => path,
+            None => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    "no wifi device found".to_string(),
+                ));
+            }
+        };
+
+        let profile_path = match self.hotspot_profile()? {
+            Some((profile_path, _)) => {
+                self.update_connection(profile_path.clone(), |connection_settings| {
+                    set_hotspot_settings(connection_settings, config);
+                })?;
+                profile_path
+            }
+            None => map_call_error(
+                "add connection",
+                self.settings()?
+                    .add_connection(hotspot_connection_settings(config)),
+            )?,
+        };
+
+        let active_connection_path = map_call_error(
+            "activate connection",
+            self.network_manager()?.activate_connection(
+                &profile_path,
+                &device_path,
+                &ObjectPath::from_static_str_unchecked("/"),
+            ),
+        )?;
+
+        match self.wait_for_activation(device_path, active_connection_path, &|_| {})? {
+            None => Ok(()),
+            Some(failure) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("hotspot did not start - {}", failure),
+                ));
+            }
+        }
+    }
+
+    fn proxy_config(&self) -> Result<ProxyConfig> {
+        read_proxy_env(&self.proxy_env_file)
+    }
+
+    /// NetworkManager only knows PAC proxies, it hands them to pacrunner
+    /// for every saved profile, manual servers go to the environment file
+    fn set_proxy_config(&self, config: &ProxyConfig) -> Result<()> {
+        info!(task = "network_backend", "setting {} proxy", config.mode);
+        write_proxy_env(&self.proxy_env_file, config)?;
+
+        for (path, connection_settings) in self.connection_settings()? {
+            let connection_type = setting_str(&connection_settings, "connection", "type");
+            let is_client_profile = match connection_type.as_deref() {
+                Some("802-3-ethernet") => true,
+                Some("802-11-wireless") => !is_access_point(&connection_settings),
+                _ => false,
+            };
+            if !is_client_profile {
+                continue;
+            }
+
+            self.update_connection(path, |connection_settings| {
+                let proxy = connection_settings.entry("proxy").or_default();
+                proxy.remove("pac-url");
+                match (config.mode, &config.pac_url) {
+                    (ProxyMode::Automatic, Some(pac_url)) => {
+                        proxy.insert("method", Value::from(PROXY_METHOD_AUTO));
+                        proxy.insert("pac-url", Value::from(pac_url.clone()));
+                    }
+                    _ => {
+                        proxy.insert("method", Value::from(PROXY_METHOD_NONE));
+                    }
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn wireguard_profiles(&self) -> Result<Vec<WireguardProfile>> {
+        let active_profiles: Vec<OwnedObjectPath> = self
+            .active_connections()?
+            .into_iter()
+            .map(|(_, profile_path)| profile_path)
+            .collect();
+        let profiles = self
+            .wireguard_connections()?
+            .into_iter()
+            .map(|(name, path)| WireguardProfile {
+                active: active_profiles.contains(&path),
+                name,
+            })
+            .collect();
+        Ok(profiles)
+    }
+
+    fn import_wireguard_profile(&self, config: &WireguardConfig) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "importing WireGuard profile {}", config.name
+        );
+        validate_wireguard_config(config)?;
+
+        for (name, path) in self.wireguard_connections()? {
+            if name == config.name {
+                let settings_connection: SettingsConnectionProxyBlocking = self.proxy(path)?;
+                map_call_error("delete connection", settings_connection.delete())?;
+            }
+        }
+        map_call_error(
+            "add connection",
+            self.settings()?
+                .add_connection(wireguard_connection_settings(config)),
+        )?;
+        Ok(())
+    }
+
+    /// WireGuard connections are not bound to a device, NetworkManager
+    /// creates the interface named in the profile when activating it
+    fn set_wireguard_active(&self, name: &str, active: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting WireGuard {} active to {}", name, active
+        );
+        let profile_path = self.wireguard_connection(name)?;
+        match active {
+            true => {
+                map_call_error(
+                    "activate connection",
+                    self.network_manager()?.activate_connection(
+                        &profile_path,
+                        &ObjectPath::from_static_str_unchecked("/"),
+                        &ObjectPath::from_static_str_unchecked("/"),
+                    ),
+                )?;
+            }
+            false => {
+                for (active_connection_path, active_profile_path) in self.active_connections()? {
+                    if active_profile_path == profile_path {
+                        map_call_error(
+                            "deactivate connection",
+                            self.network_manager()?
+                                .deactivate_connection(&active_connection_path),
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// NetworkManager does not export handshakes, they are read with `wg`
+    fn wireguard_status(&self, name: &str) -> Result<WireguardStatus> {
+        wireguard::interface_status(&self.sysfs_root, name)
+    }
+
+    fn remove_wireguard_profile(&self, name: &str) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "removing WireGuard profile {}", name
+        );
+        let settings_connection: SettingsConnectionProxyBlocking =
+            self.proxy(self.wireguard_connection(name)?)?;
+        map_call_error("delete connection", settings_connection.delete())
+    }
+
+    fn stop_hotspot(&self) -> Result<()> {
+        info!(task = "network_backend", "stopping hotspot");
+        match self.active_hotspot()? {
+            Some(active_connection_path) => map_call_error(
+                "deactivate connection",
+                self.network_manager()?
+                    .deactivate_connection(&active_connection_path),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn set_ethernet_enabled(&self, interface: &str, enabled: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting {} enabled to {}", interface, enabled
+        );
+        let device_path = map_call_error(
+            "get device by interface",
+            self.network_manager()?.get_device_by_ip_iface(interface),
+        )?;
+        match enabled {
+            // an empty connection lets NetworkManager pick the best profile
+            true => {
+                map_call_error(
+                    "activate connection",
+                    self.network_manager()?.activate_connection(
+                        &ObjectPath::from_static_str_unchecked("/"),
+                        &device_path,
+                        &ObjectPath::from_static_str_unchecked("/"),
+                    ),
+                )?;
+            }
+            // also keeps the device from autoconnecting until activated again
+            false => {
+                let device: DeviceProxyBlocking = self.proxy(device_path)?;
+                map_call_error("disconnect device", device.disconnect())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_empty_path(path: &OwnedObjectPath) -> bool {
+    path.as_str() == "/"
+}
+
+fn connection_state_from_nm(state: u32) -> ConnectionState {
+    match state {
+        1 => ConnectionState::Activating,
+        2 => ConnectionState::Activated,
+        3 => ConnectionState::Deactivating,
+        4 => ConnectionState::Deactivated,
+        _ => ConnectionState::Unknown,
+    }
+}
+
+fn connect_state_from_device_state(state: u32) -> Option<WifiConnectState> {
+    match state {
+        DEVICE_STATE_PREPARE => Some(WifiConnectState::Associating),
+        DEVICE_STATE_CONFIG | DEVICE_STATE_NEED_AUTH => Some(WifiConnectState::Authenticating),
+        DEVICE_STATE_IP_CONFIG | DEVICE_STATE_IP_CHECK | DEVICE_STATE_SECONDARIES => {
+            Some(WifiConnectState::ObtainingIp)
+        }
+        _ => None,
+    }
+}
+
+/// Maps a `NM_DEVICE_STATE_REASON_*` to a failure
+fn connect_failure_from_reason(reason: u32) -> WifiConnectFailure {
+    match reason {
+        7 | 8 => WifiConnectFailure::WrongPassword,
+        11 => WifiConnectFailure::Timeout,
+        5 | 6 | 15 | 16 | 17 => WifiConnectFailure::DhcpFailed,
+        53 => WifiConnectFailure::NetworkNotFound,
+        9 | 10 => WifiConnectFailure::Other("wpa_supplicant failed".to_string()),
+        _ => WifiConnectFailure::Other(format!("device state reason {}", reason)),
+    }
+}
+
+/// Builds the settings of a new Wi-Fi profile, the profile must be valid
+fn wifi_connection_settings(profile: &WifiProfile) -> NewConnectionSettings<'_> {
+    let mut connection = HashMap::new();
+    connection.insert("id", Value::from(profile.ssid.as_str()));
+    connection.insert("type", Value::from("802-11-wireless"));
+
+    let mut wireless = HashMap::new();
+    wireless.insert("ssid", Value::from(profile.ssid.as_bytes().to_vec()));
+    wireless.insert("mode", Value::from("infrastructure"));
+    if profile.hidden {
+        wireless.insert("hidden", Value::from(true));
+    }
+
+    let mut settings = HashMap::new();
+    settings.insert("connection", connection);
+    settings.insert("802-11-wireless", wireless);
+
+    let mut security = HashMap::new();
+    match (profile.security, &profile.enterprise) {
+        (WifiSecurity::WpaPsk, _) | (WifiSecurity::Wpa3Sae, _) => {
+            let key_mgmt = match profile.security {
+                WifiSecurity::Wpa3Sae => "sae",
+                _ => "wpa-psk",
+            };
+            security.insert("key-mgmt", Value::from(key_mgmt));
+            security.insert(
+                "psk",
+                Value::from(profile.password.as_deref().unwrap_or_default()),
+            );
+        }
+        (WifiSecurity::Enterprise, Some(enterprise)) => {
+            security.insert("key-mgmt", Value::from("wpa-eap"));
+            settings.insert("802-1x", enterprise_settings(enterprise));
+        }
+        _ => return settings,
+    }
+    settings.insert("802-11-wireless-security", security);
+    settings
+}
+
+/// Access point profile sharing the uplink, `ipv4.method=shared`
+/// makes NetworkManager run DHCP and NAT for the clients
+fn hotspot_connection_settings(config: &HotspotConfig) -> NewConnectionSettings<'static> {
+    let mut connection = HashMap::new();
+    connection.insert("id", Value::from(HOTSPOT_CONNECTION_ID));
+    connection.insert("type", Value::from("802-11-wireless"));
+    connection.insert("autoconnect", Value::from(false));
+
+    let mut ipv4 = HashMap::new();
+    ipv4.insert("method", Value::from("shared"));
+    let mut ipv6 = HashMap::new();
+    ipv6.insert("method", Value::from("ignore"));
+
+    let mut settings = HashMap::new();
+    settings.insert("connection", connection);
+    settings.insert("802-11-wireless", HashMap::new());
+    settings.insert("802-11-wireless-security", HashMap::new());
+    settings.insert("ipv4", ipv4);
+    settings.insert("ipv6", ipv6);
+    set_hotspot_settings(&mut settings, config);
+    settings
+}
+
+/// WireGuard profile with the interface named after it, the routes
+/// of the allowed IPs are added by NetworkManager (`peer-routes`) to
+/// the table of the config
+fn wireguard_connection_settings(config: &WireguardConfig) -> NewConnectionSettings<'_> {
+    let mut connection = HashMap::new();
+    connection.insert("id", Value::from(config.name.as_str()));
+    connection.insert("type", Value::from("wireguard"));
+    connection.insert("interface-name", Value::from(config.name.as_str()));
+    connection.insert("autoconnect", Value::from(false));
+
+    let mut wireguard = HashMap::new();
+    wireguard.insert("private-key", Value::from(config.private_key.as_str()));
+    if let Some(listen_port) = config.listen_port {
+        wireguard.insert("listen-port", Value::from(listen_port as u32));
+    }
+    if let Some(mtu) = config.mtu {
+        wireguard.insert("mtu", Value::from(mtu));
+    }
+    if let Some(fwmark) = config.fwmark {
+        wireguard.insert("fwmark", Value::from(fwmark));
+    }
+    if config.table == WireguardTable::Off {
+        wireguard.insert("peer-routes", Value::from(false));
+    }
+    let peers: Vec<HashMap<&str, Value>> = config
+        .peers
+        .iter()
+        .map(|peer| {
+            let mut settings = HashMap::new();
+            settings.insert("public-key", Value::from(peer.public_key.as_str()));
+            settings.insert("allowed-ips", Value::from(peer.allowed_ips.clone()));
+            if let Some(endpoint) = &peer.endpoint {
+                settings.insert("endpoint", Value::from(endpoint.as_str()));
+            }
+            if let Some(preshared_key) = &peer.preshared_key {
+                settings.insert("preshared-key", Value::from(preshared_key.as_str()));
+                // stored with the profile, there is no agent to ask for it
+                settings.insert("preshared-key-flags", Value::from(0u32));
+            }
+            if let Some(persistent_keepalive) = peer.persistent_keepalive {
+                settings.insert(
+                    "persistent-keepalive",
+                    Value::from(persistent_keepalive as u32),
+                );
+            }
+            settings
+        })
+        .collect();
+    wireguard.insert("peers", Value::from(peers));
+
+    let mut ipv4_addresses = vec![];
+    let mut ipv6_addresses = vec![];
+    for (address, prefix) in config
+        .addresses
+        .iter()
+        .filter_map(|a| wireguard::parse_cidr(a))
+    {
+        let mut address_data = HashMap::new();
+        address_data.insert("address", Value::from(address.to_string()));
+        address_data.insert("prefix", Value::from(prefix as u32));
+        match address {
+            IpAddr::V4(_) => ipv4_addresses.push(address_data),
+            IpAddr::V6(_) => ipv6_addresses.push(address_data),
+        }
+    }
+
+    let mut ipv4 = HashMap::new();
+    let mut ipv6 = HashMap::new();
+    match ipv4_addresses.is_empty() {
+        true => ipv4.insert("method", Value::from("disabled")),
+        false => ipv4.insert("method", Value::from("manual")),
+    };
+    match ipv6_addresses.is_empty() {
+        true => ipv6.insert("method", Value::from("ignore")),
+        false => ipv6.insert("method", Value::from("manual")),
+    };
+    ipv4.insert("address-data", Value::from(ipv4_addresses));
+    ipv6.insert("address-data", Value::from(ipv6_addresses));
+    if let WireguardTable::Id(table) = config.table {
+        ipv4.insert("route-table", Value::from(table));
+        ipv6.insert("route-table", Value::from(table));
+    }
+
+    let ipv4_dns: Vec<u32> = config
+        .dns
+        .iter()
+        .filter_map(|server| match server {
+            IpAddr::V4(server) => Some(u32::from_ne_bytes(server.octets())),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let ipv6_dns: Vec<Vec<u8>> = config
+        .dns
+        .iter()
+        .filter_map(|server| match server {
+            IpAddr::V6(server) => Some(server.octets().to_vec()),
+            IpAddr::V4(_) => None,
+        })
+        .collect();
+    ipv4.insert("dns", Value::from(ipv4_dns));
+    ipv6.insert("dns", Value::from(ipv6_dns));
+    if !config.dns_search.is_empty() {
+        ipv4.insert("dns-search", Value::from(config.dns_search.clone()));
+    }
+
+    let mut settings = HashMap::new();
+    settings.insert("connection", connection);
+    settings.insert("wireguard", wireguard);
+    settings.insert("ipv4", ipv4);
+    settings.insert("ipv6", ipv6);
+    settings
+}
+
+fn set_hotspot_settings(settings: &mut UpdatedConnectionSettings, config: &HotspotConfig) {
+    let wireless = settings.entry("802-11-wireless").or_default();
+    wireless.insert("ssid", Value::from(config.ssid.as_bytes().to_vec()));
+    wireless.insert("mode", Value::from("ap"));
+    let band = match config.band {
+        HotspotBand::Band2_4GHz => "bg",
+        HotspotBand::Band5GHz => "a",
+    };
+    wireless.insert("band", Value::from(band));
+    // 0 lets the driver pick a channel
+    wireless.insert("channel", Value::from(config.channel.unwrap_or(0)));
+
+    let security = settings.entry("802-11-wireless-security").or_default();
+    security.insert("key-mgmt", Value::from("wpa-psk"));
+    security.insert("proto", Value::from(vec!["rsn"]));
+    security.insert("pairwise", Value::from(vec!["ccmp"]));
+    security.insert("group", Value::from(vec!["ccmp"]));
+    security.insert("psk", Value::from(config.password.clone()));
+}
+
+fn is_access_point(settings: &ConnectionSettings) -> bool {
+    setting_str(settings, "802-11-wireless", "mode").as_deref() == Some("ap")
+}
+
+fn enterprise_settings(enterprise: &EnterpriseSettings) -> HashMap<&'static str, Value<'_>> {
+    let eap = match enterprise.eap {
+        EapMethod::Peap => "peap",
+        EapMethod::Ttls => "ttls",
+        EapMethod::Tls => "tls",
+    };
+
+    let mut settings = HashMap::new();
+    settings.insert("eap", Value::from(vec![eap]));
+    settings.insert("identity", Value::from(enterprise.identity.as_str()));
+    if let Some(anonymous_identity) = &enterprise.anonymous_identity {
+        settings.insert(
+            "anonymous-identity",
+            Value::from(anonymous_identity.as_str()),
+        );
+    }
+    if let Some(ca_cert) = &enterprise.ca_cert {
+        settings.insert("ca-cert", Value::from(certificate_path(ca_cert)));
+    }
+
+    match enterprise.eap {
+        EapMethod::Tls => {
+            if let Some(client_cert) = &enterprise.client_cert {
+                settings.insert("client-cert", Value::from(certificate_path(client_cert)));
+            }
+            if let Some(private_key) = &enterprise.private_key {
+                settings.insert("private-key", Value::from(certificate_path(private_key)));
+            }
+            if let Some(private_key_password) = &enterprise.private_key_password {
+                settings.insert(
+                    "private-key-password",
+                    Value::from(private_key_password.as_str()),
+                );
+            }
+        }
+        EapMethod::Peap | EapMethod::Ttls => {
+            let phase2_auth = match enterprise.phase2_auth {
+                Phase2Auth::Mschapv2 => "mschapv2",
+                Phase2Auth::Gtc => "gtc",
+                Phase2Auth::Pap => "pap",
+                Phase2Auth::Chap => "chap",
+            };
+            settings.insert("phase2-auth", Value::from(phase2_auth));
+            settings.insert(
+                "password",
+                Value::from(enterprise.password.as_deref().unwrap_or_default()),
+            );
+        }
+    }
+    settings
+}
+
+/// NetworkManager takes certificate paths as a nul terminated `file://` uri
+fn certificate_path(path: &str) -> Vec<u8> {
+    let mut uri = format!("file://{}", path).into_bytes();
+    uri.push(0);
+    uri
+}
+
+fn security_from_ap_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> WifiSecurity {
+    let key_mgmt = wpa_flags | rsn_flags;
+    if key_mgmt & AP_SEC_KEY_MGMT_802_1X != 0 {
+        WifiSecurity::Enterprise
+    } else if key_mgmt & AP_SEC_KEY_MGMT_SAE != 0 {
+        WifiSecurity::Wpa3Sae
+    } else if key_mgmt & AP_SEC_KEY_MGMT_PSK != 0 {
+        WifiSecurity::WpaPsk
+    } else if flags & AP_FLAGS_PRIVACY != 0 {
+        WifiSecurity::Wep
+    } else {
+        WifiSecurity::Open
+    }
+}
+
+fn security_from_key_mgmt(key_mgmt: Option<&str>) -> WifiSecurity {
+    match key_mgmt {
+        Some("wpa-psk") => WifiSecurity::WpaPsk,
+        Some("sae") => WifiSecurity::Wpa3Sae,
+        Some("wpa-eap") | Some("wpa-eap-suite-b-192") => WifiSecurity::Enterprise,
+        Some("none") | Some("ieee8021x") => WifiSecurity::Wep,
+        _ => WifiSecurity::Open,
+    }
+}
+
+fn setting_str(settings: &ConnectionSettings, group: &str, key: &str) -> Option<String> {
+    match settings
+        .get(group)
+        .and_then(|group| group.get(key))
+        .map(|v| &**v)
+    {
+        Some(Value::Str(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn setting_bytes(settings: &ConnectionSettings, group: &str, key: &str) -> Vec<u8> {
+    match settings
+        .get(group)
+        .and_then(|group| group.get(key))
+        .map(|v| &**v)
+    {
+        Some(Value::Array(array)) => array
+            .get()
+            .iter()
+            .filter_map(|value| match value {
+                Value::U8(byte) => Some(*byte),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn ipv4_config_from_settings(settings: &ConnectionSettings) -> Ipv4Config {
+    if setting_str(settings, "ipv4", "method").as_deref() != Some("manual") {
+        return Ipv4Config::Auto;
+    }
+
+    let address_data: Vec<HashMap<String, OwnedValue>> =
+        setting_value(settings, "ipv4", "address-data").unwrap_or_default();
+    let address = match ip_addresses(address_data).into_iter().next() {
+        Some(address) => address,
+        None => return Ipv4Config::Auto,
+    };
+    let dns: Vec<u32> = setting_value(settings, "ipv4", "dns").unwrap_or_default();
+
+    let parse = |address: Option<String>| {
+        address
+            .and_then(|address| address.parse::<Ipv4Addr>().ok())
+            .unwrap_or(Ipv4Addr::UNSPECIFIED)
+    };
+    Ipv4Config::Static(StaticIpv4 {
+        address: parse(Some(address.address)),
+        prefix: address.prefix,
+        gateway: parse(setting_str(settings, "ipv4", "gateway")),
+        // addresses are stored in network byte order
+        dns: dns
+            .into_iter()
+            .map(|server| Ipv4Addr::from(server.to_ne_bytes()))
+            .collect(),
+    })
+}
+
+/// Replaces the addressing of the `ipv4` group, the deprecated `addresses`
+/// key is dropped as NetworkManager ignores `address-data` when it is set
+fn set_ipv4_settings(settings: &mut UpdatedConnectionSettings, config: &Ipv4Config) {
+    let ipv4 = settings.entry("ipv4").or_default();
+    for key in ["addresses", "address-data", "gateway"] {
+        ipv4.remove(key);
+    }
+
+    match config {
+        Ipv4Config::Auto => {
+            ipv4.insert("method", Value::from("auto"));
+        }
+        Ipv4Config::Static(config) => {
+            let mut address = HashMap::new();
+            address.insert("address", Value::from(config.address.to_string()));
+            address.insert("prefix", Value::from(config.prefix));
+
+            ipv4.remove("dns-data");
+            ipv4.insert("method", Value::from("manual"));
+            ipv4.insert("address-data", Value::from(vec![address]));
+            ipv4.insert("gateway", Value::from(config.gateway.to_string()));
+            ipv4.insert(
+                "dns",
+                Value::from(
+                    config
+                        .dns
+                        .iter()
+                        .map(|server| u32::from_ne_bytes(server.octets()))
+                        .collect::<Vec<u32>>(),
+                ),
+            );
+        }
+    }
+}
+
+fn ipv6_config_from_settings(settings: &ConnectionSettings) -> Ipv6Config {
+    match setting_str(settings, "ipv6", "method").as_deref() {
+        Some("dhcp") => return Ipv6Config::Dhcp,
+        Some("link-local") => return Ipv6Config::LinkLocal,
+        Some("disabled") | Some("ignore") => return Ipv6Config::Disabled,
+        Some("manual") => (),
+        _ => return Ipv6Config::Auto,
+    }
+
+    let address_data: Vec<HashMap<String, OwnedValue>> =
+        setting_value(settings, "ipv6", "address-data").unwrap_or_default();
+    let address = match ip_addresses(address_data).into_iter().next() {
+        Some(address) => address,
+        None => return Ipv6Config::Auto,
+    };
+    let dns: Vec<Vec<u8>> = setting_value(settings, "ipv6", "dns").unwrap_or_default();
+
+    Ipv6Config::Static(StaticIpv6 {
+        address: address.address.parse().unwrap_or(Ipv6Addr::UNSPECIFIED),
+        prefix: address.prefix,
+        gateway: setting_str(settings, "ipv6", "gateway")
+            .and_then(|gateway| gateway.parse::<Ipv6Addr>().ok()),
+        dns: dns
+            .into_iter()
+            .filter_map(|server| <[u8; 16]>::try_from(server.as_slice()).ok())
+            .map(Ipv6Addr::from)
+            .collect(),
+    })
+}
+
+/// Replaces the addressing of the `ipv6` group
+fn set_ipv6_settings(settings: &mut UpdatedConnectionSettings, config: &Ipv6Config) {
+    let ipv6 = settings.entry("ipv6").or_default();
+    for key in ["addresses", "address-data", "gateway"] {
+        ipv6.remove(key);
+    }
+
+    let method = match config {
+        Ipv6Config::Auto => "auto",
+        Ipv6Config::Dhcp => "dhcp",
+        Ipv6Config::LinkLocal => "link-local",
+        Ipv6Config::Disabled => "disabled",
+        Ipv6Config::Static(config) => {
+            let mut address = HashMap::new();
+            address.insert("address", Value::from(config.address.to_string()));
+            address.insert("prefix", Value::from(config.prefix));
+            ipv6.insert("address-data", Value::from(vec![address]));
+            if let Some(gateway) = config.gateway {
+                ipv6.insert("gateway", Value::from(gateway.to_string()));
+            }
+
+            ipv6.remove("dns-data");
+            ipv6.insert(
+                "dns",
+                Value::from(
+                    config
+                        .dns
+                        .iter()
+                        .map(|server| server.octets().to_vec())
+                        .collect::<Vec<Vec<u8>>>(),
+                ),
+            );
+            "manual"
+        }
+    };
+    ipv6.insert("method", Value::from(method));
+}
+
+fn setting_value<T>(settings: &ConnectionSettings, group: &str, key: &str) -> Option<T>
+where
+    T: TryFrom<Value<'static>>,
+{
+    let value = settings.get(group)?.get(key)?;
+    T::try_from(Value::from(value.clone())).ok()
+}
+
+fn setting_bool(settings: &ConnectionSettings, group: &str, key: &str) -> Option<bool> {
+    match settings
+        .get(group)
+        .and_then(|group| group.get(key))
+        .map(|v| &**v)
+    {
+        Some(Value::Bool(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+fn dict_str(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match dict.get(key).map(|v| &**v) {
+        Some(Value::Str(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn ip_addresses(address_data: Vec<HashMap<String, OwnedValue>>) -> Vec<IpAddress> {
+    address_data
+        .iter()
+        .filter_map(|address| {
+            let prefix = match address.get("prefix").map(|v| &**v) {
+                Some(Value::U32(prefix)) => *prefix,
+                _ => return None,
+            };
+            Some(IpAddress {
+                address: dict_str(address, "address")?,
+                prefix,
+            })
+        })
+        .collect()
+}
+
+fn non_empty(value: String) -> Option<String> {
+    match value.is_empty() {
+        true => None,
+        false => Some(value),
+    }
+}
+
+fn map_call_error<T>(call: &str, result: zbus::Result<T>) -> Result<T> {
+    dbus::map_call_error(
+        LockScreenErrorCodes::NetworkBackendCallError,
+        "networkmanager",
+        call,
+        result,
+    )
+}
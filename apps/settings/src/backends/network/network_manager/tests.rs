@@ -0,0 +1,244 @@
+use std::net::Ipv4Addr;
+
+use zbus::blocking::Connection;
+
+use super::*;
+use crate::{
+    backends::{network::WifiStatus, test_bus::TestBus},
+    settings::DBusBusType,
+};
+
+#[allow(dead_code)]
+#[path = "../../../../examples/fake_network_manager.rs"]
+mod fake_network_manager;
+
+/// Starts the scripted NetworkManager on a bus of its own and returns
+/// a backend connected to it, the fake leaves the bus once its
+/// connection is dropped
+fn start_fake() -> (NetworkManagerBackend, Connection, TestBus) {
+    let bus = TestBus::start();
+    let fake_connection = bus.connection();
+    fake_network_manager::serve(&fake_connection).unwrap();
+
+    let backend = NetworkManagerBackend::new(&NetworkBackendSettings {
+        bus: DBusBusType::Session,
+        ..Default::default()
+    });
+    backend.connection.set(bus.connection());
+    (backend, fake_connection, bus)
+}
+
+fn ssids(networks: &[WifiNetwork]) -> Vec<&str> {
+    networks.iter().map(|n| n.ssid.as_str()).collect()
+}
+
+/// Settings as read back with `GetSettings`
+fn to_connection_settings(settings: UpdatedConnectionSettings) -> ConnectionSettings {
+    settings
+        .into_iter()
+        .map(|(group, values)| {
+            let values = values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), OwnedValue::from(value)))
+                .collect();
+            (group.to_string(), values)
+        })
+        .collect()
+}
+
+#[test]
+fn access_point_flags_map_to_security() {
+    assert_eq!(security_from_ap_flags(0, 0, 0), WifiSecurity::Open);
+    assert_eq!(
+        security_from_ap_flags(AP_FLAGS_PRIVACY, 0, 0),
+        WifiSecurity::Wep
+    );
+    assert_eq!(
+        security_from_ap_flags(AP_FLAGS_PRIVACY, AP_SEC_KEY_MGMT_PSK, 0),
+        WifiSecurity::WpaPsk
+    );
+    // transition networks offer both, SAE is preferred
+    assert_eq!(
+        security_from_ap_flags(
+            AP_FLAGS_PRIVACY,
+            0,
+            AP_SEC_KEY_MGMT_PSK | AP_SEC_KEY_MGMT_SAE
+        ),
+        WifiSecurity::Wpa3Sae
+    );
+    assert_eq!(
+        security_from_ap_flags(AP_FLAGS_PRIVACY, 0, AP_SEC_KEY_MGMT_802_1X),
+        WifiSecurity::Enterprise
+    );
+}
+
+#[test]
+fn device_states_map_to_connect_progress() {
+    assert_eq!(
+        connect_state_from_device_state(DEVICE_STATE_PREPARE),
+        Some(WifiConnectState::Associating)
+    );
+    assert_eq!(
+        connect_state_from_device_state(DEVICE_STATE_NEED_AUTH),
+        Some(WifiConnectState::Authenticating)
+    );
+    assert_eq!(
+        connect_state_from_device_state(DEVICE_STATE_IP_CHECK),
+        Some(WifiConnectState::ObtainingIp)
+    );
+    assert_eq!(connect_state_from_device_state(DEVICE_STATE_FAILED), None);
+    assert_eq!(
+        connect_failure_from_reason(7),
+        WifiConnectFailure::WrongPassword
+    );
+    assert_eq!(
+        connect_failure_from_reason(53),
+        WifiConnectFailure::NetworkNotFound
+    );
+    assert_eq!(connection_state_from_nm(2), ConnectionState::Activated);
+    assert_eq!(connection_state_from_nm(9), ConnectionState::Unknown);
+}
+
+#[test]
+fn static_ipv4_settings_are_read_back() {
+    let config = Ipv4Config::Static(StaticIpv4 {
+        address: Ipv4Addr::new(192, 168, 1, 50),
+        prefix: 24,
+        gateway: Ipv4Addr::new(192, 168, 1, 1),
+        dns: vec![Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(9, 9, 9, 9)],
+    });
+    let mut settings = UpdatedConnectionSettings::new();
+    set_ipv4_settings(&mut settings, &config);
+    assert_eq!(
+        ipv4_config_from_settings(&to_connection_settings(settings)),
+        config
+    );
+
+    let mut settings = UpdatedConnectionSettings::new();
+    set_ipv4_settings(&mut settings, &Ipv4Config::Auto);
+    assert_eq!(
+        ipv4_config_from_settings(&to_connection_settings(settings)),
+        Ipv4Config::Auto
+    );
+}
+
+#[test]
+fn static_ipv6_settings_are_read_back() {
+    let config = Ipv6Config::Static(StaticIpv6 {
+        address: "2001:db8::10".parse().unwrap(),
+        prefix: 64,
+        gateway: Some("fe80::1".parse().unwrap()),
+        dns: vec!["2001:db8::53".parse().unwrap()],
+    });
+    for config in [config, Ipv6Config::Dhcp, Ipv6Config::Disabled] {
+        let mut settings = UpdatedConnectionSettings::new();
+        set_ipv6_settings(&mut settings, &config);
+        assert_eq!(
+            ipv6_config_from_settings(&to_connection_settings(settings)),
+            config
+        );
+    }
+}
+
+#[test]
+fn wifi_status_follows_the_radio() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    assert_eq!(
+        backend.wifi_status().unwrap(),
+        WifiStatus {
+            enabled: true,
+            active: Some(ActiveWifi {
+                ssid: "Mecha Office".to_string(),
+                state: ConnectionState::Activated,
+            }),
+        }
+    );
+
+    backend.set_wifi_enabled(false).unwrap();
+    assert_eq!(backend.wifi_status().unwrap(), WifiStatus::default());
+    assert!(backend.request_wifi_scan().is_err());
+
+    backend.set_wifi_enabled(true).unwrap();
+    backend.request_wifi_scan().unwrap();
+    let active = backend.active_wifi().unwrap().unwrap();
+    assert_eq!(active.ssid, "Mecha Office");
+}
+
+#[test]
+fn scan_results_are_split_into_saved_and_available() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    let list = backend.wifi_network_list().unwrap();
+
+    assert_eq!(ssids(&list.known), vec!["Mecha Office", "Home"]);
+    let office = &list.known[0];
+    assert!(office.is_connected);
+    assert_eq!(office.bssid, "A0:B1:C2:D3:E4:01");
+    assert_eq!(office.security, WifiSecurity::WpaPsk);
+    // saved but out of range
+    assert_eq!(list.known[1].strength, 0);
+
+    assert_eq!(ssids(&list.available), vec!["Cafe Guest", "Lab WPA3"]);
+    assert_eq!(list.available[0].security, WifiSecurity::Open);
+    assert_eq!(list.available[1].security, WifiSecurity::Wpa3Sae);
+}
+
+#[test]
+fn forgotten_network_is_no_longer_saved() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    backend.forget_wifi("Mecha Office").unwrap();
+
+    let known = backend.known_wifi_networks().unwrap();
+    assert_eq!(known.len(), 1);
+    assert_eq!(known[0].ssid, "Home");
+    assert_eq!(backend.active_wifi().unwrap(), None);
+    let list = backend.wifi_network_list().unwrap();
+    assert!(ssids(&list.available).contains(&"Mecha Office"));
+}
+
+#[test]
+fn missing_service_is_a_call_error() {
+    let bus = TestBus::start();
+    let backend = NetworkManagerBackend::new(&NetworkBackendSettings::default());
+    backend.connection.set(bus.connection());
+    assert!(backend.wifi_enabled().is_err());
+}
+
+fn saved_psk(backend: &NetworkManagerBackend) -> Option<String> {
+    let path = OwnedObjectPath::try_from("/org/freedesktop/NetworkManager/Settings/1").unwrap();
+    let settings_connection: SettingsConnectionProxyBlocking = backend.proxy(path).unwrap();
+    let secrets = settings_connection
+        .get_secrets("802-11-wireless-security")
+        .unwrap();
+    setting_str(&secrets, "802-11-wireless-security", "psk")
+}
+
+fn connect(backend: &NetworkManagerBackend, password: &str) -> Vec<WifiConnectState> {
+    let request = WifiConnectRequest {
+        ssid: "Mecha Office".to_string(),
+        security: WifiSecurity::WpaPsk,
+        password: Some(password.to_string()),
+    };
+    let states = std::cell::RefCell::new(vec![]);
+    backend
+        .connect_wifi(&request, &|state| states.borrow_mut().push(state))
+        .unwrap();
+    states.into_inner()
+}
+
+#[test]
+fn password_entered_again_replaces_the_saved_one() {
+    let (backend, _fake_connection, _bus) = start_fake();
+    assert_eq!(saved_psk(&backend).as_deref(), Some("office-password"));
+
+    let states = connect(&backend, "new-office-password");
+    assert_eq!(states.last(), Some(&WifiConnectState::Connected));
+    assert_eq!(saved_psk(&backend).as_deref(), Some("new-office-password"));
+
+    // refused before the saved one is touched
+    let states = connect(&backend, "short");
+    assert!(matches!(
+        states.last(),
+        Some(WifiConnectState::Failed(WifiConnectFailure::Other(_)))
+    ));
+    assert_eq!(saved_psk(&backend).as_deref(), Some("new-office-password"));
+}
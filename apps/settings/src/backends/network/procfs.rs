@@ -0,0 +1,238 @@
+use std::{
+    fs,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use super::{HotspotClient, IpAddress, IpDetails};
+
+const PROC_ROOT: &str = "/proc";
+/// Relative to the procfs root, diagnostics may be pointed at a fake tree
+const ROUTE_PATH: &str = "net/route";
+const ARP_PATH: &str = "/proc/net/arp";
+/// `ATF_COM`, the neighbour answered and its address is known
+const ARP_FLAG_COMPLETE: u32 = 0x2;
+const IF_INET6_PATH: &str = "/proc/net/if_inet6";
+const IPV6_ROUTE_PATH: &str = "/proc/net/ipv6_route";
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// # Interface IP Details
+///
+/// Reads the IP configuration of an interface from the kernel for
+/// services that do not expose it, the IPv4 address has to be known
+/// by the caller as procfs only lists routes for it
+pub fn interface_ip_details(interface: &str, ipv4_address: Option<&str>) -> IpDetails {
+    let routes = fs::read_to_string(Path::new(PROC_ROOT).join(ROUTE_PATH)).unwrap_or_default();
+    let if_inet6 = fs::read_to_string(IF_INET6_PATH).unwrap_or_default();
+    let ipv6_routes = fs::read_to_string(IPV6_ROUTE_PATH).unwrap_or_default();
+    let resolv_conf = fs::read_to_string(RESOLV_CONF_PATH).unwrap_or_default();
+
+    let ipv4_addresses = match ipv4_address {
+        Some(address) => vec![IpAddress {
+            address: address.to_string(),
+            prefix: ipv4_prefix(&routes, interface).unwrap_or(32),
+        }],
+        None => vec![],
+    };
+
+    IpDetails {
+        ipv4_addresses,
+        ipv4_gateway: ipv4_gateway(&routes, interface),
+        ipv6_addresses: ipv6_addresses(&if_inet6, interface),
+        ipv6_gateway: ipv6_gateway(&ipv6_routes, interface),
+        dns: nameservers(&resolv_conf),
+    }
+}
+
+/// Interface of the IPv4 default route, the one DNS queries leave through
+pub fn default_route_interface() -> Option<String> {
+    default_route_interface_in(Path::new(PROC_ROOT))
+}
+
+/// Interface of the IPv4 default route in the procfs tree at `proc_root`
+pub fn default_route_interface_in(proc_root: &Path) -> Option<String> {
+    let routes = fs::read_to_string(proc_root.join(ROUTE_PATH)).ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.get(1) {
+            Some(&"00000000") => Some(fields[0].to_string()),
+            _ => None,
+        }
+    })
+}
+
+/// Gateway of the IPv4 default route through an interface, in the
+/// procfs tree at `proc_root`
+pub fn default_gateway(proc_root: &Path, interface: &str) -> Option<String> {
+    let routes = fs::read_to_string(proc_root.join(ROUTE_PATH)).ok()?;
+    ipv4_gateway(&routes, interface)
+}
+
+/// Neighbours of an interface from the ARP table, the devices
+/// that talked to the hotspot recently
+pub fn arp_clients(interface: &str) -> Vec<HotspotClient> {
+    let arp_table = fs::read_to_string(ARP_PATH).unwrap_or_default();
+    arp_table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || fields[5] != interface {
+                return None;
+            }
+            let flags = u32::from_str_radix(fields[2].trim_start_matches("0x"), 16).ok()?;
+            if flags & ARP_FLAG_COMPLETE == 0 {
+                return None;
+            }
+            Some(HotspotClient {
+                mac_address: fields[3].to_string(),
+                ip_address: Some(fields[0].to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Rows of `/proc/net/route` for an interface as (destination, gateway, mask)
+fn routes(routes: &str, interface: &str) -> Vec<(u32, u32, u32)> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[0] != interface {
+                return None;
+            }
+            let destination = u32::from_str_radix(fields[1], 16).ok()?;
+            let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+            let mask = u32::from_str_radix(fields[7], 16).ok()?;
+            Some((destination, gateway, mask))
+        })
+        .collect()
+}
+
+/// Prefix of the on-link route, the mask is stored in network order
+/// so counting its bits does not depend on the host byte order
+fn ipv4_prefix(route_table: &str, interface: &str) -> Option<u32> {
+    routes(route_table, interface)
+        .into_iter()
+        .find(|(destination, gateway, _)| *destination != 0 && *gateway == 0)
+        .map(|(_, _, mask)| mask.count_ones())
+}
+
+fn ipv4_gateway(route_table: &str, interface: &str) -> Option<String> {
+    routes(route_table, interface)
+        .into_iter()
+        .find(|(destination, gateway, _)| *destination == 0 && *gateway != 0)
+        .map(|(_, gateway, _)| Ipv4Addr::from(gateway.to_ne_bytes()).to_string())
+}
+
+fn ipv6_addresses(if_inet6: &str, interface: &str) -> Vec<IpAddress> {
+    if_inet6
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || fields[5] != interface {
+                return None;
+            }
+            let address = u128::from_str_radix(fields[0], 16).ok()?;
+            let prefix = u32::from_str_radix(fields[2], 16).ok()?;
+            Some(IpAddress {
+                address: Ipv6Addr::from(address).to_string(),
+                prefix,
+            })
+        })
+        .collect()
+}
+
+/// Next hop of the `::/0` route, `/proc/net/ipv6_route` has no header
+/// and lists addresses as 32 hex digits
+fn ipv6_gateway(route_table: &str, interface: &str) -> Option<String> {
+    route_table.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || fields[9] != interface {
+            return None;
+        }
+        let destination = u128::from_str_radix(fields[0], 16).ok()?;
+        let prefix = u32::from_str_radix(fields[1], 16).ok()?;
+        let next_hop = u128::from_str_radix(fields[4], 16).ok()?;
+        match destination == 0 && prefix == 0 && next_hop != 0 {
+            true => Some(Ipv6Addr::from(next_hop).to_string()),
+            false => None,
+        }
+    })
+}
+
+fn nameservers(resolv_conf: &str) -> Vec<String> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => fields.next().map(|address| address.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/proc/net/route` prints the addresses in host byte order
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn ipv4_routes_of_an_interface_are_read() {
+        let routes = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+wlan0\t0001A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t100\t000000FF\t0\t0\t0
+";
+        assert_eq!(
+            ipv4_gateway(routes, "wlan0").as_deref(),
+            Some("192.168.1.1")
+        );
+        assert_eq!(ipv4_prefix(routes, "wlan0"), Some(24));
+        assert_eq!(ipv4_gateway(routes, "eth0"), None);
+        assert_eq!(ipv4_prefix(routes, "eth0"), Some(8));
+    }
+
+    #[test]
+    fn ipv6_addresses_and_gateway_are_read() {
+        let if_inet6 = "\
+fe800000000000000000000000000001 02 40 20 80     wlan0
+20010db8000000000000000000000010 02 40 00 80     wlan0
+00000000000000000000000000000001 01 80 10 80        lo
+";
+        assert_eq!(
+            ipv6_addresses(if_inet6, "wlan0"),
+            vec![
+                IpAddress {
+                    address: "fe80::1".to_string(),
+                    prefix: 64,
+                },
+                IpAddress {
+                    address: "2001:db8::10".to_string(),
+                    prefix: 64,
+                },
+            ]
+        );
+
+        let ipv6_routes = "\
+20010db8000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001    wlan0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003    wlan0
+";
+        assert_eq!(
+            ipv6_gateway(ipv6_routes, "wlan0").as_deref(),
+            Some("fe80::1")
+        );
+        assert_eq!(ipv6_gateway(ipv6_routes, "eth0"), None);
+    }
+
+    #[test]
+    fn nameservers_are_read_from_resolv_conf() {
+        let resolv_conf = "# comment\nsearch mecha.local\nnameserver 1.1.1.1\nnameserver ::1\n";
+        assert_eq!(nameservers(resolv_conf), vec!["1.1.1.1", "::1"]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+/// `ARPHRD_ETHER` from `/sys/class/net/<iface>/type`
+const ARPHRD_ETHER: u32 = 1;
+/// `IFF_UP` from `/sys/class/net/<iface>/flags`
+const IFF_UP: u32 = 0x1;
+
+/// # Operational State
+///
+/// RFC 2863 state of a link, from `operstate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OperState {
+    #[default]
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+impl OperState {
+    fn parse(state: &str) -> Self {
+        match state {
+            "notpresent" => OperState::NotPresent,
+            "down" => OperState::Down,
+            "lowerlayerdown" => OperState::LowerLayerDown,
+            "testing" => OperState::Testing,
+            "dormant" => OperState::Dormant,
+            "up" => OperState::Up,
+            _ => OperState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for OperState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OperState::Unknown => write!(f, "Unknown"),
+            OperState::NotPresent => write!(f, "Not present"),
+            OperState::Down => write!(f, "Down"),
+            OperState::LowerLayerDown => write!(f, "Lower layer down"),
+            OperState::Testing => write!(f, "Testing"),
+            OperState::Dormant => write!(f, "Dormant"),
+            OperState::Up => write!(f, "Up"),
+        }
+    }
+}
+
+/// # Ethernet Link
+///
+/// State of a wired interface as reported by the kernel, `carrier`,
+/// `speed` and `duplex` can only be read while the interface is up
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EthernetLink {
+    pub interface: String,
+    /// Administratively up, what the user switches
+    pub enabled: bool,
+    pub operstate: OperState,
+    pub carrier: Option<bool>,
+    /// Mb/s
+    pub speed: Option<u32>,
+    pub duplex: Option<String>,
+    pub address: String,
+}
+
+/// Lists the physical wired interfaces below `<sysfs_root>/class/net`,
+/// sorted by name
+pub fn ethernet_links(sysfs_root: &Path) -> Vec<EthernetLink> {
+    let net_path = sysfs_root.join("class/net");
+    let entries = match fs::read_dir(&net_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!(
+                task = "network_backend",
+                "cannot read {:?} - {}", net_path, e
+            );
+            return vec![];
+        }
+    };
+
+    let mut links: Vec<EthernetLink> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_ethernet(&entry.path()))
+        .map(|entry| {
+            let path = entry.path();
+            EthernetLink {
+                interface: entry.file_name().to_string_lossy().to_string(),
+                enabled: read_attribute(&path, "flags")
+                    .and_then(|flags| u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok())
+                    .map(|flags| flags & IFF_UP != 0)
+                    .unwrap_or(false),
+                operstate: read_attribute(&path, "operstate")
+                    .map(|state| OperState::parse(&state))
+                    .unwrap_or_default(),
+                carrier: read_attribute(&path, "carrier").map(|carrier| carrier == "1"),
+                // drivers report -1 when the speed is not negotiated
+                speed: read_attribute(&path, "speed")
+                    .and_then(|speed| speed.parse::<i64>().ok())
+                    .filter(|speed| *speed > 0)
+                    .map(|speed| speed as u32),
+                duplex: read_attribute(&path, "duplex").filter(|duplex| duplex != "unknown"),
+                address: read_attribute(&path, "address").unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    links.sort_by(|a, b| a.interface.cmp(&b.interface));
+    links
+}
+
+/// Path of an interface directory, used to check it still exists
+pub fn interface_path(sysfs_root: &Path, interface: &str) -> PathBuf {
+    sysfs_root.join("class/net").join(interface)
+}
+
+/// Ethernet framing, backed by a device, and not a wireless card;
+/// bridges, veths and tunnels have no `device` link
+fn is_ethernet(path: &Path) -> bool {
+    let link_type =
+        read_attribute(path, "type").and_then(|link_type| link_type.parse::<u32>().ok());
+    link_type == Some(ARPHRD_ETHER)
+        && path.join("device").exists()
+        && !path.join("wireless").exists()
+        && !path.join("phy80211").exists()
+}
+
+/// Reads a sysfs attribute, the kernel fails the read of attributes
+/// that do not apply to the current state
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// Writes `class/net/<interface>` with the given attributes, a
+    /// `device` directory is added for physical interfaces
+    fn add_interface(root: &Path, interface: &str, attributes: &[(&str, &str)], physical: bool) {
+        let path = interface_path(root, interface);
+        fs::create_dir_all(&path).unwrap();
+        for (attribute, value) in attributes {
+            match *value {
+                "<dir>" => fs::create_dir_all(path.join(attribute)).unwrap(),
+                value => fs::write(path.join(attribute), format!("{}\n", value)).unwrap(),
+            }
+        }
+        if physical {
+            fs::create_dir_all(path.join("device")).unwrap();
+        }
+    }
+
+    #[test]
+    fn only_physical_wired_interfaces_are_listed() {
+        let root = env::temp_dir().join(format!("sysfs_links_{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        add_interface(
+            &root,
+            "eth1",
+            &[
+                ("type", "1"),
+                ("flags", "0x1002"),
+                ("operstate", "down"),
+                ("speed", "-1"),
+                ("duplex", "unknown"),
+                ("address", "02:00:00:00:00:02"),
+            ],
+            true,
+        );
+        add_interface(
+            &root,
+            "eth0",
+            &[
+                ("type", "1"),
+                ("flags", "0x1003"),
+                ("operstate", "up"),
+                ("carrier", "1"),
+                ("speed", "1000"),
+                ("duplex", "full"),
+                ("address", "02:00:00:00:00:01"),
+            ],
+            true,
+        );
+        add_interface(
+            &root,
+            "wlan0",
+            &[("type", "1"), ("wireless", "<dir>")],
+            true,
+        );
+        add_interface(&root, "br0", &[("type", "1")], false);
+        add_interface(&root, "lo", &[("type", "772")], false);
+
+        let links = ethernet_links(&root);
+        assert_eq!(
+            links,
+            vec![
+                EthernetLink {
+                    interface: "eth0".to_string(),
+                    enabled: true,
+                    operstate: OperState::Up,
+                    carrier: Some(true),
+                    speed: Some(1000),
+                    duplex: Some("full".to_string()),
+                    address: "02:00:00:00:00:01".to_string(),
+                },
+                EthernetLink {
+                    interface: "eth1".to_string(),
+                    enabled: false,
+                    operstate: OperState::Down,
+                    carrier: None,
+                    speed: None,
+                    duplex: None,
+                    address: "02:00:00:00:00:02".to_string(),
+                },
+            ]
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn missing_tree_has_no_links() {
+        assert!(ethernet_links(Path::new("/nonexistent")).is_empty());
+    }
+}
@@ -0,0 +1,663 @@
+use std::{
+    fmt, fs,
+    net::IpAddr,
+    path::Path,
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use tracing::warn;
+
+use super::{is_permission_error, missing_privilege, sysfs};
+use crate::errors::{LockScreenError, LockScreenErrorCodes};
+
+/// Longest interface name the kernel accepts, `IFNAMSIZ - 1`
+const MAX_INTERFACE_NAME_LEN: usize = 15;
+/// Keys are 32 bytes, 44 characters in padded base64
+const KEY_LEN: usize = 44;
+
+/// # WireGuard Peer
+///
+/// `[Peer]` section of a wg-quick config, `allowed_ips` are in
+/// `address/prefix` form
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WireguardPeer {
+    pub public_key: String,
+    pub preshared_key: Option<String>,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u16>,
+}
+
+/// Routing table the routes of the allowed IPs are added to, the
+/// `Table` key of a wg-quick config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireguardTable {
+    /// The main table, or a table of its own for a default route
+    #[default]
+    Auto,
+    /// No routes are added
+    Off,
+    Id(u32),
+}
+
+impl fmt::Display for WireguardTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireguardTable::Auto => write!(f, "auto"),
+            WireguardTable::Off => write!(f, "off"),
+            WireguardTable::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+/// # WireGuard Config
+///
+/// Tunnel as written in a wg-quick `.conf` file, `name` is the
+/// file name without extension and becomes the interface name.
+/// Non-address `DNS` entries are search domains, as in wg-quick
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WireguardConfig {
+    pub name: String,
+    pub private_key: String,
+    pub addresses: Vec<String>,
+    pub dns: Vec<IpAddr>,
+    pub dns_search: Vec<String>,
+    pub listen_port: Option<u16>,
+    pub mtu: Option<u32>,
+    pub table: WireguardTable,
+    /// Mark of the tunnel's own packets, for policy routing
+    pub fwmark: Option<u32>,
+    pub peers: Vec<WireguardPeer>,
+}
+
+impl WireguardConfig {
+    /// Reads a wg-quick config, the profile is named after the file
+    pub fn from_file(path: &Path) -> std::result::Result<Self, String> {
+        let name = match path.file_stem() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(format!("{} is not a file", path.display())),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("cannot read {} - {}", path.display(), e)),
+        };
+        let config = Self::parse(&name, &contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses the `[Interface]` and `[Peer]` sections of a wg-quick config.
+    /// Hook scripts are refused, the app runs them as root otherwise
+    pub fn parse(name: &str, contents: &str) -> std::result::Result<Self, String> {
+        let mut config = WireguardConfig {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut section = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = match line[1..line.len() - 1].trim().to_lowercase().as_str() {
+                    "interface" => Some(Section::Interface),
+                    "peer" => {
+                        config.peers.push(WireguardPeer::default());
+                        Some(Section::Peer)
+                    }
+                    other => return Err(format!("unknown section [{}]", other)),
+                };
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => return Err(format!("line {} is not key = value", index + 1)),
+            };
+            match section {
+                Some(Section::Interface) => config.set_interface_key(&key, value)?,
+                Some(Section::Peer) => {
+                    // a peer section was pushed when the header was read
+                    let peer = config.peers.last_mut().unwrap();
+                    set_peer_key(peer, &key, value)?;
+                }
+                None => return Err(format!("line {} is outside of a section", index + 1)),
+            }
+        }
+        Ok(config)
+    }
+
+    fn set_interface_key(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        match key {
+            "privatekey" => self.private_key = value.to_string(),
+            "address" => self.addresses.extend(split_list(value)),
+            "dns" => {
+                for entry in split_list(value) {
+                    match entry.parse::<IpAddr>() {
+                        Ok(address) => self.dns.push(address),
+                        Err(_) => self.dns_search.push(entry),
+                    }
+                }
+            }
+            "listenport" => self.listen_port = Some(parse_number(key, value)?),
+            "mtu" => self.mtu = Some(parse_number(key, value)?),
+            "table" => {
+                self.table = match value.to_lowercase().as_str() {
+                    "auto" => WireguardTable::Auto,
+                    "off" => WireguardTable::Off,
+                    // names of /etc/iproute2/rt_tables are not looked up
+                    _ => match value.parse() {
+                        Ok(id) => WireguardTable::Id(id),
+                        Err(_) => {
+                            return Err(format!(
+                                "table must be auto, off or a number, got {}",
+                                value
+                            ));
+                        }
+                    },
+                }
+            }
+            "fwmark" => self.fwmark = parse_fwmark(value)?,
+            // the app keeps the profile, the tunnel state is not written back
+            "saveconfig" => warn!(task = "network_backend", "ignoring SaveConfig of {}", self.name),
+            "preup" | "postup" | "predown" | "postdown" => {
+                return Err("hook scripts (PreUp, PostUp, ...) are not supported".to_string());
+            }
+            _ => return Err(format!("unsupported key {} in [Interface]", key)),
+        }
+        Ok(())
+    }
+
+    /// Checks the tunnel can be brought up, returns a message to show the user otherwise
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !is_valid_interface_name(&self.name) {
+            return Err(format!(
+                "profile name must be 1 to {} letters, digits or _=+.-",
+                MAX_INTERFACE_NAME_LEN
+            ));
+        }
+        if !is_valid_key(&self.private_key) {
+            return Err("private key is missing or not a WireGuard key".to_string());
+        }
+        if self.addresses.is_empty() {
+            return Err("at least one address is required".to_string());
+        }
+        for address in self.addresses.iter() {
+            if parse_cidr(address).is_none() {
+                return Err(format!("{} is not a valid address", address));
+            }
+        }
+        if self.peers.is_empty() {
+            return Err("at least one peer is required".to_string());
+        }
+
+        for (index, peer) in self.peers.iter().enumerate() {
+            let peer_number = index + 1;
+            if !is_valid_key(&peer.public_key) {
+                return Err(format!(
+                    "public key of peer {} is missing or not a WireGuard key",
+                    peer_number
+                ));
+            }
+            if let Some(preshared_key) = &peer.preshared_key {
+                if !is_valid_key(preshared_key) {
+                    return Err(format!(
+                        "preshared key of peer {} is not a WireGuard key",
+                        peer_number
+                    ));
+                }
+            }
+            if let Some(endpoint) = &peer.endpoint {
+                if !is_valid_endpoint(endpoint) {
+                    return Err(format!(
+                        "endpoint {} of peer {} must be host:port",
+                        endpoint, peer_number
+                    ));
+                }
+            }
+            if peer.allowed_ips.is_empty() {
+                return Err(format!("peer {} has no allowed IPs", peer_number));
+            }
+            for allowed_ip in peer.allowed_ips.iter() {
+                if parse_cidr(allowed_ip).is_none() {
+                    return Err(format!(
+                        "{} of peer {} is not a valid network",
+                        allowed_ip, peer_number
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the config back in wg-quick format
+    pub fn to_wg_quick(&self) -> String {
+        let mut lines = vec!["[Interface]".to_string()];
+        lines.push(format!("PrivateKey = {}", self.private_key));
+        lines.push(format!("Address = {}", self.addresses.join(", ")));
+        let dns: Vec<String> = self
+            .dns
+            .iter()
+            .map(|server| server.to_string())
+            .chain(self.dns_search.iter().cloned())
+            .collect();
+        if !dns.is_empty() {
+            lines.push(format!("DNS = {}", dns.join(", ")));
+        }
+        if let Some(listen_port) = self.listen_port {
+            lines.push(format!("ListenPort = {}", listen_port));
+        }
+        if let Some(mtu) = self.mtu {
+            lines.push(format!("MTU = {}", mtu));
+        }
+        if self.table != WireguardTable::Auto {
+            lines.push(format!("Table = {}", self.table));
+        }
+        if let Some(fwmark) = self.fwmark {
+            lines.push(format!("FwMark = {:#x}", fwmark));
+        }
+
+        for peer in self.peers.iter() {
+            lines.push(String::new());
+            lines.push("[Peer]".to_string());
+            lines.push(format!("PublicKey = {}", peer.public_key));
+            if let Some(preshared_key) = &peer.preshared_key {
+                lines.push(format!("PresharedKey = {}", preshared_key));
+            }
+            if let Some(endpoint) = &peer.endpoint {
+                lines.push(format!("Endpoint = {}", endpoint));
+            }
+            lines.push(format!("AllowedIPs = {}", peer.allowed_ips.join(", ")));
+            if let Some(persistent_keepalive) = peer.persistent_keepalive {
+                lines.push(format!("PersistentKeepalive = {}", persistent_keepalive));
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Interface,
+    Peer,
+}
+
+fn set_peer_key(
+    peer: &mut WireguardPeer,
+    key: &str,
+    value: &str,
+) -> std::result::Result<(), String> {
+    match key {
+        "publickey" => peer.public_key = value.to_string(),
+        "presharedkey" => peer.preshared_key = Some(value.to_string()),
+        "endpoint" => peer.endpoint = Some(value.to_string()),
+        "allowedips" => peer.allowed_ips.extend(split_list(value)),
+        "persistentkeepalive" => {
+            peer.persistent_keepalive = match value {
+                "off" => None,
+                value => Some(parse_number(key, value)?),
+            }
+        }
+        _ => return Err(format!("unsupported key {} in [Peer]", key)),
+    }
+    Ok(())
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| entry.to_string())
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> std::result::Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a number, got {}", key, value))
+}
+
+/// `FwMark` in decimal or `0x` hex, `off` and 0 clear it as in wg
+fn parse_fwmark(value: &str) -> std::result::Result<Option<u32>, String> {
+    let value = value.to_lowercase();
+    let fwmark = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None if value == "off" => Some(0),
+        None => value.parse().ok(),
+    };
+    match fwmark {
+        Some(0) => Ok(None),
+        Some(fwmark) => Ok(Some(fwmark)),
+        None => Err(format!("fwmark must be a number, got {}", value)),
+    }
+}
+
+/// wg-quick names the interface after the file
+pub fn is_valid_interface_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_INTERFACE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_=+.-".contains(c))
+}
+
+/// A 32 byte key in padded base64, the last character before the
+/// padding only carries the top two bits of the final byte
+fn is_valid_key(key: &str) -> bool {
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    const LAST_CHARACTERS: &str = "AEIMQUYcgkosw048";
+    // checked first, the slices below are on byte offsets
+    key.is_ascii()
+        && key.len() == KEY_LEN
+        && key.ends_with('=')
+        && key[..KEY_LEN - 2].chars().all(|c| ALPHABET.contains(c))
+        && LAST_CHARACTERS.contains(&key[KEY_LEN - 2..KEY_LEN - 1])
+}
+
+/// Parses `address/prefix`, a bare address is a single host
+pub fn parse_cidr(value: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix) = match value.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (value, None),
+    };
+    let address: IpAddr = address.parse().ok()?;
+    let max_prefix = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    let prefix = match prefix {
+        Some(prefix) => prefix.parse::<u8>().ok()?,
+        None => max_prefix,
+    };
+    match prefix <= max_prefix {
+        true => Some((address, prefix)),
+        false => None,
+    }
+}
+
+/// `host:port`, IPv6 addresses are written in brackets
+fn is_valid_endpoint(endpoint: &str) -> bool {
+    let (host, port) = match endpoint.rsplit_once(':') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let port_is_valid = matches!(port.parse::<u16>(), Ok(port) if port != 0);
+    let host_is_valid = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(address) => address.parse::<std::net::Ipv6Addr>().is_ok(),
+        None => {
+            !host.is_empty()
+                && !host.contains(':')
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        }
+    };
+    host_is_valid && port_is_valid
+}
+
+/// Saved WireGuard profile and whether its tunnel is up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireguardProfile {
+    pub name: String,
+    pub active: bool,
+}
+
+/// # WireGuard Status
+///
+/// Live state of a tunnel summed over its peers, `latest_handshake`
+/// is unset until a peer completed a handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WireguardStatus {
+    pub active: bool,
+    pub latest_handshake: Option<SystemTime>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl WireguardStatus {
+    pub fn handshake_age(&self) -> Option<Duration> {
+        self.latest_handshake
+            .map(|handshake| handshake.elapsed().unwrap_or_default())
+    }
+}
+
+impl fmt::Display for WireguardStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.active, self.handshake_age()) {
+            (false, _) => write!(f, "Off"),
+            (true, None) => write!(f, "No handshake yet"),
+            (true, Some(age)) => match age.as_secs() {
+                0..=59 => write!(f, "Handshake {}s ago", age.as_secs()),
+                60..=3599 => write!(f, "Handshake {}m ago", age.as_secs() / 60),
+                _ => write!(f, "Handshake {}h ago", age.as_secs() / 3600),
+            },
+        }
+    }
+}
+
+/// Reads the peers of a tunnel with `wg show <interface> dump`, an
+/// interface that does not exist is a tunnel that is down
+pub fn interface_status(sysfs_root: &Path, interface: &str) -> Result<WireguardStatus> {
+    if !sysfs::interface_path(sysfs_root, interface).exists() {
+        return Ok(WireguardStatus::default());
+    }
+
+    let output = Command::new("wg")
+        .args(["show", interface, "dump"])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Ok(parse_dump(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(output) => {
+            // the peers are only shown to processes with CAP_NET_ADMIN
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if is_permission_error(&stderr) {
+                bail!(missing_privilege(&format!(
+                    "Reading the status of {}",
+                    interface
+                )));
+            }
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot read tunnel {} - {}", interface, stderr.trim()),
+            ));
+        }
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot run wg - {}", e),
+            ));
+        }
+    }
+}
+
+/// Parses `wg show dump`, the interface line then one tab separated
+/// line per peer: `public-key preshared-key endpoint allowed-ips
+/// latest-handshake transfer-rx transfer-tx persistent-keepalive`
+fn parse_dump(dump: &str) -> WireguardStatus {
+    let mut status = WireguardStatus {
+        active: true,
+        ..Default::default()
+    };
+    for line in dump.lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 8 {
+            continue;
+        }
+        // zero until the first handshake
+        let handshake = fields[4].parse::<u64>().unwrap_or_default();
+        if handshake > 0 {
+            let handshake = UNIX_EPOCH + Duration::from_secs(handshake);
+            if status
+                .latest_handshake
+                .map_or(true, |latest| handshake > latest)
+            {
+                status.latest_handshake = Some(handshake);
+            }
+        }
+        status.rx_bytes += fields[5].parse::<u64>().unwrap_or_default();
+        status.tx_bytes += fields[6].parse::<u64>().unwrap_or_default();
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+    const PUBLIC_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+
+    const PROFILE: &str = "\
+[Interface]
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+Address = 10.8.0.2/24, fd00::2/64
+DNS = 10.8.0.1, mecha.local
+MTU = 1420
+
+# office gateway
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+Endpoint = vpn.mecha.local:51820
+AllowedIPs = 0.0.0.0/0, ::/0
+PersistentKeepalive = 25
+";
+
+    #[test]
+    fn profile_is_parsed_and_written_back() {
+        let config = WireguardConfig::parse("office", PROFILE).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.addresses, vec!["10.8.0.2/24", "fd00::2/64"]);
+        assert_eq!(config.dns, vec!["10.8.0.1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(config.dns_search, vec!["mecha.local"]);
+        assert_eq!(config.mtu, Some(1420));
+        assert_eq!(config.peers.len(), 1);
+        assert_eq!(config.peers[0].persistent_keepalive, Some(25));
+
+        let written = config.to_wg_quick();
+        assert_eq!(WireguardConfig::parse("office", &written).unwrap(), config);
+    }
+
+    #[test]
+    fn hook_scripts_and_unknown_keys_are_refused() {
+        let with_hook = PROFILE.replace("MTU = 1420", "PostUp = iptables -F");
+        assert!(WireguardConfig::parse("office", &with_hook).is_err());
+        let unknown_key = PROFILE.replace("MTU = 1420", "FwMask = 1");
+        assert!(WireguardConfig::parse("office", &unknown_key).is_err());
+        let named_table = PROFILE.replace("MTU = 1420", "Table = vpn");
+        assert!(WireguardConfig::parse("office", &named_table).is_err());
+        assert!(WireguardConfig::parse("office", "PrivateKey = x\n").is_err());
+        assert!(WireguardConfig::parse("office", "[Tunnel]\n").is_err());
+    }
+
+    #[test]
+    fn routing_keys_of_wg_quick_are_kept() {
+        let routing = PROFILE.replace(
+            "MTU = 1420",
+            "Table = 1234\nFwMark = 0xca6c\nSaveConfig = true",
+        );
+        let config = WireguardConfig::parse("office", &routing).unwrap();
+        assert_eq!(config.table, WireguardTable::Id(1234));
+        assert_eq!(config.fwmark, Some(0xca6c));
+        let written = config.to_wg_quick();
+        assert!(!written.contains("SaveConfig"));
+        assert_eq!(WireguardConfig::parse("office", &written).unwrap(), config);
+
+        let off = PROFILE.replace("MTU = 1420", "Table = off\nFwMark = off");
+        let config = WireguardConfig::parse("office", &off).unwrap();
+        assert_eq!(config.table, WireguardTable::Off);
+        assert_eq!(config.fwmark, None);
+        assert_eq!(parse_fwmark("51820"), Ok(Some(51820)));
+        assert!(parse_fwmark("0xzz").is_err());
+    }
+
+    #[test]
+    fn invalid_profiles_are_reported() {
+        let config = WireguardConfig::parse("office", PROFILE).unwrap();
+
+        let long_name = WireguardConfig {
+            name: "a-name-longer-than-ifnamsiz".to_string(),
+            ..config.clone()
+        };
+        assert!(long_name.validate().is_err());
+
+        let mut bad_endpoint = config.clone();
+        bad_endpoint.peers[0].endpoint = Some("vpn.mecha.local".to_string());
+        assert!(bad_endpoint.validate().is_err());
+
+        let mut bad_allowed_ip = config.clone();
+        bad_allowed_ip.peers[0].allowed_ips = vec!["10.0.0.0/33".to_string()];
+        assert!(bad_allowed_ip.validate().is_err());
+
+        let no_peers = WireguardConfig {
+            peers: vec![],
+            ..config
+        };
+        assert!(no_peers.validate().is_err());
+    }
+
+    #[test]
+    fn keys_must_be_32_bytes_of_base64() {
+        assert!(is_valid_key(PRIVATE_KEY));
+        assert!(is_valid_key(PUBLIC_KEY));
+        assert!(!is_valid_key(""));
+        assert!(!is_valid_key(&PRIVATE_KEY[..43]));
+        assert!(!is_valid_key(&PRIVATE_KEY.replace('+', "-")));
+        // the last character carries two bits only
+        assert!(!is_valid_key(&PRIVATE_KEY.replace("mk=", "ml=")));
+    }
+
+    #[test]
+    fn non_ascii_keys_are_rejected_without_panicking() {
+        // 44 bytes with a two byte character across the slicing offset
+        let key = format!("{}é=", "A".repeat(41));
+        assert_eq!(key.len(), KEY_LEN);
+        assert!(!is_valid_key(&key));
+        assert!(!is_valid_key(&format!("{}☕", "A".repeat(41))));
+    }
+
+    #[test]
+    fn endpoints_and_networks_are_checked() {
+        assert!(is_valid_endpoint("203.0.113.5:51820"));
+        assert!(is_valid_endpoint("[2001:db8::1]:51820"));
+        assert!(!is_valid_endpoint("2001:db8::1:51820"));
+        assert!(!is_valid_endpoint("vpn.local:0"));
+
+        assert_eq!(
+            parse_cidr("10.0.0.1"),
+            Some(("10.0.0.1".parse().unwrap(), 32))
+        );
+        assert_eq!(parse_cidr("::/0"), Some(("::".parse().unwrap(), 0)));
+        assert_eq!(parse_cidr("fd00::/129"), None);
+    }
+
+    #[test]
+    fn dump_sums_the_peers() {
+        let dump = "\
+private\tpublic\t51820\toff
+peer1\t(none)\t203.0.113.5:51820\t0.0.0.0/0\t1700000000\t1000\t2000\t25
+peer2\t(none)\t(none)\t10.9.0.0/24\t0\t10\t20\toff
+";
+        let status = parse_dump(dump);
+        assert!(status.active);
+        assert_eq!(status.rx_bytes, 1010);
+        assert_eq!(status.tx_bytes, 2020);
+        assert_eq!(
+            status.latest_handshake,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn missing_interface_is_a_tunnel_that_is_down() {
+        let status = interface_status(Path::new("/nonexistent"), "wg0").unwrap();
+        assert_eq!(status, WireguardStatus::default());
+        assert_eq!(status.to_string(), "Off");
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    fs, io,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// wpa_supplicant answers most commands at once, scans results can take longer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+const REPLY_BUFFER_SIZE: usize = 64 * 1024;
+
+static CLIENT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// # wpa_supplicant Control Socket
+///
+/// Client end of the control interface, a datagram socket is bound
+/// in the client directory for wpa_supplicant to send replies to
+pub struct WpaCtrl {
+    socket: UnixDatagram,
+    local_path: PathBuf,
+}
+
+impl WpaCtrl {
+    pub fn open(ctrl_interface: &Path, client_dir: &Path) -> io::Result<Self> {
+        let local_path = client_dir.join(format!(
+            "settings_wpa_ctrl_{}_{}",
+            process::id(),
+            CLIENT_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_file(&local_path);
+
+        let socket = UnixDatagram::bind(&local_path)?;
+        let ctrl = Self { socket, local_path };
+        ctrl.socket.connect(ctrl_interface)?;
+        ctrl.socket.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        Ok(ctrl)
+    }
+
+    /// Sends a command and waits for its reply
+    pub fn request(&self, command: &str) -> io::Result<String> {
+        self.socket.send(command.as_bytes())?;
+
+        let mut buffer = vec![0; REPLY_BUFFER_SIZE];
+        loop {
+            let size = self.socket.recv(&mut buffer)?;
+            let reply = String::from_utf8_lossy(&buffer[..size]);
+            // unsolicited events start with a priority like `<3>`
+            if reply.starts_with('<') {
+                continue;
+            }
+            return Ok(reply.to_string());
+        }
+    }
+}
+
+impl Drop for WpaCtrl {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.local_path);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use anyhow::{bail, Result};
+use tracing::info;
+
+use super::{ctrl::WpaCtrl, parse_status};
+use crate::{
+    backends::network::{HotspotBand, HotspotConfig},
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::HostapdSettings,
+};
+
+/// Upper bound of stations walked with `STA-NEXT`
+const MAX_STATIONS: usize = 256;
+
+/// # hostapd Control Interface
+///
+/// hostapd speaks the same control protocol as wpa_supplicant, the
+/// access point is configured at runtime with `SET` and restarted
+/// with `DISABLE` and `ENABLE`. hostapd does not hand the passphrase
+/// back, the last config started from the app is kept instead
+pub struct Hostapd {
+    ctrl_interface: PathBuf,
+    client_dir: PathBuf,
+    ctrl: Mutex<Option<WpaCtrl>>,
+    last_config: Mutex<Option<HotspotConfig>>,
+}
+
+impl Hostapd {
+    pub fn new(settings: &HostapdSettings, client_dir: PathBuf) -> Self {
+        Self {
+            ctrl_interface: PathBuf::from(&settings.ctrl_interface),
+            client_dir,
+            ctrl: Mutex::new(None),
+            last_config: Mutex::new(None),
+        }
+    }
+
+    fn request(&self, command: &str) -> Result<String> {
+        let mut ctrl = self.ctrl.lock().unwrap();
+        if ctrl.is_none() {
+            info!(
+                task = "network_backend",
+                "connecting to hostapd at {:?}", self.ctrl_interface
+            );
+            match WpaCtrl::open(&self.ctrl_interface, &self.client_dir) {
+                Ok(new_ctrl) => *ctrl = Some(new_ctrl),
+                Err(e) => {
+                    bail!(LockScreenError::new(
+                        LockScreenErrorCodes::NetworkBackendConnectError,
+                        format!(
+                            "cannot connect to hostapd at {:?} - {}",
+                            self.ctrl_interface, e
+                        ),
+                    ));
+                }
+            }
+        }
+
+        match ctrl.as_ref().unwrap().request(command) {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                *ctrl = None;
+                let command_name = command.split_whitespace().next().unwrap_or_default();
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("hostapd call failed - {} - {}", command_name, e),
+                ));
+            }
+        }
+    }
+
+    fn request_ok(&self, command: &str) -> Result<()> {
+        let reply = self.request(command)?;
+        if reply.trim() != "OK" {
+            // `SET` arguments hold the passphrase, only the key is logged
+            let command_name: Vec<&str> = command.split_whitespace().take(2).collect();
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!(
+                    "hostapd call failed - {} - {}",
+                    command_name.join(" "),
+                    reply.trim()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<HashMap<String, String>> {
+        Ok(parse_status(&self.request("STATUS")?))
+    }
+
+    pub fn is_enabled(&self) -> Result<bool> {
+        Ok(self.status()?.get("state").map(|state| state.as_str()) == Some("ENABLED"))
+    }
+
+    pub fn config(&self) -> Result<HotspotConfig> {
+        if let Some(config) = self.last_config.lock().unwrap().clone() {
+            return Ok(config);
+        }
+
+        let status = self.status()?;
+        let mut config = HotspotConfig::default();
+        if let Some(ssid) = status.get("ssid[0]") {
+            config.ssid = ssid.clone();
+        }
+        if let Some(frequency) = status.get("freq").and_then(|freq| freq.parse::<u32>().ok()) {
+            config.band = HotspotBand::from_frequency(frequency);
+        }
+        config.channel = status
+            .get("channel")
+            .and_then(|channel| channel.parse::<u32>().ok())
+            .filter(|channel| *channel != 0);
+        Ok(config)
+    }
+
+    /// MAC addresses of the associated stations
+    pub fn stations(&self) -> Result<Vec<String>> {
+        let mut stations = vec![];
+        let mut reply = self.request("STA-FIRST")?;
+        while stations.len() < MAX_STATIONS {
+            // the first line is the address, station details follow
+            let address = match reply.lines().next() {
+                Some(address) if !address.is_empty() && address != "FAIL" => address.to_string(),
+                _ => break,
+            };
+            reply = self.request(&format!("STA-NEXT {}", address))?;
+            stations.push(address);
+        }
+        Ok(stations)
+    }
+
+    pub fn start(&self, config: &HotspotConfig) -> Result<()> {
+        let hw_mode = match config.band {
+            HotspotBand::Band2_4GHz => "g",
+            HotspotBand::Band5GHz => "a",
+        };
+        // channel 0 asks hostapd to pick one through ACS
+        let channel = config.channel.unwrap_or(0).to_string();
+
+        for (key, value) in [
+            ("ssid", config.ssid.as_str()),
+            ("wpa", "2"),
+            ("wpa_key_mgmt", "WPA-PSK"),
+            ("rsn_pairwise", "CCMP"),
+            ("wpa_passphrase", config.password.as_str()),
+            ("hw_mode", hw_mode),
+            ("channel", channel.as_str()),
+        ] {
+            self.request_ok(&format!("SET {} {}", key, value))?;
+        }
+
+        // the new settings are only used once the interface is set up again
+        let _ = self.request_ok("DISABLE");
+        self.request_ok("ENABLE")?;
+        *self.last_config.lock().unwrap() = Some(config.clone());
+        Ok(())
+    }
+
+    pub fn stop(&self) -> Result<()> {
+        self.request_ok("DISABLE")
+    }
+}
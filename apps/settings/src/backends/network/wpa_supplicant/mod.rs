@@ -0,0 +1,1007 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, info, warn};
+
+use super::{
+    collapse_by_ssid, is_permission_error, missing_privilege, procfs, read_proxy_env, sysfs,
+    wireguard, write_proxy_env, ActiveWifi, ConnectionState, EapMethod, EthernetLink,
+    HotspotClient, HotspotConfig, HotspotStatus, IpConnection, Ipv4Config, Ipv6Config, KnownWifi,
+    MacAddressMode, NetworkBackend, Phase2Auth, ProxyConfig, ProxyMode, WifiConnectFailure,
+    WifiConnectRequest, WifiConnectState, WifiDetails, WifiNetwork, WifiProfile, WifiSecurity,
+    WireguardConfig, WireguardProfile, WireguardStatus,
+};
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::NetworkBackendSettings,
+};
+
+mod ctrl;
+mod hostapd;
+mod wg_quick;
+
+#[cfg(test)]
+mod tests;
+
+use ctrl::WpaCtrl;
+use hostapd::Hostapd;
+use wg_quick::WgQuick;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(45);
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Time given to the DHCP client once the link is up
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Network configured in wpa_supplicant, from `LIST_NETWORKS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfiguredNetwork {
+    id: u32,
+    ssid: String,
+    flags: String,
+}
+
+impl ConfiguredNetwork {
+    fn is_disabled(&self) -> bool {
+        self.flags.contains("[DISABLED]")
+    }
+
+    fn is_temp_disabled(&self) -> bool {
+        self.flags.contains("[TEMP-DISABLED]")
+    }
+}
+
+/// # wpa_supplicant Backend
+///
+/// Implements the network backend over the wpa_supplicant control
+/// interface for images without NetworkManager, addressing is left
+/// to systemd-networkd and read back from the kernel
+pub struct WpaSupplicantBackend {
+    ctrl_interface: PathBuf,
+    client_dir: PathBuf,
+    interface: String,
+    sysfs_root: PathBuf,
+    proxy_env_file: PathBuf,
+    ctrl: Mutex<Option<WpaCtrl>>,
+    disconnected: AtomicBool,
+    hostapd: Hostapd,
+    wg_quick: WgQuick,
+}
+
+impl WpaSupplicantBackend {
+    pub fn new(backend_settings: &NetworkBackendSettings) -> Self {
+        let settings = &backend_settings.wpa_supplicant;
+        let ctrl_interface = PathBuf::from(&settings.ctrl_interface);
+        // control sockets are named after the interface they manage
+        let interface = ctrl_interface
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            ctrl_interface,
+            client_dir: PathBuf::from(&settings.client_dir),
+            interface,
+            sysfs_root: PathBuf::from(&backend_settings.sysfs_root),
+            proxy_env_file: PathBuf::from(&backend_settings.proxy_env_file),
+            ctrl: Mutex::new(None),
+            disconnected: AtomicBool::new(false),
+            hostapd: Hostapd::new(
+                &backend_settings.hostapd,
+                PathBuf::from(&settings.client_dir),
+            ),
+            wg_quick: WgQuick::new(
+                PathBuf::from(&backend_settings.wireguard_dir),
+                PathBuf::from(&backend_settings.sysfs_root),
+            ),
+        }
+    }
+
+    /// Sends a command, the socket is reopened on the next call after an error
+    fn request(&self, command: &str) -> Result<String> {
+        // only the command name is logged, arguments can hold secrets
+        let command_name = command.split_whitespace().next().unwrap_or_default();
+        let mut ctrl = self.ctrl.lock().unwrap();
+        if ctrl.is_none() {
+            info!(
+                task = "network_backend",
+                "connecting to wpa_supplicant at {:?}", self.ctrl_interface
+            );
+            match WpaCtrl::open(&self.ctrl_interface, &self.client_dir) {
+                Ok(new_ctrl) => *ctrl = Some(new_ctrl),
+                Err(e) => {
+                    bail!(LockScreenError::new(
+                        LockScreenErrorCodes::NetworkBackendConnectError,
+                        format!(
+                            "cannot connect to wpa_supplicant at {:?} - {}",
+                            self.ctrl_interface, e
+                        ),
+                    ));
+                }
+            }
+        }
+
+        match ctrl.as_ref().unwrap().request(command) {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                *ctrl = None;
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("wpa_supplicant call failed - {} - {}", command_name, e),
+                ));
+            }
+        }
+    }
+
+    /// Sends a command that answers `OK` on success
+    fn request_ok(&self, command: &str) -> Result<()> {
+        let reply = self.request(command)?;
+        if reply.trim() != "OK" {
+            let command_name = command.split_whitespace().next().unwrap_or_default();
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!(
+                    "wpa_supplicant call failed - {} - {}",
+                    command_name,
+                    reply.trim()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<HashMap<String, String>> {
+        Ok(parse_status(&self.request("STATUS")?))
+    }
+
+    fn configured_networks(&self) -> Result<Vec<ConfiguredNetwork>> {
+        Ok(parse_networks(&self.request("LIST_NETWORKS")?))
+    }
+
+    fn configured_networks_for(&self, ssid: &str) -> Result<Vec<ConfiguredNetwork>> {
+        let networks = self
+            .configured_networks()?
+            .into_iter()
+            .filter(|network| network.ssid == ssid)
+            .collect();
+        Ok(networks)
+    }
+
+    /// `mac_addr` of a network block, unset falls back to the device address
+    fn configured_mac_address_mode(&self, id: u32) -> MacAddressMode {
+        match self.request(&format!("GET_NETWORK {} mac_addr", id)) {
+            Ok(mac_addr) if mac_addr.trim() == "1" => MacAddressMode::Random,
+            _ => MacAddressMode::Device,
+        }
+    }
+
+    fn configured_security(&self, id: u32) -> WifiSecurity {
+        match self.request(&format!("GET_NETWORK {} key_mgmt", id)) {
+            Ok(key_mgmt) => security_from_key_mgmt(key_mgmt.trim()),
+            Err(_) => WifiSecurity::Open,
+        }
+    }
+
+    /// Adds a network block for a profile, the new network is left disabled
+    fn add_network(&self, profile: &WifiProfile) -> Result<u32> {
+        let reply = self.request("ADD_NETWORK")?;
+        let id = match reply.trim().parse::<u32>() {
+            Ok(id) => id,
+            Err(_) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!(
+                        "wpa_supplicant call failed - ADD_NETWORK - {}",
+                        reply.trim()
+                    ),
+                ));
+            }
+        };
+
+        for (key, value) in network_parameters(profile) {
+            if let Err(e) = self.request_ok(&format!("SET_NETWORK {} {} {}", id, key, value)) {
+                let _ = self.request_ok(&format!("REMOVE_NETWORK {}", id));
+                return Err(e);
+            }
+        }
+        Ok(id)
+    }
+
+    /// Sets the password of a configured PSK or SAE network, only
+    /// kept once `SAVE_CONFIG` is sent
+    fn set_network_key(&self, id: u32, security: WifiSecurity, password: &str) -> Result<()> {
+        let key = match security {
+            WifiSecurity::Wpa3Sae => "sae_password",
+            _ => "psk",
+        };
+        self.request_ok(&format!("SET_NETWORK {} {} {}", id, key, quoted(password)))
+    }
+
+    /// Persists the configuration, fails when `update_config` is not set
+    fn save_config(&self) {
+        if let Err(e) = self.request_ok("SAVE_CONFIG") {
+            warn!(
+                task = "network_backend",
+                "wpa_supplicant config not saved {}", e
+            );
+        }
+    }
+
+    /// Polls the status until the network is up, returns the failure otherwise
+    fn wait_for_connection(
+        &self,
+        id: u32,
+        on_state: &dyn Fn(WifiConnectState),
+    ) -> Result<Option<WifiConnectFailure>> {
+        let started_at = Instant::now();
+        let mut completed_at: Option<Instant> = None;
+        let mut last_state: Option<WifiConnectState> = None;
+        let mut reached_handshake = false;
+        loop {
+            if started_at.elapsed() > CONNECT_TIMEOUT {
+                return Ok(Some(WifiConnectFailure::Timeout));
+            }
+
+            let status = self.status()?;
+            let wpa_state = status
+                .get("wpa_state")
+                .map(|s| s.as_str())
+                .unwrap_or_default();
+            let is_selected = status.get("id").and_then(|id| id.parse::<u32>().ok()) == Some(id);
+
+            let state = match wpa_state {
+                "COMPLETED" if is_selected => match status.contains_key("ip_address") {
+                    true => Some(WifiConnectState::Connected),
+                    false => Some(WifiConnectState::ObtainingIp),
+                },
+                "4WAY_HANDSHAKE" | "GROUP_HANDSHAKE" => {
+                    reached_handshake = true;
+                    Some(WifiConnectState::Authenticating)
+                }
+                "AUTHENTICATING" | "ASSOCIATING" | "ASSOCIATED" | "SCANNING" => {
+                    Some(WifiConnectState::Associating)
+                }
+                _ => None,
+            };
+
+            if let Some(state) = state {
+                if last_state.as_ref() != Some(&state) {
+                    on_state(state.clone());
+                    last_state = Some(state.clone());
+                }
+                match state {
+                    WifiConnectState::Connected => return Ok(None),
+                    WifiConnectState::ObtainingIp => {
+                        let completed_at = *completed_at.get_or_insert_with(Instant::now);
+                        if completed_at.elapsed() > DHCP_TIMEOUT {
+                            return Ok(Some(WifiConnectFailure::DhcpFailed));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            // repeated handshake failures disable the network for a while
+            let temp_disabled = self
+                .configured_networks()?
+                .iter()
+                .any(|network| network.id == id && network.is_temp_disabled());
+            if temp_disabled {
+                return Ok(Some(match reached_handshake {
+                    true => WifiConnectFailure::WrongPassword,
+                    false => {
+                        WifiConnectFailure::Other("network rejected the connection".to_string())
+                    }
+                }));
+            }
+
+            thread::sleep(CONNECT_POLL_INTERVAL);
+        }
+    }
+}
+
+impl NetworkBackend for WpaSupplicantBackend {
+    fn wifi_enabled(&self) -> Result<bool> {
+        let status = self.status()?;
+        let interface_enabled =
+            status.get("wpa_state").map(|s| s.as_str()) != Some("INTERFACE_DISABLED");
+        Ok(interface_enabled && !self.disconnected.load(Ordering::Relaxed))
+    }
+
+    fn set_wifi_enabled(&self, enabled: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting wifi enabled to {}", enabled
+        );
+        match enabled {
+            true => self.request_ok("RECONNECT")?,
+            false => self.request_ok("DISCONNECT")?,
+        }
+        self.disconnected.store(!enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn active_wifi(&self) -> Result<Option<ActiveWifi>> {
+        let status = self.status()?;
+        let ssid = match status.get("ssid") {
+            Some(ssid) => decode_ssid(ssid),
+            None => return Ok(None),
+        };
+
+        let state = match status.get("wpa_state").map(|s| s.as_str()) {
+            Some("COMPLETED") if status.contains_key("ip_address") => ConnectionState::Activated,
+            Some("COMPLETED")
+            | Some("ASSOCIATING")
+            | Some("ASSOCIATED")
+            | Some("AUTHENTICATING")
+            | Some("4WAY_HANDSHAKE")
+            | Some("GROUP_HANDSHAKE") => ConnectionState::Activating,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(ActiveWifi { ssid, state }))
+    }
+
+    fn request_wifi_scan(&self) -> Result<()> {
+        info!(task = "network_backend", "requesting wifi scan");
+        let reply = self.request("SCAN")?;
+        match reply.trim() {
+            "OK" => Ok(()),
+            // a scan is already running, its results will do
+            "FAIL-BUSY" => Ok(()),
+            reply => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("wpa_supplicant call failed - SCAN - {}", reply),
+                ));
+            }
+        }
+    }
+
+    fn wifi_networks(&self) -> Result<Vec<WifiNetwork>> {
+        let status = self.status()?;
+        let connected_bssid = match status.get("wpa_state").map(|s| s.as_str()) {
+            Some("COMPLETED") => status.get("bssid").map(|bssid| bssid.to_uppercase()),
+            _ => None,
+        };
+
+        let mut networks = parse_scan_results(&self.request("SCAN_RESULTS")?);
+        for network in networks.iter_mut() {
+            network.is_connected = connected_bssid.as_deref() == Some(network.bssid.as_str());
+        }
+        Ok(networks)
+    }
+
+    fn known_wifi_networks(&self) -> Result<Vec<KnownWifi>> {
+        let known_networks = self
+            .configured_networks()?
+            .into_iter()
+            .map(|network| KnownWifi {
+                id: network.id.to_string(),
+                security: self.configured_security(network.id),
+                ssid: network.ssid,
+            })
+            .collect();
+        Ok(known_networks)
+    }
+
+    fn connect_wifi(
+        &self,
+        request: &WifiConnectRequest,
+        on_state: &dyn Fn(WifiConnectState),
+    ) -> Result<()> {
+        info!(task = "network_backend", "connecting to {}", request.ssid);
+        let configured_networks = self.configured_networks()?;
+        let known_id = configured_networks
+            .iter()
+            .find(|network| network.ssid == request.ssid)
+            .map(|network| network.id);
+        let (id, is_new, is_key_changed) = match known_id {
+            Some(id) => {
+                // a password entered again, after a failure or a change
+                // on the router, replaces the saved one
+                let security = self.configured_security(id);
+                let is_key_changed = match &request.password {
+                    Some(password)
+                        if !password.is_empty()
+                            && matches!(security, WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae) =>
+                    {
+                        let profile = WifiProfile {
+                            security,
+                            ..WifiProfile::from(request)
+                        };
+                        if let Err(reason) = profile.validate() {
+                            on_state(WifiConnectState::Failed(WifiConnectFailure::Other(reason)));
+                            return Ok(());
+                        }
+                        self.set_network_key(id, security, password)?;
+                        true
+                    }
+                    _ => false,
+                };
+                (id, false, is_key_changed)
+            }
+            None => {
+                if request.security == WifiSecurity::Enterprise {
+                    on_state(WifiConnectState::Failed(WifiConnectFailure::Other(
+                        "enterprise networks must be added from Add Network".to_string(),
+                    )));
+                    return Ok(());
+                }
+                let profile = WifiProfile::from(request);
+                if let Err(reason) = profile.validate() {
+                    on_state(WifiConnectState::Failed(WifiConnectFailure::Other(reason)));
+                    return Ok(());
+                }
+                (self.add_network(&profile)?, true, false)
+            }
+        };
+
+        // SELECT_NETWORK disables every other network, the ones that
+        // auto-connected before are enabled again once the attempt is
+        // over so the saved config keeps them
+        let enabled_ids: Vec<u32> = configured_networks
+            .iter()
+            .filter(|network| network.id != id && !network.is_disabled())
+            .map(|network| network.id)
+            .collect();
+
+        self.disconnected.store(false, Ordering::Relaxed);
+        self.request_ok(&format!("SELECT_NETWORK {}", id))?;
+        let result = self.wait_for_connection(id, on_state);
+        for enabled_id in enabled_ids {
+            if let Err(e) = self.request_ok(&format!("ENABLE_NETWORK {}", enabled_id)) {
+                warn!(
+                    task = "network_backend",
+                    "network {} not enabled again {}", enabled_id, e
+                );
+            }
+        }
+
+        match result? {
+            None => {
+                if is_new || is_key_changed {
+                    self.save_config();
+                }
+            }
+            Some(failure) => {
+                debug!(task = "network_backend", "connection failed {:?}", failure);
+                // do not keep a profile with a wrong password around
+                if is_new {
+                    let _ = self.request_ok(&format!("REMOVE_NETWORK {}", id));
+                }
+                on_state(WifiConnectState::Failed(failure));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ipv4_config(&self, _connection: &IpConnection) -> Result<Ipv4Config> {
+        bail!(networkd_managed("ipv4 configuration"));
+    }
+
+    fn set_ipv4_config(&self, _connection: &IpConnection, _config: &Ipv4Config) -> Result<()> {
+        bail!(networkd_managed("ipv4 configuration"));
+    }
+
+    fn ipv6_config(&self, _connection: &IpConnection) -> Result<Ipv6Config> {
+        bail!(networkd_managed("ipv6 configuration"));
+    }
+
+    fn set_ipv6_config(&self, _connection: &IpConnection, _config: &Ipv6Config) -> Result<()> {
+        bail!(networkd_managed("ipv6 configuration"));
+    }
+
+    fn add_wifi_profile(&self, profile: &WifiProfile) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "adding wifi profile {}", profile.ssid
+        );
+        if let Err(reason) = profile.validate() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("invalid wifi profile - {}", reason),
+            ));
+        }
+
+        let id = self.add_network(profile)?;
+        self.request_ok(&format!("ENABLE_NETWORK {}", id))?;
+        self.save_config();
+        Ok(())
+    }
+
+    fn wifi_details(&self, ssid: &str) -> Result<WifiDetails> {
+        let configured_networks = self.configured_networks_for(ssid)?;
+        let network = collapse_by_ssid(self.wifi_networks()?)
+            .into_iter()
+            .find(|network| network.ssid == ssid);
+
+        // out of range networks are described by their network block
+        let network = match network {
+            Some(network) => network,
+            None => WifiNetwork {
+                ssid: ssid.to_string(),
+                security: configured_networks
+                    .first()
+                    .map(|network| self.configured_security(network.id))
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+        };
+
+        let ip = match network.is_connected {
+            true => {
+                let status = self.status()?;
+                Some(procfs::interface_ip_details(
+                    &self.interface,
+                    status.get("ip_address").map(|s| s.as_str()),
+                ))
+            }
+            false => None,
+        };
+
+        let mac_address_mode = configured_networks
+            .first()
+            .map(|network| self.configured_mac_address_mode(network.id));
+
+        Ok(WifiDetails {
+            network,
+            is_known: !configured_networks.is_empty(),
+            autoconnect: configured_networks
+                .iter()
+                .any(|network| !network.is_disabled()),
+            mac_address_mode,
+            metered: None,
+            ip,
+        })
+    }
+
+    fn forget_wifi(&self, ssid: &str) -> Result<()> {
+        info!(task = "network_backend", "forgetting wifi {}", ssid);
+        for network in self.configured_networks_for(ssid)? {
+            self.request_ok(&format!("REMOVE_NETWORK {}", network.id))?;
+        }
+        self.save_config();
+        Ok(())
+    }
+
+    fn set_wifi_autoconnect(&self, ssid: &str, autoconnect: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting autoconnect of {} to {}", ssid, autoconnect
+        );
+        let configured_networks = self.configured_networks_for(ssid)?;
+        if configured_networks.is_empty() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no saved profile for {}", ssid),
+            ));
+        }
+
+        let command = match autoconnect {
+            true => "ENABLE_NETWORK",
+            false => "DISABLE_NETWORK",
+        };
+        for network in configured_networks {
+            self.request_ok(&format!("{} {}", command, network.id))?;
+        }
+        self.save_config();
+        Ok(())
+    }
+
+    /// wpa_supplicant picks a new random address for every association,
+    /// it has no address that stays the same per network
+    fn set_wifi_mac_address_mode(&self, ssid: &str, mode: MacAddressMode) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting mac address of {} to {}", ssid, mode
+        );
+        let mac_addr = match mode {
+            MacAddressMode::Device => "0",
+            MacAddressMode::Random => "1",
+            MacAddressMode::Stable => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    "a stable address per network needs NetworkManager".to_string(),
+                ));
+            }
+        };
+        let configured_networks = self.configured_networks_for(ssid)?;
+        if configured_networks.is_empty() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no saved profile for {}", ssid),
+            ));
+        }
+
+        for network in configured_networks {
+            self.request_ok(&format!("SET_NETWORK {} mac_addr {}", network.id, mac_addr))?;
+        }
+        self.save_config();
+        Ok(())
+    }
+
+    /// systemd-networkd has no notion of metered links
+    fn set_wifi_metered(&self, _ssid: &str, _metered: bool) -> Result<()> {
+        bail!(LockScreenError::new(
+            LockScreenErrorCodes::NetworkBackendCallError,
+            "metered networks need NetworkManager".to_string(),
+        ));
+    }
+
+    fn ethernet_links(&self) -> Result<Vec<EthernetLink>> {
+        Ok(sysfs::ethernet_links(&self.sysfs_root))
+    }
+
+    fn hotspot_config(&self) -> Result<HotspotConfig> {
+        self.hostapd.config()
+    }
+
+    fn hotspot_status(&self) -> Result<HotspotStatus> {
+        if !self.hostapd.is_enabled()? {
+            return Ok(HotspotStatus::default());
+        }
+
+        // addresses come from the DHCP server, the ARP table is the common ground
+        let neighbours = procfs::arp_clients(&self.interface);
+        let clients = self
+            .hostapd
+            .stations()?
+            .into_iter()
+            .map(|mac_address| {
+                let ip_address = neighbours
+                    .iter()
+                    .find(|neighbour| neighbour.mac_address.eq_ignore_ascii_case(&mac_address))
+                    .and_then(|neighbour| neighbour.ip_address.clone());
+                HotspotClient {
+                    mac_address,
+                    ip_address,
+                }
+            })
+            .collect();
+        Ok(HotspotStatus {
+            active: true,
+            clients,
+        })
+    }
+
+    /// wpa_supplicant lets go of the radio before hostapd takes it
+    fn start_hotspot(&self, config: &HotspotConfig) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "starting hotspot {} on {}", config.ssid, config.band
+        );
+        if let Err(reason) = config.validate() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("invalid hotspot settings - {}", reason),
+            ));
+        }
+
+        self.request_ok("DISCONNECT")?;
+        if let Err(e) = self.hostapd.start(config) {
+            if !self.disconnected.load(Ordering::Relaxed) {
+                let _ = self.request_ok("RECONNECT");
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn stop_hotspot(&self) -> Result<()> {
+        info!(task = "network_backend", "stopping hotspot");
+        self.hostapd.stop()?;
+        // only rejoin when Wi-Fi was not switched off by the user
+        if !self.disconnected.load(Ordering::Relaxed) {
+            self.request_ok("RECONNECT")?;
+        }
+        Ok(())
+    }
+
+    fn proxy_config(&self) -> Result<ProxyConfig> {
+        read_proxy_env(&self.proxy_env_file)
+    }
+
+    /// systemd-networkd has no proxy settings, the environment file is all there is.
+    /// It only holds proxy servers, so there is nothing to apply a PAC script
+    fn set_proxy_config(&self, config: &ProxyConfig) -> Result<()> {
+        if config.mode == ProxyMode::Automatic {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                "PAC scripts need NetworkManager, enter the proxy servers instead".to_string(),
+            ));
+        }
+        info!(task = "network_backend", "setting {} proxy", config.mode);
+        write_proxy_env(&self.proxy_env_file, config)
+    }
+
+    fn wireguard_profiles(&self) -> Result<Vec<WireguardProfile>> {
+        self.wg_quick.profiles()
+    }
+
+    fn import_wireguard_profile(&self, config: &WireguardConfig) -> Result<()> {
+        self.wg_quick.import(config)
+    }
+
+    fn set_wireguard_active(&self, name: &str, active: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting WireGuard {} active to {}", name, active
+        );
+        self.wg_quick.set_active(name, active)
+    }
+
+    fn wireguard_status(&self, name: &str) -> Result<WireguardStatus> {
+        wireguard::interface_status(&self.sysfs_root, name)
+    }
+
+    fn remove_wireguard_profile(&self, name: &str) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "removing WireGuard profile {}", name
+        );
+        self.wg_quick.remove(name)
+    }
+
+    /// systemd-networkd follows the administrative state of the link,
+    /// so the interface is set up or down with iproute2
+    fn set_ethernet_enabled(&self, interface: &str, enabled: bool) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "setting {} enabled to {}", interface, enabled
+        );
+        if !sysfs::interface_path(&self.sysfs_root, interface).exists() {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("no interface named {}", interface),
+            ));
+        }
+
+        let state = match enabled {
+            true => "up",
+            false => "down",
+        };
+        let output = Command::new("ip")
+            .args(["link", "set", "dev", interface, state])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => {
+                // without NetworkManager nothing can bring links up for
+                // the user, the page tells them instead
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if is_permission_error(&stderr) {
+                    let action = match enabled {
+                        true => "on",
+                        false => "off",
+                    };
+                    bail!(missing_privilege(&format!(
+                        "Turning {} {}",
+                        interface, action
+                    )));
+                }
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("cannot set {} {} - {}", interface, state, stderr.trim()),
+                ));
+            }
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("cannot run ip - {}", e),
+                ));
+            }
+        }
+    }
+}
+
+/// Addressing is owned by systemd-networkd, wpa_supplicant cannot change it
+fn networkd_managed(setting: &str) -> LockScreenError {
+    LockScreenError::new(
+        LockScreenErrorCodes::NetworkBackendCallError,
+        format!("{} is managed by systemd-networkd on this device", setting),
+    )
+}
+
+/// Parses the `key=value` lines of `STATUS`
+fn parse_status(reply: &str) -> HashMap<String, String> {
+    reply
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Parses `LIST_NETWORKS`, a header line then `id / ssid / bssid / flags`
+fn parse_networks(reply: &str) -> Vec<ConfiguredNetwork> {
+    reply
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 2 {
+                return None;
+            }
+            Some(ConfiguredNetwork {
+                id: fields[0].parse().ok()?,
+                ssid: decode_ssid(fields[1]),
+                flags: fields.get(3).unwrap_or(&"").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses `SCAN_RESULTS`, a header line then
+/// `bssid / frequency / signal level / flags / ssid`
+fn parse_scan_results(reply: &str) -> Vec<WifiNetwork> {
+    reply
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 4 {
+                return None;
+            }
+            Some(WifiNetwork {
+                bssid: fields[0].to_uppercase(),
+                frequency: fields[1].parse().ok()?,
+                strength: strength_from_signal(fields[2].parse().ok()?),
+                security: security_from_flags(fields[3]),
+                ssid: decode_ssid(fields.get(4).unwrap_or(&"")),
+                is_connected: false,
+            })
+        })
+        .collect()
+}
+
+/// Maps a signal level in dBm to a 0-100 strength, -100 dBm or below is 0
+/// and -50 dBm or above is 100, drivers reporting a quality pass through
+fn strength_from_signal(signal: i32) -> u8 {
+    match signal {
+        signal if signal > 0 => signal.min(100) as u8,
+        signal => ((signal + 100) * 2).clamp(0, 100) as u8,
+    }
+}
+
+fn security_from_flags(flags: &str) -> WifiSecurity {
+    if flags.contains("EAP") {
+        WifiSecurity::Enterprise
+    } else if flags.contains("SAE") {
+        WifiSecurity::Wpa3Sae
+    } else if flags.contains("PSK") {
+        WifiSecurity::WpaPsk
+    } else if flags.contains("WEP") {
+        WifiSecurity::Wep
+    } else {
+        WifiSecurity::Open
+    }
+}
+
+fn security_from_key_mgmt(key_mgmt: &str) -> WifiSecurity {
+    if key_mgmt.contains("EAP") {
+        WifiSecurity::Enterprise
+    } else if key_mgmt.contains("SAE") {
+        WifiSecurity::Wpa3Sae
+    } else if key_mgmt.contains("PSK") {
+        WifiSecurity::WpaPsk
+    } else {
+        WifiSecurity::Open
+    }
+}
+
+/// Reverses the escaping wpa_supplicant applies to SSIDs in replies,
+/// `\xNN` for non printable bytes and `\\`, `\"` for the escapes
+fn decode_ssid(escaped: &str) -> String {
+    let mut bytes = vec![];
+    let mut chars = escaped.as_bytes().iter().copied().peekable();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match chars.next() {
+            Some(b'x') => {
+                let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&String::from_utf8_lossy(&hex), 16) {
+                    Ok(value) => bytes.push(value),
+                    Err(_) => bytes.extend_from_slice(&hex),
+                }
+            }
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'e') => bytes.push(0x1b),
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Quotes a string parameter for `SET_NETWORK`
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+/// `SET_NETWORK` parameters of a valid profile, the SSID is sent
+/// hex encoded so any byte survives the text protocol
+fn network_parameters(profile: &WifiProfile) -> Vec<(&'static str, String)> {
+    let ssid_hex: String = profile
+        .ssid
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let mut parameters = vec![("ssid", ssid_hex)];
+    if profile.hidden {
+        parameters.push(("scan_ssid", "1".to_string()));
+    }
+
+    let password = profile.password.as_deref().unwrap_or_default();
+    match profile.security {
+        WifiSecurity::Open | WifiSecurity::Wep => {
+            parameters.push(("key_mgmt", "NONE".to_string()));
+        }
+        WifiSecurity::WpaPsk => {
+            parameters.push(("key_mgmt", "WPA-PSK".to_string()));
+            parameters.push(("psk", quoted(password)));
+        }
+        WifiSecurity::Wpa3Sae => {
+            parameters.push(("key_mgmt", "SAE".to_string()));
+            parameters.push(("ieee80211w", "2".to_string()));
+            parameters.push(("sae_password", quoted(password)));
+        }
+        WifiSecurity::Enterprise => {
+            parameters.push(("key_mgmt", "WPA-EAP".to_string()));
+            if let Some(enterprise) = &profile.enterprise {
+                let eap = match enterprise.eap {
+                    EapMethod::Peap => "PEAP",
+                    EapMethod::Ttls => "TTLS",
+                    EapMethod::Tls => "TLS",
+                };
+                parameters.push(("eap", eap.to_string()));
+                parameters.push(("identity", quoted(&enterprise.identity)));
+                if let Some(anonymous_identity) = &enterprise.anonymous_identity {
+                    parameters.push(("anonymous_identity", quoted(anonymous_identity)));
+                }
+                if let Some(ca_cert) = &enterprise.ca_cert {
+                    parameters.push(("ca_cert", quoted(ca_cert)));
+                }
+                match enterprise.eap {
+                    EapMethod::Tls => {
+                        if let Some(client_cert) = &enterprise.client_cert {
+                            parameters.push(("client_cert", quoted(client_cert)));
+                        }
+                        if let Some(private_key) = &enterprise.private_key {
+                            parameters.push(("private_key", quoted(private_key)));
+                        }
+                        if let Some(private_key_password) = &enterprise.private_key_password {
+                            parameters.push(("private_key_passwd", quoted(private_key_password)));
+                        }
+                    }
+                    EapMethod::Peap | EapMethod::Ttls => {
+                        let phase2_auth = match enterprise.phase2_auth {
+                            Phase2Auth::Mschapv2 => "MSCHAPV2",
+                            Phase2Auth::Gtc => "GTC",
+                            Phase2Auth::Pap => "PAP",
+                            Phase2Auth::Chap => "CHAP",
+                        };
+                        parameters.push(("phase2", quoted(&format!("auth={}", phase2_auth))));
+                        let password = enterprise.password.as_deref().unwrap_or_default();
+                        parameters.push(("password", quoted(password)));
+                    }
+                }
+            }
+        }
+    }
+    parameters
+}
@@ -0,0 +1,289 @@
+use std::{cell::RefCell, env, fs, os::unix::net::UnixDatagram, path::Path, process, thread};
+
+use super::*;
+use crate::{
+    backends::network::WifiNetworkList,
+    settings::{NetworkServiceType, WpaSupplicantSettings},
+};
+
+#[allow(dead_code)]
+#[path = "../../../../examples/fake_wpa_supplicant.rs"]
+mod fake_wpa_supplicant;
+
+/// Starts the scripted wpa_supplicant on a socket of its own and
+/// returns a backend talking to it
+fn start_fake(name: &str) -> (WpaSupplicantBackend, PathBuf) {
+    let dir = env::temp_dir().join(format!("wpa_supplicant_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let socket_path = dir.join("wlan0");
+    let socket = UnixDatagram::bind(&socket_path).unwrap();
+    thread::spawn(move || fake_wpa_supplicant::serve(socket));
+
+    let settings = NetworkBackendSettings {
+        service: NetworkServiceType::WpaSupplicant,
+        wpa_supplicant: WpaSupplicantSettings {
+            ctrl_interface: socket_path.to_string_lossy().to_string(),
+            client_dir: dir.to_string_lossy().to_string(),
+        },
+        ..Default::default()
+    };
+    (WpaSupplicantBackend::new(&settings), dir)
+}
+
+/// Connects and returns every state reported on the way
+fn connect(
+    backend: &WpaSupplicantBackend,
+    ssid: &str,
+    security: WifiSecurity,
+    password: Option<&str>,
+) -> Vec<WifiConnectState> {
+    let states = RefCell::new(vec![]);
+    let request = WifiConnectRequest {
+        ssid: ssid.to_string(),
+        security,
+        password: password.map(String::from),
+    };
+    backend
+        .connect_wifi(&request, &|state| states.borrow_mut().push(state))
+        .unwrap();
+    states.into_inner()
+}
+
+fn ssids(networks: &[WifiNetwork]) -> Vec<&str> {
+    networks.iter().map(|n| n.ssid.as_str()).collect()
+}
+
+#[test]
+fn scan_results_are_parsed() {
+    let reply = "bssid / frequency / signal level / flags / ssid\n\
+        a0:b1:c2:d3:e4:01\t5180\t-48\t[WPA2-PSK-CCMP][ESS]\tMecha Office\n\
+        a0:b1:c2:d3:e4:04\t5745\t-66\t[WPA2-SAE-CCMP][ESS]\tLab WPA3\n\
+        a0:b1:c2:d3:e4:05\t2462\t-82\t[WPA2-EAP-CCMP][ESS]\tCorp\\xe2\\x98\\x95\n\
+        a0:b1:c2:d3:e4:06\t2412\t-120\t[ESS]\t\n\
+        broken line\n";
+    let networks = parse_scan_results(reply);
+    assert_eq!(networks.len(), 4);
+    assert_eq!(networks[0].bssid, "A0:B1:C2:D3:E4:01");
+    assert_eq!(networks[0].strength, 100);
+    assert_eq!(networks[0].security, WifiSecurity::WpaPsk);
+    assert_eq!(networks[1].security, WifiSecurity::Wpa3Sae);
+    assert_eq!(networks[2].ssid, "Corp☕");
+    assert_eq!(networks[2].security, WifiSecurity::Enterprise);
+    assert_eq!(networks[3].ssid, "");
+    assert_eq!(networks[3].strength, 0);
+    assert_eq!(networks[3].security, WifiSecurity::Open);
+}
+
+#[test]
+fn escaped_ssids_are_decoded() {
+    assert_eq!(decode_ssid("Plain"), "Plain");
+    assert_eq!(decode_ssid("Caf\\xc3\\xa9"), "Café");
+    assert_eq!(decode_ssid("say \\\"hi\\\""), "say \"hi\"");
+    assert_eq!(decode_ssid("tab\\there"), "tab\there");
+    assert_eq!(decode_ssid("back\\\\slash"), "back\\slash");
+    assert_eq!(decode_ssid("trailing\\"), "trailing\\");
+}
+
+#[test]
+fn network_list_flags_are_read() {
+    let reply = "network id / ssid / bssid / flags\n\
+        0\tMecha Office\tany\t[CURRENT]\n\
+        1\tCafe Guest\tany\t[DISABLED]\n\
+        2\tLab WPA3\tany\t[TEMP-DISABLED]\n";
+    let networks = parse_networks(reply);
+    assert_eq!(networks.len(), 3);
+    assert!(!networks[0].is_disabled());
+    assert!(networks[1].is_disabled());
+    assert!(networks[2].is_temp_disabled());
+}
+
+#[test]
+fn signal_levels_map_to_strength() {
+    assert_eq!(strength_from_signal(-40), 100);
+    assert_eq!(strength_from_signal(-75), 50);
+    assert_eq!(strength_from_signal(-100), 0);
+    // drivers reporting a quality
+    assert_eq!(strength_from_signal(70), 70);
+}
+
+#[test]
+fn profiles_are_sent_hex_encoded_with_quoted_secrets() {
+    let profile = WifiProfile {
+        ssid: "Café".to_string(),
+        hidden: true,
+        security: WifiSecurity::Wpa3Sae,
+        password: Some("lab secret".to_string()),
+        enterprise: None,
+    };
+    assert_eq!(
+        network_parameters(&profile),
+        vec![
+            ("ssid", "436166c3a9".to_string()),
+            ("scan_ssid", "1".to_string()),
+            ("key_mgmt", "SAE".to_string()),
+            ("ieee80211w", "2".to_string()),
+            ("sae_password", "\"lab secret\"".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn scan_lists_each_network_once() {
+    let (backend, dir) = start_fake("scan");
+    backend.request_wifi_scan().unwrap();
+
+    let WifiNetworkList { known, available } = backend.wifi_network_list().unwrap();
+    assert!(known.is_empty());
+    assert_eq!(
+        ssids(&available),
+        vec!["Mecha Office", "Cafe Guest", "Lab WPA3", "Corp☕"]
+    );
+    // the strongest access point of the network is kept
+    assert_eq!(available[0].bssid, "A0:B1:C2:D3:E4:01");
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn open_network_is_joined_and_saved() {
+    let (backend, dir) = start_fake("open");
+    assert_eq!(backend.wifi_status().unwrap().active, None);
+
+    let states = connect(&backend, "Cafe Guest", WifiSecurity::Open, None);
+    assert_eq!(states.first(), Some(&WifiConnectState::Associating));
+    assert_eq!(states.last(), Some(&WifiConnectState::Connected));
+    assert!(!states.contains(&WifiConnectState::Authenticating));
+
+    let status = backend.wifi_status().unwrap();
+    assert!(status.enabled);
+    assert_eq!(
+        status.active,
+        Some(ActiveWifi {
+            ssid: "Cafe Guest".to_string(),
+            state: ConnectionState::Activated,
+        })
+    );
+    let list = backend.wifi_network_list().unwrap();
+    assert_eq!(ssids(&list.known), vec!["Cafe Guest"]);
+    assert!(list.known[0].is_connected);
+
+    backend.set_wifi_enabled(false).unwrap();
+    assert!(!backend.wifi_enabled().unwrap());
+    assert_eq!(backend.wifi_status().unwrap().active, None);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn wrong_password_of_a_new_network_is_not_kept() {
+    let (backend, dir) = start_fake("wrong_password");
+    let states = connect(
+        &backend,
+        "Mecha Office",
+        WifiSecurity::WpaPsk,
+        Some("not the password"),
+    );
+    assert!(states.contains(&WifiConnectState::Authenticating));
+    assert_eq!(
+        states.last(),
+        Some(&WifiConnectState::Failed(WifiConnectFailure::WrongPassword))
+    );
+    assert!(backend.known_wifi_networks().unwrap().is_empty());
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn password_entered_again_replaces_the_saved_one() {
+    let (backend, dir) = start_fake("retry");
+    connect(&backend, "Cafe Guest", WifiSecurity::Open, None);
+    backend
+        .add_wifi_profile(&WifiProfile {
+            ssid: "Mecha Office".to_string(),
+            security: WifiSecurity::WpaPsk,
+            password: Some("old password".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // the saved password fails, the network stays saved
+    let states = connect(&backend, "Mecha Office", WifiSecurity::WpaPsk, None);
+    assert_eq!(
+        states.last(),
+        Some(&WifiConnectState::Failed(WifiConnectFailure::WrongPassword))
+    );
+    let known = backend.known_wifi_networks().unwrap();
+    assert_eq!(known.len(), 2);
+    assert_eq!(known[1].security, WifiSecurity::WpaPsk);
+
+    let states = connect(
+        &backend,
+        "Mecha Office",
+        WifiSecurity::WpaPsk,
+        Some("mecha1234"),
+    );
+    assert_eq!(states.last(), Some(&WifiConnectState::Connected));
+    assert_eq!(
+        backend.active_wifi().unwrap().map(|active| active.ssid),
+        Some("Mecha Office".to_string())
+    );
+
+    // selecting a network does not take autoconnect from the others
+    let details = backend.wifi_details("Cafe Guest").unwrap();
+    assert!(details.is_known);
+    assert!(details.autoconnect);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn short_password_is_refused_before_joining() {
+    let (backend, dir) = start_fake("short_password");
+    let states = connect(&backend, "Lab WPA3", WifiSecurity::Wpa3Sae, Some("short"));
+    assert!(matches!(
+        states.as_slice(),
+        [WifiConnectState::Failed(WifiConnectFailure::Other(_))]
+    ));
+    assert!(backend.known_wifi_networks().unwrap().is_empty());
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn missing_socket_is_a_connect_error() {
+    let settings = NetworkBackendSettings {
+        wpa_supplicant: WpaSupplicantSettings {
+            ctrl_interface: "/nonexistent/wlan0".to_string(),
+            client_dir: env::temp_dir().to_string_lossy().to_string(),
+        },
+        ..Default::default()
+    };
+    let backend = WpaSupplicantBackend::new(&settings);
+    let e = backend.wifi_enabled().unwrap_err();
+    assert!(e.to_string().contains("cannot connect to wpa_supplicant"));
+    assert!(!Path::new("/nonexistent").exists());
+}
+
+#[test]
+fn pac_scripts_are_refused_without_networkmanager() {
+    let dir = env::temp_dir().join(format!("wpa_supplicant_proxy_{}", process::id()));
+    let proxy_env_file = dir.join("environment.d/90-proxy.conf");
+    let settings = NetworkBackendSettings {
+        proxy_env_file: proxy_env_file.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    let backend = WpaSupplicantBackend::new(&settings);
+
+    let automatic = ProxyConfig {
+        mode: ProxyMode::Automatic,
+        pac_url: Some("http://wpad.local/wpad.dat".to_string()),
+        ..Default::default()
+    };
+    assert!(backend.set_proxy_config(&automatic).is_err());
+    assert!(!proxy_env_file.exists());
+
+    let manual = ProxyConfig {
+        mode: ProxyMode::Manual,
+        http: Some("proxy.local:3128".to_string()),
+        ..Default::default()
+    };
+    backend.set_proxy_config(&manual).unwrap();
+    assert_eq!(backend.proxy_config().unwrap(), manual);
+    let _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic_private;
+use tracing::{info, warn};
+
+use crate::{
+    backends::network::{
+        is_permission_error, missing_privilege, no_wireguard_profile, sysfs,
+        validate_wireguard_config, wireguard, WireguardConfig, WireguardProfile,
+    },
+    errors::{LockScreenError, LockScreenErrorCodes},
+};
+
+/// # wg-quick Profiles
+///
+/// WireGuard profiles kept as `<name>.conf` in the wg-quick config
+/// directory, tunnels are brought up and down with `wg-quick` which
+/// also sets the addresses, routes and DNS of the profile
+pub struct WgQuick {
+    config_dir: PathBuf,
+    sysfs_root: PathBuf,
+}
+
+impl WgQuick {
+    pub fn new(config_dir: PathBuf, sysfs_root: PathBuf) -> Self {
+        Self {
+            config_dir,
+            sysfs_root,
+        }
+    }
+
+    fn config_path(&self, name: &str) -> PathBuf {
+        self.config_dir.join(format!("{}.conf", name))
+    }
+
+    /// wg-quick names the interface after the profile
+    fn is_active(&self, name: &str) -> bool {
+        sysfs::interface_path(&self.sysfs_root, name).exists()
+    }
+
+    pub fn profiles(&self) -> Result<Vec<WireguardProfile>> {
+        let entries = match fs::read_dir(&self.config_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                bail!(LockScreenError::new(
+                    LockScreenErrorCodes::NetworkBackendCallError,
+                    format!("cannot list {:?} - {}", self.config_dir, e),
+                ));
+            }
+        };
+
+        let mut profiles: Vec<WireguardProfile> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "conf")
+            })
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+            .filter(|name| wireguard::is_valid_interface_name(name))
+            .map(|name| WireguardProfile {
+                active: self.is_active(&name),
+                name,
+            })
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(profiles)
+    }
+
+    /// The file holds the private key, it is only readable by its owner
+    pub fn import(&self, config: &WireguardConfig) -> Result<()> {
+        info!(
+            task = "network_backend",
+            "importing WireGuard profile {}", config.name
+        );
+        validate_wireguard_config(config)?;
+
+        let path = self.config_path(&config.name);
+        // the profile holds the private key
+        if let Err(e) = write_atomic_private(&path, config.to_wg_quick()) {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot write {:?} - {}", path, e),
+            ));
+        }
+        if self.is_active(&config.name) {
+            warn!(
+                task = "network_backend",
+                "WireGuard {} is up, the new profile is used once it is restarted", config.name
+            );
+        }
+        Ok(())
+    }
+
+    pub fn set_active(&self, name: &str, active: bool) -> Result<()> {
+        let path = self.config_path(name);
+        if !path.exists() {
+            bail!(no_wireguard_profile(name));
+        }
+        if self.is_active(name) == active {
+            return Ok(());
+        }
+        let action = match active {
+            true => "up",
+            false => "down",
+        };
+        run_wg_quick(action, &path)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let path = self.config_path(name);
+        if !path.exists() {
+            bail!(no_wireguard_profile(name));
+        }
+        if self.is_active(name) {
+            run_wg_quick("down", &path)?;
+        }
+        if let Err(e) = fs::remove_file(&path) {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot remove {:?} - {}", path, e),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn run_wg_quick(action: &str, path: &Path) -> Result<()> {
+    let name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let turning = match action {
+        "up" => "on",
+        _ => "off",
+    };
+    // wg-quick falls back to sudo for other users, which would wait
+    // for a password nobody can enter
+    if unsafe { libc::geteuid() } != 0 {
+        bail!(missing_privilege(&format!("Turning {} {}", name, turning)));
+    }
+
+    let output = Command::new("wg-quick").arg(action).arg(path).output();
+    match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if is_permission_error(&stderr) {
+                bail!(missing_privilege(&format!("Turning {} {}", name, turning)));
+            }
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("wg-quick {} failed - {}", action, stderr.trim()),
+            ));
+        }
+        Err(e) => {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::NetworkBackendCallError,
+                format!("cannot run wg-quick - {}", e),
+            ));
+        }
+    }
+}
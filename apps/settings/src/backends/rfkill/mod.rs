@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use custom_utils::atomic_file::write_atomic;
+use tracing::{debug, info, warn};
+
+use crate::{
+    errors::{LockScreenError, LockScreenErrorCodes},
+    settings::RfkillBackendSettings,
+};
+
+/// `RFKILL_TYPE_WLAN` and `RFKILL_TYPE_BLUETOOTH` from `linux/rfkill.h`
+const TYPE_WLAN: u8 = 1;
+const TYPE_BLUETOOTH: u8 = 2;
+
+/// `enum rfkill_operation`
+const OP_ADD: u8 = 0;
+const OP_DEL: u8 = 1;
+const OP_CHANGE: u8 = 2;
+const OP_CHANGE_ALL: u8 = 3;
+
+/// `RFKILL_EVENT_SIZE_V1`, newer kernels append fields the app does not use
+const EVENT_SIZE_V1: usize = 8;
+
+/// Time before the watcher opens the device again after a failed read
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Radios airplane mode switches off
+const AIRPLANE_RADIOS: [Radio; 2] = [Radio::Wifi, Radio::Bluetooth];
+
+/// Radio the kernel can block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radio {
+    Wifi,
+    Bluetooth,
+}
+
+impl Radio {
+    fn rfkill_type(&self) -> u8 {
+        match self {
+            Radio::Wifi => TYPE_WLAN,
+            Radio::Bluetooth => TYPE_BLUETOOTH,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Radio::Wifi => "wifi",
+            Radio::Bluetooth => "bluetooth",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        AIRPLANE_RADIOS
+            .into_iter()
+            .find(|radio| radio.name() == name)
+    }
+}
+
+/// # Radio State
+///
+/// Combined state of every rfkill switch of a radio type, `hard_blocked`
+/// is a physical switch or firmware lock the app cannot change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RadioState {
+    pub present: bool,
+    pub soft_blocked: bool,
+    pub hard_blocked: bool,
+}
+
+impl RadioState {
+    pub fn enabled(&self) -> bool {
+        self.present && !self.soft_blocked && !self.hard_blocked
+    }
+}
+
+/// State of the radios shown in the app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RfkillState {
+    pub wifi: RadioState,
+    pub bluetooth: RadioState,
+}
+
+impl RfkillState {
+    pub fn radio(&self, radio: Radio) -> RadioState {
+        match radio {
+            Radio::Wifi => self.wifi,
+            Radio::Bluetooth => self.bluetooth,
+        }
+    }
+
+    /// On while every radio the device has is soft blocked
+    pub fn airplane_mode(&self) -> bool {
+        let radios: Vec<RadioState> = AIRPLANE_RADIOS
+            .iter()
+            .map(|radio| self.radio(*radio))
+            .filter(|state| state.present)
+            .collect();
+        !radios.is_empty() && radios.iter().all(|state| state.soft_blocked)
+    }
+}
+
+/// `struct rfkill_event`
+#[derive(Debug, Clone, Copy)]
+struct RfkillEvent {
+    index: u32,
+    rfkill_type: u8,
+    op: u8,
+    soft: bool,
+    hard: bool,
+}
+
+impl RfkillEvent {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < EVENT_SIZE_V1 {
+            return None;
+        }
+        Some(Self {
+            index: u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            rfkill_type: bytes[4],
+            op: bytes[5],
+            soft: bytes[6] != 0,
+            hard: bytes[7] != 0,
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; EVENT_SIZE_V1] {
+        let index = self.index.to_ne_bytes();
+        [
+            index[0],
+            index[1],
+            index[2],
+            index[3],
+            self.rfkill_type,
+            self.op,
+            self.soft as u8,
+            self.hard as u8,
+        ]
+    }
+}
+
+/// rfkill switches known from the events read so far, by index
+#[derive(Debug, Default)]
+struct RfkillDevices(HashMap<u32, RfkillEvent>);
+
+impl RfkillDevices {
+    fn apply(&mut self, event: RfkillEvent) {
+        match event.op {
+            OP_ADD | OP_CHANGE => {
+                self.0.insert(event.index, event);
+            }
+            OP_DEL => {
+                self.0.remove(&event.index);
+            }
+            // only seen when a file stands in for the device, the
+            // kernel answers a write with a change per switch
+            OP_CHANGE_ALL => {
+                for device in self.0.values_mut() {
+                    if device.rfkill_type == event.rfkill_type {
+                        device.soft = event.soft;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn state(&self) -> RfkillState {
+        let radio_state = |radio: Radio| {
+            let switches: Vec<&RfkillEvent> = self
+                .0
+                .values()
+                .filter(|device| device.rfkill_type == radio.rfkill_type())
+                .collect();
+            RadioState {
+                present: !switches.is_empty(),
+                soft_blocked: !switches.is_empty() && switches.iter().all(|device| device.soft),
+                hard_blocked: switches.iter().any(|device| device.hard),
+            }
+        };
+        RfkillState {
+            wifi: radio_state(Radio::Wifi),
+            bluetooth: radio_state(Radio::Bluetooth),
+        }
+    }
+}
+
+/// # rfkill Backend
+///
+/// Reads and blocks radios through the kernel rfkill device, opening
+/// it queues an `ADD` event for every switch followed by changes as
+/// they happen. The radios that were on when airplane mode started
+/// are kept in `saved_radios_path` so they come back after a restart
+pub struct RfkillBackend {
+    device_path: PathBuf,
+    saved_radios_path: PathBuf,
+}
+
+pub type RfkillBackendHandle = Arc<RfkillBackend>;
+
+impl RfkillBackend {
+    pub fn new(settings: &RfkillBackendSettings) -> Self {
+        Self {
+            device_path: PathBuf::from(&settings.device_path),
+            saved_radios_path: PathBuf::from(&settings.saved_radios_path),
+        }
+    }
+
+    /// Current state, read from the events queued when the device is opened
+    pub fn state(&self) -> Result<RfkillState> {
+        let mut file = match OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.device_path)
+        {
+            Ok(file) => file,
+            Err(e) => bail!(self.read_error(e)),
+        };
+
+        let mut devices = RfkillDevices::default();
+        loop {
+            match read_event(&mut file) {
+                Ok(Some(event)) => devices.apply(event),
+                Ok(None) => break,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => bail!(self.read_error(e)),
+            }
+        }
+        Ok(devices.state())
+    }
+
+    pub fn set_blocked(&self, radio: Radio, blocked: bool) -> Result<()> {
+        info!(
+            task = "rfkill_backend",
+            "setting {} blocked {}",
+            radio.name(),
+            blocked
+        );
+        let event = RfkillEvent {
+            index: 0,
+            rfkill_type: radio.rfkill_type(),
+            op: OP_CHANGE_ALL,
+            soft: blocked,
+            hard: false,
+        };
+        let result = OpenOptions::new()
+            .append(true)
+            .open(&self.device_path)
+            .and_then(|mut file| file.write_all(&event.to_bytes()));
+        if let Err(e) = result {
+            bail!(LockScreenError::new(
+                LockScreenErrorCodes::RfkillBackendWriteError,
+                format!(
+                    "cannot block {} through {:?} - {}",
+                    radio.name(),
+                    self.device_path,
+                    e
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Blocks Wi-Fi and Bluetooth together, turning it off unblocks
+    /// the radios that were on before instead of all of them
+    pub fn set_airplane_mode(&self, enabled: bool) -> Result<()> {
+        match enabled {
+            true => {
+                let state = self.state()?;
+                let enabled_radios: Vec<Radio> = AIRPLANE_RADIOS
+                    .into_iter()
+                    .filter(|radio| {
+                        state.radio(*radio).present && !state.radio(*radio).soft_blocked
+                    })
+                    .collect();
+                // turning it on again keeps the radios saved the first
+                // time, nothing would be unblocked when it is turned off
+                if !enabled_radios.is_empty() || self.saved_radios().is_none() {
+                    self.save_radios(&enabled_radios);
+                }
+                for radio in AIRPLANE_RADIOS {
+                    self.set_blocked(radio, true)?;
+                }
+            }
+            false => {
+                // without a saved list the mode was set outside the app
+                let radios = self
+                    .saved_radios()
+                    .unwrap_or_else(|| AIRPLANE_RADIOS.to_vec());
+                for radio in radios {
+                    self.set_blocked(radio, false)?;
+                }
+                let _ = fs::remove_file(&self.saved_radios_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `on_change` with the state after every rfkill event, the
+    /// first calls replay the switches present when the watch starts
+    pub fn watch<F>(&self, on_change: F)
+    where
+        F: Fn(RfkillState) + Send + 'static,
+    {
+        let device_path = self.device_path.clone();
+        thread::spawn(move || loop {
+            let mut file = match File::open(&device_path) {
+                Ok(file) => file,
+                Err(e) => {
+                    warn!(
+                        task = "rfkill_backend",
+                        "cannot open {:?}, radio state is not watched - {}", device_path, e
+                    );
+                    return;
+                }
+            };
+
+            let mut devices = RfkillDevices::default();
+            loop {
+                match read_event(&mut file) {
+                    Ok(Some(event)) => {
+                        debug!(task = "rfkill_backend", "rfkill event {:?}", event);
+                        devices.apply(event);
+                        on_change(devices.state());
+                    }
+                    // only a file standing in for the device runs out
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!(
+                            task = "rfkill_backend",
+                            "error while reading {:?} - {}", device_path, e
+                        );
+                        break;
+                    }
+                }
+            }
+            thread::sleep(WATCH_RETRY_INTERVAL);
+        });
+    }
+
+    fn saved_radios(&self) -> Option<Vec<Radio>> {
+        let contents = fs::read_to_string(&self.saved_radios_path).ok()?;
+        Some(
+            contents
+                .lines()
+                .filter_map(|line| Radio::from_name(line.trim()))
+                .collect(),
+        )
+    }
+
+    fn save_radios(&self, radios: &[Radio]) {
+        let contents: String = radios
+            .iter()
+            .map(|radio| format!("{}\n", radio.name()))
+            .collect();
+        if let Err(e) = write_atomic(&self.saved_radios_path, contents) {
+            warn!(
+                task = "rfkill_backend",
+                "cannot save radios to {:?}, all radios are turned on when airplane mode ends - {}",
+                self.saved_radios_path,
+                e
+            );
+        }
+    }
+
+    fn read_error(&self, e: io::Error) -> LockScreenError {
+        LockScreenError::new(
+            LockScreenErrorCodes::RfkillBackendReadError,
+            format!(
+                "cannot read rfkill state from {:?} - {}",
+                self.device_path, e
+            ),
+        )
+    }
+}
+
+/// Reads one event, the kernel hands out a single event per read and
+/// drops the fields past the buffer; `None` once a file runs out
+fn read_event(file: &mut File) -> io::Result<Option<RfkillEvent>> {
+    let mut buffer = [0u8; EVENT_SIZE_V1];
+    let size = file.read(&mut buffer)?;
+    Ok(RfkillEvent::parse(&buffer[..size]))
+}
+
+pub fn init_rfkill_backend(settings: &RfkillBackendSettings) -> RfkillBackendHandle {
+    Arc::new(RfkillBackend::new(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn event(index: u32, rfkill_type: u8, op: u8, soft: bool, hard: bool) -> RfkillEvent {
+        RfkillEvent {
+            index,
+            rfkill_type,
+            op,
+            soft,
+            hard,
+        }
+    }
+
+    /// File standing in for the device, with the events queued on open
+    fn fake_device(name: &str, events: &[RfkillEvent]) -> (PathBuf, RfkillBackend) {
+        let dir = env::temp_dir().join(format!("{}_{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bytes: Vec<u8> = events.iter().flat_map(|event| event.to_bytes()).collect();
+        fs::write(dir.join("rfkill"), bytes).unwrap();
+        let backend = RfkillBackend::new(&RfkillBackendSettings {
+            device_path: dir.join("rfkill").to_string_lossy().to_string(),
+            saved_radios_path: dir.join("radios").to_string_lossy().to_string(),
+        });
+        (dir, backend)
+    }
+
+    #[test]
+    fn events_are_combined_per_radio() {
+        let bytes = event(7, TYPE_BLUETOOTH, OP_ADD, true, false).to_bytes();
+        let parsed = RfkillEvent::parse(&bytes).unwrap();
+        assert_eq!(
+            (parsed.index, parsed.rfkill_type, parsed.soft),
+            (7, TYPE_BLUETOOTH, true)
+        );
+        assert!(RfkillEvent::parse(&bytes[..4]).is_none());
+
+        let mut devices = RfkillDevices::default();
+        devices.apply(event(0, TYPE_WLAN, OP_ADD, true, false));
+        devices.apply(event(1, TYPE_WLAN, OP_ADD, false, true));
+        let state = devices.state();
+        assert!(!state.wifi.soft_blocked);
+        assert!(state.wifi.hard_blocked);
+        assert!(!state.bluetooth.present);
+        assert!(!state.airplane_mode());
+
+        devices.apply(event(1, TYPE_WLAN, OP_DEL, false, false));
+        assert!(devices.state().airplane_mode());
+        devices.apply(event(0, TYPE_WLAN, OP_CHANGE_ALL, false, false));
+        assert!(devices.state().wifi.enabled());
+    }
+
+    #[test]
+    fn airplane_mode_brings_back_the_radios_that_were_on() {
+        let (dir, backend) = fake_device(
+            "rfkill_airplane",
+            &[
+                event(0, TYPE_WLAN, OP_ADD, false, false),
+                event(1, TYPE_BLUETOOTH, OP_ADD, true, false),
+            ],
+        );
+
+        backend.set_airplane_mode(true).unwrap();
+        assert!(backend.state().unwrap().airplane_mode());
+        // turned on again, the radios saved the first time are kept
+        backend.set_airplane_mode(true).unwrap();
+        assert_eq!(backend.saved_radios(), Some(vec![Radio::Wifi]));
+
+        backend.set_airplane_mode(false).unwrap();
+        let state = backend.state().unwrap();
+        assert!(state.wifi.enabled());
+        assert!(state.bluetooth.soft_blocked);
+        assert_eq!(backend.saved_radios(), None);
+
+        // set outside the app, every radio comes back
+        backend.set_blocked(Radio::Wifi, true).unwrap();
+        backend.set_airplane_mode(false).unwrap();
+        let state = backend.state().unwrap();
+        assert!(state.wifi.enabled() && state.bluetooth.enabled());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_device_is_a_read_error() {
+        let backend = RfkillBackend::new(&RfkillBackendSettings {
+            device_path: "/nonexistent/rfkill".to_string(),
+            ..Default::default()
+        });
+        assert!(backend.state().is_err());
+        assert!(backend.set_blocked(Radio::Wifi, true).is_err());
+    }
+}
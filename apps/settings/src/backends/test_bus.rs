@@ -0,0 +1,60 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+use zbus::blocking::{Connection, ConnectionBuilder};
+
+/// # Test Bus
+///
+/// D-Bus daemon of its own for the tests of the D-Bus backends, the
+/// fakes and the backend under test connect to it by address so the
+/// bus of the user is left alone. Stopped when dropped
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    /// Starts the daemon, the D-Bus tests need dbus-daemon installed
+    pub fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args([
+                "--session",
+                "--nofork",
+                "--print-address",
+                "--address=unix:tmpdir=/tmp",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("cannot start dbus-daemon, the D-Bus tests need it installed");
+
+        // the address is printed once the daemon listens
+        let mut address = String::new();
+        let stdout = daemon.stdout.take().unwrap();
+        let result = BufReader::new(stdout).read_line(&mut address);
+        if result.is_err() || address.is_empty() {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+            panic!("dbus-daemon exited without printing its address");
+        }
+        Self {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+
+    pub fn connection(&self) -> Connection {
+        ConnectionBuilder::address(self.address.as_str())
+            .and_then(|builder| builder.build())
+            .unwrap()
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
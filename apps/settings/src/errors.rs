@@ -21,6 +21,22 @@ pub enum LockScreenErrorCodes {
     StreamReadCaptchaError,
     StreamWriteCaptchaError,
     StreamReadAuthResponseError,
+    NetworkBackendConnectError,
+    NetworkBackendCallError,
+    NetworkBackendPermissionError,
+    DnsBackendConnectError,
+    DnsBackendCallError,
+    RfkillBackendReadError,
+    RfkillBackendWriteError,
+    DataUsageReadError,
+    DataUsageWriteError,
+    DiagnosticsError,
+    BluetoothBackendConnectError,
+    BluetoothBackendCallError,
+    BluetoothFileReceiveError,
+    DisplayBackendReadError,
+    DisplayBackendWriteError,
+    IdleDaemonError,
 }
 
 impl fmt::Display for LockScreenErrorCodes {
@@ -53,6 +69,52 @@ impl fmt::Display for LockScreenErrorCodes {
             LockScreenErrorCodes::StreamReadAuthResponseError => {
                 write!(f, "StreamReadAuthResponseError")
             }
+            LockScreenErrorCodes::NetworkBackendConnectError => {
+                write!(f, "NetworkBackendConnectError")
+            }
+            LockScreenErrorCodes::NetworkBackendCallError => {
+                write!(f, "NetworkBackendCallError")
+            }
+            LockScreenErrorCodes::NetworkBackendPermissionError => {
+                write!(f, "NetworkBackendPermissionError")
+            }
+            LockScreenErrorCodes::DnsBackendConnectError => {
+                write!(f, "DnsBackendConnectError")
+            }
+            LockScreenErrorCodes::DnsBackendCallError => {
+                write!(f, "DnsBackendCallError")
+            }
+            LockScreenErrorCodes::RfkillBackendReadError => {
+                write!(f, "RfkillBackendReadError")
+            }
+            LockScreenErrorCodes::RfkillBackendWriteError => {
+                write!(f, "RfkillBackendWriteError")
+            }
+            LockScreenErrorCodes::DataUsageReadError => {
+                write!(f, "DataUsageReadError")
+            }
+            LockScreenErrorCodes::DataUsageWriteError => {
+                write!(f, "DataUsageWriteError")
+            }
+            LockScreenErrorCodes::DiagnosticsError => {
+                write!(f, "DiagnosticsError")
+            }
+            LockScreenErrorCodes::BluetoothBackendConnectError => {
+                write!(f, "BluetoothBackendConnectError")
+            }
+            LockScreenErrorCodes::BluetoothBackendCallError => {
+                write!(f, "BluetoothBackendCallError")
+            }
+            LockScreenErrorCodes::BluetoothFileReceiveError => {
+                write!(f, "BluetoothFileReceiveError")
+            }
+            LockScreenErrorCodes::DisplayBackendReadError => {
+                write!(f, "DisplayBackendReadError")
+            }
+            LockScreenErrorCodes::DisplayBackendWriteError => {
+                write!(f, "DisplayBackendWriteError")
+            }
+            LockScreenErrorCodes::IdleDaemonError => write!(f, "IdleDaemonError"),
         }
     }
 }
@@ -1,9 +1,10 @@
-use std::fmt;
+use std::{fmt, thread};
 
 use gtk::{glib::clone, prelude::GtkWindowExt};
 use relm4::{gtk, ComponentParts, ComponentSender, RelmApp, SimpleComponent};
 use relm4::{Component, ComponentController, Controller};
 
+mod backends;
 mod pages;
 mod settings;
 mod theme;
@@ -16,6 +17,10 @@ use pages::{
         BluetoothDetailsPage, Message as BluetoothDetailsPageMessage,
         Settings as BluetoothDetailsPageSettings,
     },
+    bluetooth_file_request_page::{
+        BluetoothFileRequestPage, Message as BluetoothFileRequestPageMessage,
+        Settings as BluetoothFileRequestPageSettings,
+    },
     bluetooth_pair_request_page::{
         BluetoothPairRequestPage, Message as BluetoothPairRequestPageMessage,
         Settings as BluetoothPairRequestPageSettings,
@@ -49,6 +54,10 @@ use pages::{
         Message as NetworkDetailsPageMessage, NetworkDetailsPage,
         Settings as NetworkDetailsPageSettings,
     },
+    received_files_page::{
+        Message as ReceivedFilesPageMessage, ReceivedFilesPage,
+        Settings as ReceivedFilesPageSettings,
+    },
     networks_page::{
         Message as NetworksPageMessage, NetworksPage, Settings as NetworksPageSettings,
     },
@@ -61,6 +70,19 @@ use pages::{
     dns_page::{
         Message as DNSPageMessage, DNSPage, Settings as DNSPageSettings,
     },
+    hotspot_page::{
+        Message as HotspotPageMessage, HotspotPage, Settings as HotspotPageSettings,
+    },
+    proxy_page::{
+        Message as ProxyPageMessage, ProxyPage, Settings as ProxyPageSettings,
+    },
+    data_usage_page::{
+        Message as DataUsagePageMessage, DataUsagePage, Settings as DataUsagePageSettings,
+    },
+    diagnostics_page::{
+        Message as DiagnosticsPageMessage, DiagnosticsPage, Settings as DiagnosticsPageSettings,
+    },
+    vpn_page::{Message as VpnPageMessage, VpnPage, Settings as VpnPageSettings},
     protocol_modes_page::{
         Message as ProtocolModesPageMessage, ProtocolModesPage, Settings as ProtocolModesPageSettings,
     },
@@ -103,8 +125,16 @@ use pages::{
         Message as AboutPageMessage, AboutPage, Settings as AboutPageSettings,
     }
 };
+use backends::{
+    bluetooth::{
+        BluetoothDevice, BluetoothEvent, IncomingFile, PairingEvent, PairingKind, TransferEvent,
+    },
+    display::Backlight,
+    network::{IpConnection, IpFamily, WifiNetwork},
+    rfkill::RfkillState,
+};
 use settings::LockScreenSettings;
-use tracing::info;
+use tracing::{error, info};
 pub mod errors; 
 use crate::theme::LockScreenTheme;
 
@@ -127,6 +157,8 @@ struct LockScreen {
     bluetooth_details_page: Controller<BluetoothDetailsPage>,
     connect_bluetooth_page: Controller<ConnectBluetoothPage>,
     bluetooth_pair_request_page: Controller<BluetoothPairRequestPage>,
+    bluetooth_file_request_page: Controller<BluetoothFileRequestPage>,
+    received_files_page: Controller<ReceivedFilesPage>,
     display_page: Controller<DisplayPage>,
     screen_timeout_page: Controller<ScreenTimeoutPage>,
     sound_page: Controller<SoundPage>,
@@ -143,7 +175,12 @@ struct LockScreen {
     protocol_modes_page: Controller<ProtocolModesPage>,
     protocol_details_page: Controller<ProtocolDetailsPage>,
     ethernet_page: Controller<EthernetPage>,
-    dns_page: Controller<DNSPage>
+    dns_page: Controller<DNSPage>,
+    hotspot_page: Controller<HotspotPage>,
+    proxy_page: Controller<ProxyPage>,
+    data_usage_page: Controller<DataUsagePage>,
+    diagnostics_page: Controller<DiagnosticsPage>,
+    vpn_page: Controller<VpnPage>
 }
 
 #[derive(Debug, Clone)]
@@ -162,6 +199,8 @@ pub enum Screens {
     BluetoothDetails,
     ConnectBluetooth,
     BluetoothPairRequest,
+    BluetoothFileRequest,
+    ReceivedFiles,
     Display,
     ScreenTimeout,
     Sound,
@@ -177,6 +216,11 @@ pub enum Screens {
     IPSettings,
     Ethernet,
     DNSPage,
+    Hotspot,
+    Proxy,
+    DataUsage,
+    Diagnostics,
+    Vpn,
     ProtocolModes,
     ProtocolDetails,
 }
@@ -197,6 +241,8 @@ impl fmt::Display for Screens {
             Screens::BluetoothDetails => write!(f, "bluetooth_details"),
             Screens::ConnectBluetooth => write!(f, "connect_bluetooth"),
             Screens::BluetoothPairRequest => write!(f, "bluetooth_pair_request"),
+            Screens::BluetoothFileRequest => write!(f, "bluetooth_file_request"),
+            Screens::ReceivedFiles => write!(f, "received_files"),
             Screens::Display => write!(f, "display"),
             Screens::ScreenTimeout => write!(f, "screen_timeout"),
             Screens::Sound => write!(f, "sound"),
@@ -215,6 +261,11 @@ impl fmt::Display for Screens {
             Screens::ProtocolDetails => write!(f, "protocol_details"),
             Screens::Ethernet => write!(f, "ethernet_page"),
             Screens::DNSPage => write!(f, "dns_page"),
+            Screens::Hotspot => write!(f, "hotspot"),
+            Screens::Proxy => write!(f, "proxy"),
+            Screens::DataUsage => write!(f, "data_usage"),
+            Screens::Diagnostics => write!(f, "diagnostics"),
+            Screens::Vpn => write!(f, "vpn"),
         }
     }
 }
@@ -227,6 +278,28 @@ impl fmt::Display for Screens {
 pub enum Message {
     ChangeScreen(Screens),
     GoBack,
+    ConnectNetwork(WifiNetwork),
+    NetworkDetails(String),
+    IpSettings(IpConnection),
+    IpConfigApplied(IpConnection),
+    ProtocolModes(IpFamily),
+    DnsSettings,
+    ProxySettings,
+    RfkillChanged(RfkillState),
+    BluetoothChanged(BluetoothEvent),
+    BluetoothDetails(BluetoothDevice),
+    PairingChanged(PairingEvent),
+    /// Pairing of a device ended, by device `id`
+    PairingFinished(String),
+    /// A pairing page closed, returns from it while it is shown
+    PairingClosed(Screens),
+    TransferChanged(TransferEvent),
+    /// The file request page closed, with the file if accepted
+    FileRequestClosed(Option<IncomingFile>),
+    ReceivedFiles,
+    BacklightChanged(Option<Backlight>),
+    /// Seconds before the screen turns off, shown by the display and battery pages
+    ScreenTimeoutChanged(u32),
     Dummy,
 }
 
@@ -347,6 +420,20 @@ impl SimpleComponent for LockScreen {
         let modules = settings.modules.clone();
         let layout = settings.layout.clone();
         let widget_configs = settings.widget_configs.clone();
+        let network_backend = backends::network::init_network_backend(&settings.backends.network);
+        let dns_backend = backends::dns::init_dns_backend(&settings.backends.dns);
+        let dns_settings = settings.backends.dns.clone();
+        thread::spawn(move || backends::dns::restore_manual_dns(&dns_settings));
+        let rfkill_backend = backends::rfkill::init_rfkill_backend(&settings.backends.rfkill);
+        let bluetooth_backend =
+            backends::bluetooth::init_bluetooth_backend(&settings.backends.bluetooth);
+        let data_usage_backend =
+            backends::data_usage::init_data_usage_backend(&settings.backends.data_usage);
+        let diagnostics_backend = backends::diagnostics::init_diagnostics_backend(
+            &settings.backends.diagnostics,
+            &settings.backends.network.sysfs_root,
+        );
+        let display_backend = backends::display::init_display_backend(&settings.backends.display);
 
         //Stack used to render different screens
         //At a time one screen will be rendered
@@ -395,7 +482,10 @@ impl SimpleComponent for LockScreen {
             .launch(NetworksPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
+                rfkill_backend: rfkill_backend.clone(),
+                refresh_interval: settings.backends.network.refresh_interval,
             })
             .forward(
                 sender.input_sender(),
@@ -404,11 +494,18 @@ impl SimpleComponent for LockScreen {
                     println!("network_page - auth page message to parent {:?}", msg);
                     match msg {
                         NetworksPageMessage::BackPressed => Message::GoBack,
-                        NetworksPageMessage::EnableNetworkPressed => Message::ChangeScreen(Screens::NetworkDetails),
+                        NetworksPageMessage::ActiveNetworkPressed(ssid) => Message::NetworkDetails(ssid),
                         NetworksPageMessage::ManageNetworkPressed => Message::ChangeScreen(Screens::ManageNetworks),
-                        NetworksPageMessage::IpSettingsPressed => Message::ChangeScreen(Screens::IPSettings),
+                        NetworksPageMessage::ActiveNetworkIpSettingsPressed(ssid) => {
+                            Message::IpSettings(IpConnection::Wifi(ssid))
+                        }
                         NetworksPageMessage::EthernetPressed => Message::ChangeScreen(Screens::Ethernet),
-                        NetworksPageMessage::DNSPressed => Message::ChangeScreen(Screens::DNSPage),
+                        NetworksPageMessage::DNSPressed => Message::DnsSettings,
+                        NetworksPageMessage::HotspotPressed => Message::ChangeScreen(Screens::Hotspot),
+                        NetworksPageMessage::ProxyPressed => Message::ProxySettings,
+                        NetworksPageMessage::DataUsagePressed => Message::ChangeScreen(Screens::DataUsage),
+                        NetworksPageMessage::DiagnosticsPressed => Message::ChangeScreen(Screens::Diagnostics),
+                        NetworksPageMessage::VpnPressed => Message::ChangeScreen(Screens::Vpn),
                         NetworksPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -424,7 +521,9 @@ impl SimpleComponent for LockScreen {
             .launch(ManageNetworksPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
+                refresh_interval: settings.backends.network.refresh_interval,
             })
             .forward(
                 sender.input_sender(),
@@ -432,10 +531,10 @@ impl SimpleComponent for LockScreen {
                     info!("manage_networks_page - auth page message to parent {:?}", msg);
                     match msg {
                         ManageNetworksPageMessage::BackPressed => Message::GoBack,
-                        ManageNetworksPageMessage::KnownNetworkPressed => 
-                        Message::ChangeScreen(Screens::NetworkDetails),
-                        ManageNetworksPageMessage::AvailableNetworkPressed => 
-                        Message::ChangeScreen(Screens::ConnectNetwork),
+                        ManageNetworksPageMessage::KnownNetworkPressed(network) => 
+                        Message::NetworkDetails(network.ssid),
+                        ManageNetworksPageMessage::AvailableNetworkPressed(network) => 
+                        Message::ConnectNetwork(network),
                         ManageNetworksPageMessage::AddNetworkPressed => Message::ChangeScreen(Screens::AddNetwork), 
                         _ => Message::Dummy
                     }
@@ -451,7 +550,8 @@ impl SimpleComponent for LockScreen {
             .launch(IPSettingsPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -459,7 +559,7 @@ impl SimpleComponent for LockScreen {
                     info!("ip_settings_page - auth page message to parent {:?}", msg);
                     match msg {
                         IPSettingsPageMessage::BackPressed => Message::GoBack,
-                        IPSettingsPageMessage::ProtocolModes => Message::ChangeScreen(Screens::ProtocolModes),
+                        IPSettingsPageMessage::ProtocolModes(family) => Message::ProtocolModes(family),
                         _ => Message::Dummy
                     }
                 }),
@@ -474,7 +574,9 @@ impl SimpleComponent for LockScreen {
         .launch(EthernetPageSettings {
             modules: modules.clone(),
             layout: layout.clone(),
-            widget_configs: widget_configs.clone()
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
+            refresh_interval: settings.backends.network.refresh_interval,
         })
         .forward(
             sender.input_sender(),
@@ -482,7 +584,7 @@ impl SimpleComponent for LockScreen {
                 info!("ethernet_page - auth page message to parent {:?}", msg);
                 match msg {
                     EthernetPageMessage::BackPressed => Message::GoBack,
-                    EthernetPageMessage::IPSettingsPressed => Message::ChangeScreen(Screens::IPSettings),
+                    EthernetPageMessage::IPSettingsPressed => Message::IpSettings(IpConnection::Ethernet),
                     _ => Message::Dummy
                 }
             }),
@@ -497,7 +599,8 @@ impl SimpleComponent for LockScreen {
         .launch(DNSPageSettings {
             modules: modules.clone(),
             layout: layout.clone(),
-            widget_configs: widget_configs.clone()
+            widget_configs: widget_configs.clone(),
+            dns_backend: dns_backend.clone(),
         })
         .forward(
             sender.input_sender(),
@@ -515,11 +618,130 @@ impl SimpleComponent for LockScreen {
             Option::from(Screens::DNSPage.to_string().as_str()),
         );
 
+        let hotspot_page : Controller<HotspotPage> = HotspotPage::builder()
+        .launch(HotspotPageSettings {
+            modules: modules.clone(),
+            layout: layout.clone(),
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
+            refresh_interval: settings.backends.network.refresh_interval,
+        })
+        .forward(
+            sender.input_sender(),
+            clone!(@strong modules => move|msg| {
+                info!("hotspot_page - auth page message to parent {:?}", msg);
+                match msg {
+                    HotspotPageMessage::BackPressed => Message::GoBack,
+                    _ => Message::Dummy
+                }
+            }),
+        );
+
+        screens_stack.add_named(
+            hotspot_page.widget(),
+            Option::from(Screens::Hotspot.to_string().as_str()),
+        );
+
+        let proxy_page : Controller<ProxyPage> = ProxyPage::builder()
+        .launch(ProxyPageSettings {
+            modules: modules.clone(),
+            layout: layout.clone(),
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
+        })
+        .forward(
+            sender.input_sender(),
+            clone!(@strong modules => move|msg| {
+                info!("proxy_page - auth page message to parent {:?}", msg);
+                match msg {
+                    ProxyPageMessage::BackPressed => Message::GoBack,
+                    _ => Message::Dummy
+                }
+            }),
+        );
+
+        screens_stack.add_named(
+            proxy_page.widget(),
+            Option::from(Screens::Proxy.to_string().as_str()),
+        );
+
+        let data_usage_page : Controller<DataUsagePage> = DataUsagePage::builder()
+        .launch(DataUsagePageSettings {
+            modules: modules.clone(),
+            layout: layout.clone(),
+            widget_configs: widget_configs.clone(),
+            data_usage_backend: data_usage_backend.clone(),
+            refresh_interval: settings.backends.network.refresh_interval,
+        })
+        .forward(
+            sender.input_sender(),
+            clone!(@strong modules => move|msg| {
+                info!("data_usage_page - auth page message to parent {:?}", msg);
+                match msg {
+                    DataUsagePageMessage::BackPressed => Message::GoBack,
+                    _ => Message::Dummy
+                }
+            }),
+        );
+
+        screens_stack.add_named(
+            data_usage_page.widget(),
+            Option::from(Screens::DataUsage.to_string().as_str()),
+        );
+
+        let diagnostics_page : Controller<DiagnosticsPage> = DiagnosticsPage::builder()
+        .launch(DiagnosticsPageSettings {
+            modules: modules.clone(),
+            layout: layout.clone(),
+            widget_configs: widget_configs.clone(),
+            diagnostics_backend: diagnostics_backend.clone(),
+        })
+        .forward(
+            sender.input_sender(),
+            clone!(@strong modules => move|msg| {
+                info!("diagnostics_page - auth page message to parent {:?}", msg);
+                match msg {
+                    DiagnosticsPageMessage::BackPressed => Message::GoBack,
+                    _ => Message::Dummy
+                }
+            }),
+        );
+
+        screens_stack.add_named(
+            diagnostics_page.widget(),
+            Option::from(Screens::Diagnostics.to_string().as_str()),
+        );
+
+        let vpn_page : Controller<VpnPage> = VpnPage::builder()
+        .launch(VpnPageSettings {
+            modules: modules.clone(),
+            layout: layout.clone(),
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
+            refresh_interval: settings.backends.network.refresh_interval,
+        })
+        .forward(
+            sender.input_sender(),
+            clone!(@strong modules => move|msg| {
+                info!("vpn_page - auth page message to parent {:?}", msg);
+                match msg {
+                    VpnPageMessage::BackPressed => Message::GoBack,
+                    _ => Message::Dummy
+                }
+            }),
+        );
+
+        screens_stack.add_named(
+            vpn_page.widget(),
+            Option::from(Screens::Vpn.to_string().as_str()),
+        );
+
         let protocol_modes_page: Controller<ProtocolModesPage> = ProtocolModesPage::builder()
         .launch(ProtocolModesPageSettings {
             modules: modules.clone(),
             layout: layout.clone(),
-            widget_configs: widget_configs.clone()
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
         })
         .forward(
             sender.input_sender(),
@@ -529,6 +751,7 @@ impl SimpleComponent for LockScreen {
                 // back -> ProtocolModes
                 ProtocolModesPageMessage::BackPressed => Message::GoBack,   // IPSettings
                 ProtocolModesPageMessage::StaticModePressed => Message::ChangeScreen(Screens::ProtocolDetails),
+                ProtocolModesPageMessage::ConfigApplied(connection) => Message::IpConfigApplied(connection),
                 _ => Message::Dummy
                 }
             }),
@@ -544,7 +767,8 @@ impl SimpleComponent for LockScreen {
         .launch(ProtocolDetailsPageSettings {
             modules: modules.clone(),
             layout: layout.clone(),
-            widget_configs: widget_configs.clone()
+            widget_configs: widget_configs.clone(),
+            network_backend: network_backend.clone(),
         })
         .forward(
             sender.input_sender(),
@@ -553,6 +777,7 @@ impl SimpleComponent for LockScreen {
                 match msg {
                     // back -> ProtocolModes
                     ProtocolDetailsPageMessage::BackPressed => Message::GoBack,
+                    ProtocolDetailsPageMessage::ConfigApplied(connection) => Message::IpConfigApplied(connection),
                     _ => Message::Dummy
                 }
             }),
@@ -567,7 +792,9 @@ impl SimpleComponent for LockScreen {
             .launch(NetworkDetailsPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
+                refresh_interval: settings.backends.network.refresh_interval,
             })
             .forward(
                 sender.input_sender(),
@@ -576,6 +803,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageNetworks 
                         NetworkDetailsPageMessage::BackPressed => Message::GoBack,
+                        NetworkDetailsPageMessage::NetworkForgotten => Message::GoBack,
                         NetworkDetailsPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                             _ => Message::Dummy
                     }
@@ -591,7 +819,8 @@ impl SimpleComponent for LockScreen {
             .launch(ConnectNetworkPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -600,6 +829,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageNetworks 
                         ConnectNetworkPageMessage::BackPressed => Message::GoBack,
+                        ConnectNetworkPageMessage::Connected => Message::GoBack,
                         ConnectNetworkPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -615,7 +845,8 @@ impl SimpleComponent for LockScreen {
             .launch(AddNetworkPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                network_backend: network_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -624,6 +855,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageNetworks 
                         AddNetworkPageMessage::BackPressed => Message::GoBack,
+                        AddNetworkPageMessage::NetworkAdded => Message::GoBack,
                         AddNetworkPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                             _ => Message::Dummy
                     }
@@ -639,7 +871,9 @@ impl SimpleComponent for LockScreen {
             .launch(ManageBluetoothPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                rfkill_backend: rfkill_backend.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -647,9 +881,10 @@ impl SimpleComponent for LockScreen {
                     info!("auth page message to parent {:?}", msg);
                     match msg { 
                         ManageBluetoothPageMessage::BackPressed => Message::GoBack,
-                        ManageBluetoothPageMessage::AvaiableDevicePressed => Message::ChangeScreen(Screens::BluetoothDetails),
-                        ManageBluetoothPageMessage::OtherDevicePressed => Message::ChangeScreen(Screens::ConnectBluetooth),
-                        // ManageBluetoothPageMessage::OtherDevicePressed => Message::ChangeScreen(Screens::BluetoothPairRequest),
+                        ManageBluetoothPageMessage::AvaiableDevicePressed(device) => Message::BluetoothDetails(device),
+                        // other devices pair, the agent brings up the pairing pages
+                        ManageBluetoothPageMessage::PairingFinished(id, _) => Message::PairingFinished(id),
+                        ManageBluetoothPageMessage::ReceivedFilesPressed => Message::ReceivedFiles,
                         ManageBluetoothPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -665,7 +900,8 @@ impl SimpleComponent for LockScreen {
             .launch(BluetoothDetailsPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -674,6 +910,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageBluetooth
                         BluetoothDetailsPageMessage::BackPressed => Message::GoBack,
+                        BluetoothDetailsPageMessage::DeviceForgotten => Message::GoBack,
                         BluetoothDetailsPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -689,7 +926,8 @@ impl SimpleComponent for LockScreen {
             .launch(ConnectBluetoothPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -698,6 +936,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageBluetooth
                         ConnectBluetoothPageMessage::BackPressed => Message::GoBack,
+                        ConnectBluetoothPageMessage::Closed => Message::PairingClosed(Screens::ConnectBluetooth),
                         ConnectBluetoothPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -713,7 +952,8 @@ impl SimpleComponent for LockScreen {
             .launch(BluetoothPairRequestPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -722,6 +962,7 @@ impl SimpleComponent for LockScreen {
                     match msg {
                         // back -> ManageBluetooth
                         BluetoothPairRequestPageMessage::BackPressed => Message::GoBack,
+                        BluetoothPairRequestPageMessage::Closed => Message::PairingClosed(Screens::BluetoothPairRequest),
                         BluetoothPairRequestPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -733,11 +974,64 @@ impl SimpleComponent for LockScreen {
             Option::from(Screens::BluetoothPairRequest.to_string().as_str()),
         );
 
+        let bluetooth_file_request_page: Controller<BluetoothFileRequestPage> = BluetoothFileRequestPage::builder()
+            .launch(BluetoothFileRequestPageSettings {
+                modules: modules.clone(),
+                layout: layout.clone(),
+                widget_configs: widget_configs.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
+                download_dir: settings.backends.bluetooth.download_dir.clone(),
+            })
+            .forward(
+                sender.input_sender(),
+                clone!(@strong modules => move|msg| {
+                    info!("auth page message to parent {:?}", msg);
+                    match msg {
+                        BluetoothFileRequestPageMessage::BackPressed => Message::GoBack,
+                        BluetoothFileRequestPageMessage::Closed(file) => Message::FileRequestClosed(file),
+                        BluetoothFileRequestPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
+                        _ => Message::Dummy
+                    }
+                }),
+            );
+
+        screens_stack.add_named(
+            bluetooth_file_request_page.widget(),
+            Option::from(Screens::BluetoothFileRequest.to_string().as_str()),
+        );
+
+        let received_files_page: Controller<ReceivedFilesPage> = ReceivedFilesPage::builder()
+            .launch(ReceivedFilesPageSettings {
+                modules: modules.clone(),
+                layout: layout.clone(),
+                widget_configs: widget_configs.clone(),
+                bluetooth_backend: bluetooth_backend.clone(),
+                download_dir: settings.backends.bluetooth.download_dir.clone(),
+            })
+            .forward(
+                sender.input_sender(),
+                clone!(@strong modules => move|msg| {
+                    info!("auth page message to parent {:?}", msg);
+                    match msg {
+                        // back -> ManageBluetooth
+                        ReceivedFilesPageMessage::BackPressed => Message::GoBack,
+                        ReceivedFilesPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
+                        _ => Message::Dummy
+                    }
+                }),
+            );
+
+        screens_stack.add_named(
+            received_files_page.widget(),
+            Option::from(Screens::ReceivedFiles.to_string().as_str()),
+        );
+
         let display_page: Controller<DisplayPage> = DisplayPage::builder()
             .launch(DisplayPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                display_backend: display_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -784,7 +1078,8 @@ impl SimpleComponent for LockScreen {
             .launch(ScreenTimeoutPageSettings {
                 modules: modules.clone(),
                 layout: layout.clone(),
-                widget_configs: widget_configs.clone()
+                widget_configs: widget_configs.clone(),
+                display_backend: display_backend.clone(),
             })
             .forward(
                 sender.input_sender(),
@@ -793,6 +1088,7 @@ impl SimpleComponent for LockScreen {
                     match msg { 
                         // back -> Display or Battery
                         ScreenTimeoutPageMessage::BackPressed => Message::GoBack,
+                        ScreenTimeoutPageMessage::TimeoutChanged(timeout) => Message::ScreenTimeoutChanged(timeout),
                         ScreenTimeoutPageMessage::HomeIconPressed => Message::ChangeScreen(Screens::LockScreen),
                         _ => Message::Dummy
                     }
@@ -1019,6 +1315,31 @@ impl SimpleComponent for LockScreen {
         );
 
 
+        // radios can also be switched by hardware keys or other tools
+        rfkill_backend.watch(clone!(@strong sender => move |rfkill_state| {
+            sender.input(Message::RfkillChanged(rfkill_state));
+        }));
+
+        // devices come and go while discovering or when paired elsewhere
+        bluetooth_backend.watch(Box::new(clone!(@strong sender => move |event| {
+            sender.input(Message::BluetoothChanged(event));
+        })));
+
+        // devices ask for codes while pairing, whoever started it
+        bluetooth_backend.register_agent(Box::new(clone!(@strong sender => move |event| {
+            sender.input(Message::PairingChanged(event));
+        })));
+
+        // phones push files through obexd
+        bluetooth_backend.register_file_agent(Box::new(clone!(@strong sender => move |event| {
+            sender.input(Message::TransferChanged(event));
+        })));
+
+        // hardware keys and the compositor change the backlight too
+        display_backend.watch(clone!(@strong sender => move |backlight| {
+            sender.input(Message::BacklightChanged(backlight));
+        }));
+
         let current_screen = Screens::Settings;
 
         //Setting current active screen in stack
@@ -1043,6 +1364,8 @@ impl SimpleComponent for LockScreen {
             bluetooth_details_page,
             connect_bluetooth_page,
             bluetooth_pair_request_page,
+            bluetooth_file_request_page,
+            received_files_page,
             display_page,
             screen_timeout_page,
             sound_page,
@@ -1059,7 +1382,12 @@ impl SimpleComponent for LockScreen {
             protocol_modes_page,
             protocol_details_page,
             ethernet_page,
-            dns_page
+            dns_page,
+            hotspot_page,
+            proxy_page,
+            data_usage_page,
+            diagnostics_page,
+            vpn_page
         };
 
         let widgets = AppWidgets { screens_stack };
@@ -1067,7 +1395,7 @@ impl SimpleComponent for LockScreen {
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         info!("Update message is {:?}", message);
         match message {
             Message::ChangeScreen(screen) => {
@@ -1081,6 +1409,141 @@ impl SimpleComponent for LockScreen {
                 self.current_screen = previous_screen;
                 }
             }
+            Message::ConnectNetwork(network) => {
+                self.connect_network_page.emit(ConnectNetworkPageMessage::SetNetwork(network));
+                sender.input(Message::ChangeScreen(Screens::ConnectNetwork));
+            }
+            Message::NetworkDetails(ssid) => {
+                self.network_details_page.emit(NetworkDetailsPageMessage::SetNetwork(ssid));
+                sender.input(Message::ChangeScreen(Screens::NetworkDetails));
+            }
+            Message::IpSettings(connection) => {
+                self.emit_ip_connection(connection);
+                sender.input(Message::ChangeScreen(Screens::IPSettings));
+            }
+            Message::ProtocolModes(family) => {
+                self.protocol_modes_page
+                    .emit(ProtocolModesPageMessage::SetFamily(family));
+                self.protocol_details_page
+                    .emit(ProtocolDetailsPageMessage::SetFamily(family));
+                sender.input(Message::ChangeScreen(Screens::ProtocolModes));
+            }
+            Message::DnsSettings => {
+                self.dns_page.emit(DNSPageMessage::RefreshConfig);
+                sender.input(Message::ChangeScreen(Screens::DNSPage));
+            }
+            Message::RfkillChanged(rfkill_state) => {
+                self.network_page
+                    .emit(NetworksPageMessage::RfkillStateChanged(rfkill_state));
+                self.manage_bluetooth_page
+                    .emit(ManageBluetoothPageMessage::RfkillStateChanged(rfkill_state));
+            }
+            Message::BacklightChanged(backlight) => {
+                self.display_page
+                    .emit(DisplayPageMessage::BacklightChanged(backlight));
+            }
+            Message::ScreenTimeoutChanged(timeout) => {
+                self.display_page
+                    .emit(DisplayPageMessage::SetScreenTimeout(timeout));
+                self.battery_page
+                    .emit(BatteryPageMessage::SetScreenTimeout(timeout));
+            }
+            Message::BluetoothChanged(event) => {
+                self.bluetooth_details_page
+                    .emit(BluetoothDetailsPageMessage::BluetoothEvent(event.clone()));
+                self.manage_bluetooth_page
+                    .emit(ManageBluetoothPageMessage::BluetoothEvent(event));
+            }
+            Message::BluetoothDetails(device) => {
+                self.bluetooth_details_page
+                    .emit(BluetoothDetailsPageMessage::SetDevice(device));
+                sender.input(Message::ChangeScreen(Screens::BluetoothDetails));
+            }
+            Message::PairingChanged(event) => match event {
+                PairingEvent::Requested(request) => {
+                    let screen = match request.kind {
+                        PairingKind::Passkey | PairingKind::PinCode => {
+                            self.connect_bluetooth_page
+                                .emit(ConnectBluetoothPageMessage::SetRequest(request));
+                            Screens::ConnectBluetooth
+                        }
+                        _ => {
+                            self.bluetooth_pair_request_page
+                                .emit(BluetoothPairRequestPageMessage::SetRequest(request));
+                            Screens::BluetoothPairRequest
+                        }
+                    };
+                    // a request following one on the same page replaces it
+                    if self.current_screen.to_string() != screen.to_string() {
+                        sender.input(Message::ChangeScreen(screen));
+                    }
+                }
+                PairingEvent::Cancelled(id) => {
+                    self.connect_bluetooth_page
+                        .emit(ConnectBluetoothPageMessage::RequestCancelled(id));
+                    self.bluetooth_pair_request_page
+                        .emit(BluetoothPairRequestPageMessage::RequestCancelled(id));
+                }
+            },
+            Message::PairingFinished(id) => {
+                self.bluetooth_pair_request_page
+                    .emit(BluetoothPairRequestPageMessage::PairingFinished(id));
+            }
+            Message::PairingClosed(screen) => {
+                if self.current_screen.to_string() == screen.to_string() {
+                    sender.input(Message::GoBack);
+                }
+            }
+            Message::TransferChanged(event) => match event {
+                TransferEvent::Requested(file) => {
+                    self.bluetooth_file_request_page
+                        .emit(BluetoothFileRequestPageMessage::SetFile(file));
+                    // an offer following one replaces it
+                    if self.current_screen.to_string() != Screens::BluetoothFileRequest.to_string() {
+                        sender.input(Message::ChangeScreen(Screens::BluetoothFileRequest));
+                    }
+                }
+                TransferEvent::Cancelled(id) => {
+                    self.bluetooth_file_request_page
+                        .emit(BluetoothFileRequestPageMessage::RequestCancelled(id));
+                }
+                event => {
+                    self.received_files_page
+                        .emit(ReceivedFilesPageMessage::TransferEvent(event));
+                }
+            },
+            Message::FileRequestClosed(file) => {
+                if self.current_screen.to_string() == Screens::BluetoothFileRequest.to_string() {
+                    sender.input(Message::GoBack);
+                }
+                // the progress of an accepted file shows with the received ones
+                if let Some(file) = file {
+                    self.received_files_page
+                        .emit(ReceivedFilesPageMessage::FileAccepted(file));
+                    sender.input(Message::ReceivedFiles);
+                }
+            }
+            Message::ReceivedFiles => {
+                self.received_files_page
+                    .emit(ReceivedFilesPageMessage::RefreshHistory);
+                if self.current_screen.to_string() != Screens::ReceivedFiles.to_string() {
+                    sender.input(Message::ChangeScreen(Screens::ReceivedFiles));
+                }
+            }
+            Message::ProxySettings => {
+                self.proxy_page.emit(ProxyPageMessage::RefreshConfig);
+                sender.input(Message::ChangeScreen(Screens::Proxy));
+            }
+            Message::IpConfigApplied(connection) => {
+                self.emit_ip_connection(connection);
+                // return to the IP settings the change was made from
+                while self.current_screen.to_string() != Screens::IPSettings.to_string() {
+                    match self.previous_screen.pop() {
+                        Some(previous_screen) => self.current_screen = previous_screen,
+                        None => break,
+                    }
+                }
+            }
             _ => (),
         }
     }
@@ -1094,6 +1557,30 @@ impl SimpleComponent for LockScreen {
     }
 }
 
+impl LockScreen {
+    /// Points the IP settings pages at the connection being configured
+    fn emit_ip_connection(&self, connection: IpConnection) {
+        self.ip_settings_page
+            .emit(IPSettingsPageMessage::SetConnection(connection.clone()));
+        self.protocol_modes_page
+            .emit(ProtocolModesPageMessage::SetConnection(connection.clone()));
+        self.protocol_details_page
+            .emit(ProtocolDetailsPageMessage::SetConnection(connection));
+    }
+}
+
+/// Settings of a headless service, which exits rather than running
+/// on defaults the device was not set up with
+fn read_service_settings() -> LockScreenSettings {
+    match settings::read_settings_yml() {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("error while reading settings {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     // Enables logger
     // install global collector configured based on RUST_LOG env var.
@@ -1102,6 +1589,40 @@ fn main() {
         .with_env_filter("settings=trace")
         .with_thread_names(true)
         .init();
+
+    // runs headless as a service so traffic is counted while the app is closed
+    if std::env::args().any(|arg| arg == "--collect-data-usage") {
+        let settings = read_service_settings();
+        backends::data_usage::init_data_usage_backend(&settings.backends.data_usage)
+            .run_collector();
+        return;
+    }
+
+    // runs headless as the only owner of the backlight while the switch
+    // on the display page is on, the app only writes the switch
+    if std::env::args().any(|arg| arg == "--auto-brightness") {
+        let settings = read_service_settings();
+        backends::display::init_display_backend(&settings.backends.display)
+            .run_auto_brightness();
+        return;
+    }
+
+    // runs headless once at session start to apply the manual DNS
+    // servers again, the network service replaced them on link up
+    if std::env::args().any(|arg| arg == "--restore-dns") {
+        let settings = read_service_settings();
+        backends::dns::restore_manual_dns(&settings.backends.dns);
+        return;
+    }
+
+    // runs headless in the session of the compositor to turn the
+    // screen off after the screen off timeout
+    if std::env::args().any(|arg| arg == "--idle-daemon") {
+        let settings = read_service_settings();
+        backends::display::init_display_backend(&settings.backends.display).run_idle_daemon();
+        return;
+    }
+
     let app = RelmApp::new("apps.settings").with_args(vec![]);
     app.run::<LockScreen>(());
 }
@@ -1,10 +1,17 @@
+use std::thread;
 
-use gtk::{prelude::*};
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
+use crate::{
+    backends::network::{
+        EapMethod, EnterpriseSettings, NetworkBackendHandle, Phase2Auth, WifiProfile,
+        WifiSecurity,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
 use custom_widgets::{
     icon_input::{
         IconInput, IconInputCss, InitSettings as IconInputSettings, OutputMessage as IconInputOutputMessage,
@@ -16,24 +23,56 @@ use custom_widgets::{
         IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
     }
 };
-use tracing::info;
+use tracing::{error, info};
+
+/// Security options in the order they are listed in the dropdown
+const SECURITY_OPTIONS: [WifiSecurity; 4] = [
+    WifiSecurity::Open,
+    WifiSecurity::WpaPsk,
+    WifiSecurity::Wpa3Sae,
+    WifiSecurity::Enterprise,
+];
+
+const EAP_OPTIONS: [EapMethod; 3] = [EapMethod::Peap, EapMethod::Ttls, EapMethod::Tls];
+
+const PHASE2_AUTH_OPTIONS: [Phase2Auth; 4] = [
+    Phase2Auth::Mschapv2,
+    Phase2Auth::Gtc,
+    Phase2Auth::Pap,
+    Phase2Auth::Chap,
+];
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
 }
 
 //Model
 pub struct AddNetworkPage {
     settings: Settings,
+    network_name: String,
+    security: WifiSecurity,
+    hidden: bool,
+    password: String,
+    enterprise: EnterpriseSettings,
+    error: Option<String>,
+    is_saving: bool,
 }
 
 //Widgets
 pub struct AddNetworkPageWidgets {
     network_name_input: Controller<IconInput>,
+    password_box: gtk::Box,
     password_input: Controller<IconInputPassword>,
+    enterprise_box: gtk::Box,
+    phase2_auth_box: gtk::Box,
+    tls_box: gtk::Box,
+    enterprise_inputs: Vec<Controller<IconInput>>,
+    enterprise_password_inputs: Vec<Controller<IconInputPassword>>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
     submit_button: Controller<IconButton>,
 }
@@ -45,7 +84,20 @@ pub enum Message {
     HomeIconPressed,
     PasswordChange(String),
     NetworkNameChange(String),
-    SubmitPressed
+    SecurityChanged(u32),
+    HiddenToggled(bool),
+    EapMethodChanged(u32),
+    Phase2AuthChanged(u32),
+    IdentityChange(String),
+    AnonymousIdentityChange(String),
+    EapPasswordChange(String),
+    CaCertChange(String),
+    ClientCertChange(String),
+    PrivateKeyChange(String),
+    PrivateKeyPasswordChange(String),
+    SubmitPressed,
+    SaveFailed(String),
+    NetworkAdded,
 }
 
 impl SimpleComponent for AddNetworkPage {
@@ -89,16 +141,43 @@ impl SimpleComponent for AddNetworkPage {
             .css_classes(["add-network-name-label"])
             .build();
 
-        let network_name_input = IconInput::builder()
-            .launch(IconInputSettings {
-                clear_icon: None,
-                icon: None,
-                placeholder: Option::from("Network Name".to_string()),
-                css: IconInputCss::default(),
-            })
-            .forward(sender.input_sender(), |msg| match msg {
-                IconInputOutputMessage::InputChange(text) => Message::NetworkNameChange(text),
-            });
+        let network_name_input = text_input("Network Name", &sender, Message::NetworkNameChange);
+
+        let security_label = field_label("Security");
+        let security_names: Vec<String> =
+            SECURITY_OPTIONS.iter().map(|security| security.to_string()).collect();
+        let security_dropdown = dropdown(&security_names);
+        security_dropdown.set_selected(1);
+        security_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::SecurityChanged(dropdown.selected()));
+        }));
+
+        let hidden_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .css_classes(["settings-item-details-box-row", "add-network-hidden-row"])
+            .build();
+
+        let hidden_text = gtk::Label::builder()
+            .label("Hidden Network")
+            .hexpand(true)
+            .halign(gtk::Align::Start)
+            .css_classes(["custom-switch-text"])
+            .build();
+
+        let hidden_switch = gtk::Switch::new();
+        hidden_switch.add_css_class("custom-switch");
+        hidden_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::HiddenToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        hidden_row.append(&hidden_text);
+        hidden_row.append(&hidden_switch);
+
+        let password_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
 
         let password_label = gtk::Label::builder()
             .label("Password")
@@ -106,22 +185,103 @@ impl SimpleComponent for AddNetworkPage {
             .css_classes(["add-network-password-label"])
             .build();
 
-        let password_input = IconInputPassword::builder()
-            .launch(IconInputPasswordSettings {
-                icon: modules.peek_password.icon.default.to_owned(),
-                toggle_icon: None,
-                placeholder: Option::from("Password".to_string()),
-                css: IconInputPasswordCss::default(),
-            })
-            .forward(sender.input_sender(), |msg| match msg {
-                IconInputPasswordOutputMessage::InputChange(text) => Message::PasswordChange(text),
-            });
+        let password_input = password_input(&modules, "Password", &sender, Message::PasswordChange);
+
+        password_box.append(&password_label);
+        password_box.append(password_input.widget());
+
+        // 802.1X fields, only shown for enterprise networks
+        let enterprise_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+
+        let eap_names: Vec<String> = EAP_OPTIONS.iter().map(|eap| eap.to_string()).collect();
+        let eap_dropdown = dropdown(&eap_names);
+        eap_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::EapMethodChanged(dropdown.selected()));
+        }));
+        enterprise_box.append(&field_label("EAP Method"));
+        enterprise_box.append(&eap_dropdown);
+
+        let phase2_auth_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        let phase2_auth_names: Vec<String> =
+            PHASE2_AUTH_OPTIONS.iter().map(|auth| auth.to_string()).collect();
+        let phase2_auth_dropdown = dropdown(&phase2_auth_names);
+        phase2_auth_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::Phase2AuthChanged(dropdown.selected()));
+        }));
+        phase2_auth_box.append(&field_label("Phase 2 Authentication"));
+        phase2_auth_box.append(&phase2_auth_dropdown);
+        enterprise_box.append(&phase2_auth_box);
+
+        let identity_input = text_input("Identity", &sender, Message::IdentityChange);
+        let anonymous_identity_input =
+            text_input("Anonymous Identity", &sender, Message::AnonymousIdentityChange);
+        let ca_cert_input = text_input("CA Certificate Path", &sender, Message::CaCertChange);
+        let eap_password_input =
+            password_input(&modules, "Password", &sender, Message::EapPasswordChange);
+
+        enterprise_box.append(&field_label("Identity"));
+        enterprise_box.append(identity_input.widget());
+        enterprise_box.append(&field_label("Anonymous Identity"));
+        enterprise_box.append(anonymous_identity_input.widget());
+        enterprise_box.append(&field_label("CA Certificate"));
+        enterprise_box.append(ca_cert_input.widget());
+        phase2_auth_box.append(&field_label("Password"));
+        phase2_auth_box.append(eap_password_input.widget());
+
+        let tls_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+        let client_cert_input =
+            text_input("Client Certificate Path", &sender, Message::ClientCertChange);
+        let private_key_input = text_input("Private Key Path", &sender, Message::PrivateKeyChange);
+        let private_key_password_input = password_input(
+            &modules,
+            "Private Key Password",
+            &sender,
+            Message::PrivateKeyPasswordChange,
+        );
+        tls_box.append(&field_label("Client Certificate"));
+        tls_box.append(client_cert_input.widget());
+        tls_box.append(&field_label("Private Key"));
+        tls_box.append(private_key_input.widget());
+        tls_box.append(private_key_password_input.widget());
+        enterprise_box.append(&tls_box);
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        let form = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        form.append(&network_name_label);
+        form.append(network_name_input.widget());
+        form.append(&security_label);
+        form.append(&security_dropdown);
+        form.append(&hidden_row);
+        form.append(&password_box);
+        form.append(&enterprise_box);
+        form.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&form)
+            .build();
 
         root.append(&header);
-        root.append(&network_name_label);
-        root.append(network_name_input.widget());
-        root.append(&password_label);
-        root.append(password_input.widget());
+        root.append(&scrolled_window);
 
         let footer = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
@@ -161,11 +321,33 @@ impl SimpleComponent for AddNetworkPage {
         root.append(&footer);
 
 
-        let model = AddNetworkPage { settings: init };
+        let model = AddNetworkPage {
+            settings: init,
+            network_name: String::new(),
+            security: SECURITY_OPTIONS[1],
+            hidden: false,
+            password: String::new(),
+            enterprise: EnterpriseSettings::default(),
+            error: None,
+            is_saving: false,
+        };
 
         let widgets = AddNetworkPageWidgets {
             password_input,
             network_name_input,
+            password_box,
+            enterprise_box,
+            phase2_auth_box,
+            tls_box,
+            enterprise_inputs: vec![
+                identity_input,
+                anonymous_identity_input,
+                ca_cert_input,
+                client_cert_input,
+                private_key_input,
+            ],
+            enterprise_password_inputs: vec![eap_password_input, private_key_password_input],
+            error_label,
             back_button,
             submit_button,
         };
@@ -182,11 +364,177 @@ impl SimpleComponent for AddNetworkPage {
             Message::HomeIconPressed => {
                 sender.output(Message::HomeIconPressed);
             },
-            Message::PasswordChange(text) => {},
-            Message::NetworkNameChange(text) => {},
-            Message::SubmitPressed => {}
+            Message::PasswordChange(text) => {
+                self.password = text;
+            },
+            Message::NetworkNameChange(text) => {
+                self.network_name = text;
+            },
+            Message::SecurityChanged(index) => {
+                if let Some(security) = SECURITY_OPTIONS.get(index as usize) {
+                    self.security = *security;
+                    self.error = None;
+                }
+            }
+            Message::HiddenToggled(hidden) => {
+                self.hidden = hidden;
+            }
+            Message::EapMethodChanged(index) => {
+                if let Some(eap) = EAP_OPTIONS.get(index as usize) {
+                    self.enterprise.eap = *eap;
+                }
+            }
+            Message::Phase2AuthChanged(index) => {
+                if let Some(phase2_auth) = PHASE2_AUTH_OPTIONS.get(index as usize) {
+                    self.enterprise.phase2_auth = *phase2_auth;
+                }
+            }
+            Message::IdentityChange(text) => {
+                self.enterprise.identity = text;
+            }
+            Message::AnonymousIdentityChange(text) => {
+                self.enterprise.anonymous_identity = non_empty(text);
+            }
+            Message::EapPasswordChange(text) => {
+                self.enterprise.password = non_empty(text);
+            }
+            Message::CaCertChange(text) => {
+                self.enterprise.ca_cert = non_empty(text);
+            }
+            Message::ClientCertChange(text) => {
+                self.enterprise.client_cert = non_empty(text);
+            }
+            Message::PrivateKeyChange(text) => {
+                self.enterprise.private_key = non_empty(text);
+            }
+            Message::PrivateKeyPasswordChange(text) => {
+                self.enterprise.private_key_password = non_empty(text);
+            }
+            Message::SubmitPressed => {
+                if self.is_saving {
+                    return;
+                }
+
+                let profile = self.profile();
+                if let Err(reason) = profile.validate() {
+                    self.error = Some(reason);
+                    return;
+                }
+
+                self.is_saving = true;
+                self.error = None;
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.add_wifi_profile(&profile) {
+                    Ok(_) => sender.input(Message::NetworkAdded),
+                    Err(e) => {
+                        error!("error while adding network {} {}", profile.ssid, e);
+                        sender.input(Message::SaveFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::SaveFailed(reason) => {
+                self.is_saving = false;
+                self.error = Some(reason);
+            }
+            Message::NetworkAdded => {
+                self.is_saving = false;
+                let _ = sender.output(Message::NetworkAdded);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let is_enterprise = self.security == WifiSecurity::Enterprise;
+        widgets.password_box.set_visible(matches!(
+            self.security,
+            WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae
+        ));
+        widgets.enterprise_box.set_visible(is_enterprise);
+        widgets
+            .phase2_auth_box
+            .set_visible(self.enterprise.eap != EapMethod::Tls);
+        widgets.tls_box.set_visible(self.enterprise.eap == EapMethod::Tls);
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+impl AddNetworkPage {
+    /// Builds the profile to save from the fields relevant to the chosen security
+    fn profile(&self) -> WifiProfile {
+        let password = match self.security {
+            WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae => Some(self.password.clone()),
+            _ => None,
+        };
+        let enterprise = match self.security {
+            WifiSecurity::Enterprise => Some(self.enterprise.clone()),
+            _ => None,
+        };
+
+        WifiProfile {
+            ssid: self.network_name.trim().to_string(),
+            hidden: self.hidden,
+            security: self.security,
+            password,
+            enterprise,
+        }
+    }
+}
+
+fn non_empty(text: String) -> Option<String> {
+    match text.trim().is_empty() {
+        true => None,
+        false => Some(text.trim().to_string()),
+    }
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
+
+fn dropdown(options: &[String]) -> gtk::DropDown {
+    let options: Vec<&str> = options.iter().map(|s| s.as_str()).collect();
+    let model = gtk::StringList::new(&options);
+    gtk::DropDown::new(Some(model), gtk::Expression::NONE)
+}
+
+fn text_input(
+    placeholder: &str,
+    sender: &ComponentSender<AddNetworkPage>,
+    on_change: fn(String) -> Message,
+) -> Controller<IconInput> {
+    IconInput::builder()
+        .launch(IconInputSettings {
+            clear_icon: None,
+            icon: None,
+            placeholder: Option::from(placeholder.to_string()),
+            css: IconInputCss::default(),
+        })
+        .forward(sender.input_sender(), move |msg| match msg {
+            IconInputOutputMessage::InputChange(text) => on_change(text),
+        })
+}
+
+fn password_input(
+    modules: &Modules,
+    placeholder: &str,
+    sender: &ComponentSender<AddNetworkPage>,
+    on_change: fn(String) -> Message,
+) -> Controller<IconInputPassword> {
+    IconInputPassword::builder()
+        .launch(IconInputPasswordSettings {
+            icon: modules.peek_password.icon.default.to_owned(),
+            toggle_icon: None,
+            placeholder: Option::from(placeholder.to_string()),
+            css: IconInputPasswordCss::default(),
+        })
+        .forward(sender.input_sender(), move |msg| match msg {
+            IconInputPasswordOutputMessage::InputChange(text) => on_change(text),
+        })
 }
@@ -6,9 +6,11 @@ use relm4::{
 };
 
 use crate::{
+    backends::display::format_timeout,
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_item::{
-            CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
+            CustomListItem, CustomListItemSettings, InputMessage as CustomListItemInputMessage,
+            Message as CustomListItemMessage,
         },
 };
 
@@ -28,10 +30,13 @@ pub struct Settings {
 //Model
 pub struct BatteryPage {
     settings: Settings,
+    /// Screen off timeout in seconds, the same one the display page shows
+    screen_timeout: Option<u32>,
 }
 
 //Widgets
 pub struct BatteryPageWidgets {
+    screen_off_timeout: Controller<CustomListItem>,
     back_button: Controller<IconButton>,
 }
 
@@ -41,7 +46,8 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     ScreenTimeoutOpted,
-    PerformanceOpted
+    PerformanceOpted,
+    SetScreenTimeout(u32),
 }
 
 pub struct SettingItem {
@@ -180,9 +186,13 @@ impl SimpleComponent for BatteryPage {
 
         root.append(&footer);
 
-        let model = BatteryPage { settings: init };
+        let model = BatteryPage {
+            settings: init,
+            screen_timeout: None,
+        };
 
         let widgets = BatteryPageWidgets {
+            screen_off_timeout,
             back_button
         };
 
@@ -202,8 +212,17 @@ impl SimpleComponent for BatteryPage {
             Message::PerformanceOpted => {
                 let _ = sender.output(Message::PerformanceOpted);
             }
+            Message::SetScreenTimeout(timeout) => {
+                self.screen_timeout = Some(timeout);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        if let Some(timeout) = self.screen_timeout {
+            widgets
+                .screen_off_timeout
+                .emit(CustomListItemInputMessage::SetValue(format_timeout(timeout)));
+        }
+    }
 }
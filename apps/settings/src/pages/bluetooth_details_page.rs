@@ -1,31 +1,65 @@
-use gtk::prelude::*;
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     ComponentParts, ComponentSender, SimpleComponent, Controller, Component, ComponentController,
 };
 
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
-use custom_widgets::icon_button::{
-    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+use crate::{
+    backends::bluetooth::{BluetoothBackendHandle, BluetoothDevice, BluetoothEvent},
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
 };
 
-use tracing::info;
+use tracing::{error, info};
+
+/// Longest name a Bluetooth device can have, in bytes
+const MAX_ALIAS_LENGTH: usize = 248;
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub bluetooth_backend: BluetoothBackendHandle,
 }
 
 //Model
 pub struct BluetoothDetailsPage {
     settings: Settings,
+    device: Option<BluetoothDevice>,
+    alias: String,
+    is_busy: bool,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct BluetoothDetailsPageWidgets {
+    device_name: gtk::Label,
+    alias_input: Controller<IconInput>,
+    rename_button: gtk::Button,
+    address_value: gtk::Label,
+    device_type_value: gtk::Label,
+    status_value: gtk::Label,
+    paired_value: gtk::Label,
+    battery_row: gtk::Box,
+    battery_value: gtk::Label,
+    trusted_switch: gtk::Switch,
+    blocked_switch: gtk::Switch,
+    connect_button: gtk::Button,
+    forget_button: gtk::Button,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
+    rendered_id: Option<String>,
 }
 
 //Messages
@@ -34,6 +68,19 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     HomeIconPressed,
+    SetDevice(BluetoothDevice),
+    BluetoothEvent(BluetoothEvent),
+    AliasChange(String),
+    RenamePressed,
+    ConnectPressed,
+    TrustedToggled(bool),
+    BlockedToggled(bool),
+    ForgetPressed,
+    RefreshDevice,
+    DeviceUpdated(BluetoothDevice),
+    RequestDone,
+    RequestFailed(String),
+    DeviceForgotten,
 }
 
 pub struct SettingItem {
@@ -63,8 +110,8 @@ impl SimpleComponent for BluetoothDetailsPage {
         let layout = init.layout.clone();
         let widget_configs = init.widget_configs.clone();
 
-        let network_name = gtk::Label::builder()
-            .label("Macbook Pro")
+        let device_name = gtk::Label::builder()
+            .label("")
             .css_classes(["header-title"])
             .build();
 
@@ -73,7 +120,34 @@ impl SimpleComponent for BluetoothDetailsPage {
             .css_classes(["header"])
             .build();
 
-        header.append(&network_name);
+        header.append(&device_name);
+
+        let alias_label = gtk::Label::builder()
+            .label("Name")
+            .css_classes(["bluetooth-details-list-label"])
+            .halign(gtk::Align::Start)
+            .build();
+
+        // an empty name goes back to the one the device gives itself
+        let alias_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("Device name".to_string()),
+                css: IconInputCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::AliasChange(text),
+            });
+
+        let rename_button = gtk::Button::builder()
+            .label("Rename")
+            .hexpand(true)
+            .css_classes(["bluetooth-action"])
+            .build();
+        rename_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::RenamePressed);
+        }));
 
         let device_type_label = gtk::Label::builder()
             .label("Device Type")
@@ -87,28 +161,100 @@ impl SimpleComponent for BluetoothDetailsPage {
             .build();
 
         let device_type_value = gtk::Label::builder()
-            .label("Mecha MX")
+            .label("")
             .css_classes(["bluetooth-details-device-type-value"])
             .halign(gtk::Align::Start)
             .build();
         device_type_box.append(&device_type_value);
 
-        let forget_network_button = gtk::Box::builder()
+        let details_box = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
-            .css_classes(["bluetooth-details-forget-btn-box"])
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let (address_row, address_value) = details_row("Address");
+        let (status_row, status_value) = details_row("Status");
+        let (paired_row, paired_value) = details_row("Paired");
+        let (battery_row, battery_value) = details_row("Battery");
+        battery_row.set_visible(false);
+
+        details_box.append(&address_row);
+        details_box.append(&status_row);
+        details_box.append(&paired_row);
+        details_box.append(&battery_row);
+
+        let switches_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        // trusted devices connect without asking the pairing agent
+        let trusted_switch = switch_row(&switches_box, "Trusted");
+        trusted_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::TrustedToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        let blocked_switch = switch_row(&switches_box, "Blocked");
+        blocked_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::BlockedToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        let connect_button = gtk::Button::builder()
+            .label("Connect")
+            .hexpand(true)
+            .css_classes(["bluetooth-action"])
             .build();
+        connect_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::ConnectPressed);
+        }));
 
-        let forget_network_text = gtk::Label::builder()
-            .label("Forget this network")
+        let forget_device_text = gtk::Label::builder()
+            .label("Forget this device")
             .css_classes(["bluetooth-details-forget-btn-text"])
             .halign(gtk::Align::Center)
             .build();
-        forget_network_button.append(&forget_network_text);
+
+        let forget_button = gtk::Button::builder()
+            .child(&forget_device_text)
+            .css_classes(["bluetooth-details-forget-btn-box"])
+            .build();
+        forget_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::ForgetPressed);
+        }));
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
 
         root.append(&header);
-        root.append(&device_type_label);
-        root.append(&device_type_box);
-        root.append(&forget_network_button);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&alias_label);
+        scrollable_content.append(alias_input.widget());
+        scrollable_content.append(&rename_button);
+        scrollable_content.append(&device_type_label);
+        scrollable_content.append(&device_type_box);
+        scrollable_content.append(&details_box);
+        scrollable_content.append(&switches_box);
+        scrollable_content.append(&connect_button);
+        scrollable_content.append(&forget_button);
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
 
         let footer = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
@@ -131,10 +277,31 @@ impl SimpleComponent for BluetoothDetailsPage {
         footer.append(back_button.widget());
         root.append(&footer);
 
-        let model = BluetoothDetailsPage { settings: init };
+        let model = BluetoothDetailsPage {
+            settings: init,
+            device: None,
+            alias: String::new(),
+            is_busy: false,
+            error: None,
+        };
 
         let widgets = BluetoothDetailsPageWidgets {
-            back_button
+            device_name,
+            alias_input,
+            rename_button,
+            address_value,
+            device_type_value,
+            status_value,
+            paired_value,
+            battery_row,
+            battery_value,
+            trusted_switch,
+            blocked_switch,
+            connect_button,
+            forget_button,
+            error_label,
+            back_button,
+            rendered_id: None,
         };
 
         ComponentParts { model, widgets }
@@ -144,13 +311,276 @@ impl SimpleComponent for BluetoothDetailsPage {
         info!("Update message is {:?}", message);
         match message {
             Message::MenuItemPressed(key) => {}
-            Message::BackPressed => {  
+            Message::BackPressed => {
                 let _ = sender.output(Message::BackPressed);}
             Message::HomeIconPressed => {
                 let _ = sender.output(Message::HomeIconPressed);
             }
+            Message::SetDevice(device) => {
+                self.alias = device.alias.clone();
+                self.device = Some(device);
+                self.is_busy = false;
+                self.error = None;
+            }
+            Message::BluetoothEvent(event) => match event {
+                BluetoothEvent::DeviceChanged(device) => {
+                    sender.input(Message::DeviceUpdated(device));
+                }
+                BluetoothEvent::DeviceRemoved(id) => {
+                    if self.device.as_ref().is_some_and(|device| device.id == id) {
+                        self.device = None;
+                        self.error = Some("the device is no longer known".to_string());
+                    }
+                }
+                BluetoothEvent::AdapterChanged(_) => {}
+            },
+            Message::AliasChange(text) => {
+                self.alias = text;
+            }
+            Message::RenamePressed => {
+                let id = match &self.device {
+                    Some(device) => device.id.clone(),
+                    None => return,
+                };
+                let alias = self.alias.trim().to_string();
+                if alias.len() > MAX_ALIAS_LENGTH {
+                    self.error = Some(format!(
+                        "the name must be at most {} bytes",
+                        MAX_ALIAS_LENGTH
+                    ));
+                    return;
+                }
+                self.error = None;
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_alias(&id, &alias)
+                });
+            }
+            Message::ConnectPressed => {
+                let (id, connected) = match &self.device {
+                    Some(device) => (device.id.clone(), device.connected),
+                    None => return,
+                };
+                if self.is_busy {
+                    return;
+                }
+                self.is_busy = true;
+                self.error = None;
+                self.request(sender, move |bluetooth_backend| match connected {
+                    true => bluetooth_backend.disconnect(&id),
+                    false => bluetooth_backend.connect(&id),
+                });
+            }
+            Message::TrustedToggled(trusted) => {
+                let device = match &mut self.device {
+                    Some(device) => device,
+                    None => return,
+                };
+                // the switch also emits when synced from the device
+                if device.trusted == trusted {
+                    return;
+                }
+                device.trusted = trusted;
+                let id = device.id.clone();
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_trusted(&id, trusted)
+                });
+            }
+            Message::BlockedToggled(blocked) => {
+                let device = match &mut self.device {
+                    Some(device) => device,
+                    None => return,
+                };
+                if device.blocked == blocked {
+                    return;
+                }
+                device.blocked = blocked;
+                let id = device.id.clone();
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_blocked(&id, blocked)
+                });
+            }
+            Message::ForgetPressed => {
+                let id = match &self.device {
+                    Some(device) => device.id.clone(),
+                    None => return,
+                };
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || match bluetooth_backend.remove_device(&id) {
+                    Ok(()) => sender.input(Message::DeviceForgotten),
+                    Err(e) => {
+                        error!("error while removing {} {}", id, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::RefreshDevice => {
+                let id = match &self.device {
+                    Some(device) => device.id.clone(),
+                    None => return,
+                };
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || match bluetooth_backend.devices() {
+                    Ok(devices) => {
+                        if let Some(device) = devices.into_iter().find(|device| device.id == id) {
+                            sender.input(Message::DeviceUpdated(device));
+                        }
+                    }
+                    Err(e) => error!("error while reading {} {}", id, e),
+                });
+            }
+            Message::DeviceUpdated(device) => {
+                // ignore devices the page is not showing
+                if self.device.as_ref().is_some_and(|known| known.id == device.id) {
+                    self.device = Some(device);
+                }
+            }
+            Message::RequestDone => {
+                self.is_busy = false;
+            }
+            Message::RequestFailed(e) => {
+                self.is_busy = false;
+                self.error = Some(e);
+                // switches were moved ahead of the device
+                sender.input(Message::RefreshDevice);
+            }
+            Message::DeviceForgotten => {
+                self.device = None;
+                let _ = sender.output(Message::DeviceForgotten);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+
+        let device = match &self.device {
+            Some(device) => device,
+            None => {
+                for widget in [
+                    widgets.rename_button.upcast_ref::<gtk::Widget>(),
+                    widgets.trusted_switch.upcast_ref(),
+                    widgets.blocked_switch.upcast_ref(),
+                    widgets.connect_button.upcast_ref(),
+                    widgets.forget_button.upcast_ref(),
+                ] {
+                    widget.set_sensitive(false);
+                }
+                return;
+            }
+        };
+
+        if widgets.rendered_id.as_ref() != Some(&device.id) {
+            widgets
+                .alias_input
+                .emit(IconInputInputMessage::SetText(device.alias.clone()));
+            widgets.rendered_id = Some(device.id.clone());
+        }
+
+        widgets.device_name.set_label(&device.to_string());
+        widgets.address_value.set_label(&device.address);
+        widgets.device_type_value.set_label(device.category());
+        widgets.status_value.set_label(match device.connected {
+            true => "Connected",
+            false => "Not connected",
+        });
+        widgets
+            .paired_value
+            .set_label(if device.paired { "Yes" } else { "No" });
+        widgets.battery_row.set_visible(device.battery.is_some());
+        widgets
+            .battery_value
+            .set_label(&format!("{}%", device.battery.unwrap_or_default()));
+
+        widgets.trusted_switch.set_active(device.trusted);
+        widgets.blocked_switch.set_active(device.blocked);
+
+        let connect_label = match (self.is_busy, device.connected) {
+            (true, true) => "Disconnecting...",
+            (true, false) => "Connecting...",
+            (false, true) => "Disconnect",
+            (false, false) => "Connect",
+        };
+        widgets.connect_button.set_label(connect_label);
+        // blocked devices cannot connect until unblocked
+        widgets
+            .connect_button
+            .set_sensitive(!self.is_busy && !device.blocked);
+
+        for widget in [
+            widgets.rename_button.upcast_ref::<gtk::Widget>(),
+            widgets.trusted_switch.upcast_ref(),
+            widgets.blocked_switch.upcast_ref(),
+            widgets.forget_button.upcast_ref(),
+        ] {
+            widget.set_sensitive(true);
+        }
+    }
+}
+
+impl BluetoothDetailsPage {
+    /// Runs a call of the backend off the main thread
+    fn request<F>(&self, sender: ComponentSender<Self>, call: F)
+    where
+        F: FnOnce(&BluetoothBackendHandle) -> anyhow::Result<()> + Send + 'static,
+    {
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        thread::spawn(move || match call(&bluetooth_backend) {
+            Ok(()) => sender.input(Message::RequestDone),
+            Err(e) => {
+                error!("error while changing bluetooth device {}", e);
+                sender.input(Message::RequestFailed(e.to_string()));
+            }
+        });
+    }
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .selectable(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
+
+/// Appends a row with a switch to `container`, returns the switch
+fn switch_row(container: &gtk::Box, text: &str) -> gtk::Switch {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let label = gtk::Label::builder()
+        .label(text)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-switch-text"])
+        .build();
+
+    let switch = gtk::Switch::new();
+    switch.add_css_class("custom-switch");
+
+    row.append(&label);
+    row.append(&switch);
+    container.append(&row);
+    switch
 }
@@ -0,0 +1,268 @@
+use std::thread;
+
+use gtk::prelude::*;
+use relm4::{
+    gtk::{self},
+    ComponentParts, ComponentSender, SimpleComponent, Controller, Component, ComponentController,
+};
+use crate::{
+    backends::{
+        bluetooth::{BluetoothBackendHandle, IncomingFile},
+        data_usage::format_bytes,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::icon_button::{
+    IconButton, IconButtonCss, InitSettings as IconButtonStetings,
+    OutputMessage as IconButtonOutputMessage,
+};
+
+use tracing::{error, info};
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub bluetooth_backend: BluetoothBackendHandle,
+    pub download_dir: String,
+}
+
+//Model
+pub struct BluetoothFileRequestPage {
+    settings: Settings,
+    file: Option<IncomingFile>,
+}
+
+//Widgets
+pub struct BluetoothFileRequestPageWidgets {
+    file_request_label: gtk::Label,
+    file_name_value: gtk::Label,
+    file_size_value: gtk::Label,
+    back_button: Controller<IconButton>,
+    submit_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    MenuItemPressed(String),
+    BackPressed,
+    HomeIconPressed,
+    SubmitPressed,
+    SetFile(IncomingFile),
+    /// The sender gave up on the file, by `id`
+    RequestCancelled(u64),
+    /// The file was answered or went away, with the file if accepted.
+    /// Accepted files are answered where their progress is shown
+    Closed(Option<IncomingFile>),
+}
+
+impl SimpleComponent for BluetoothFileRequestPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = BluetoothFileRequestPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let modules = init.modules.clone();
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Incoming File")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        let file_request_label = gtk::Label::builder()
+            .label("")
+            .wrap(true)
+            .css_classes(["bluetooth-pair-request-list-label"])
+            .halign(gtk::Align::Start)
+            .build();
+
+        let file_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let (file_name_row, file_name_value) = details_row("Name");
+        let (file_size_row, file_size_value) = details_row("Size");
+        file_box.append(&file_name_row);
+        file_box.append(&file_size_row);
+
+        root.append(&header);
+        root.append(&file_request_label);
+        root.append(&file_box);
+
+        let footer = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["footer"])
+            .vexpand(true)
+            .hexpand(true)
+            .valign(gtk::Align::End)
+            .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+
+        let submit_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: modules.submit.icon.default.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::SubmitPressed,
+            });
+        let submit_button_widget = submit_button.widget();
+        submit_button_widget.set_hexpand(true);
+        submit_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(submit_button_widget);
+
+        root.append(&footer);
+
+        let model = BluetoothFileRequestPage {
+            settings: init,
+            file: None,
+        };
+
+        let widgets = BluetoothFileRequestPageWidgets {
+            file_request_label,
+            file_name_value,
+            file_size_value,
+            back_button,
+            submit_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::MenuItemPressed(key) => {},
+            Message::BackPressed => {
+                // leaving the page declines, obexd would wait for an answer
+                match self.file.take() {
+                    Some(file) => {
+                        self.decline(&file);
+                        let _ = sender.output(Message::Closed(None));
+                    }
+                    None => {
+                        let _ = sender.output(Message::BackPressed);
+                    }
+                }
+            },
+            Message::HomeIconPressed => {
+                sender.output(Message::HomeIconPressed);
+            }
+            Message::SubmitPressed => {
+                if let Some(file) = self.file.take() {
+                    let _ = sender.output(Message::Closed(Some(file)));
+                }
+            }
+            Message::SetFile(file) => {
+                // a file the page replaces is declined
+                if let Some(previous) = self.file.take() {
+                    self.decline(&previous);
+                }
+                self.file = Some(file);
+            }
+            Message::RequestCancelled(id) => {
+                if self.file.as_ref().is_some_and(|file| file.id == id) {
+                    self.file = None;
+                    let _ = sender.output(Message::Closed(None));
+                }
+            }
+            Message::Closed(_) => {}
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        widgets.file_request_label.set_label(&format!(
+            "'{}' wants to send you a file. \nAccepted files are saved to {}.",
+            file.sender, self.settings.download_dir
+        ));
+        widgets.file_name_value.set_label(match file.filename.is_empty() {
+            true => "Unnamed",
+            false => &file.filename,
+        });
+        widgets.file_size_value.set_label(
+            &file
+                .size
+                .map(format_bytes)
+                .unwrap_or_else(|| String::from("Unknown")),
+        );
+    }
+}
+
+impl BluetoothFileRequestPage {
+    /// Declines the offer off the main thread
+    fn decline(&self, file: &IncomingFile) {
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        let id = file.id;
+        thread::spawn(move || {
+            if let Err(e) = bluetooth_backend.reply_file(id, false) {
+                error!("error while declining file offer {} {}", id, e);
+            }
+        });
+    }
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .wrap(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
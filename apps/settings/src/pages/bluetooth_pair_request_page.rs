@@ -1,31 +1,43 @@
+use std::thread;
 
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     ComponentParts, ComponentSender, SimpleComponent, Controller, Component, ComponentController,
 };
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
+use crate::{
+    backends::bluetooth::{
+        format_passkey, service_name, BluetoothBackendHandle, PairingKind, PairingReply,
+        PairingRequest,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
 use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings,
     InputMessage as IconButtonInputMessage, OutputMessage as IconButtonOutputMessage,
 };
 
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub bluetooth_backend: BluetoothBackendHandle,
 }
 
 //Model
 pub struct BluetoothPairRequestPage {
     settings: Settings,
+    request: Option<PairingRequest>,
 }
 
 //Widgets
 pub struct BluetoothPairRequestPageWidgets {
+    pairing_request_label: gtk::Label,
+    pairing_request_box: gtk::Box,
+    pairing_request_value: gtk::Label,
     back_button: Controller<IconButton>,
     submit_button: Controller<IconButton>,
 }
@@ -36,7 +48,14 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     HomeIconPressed,
-    SubmitPressed
+    SubmitPressed,
+    SetRequest(PairingRequest),
+    /// BlueZ gave up on a request, by `id`
+    RequestCancelled(u64),
+    /// Pairing of a device ended, by device `id`
+    PairingFinished(String),
+    /// The request was answered or went away
+    Closed,
 }
 
 pub struct SettingItem {
@@ -79,7 +98,8 @@ impl SimpleComponent for BluetoothPairRequestPage {
         header.append(&header_title);
 
         let pairing_request_label = gtk::Label::builder()
-            .label("'Macbook Pro' has requested to pair with your device. \nConfirm this code on their device to connect.")
+            .label("")
+            .wrap(true)
             .css_classes(["bluetooth-pair-request-list-label"])
             .halign(gtk::Align::Start)
             .build();
@@ -90,7 +110,7 @@ impl SimpleComponent for BluetoothPairRequestPage {
             .build();
 
         let pairing_request_value = gtk::Label::builder()
-            .label("243 562")
+            .label("")
             .css_classes(["bluetooth-pair-request-value"])
             .halign(gtk::Align::Center)
             .build();
@@ -137,9 +157,15 @@ impl SimpleComponent for BluetoothPairRequestPage {
 
         root.append(&footer);
 
-        let model = BluetoothPairRequestPage { settings: init };
+        let model = BluetoothPairRequestPage {
+            settings: init,
+            request: None,
+        };
 
         let widgets = BluetoothPairRequestPageWidgets {
+            pairing_request_label,
+            pairing_request_box,
+            pairing_request_value,
             back_button,
             submit_button,
         };
@@ -152,14 +178,113 @@ impl SimpleComponent for BluetoothPairRequestPage {
         match message {
             Message::MenuItemPressed(key) => {},
             Message::BackPressed => {
-                let _ = sender.output(Message::BackPressed);
+                // leaving the page declines, BlueZ would wait for an answer
+                match self.request.take() {
+                    Some(request) => {
+                        self.reply(request, PairingReply::Reject);
+                        let _ = sender.output(Message::Closed);
+                    }
+                    None => {
+                        let _ = sender.output(Message::BackPressed);
+                    }
+                }
             },
             Message::HomeIconPressed => {
                 sender.output(Message::HomeIconPressed);
             }
-            Message::SubmitPressed => {}
+            Message::SubmitPressed => {
+                if let Some(request) = self.request.take() {
+                    self.reply(request, PairingReply::Accept);
+                    let _ = sender.output(Message::Closed);
+                }
+            }
+            Message::SetRequest(request) => {
+                // a request the page replaces is declined
+                if let Some(previous) = self.request.take() {
+                    self.reply(previous, PairingReply::Reject);
+                }
+                self.request = Some(request);
+            }
+            Message::RequestCancelled(id) => {
+                if self.request.as_ref().is_some_and(|request| request.id == id) {
+                    self.request = None;
+                    let _ = sender.output(Message::Closed);
+                }
+            }
+            Message::PairingFinished(id) => {
+                // displayed codes stay up until the device finished pairing
+                let is_displayed = self.request.as_ref().is_some_and(|request| {
+                    request.device.id == id && !request.kind.needs_reply()
+                });
+                if is_displayed {
+                    self.request = None;
+                    let _ = sender.output(Message::Closed);
+                }
+            }
+            Message::Closed => {}
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let request = match &self.request {
+            Some(request) => request,
+            None => return,
+        };
+        let device = &request.device;
+        let (text, code) = match &request.kind {
+            PairingKind::Confirmation(passkey) => (
+                format!(
+                    "'{}' has requested to pair with your device. \nConfirm this code is shown on their device to connect.",
+                    device
+                ),
+                Some(format_passkey(*passkey)),
+            ),
+            PairingKind::DisplayPasskey(passkey, entered) => (
+                format!(
+                    "Type this code on '{}' to pair. \n{} of 6 digits typed.",
+                    device, entered
+                ),
+                Some(format_passkey(*passkey)),
+            ),
+            PairingKind::DisplayPinCode(pin_code) => (
+                format!("Type this PIN code on '{}' to pair.", device),
+                Some(pin_code.clone()),
+            ),
+            PairingKind::Authorization => (
+                format!("'{}' has requested to pair with your device.", device),
+                None,
+            ),
+            PairingKind::AuthorizeService(uuid) => (
+                format!(
+                    "'{}' wants to use {} on your device.",
+                    device,
+                    service_name(uuid).unwrap_or(uuid.as_str())
+                ),
+                None,
+            ),
+            // typed codes are asked for on the connect page
+            PairingKind::Passkey | PairingKind::PinCode => (String::new(), None),
+        };
+        widgets.pairing_request_label.set_label(&text);
+        widgets.pairing_request_box.set_visible(code.is_some());
+        widgets
+            .pairing_request_value
+            .set_label(code.as_deref().unwrap_or(""));
+    }
+}
+
+impl BluetoothPairRequestPage {
+    /// Answers the request off the main thread, displayed codes have
+    /// no one waiting for an answer
+    fn reply(&self, request: PairingRequest, reply: PairingReply) {
+        if !request.kind.needs_reply() {
+            return;
+        }
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        thread::spawn(move || {
+            if let Err(e) = bluetooth_backend.reply_pairing(request.id, reply) {
+                error!("error while answering pairing request {} {}", request.id, e);
+            }
+        });
+    }
 }
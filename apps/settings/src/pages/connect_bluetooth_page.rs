@@ -1,14 +1,20 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::bluetooth::{
+        BluetoothBackendHandle, PairingKind, PairingReply, PairingRequest, MAX_PASSKEY,
+    },
     settings::{LayoutSettings, Modules, WidgetConfigs},
 };
 use custom_widgets::{
     icon_input::{
-        IconInput, IconInputCss, InitSettings as IconInputSettings, OutputMessage as IconInputOutputMessage,
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
     },
     icon_button::{
         IconButton, IconButtonCss, InitSettings as IconButtonStetings,
@@ -16,26 +22,36 @@ use custom_widgets::{
     }
 };
 
+use tracing::{error, info};
 
-use tracing::info;
+/// Longest PIN code BlueZ accepts
+const MAX_PIN_CODE_LENGTH: usize = 16;
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub bluetooth_backend: BluetoothBackendHandle,
 }
 
 //Model
 pub struct ConnectBluetoothPage {
     settings: Settings,
+    request: Option<PairingRequest>,
+    code: String,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct ConnectBluetoothPageWidgets {
+    header_label: gtk::Label,
+    code_input_label: gtk::Label,
     code_input: Controller<IconInput>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
     submit_button: Controller<IconButton>,
+    rendered_request: Option<PairingRequest>,
 }
 
 //Messages
@@ -45,7 +61,12 @@ pub enum Message {
     BackPressed,
     HomeIconPressed,
     PasswordChange(String),
-    SubmitPressed
+    SubmitPressed,
+    SetRequest(PairingRequest),
+    /// BlueZ gave up on a request, by `id`
+    RequestCancelled(u64),
+    /// The request was answered or went away
+    Closed,
 }
 
 pub struct SettingItem {
@@ -76,7 +97,7 @@ impl SimpleComponent for ConnectBluetoothPage {
         let widget_configs = init.widget_configs.clone();
 
         let enter_password_label = gtk::Label::builder()
-            .label("Pair with 'Macbook Pro'")
+            .label("")
             .css_classes(["header-title"])
             .build();
 
@@ -90,6 +111,7 @@ impl SimpleComponent for ConnectBluetoothPage {
         let code_input_label = gtk::Label::builder()
             .label("Enter code shared by the device here")
             .halign(gtk::Align::Start)
+            .wrap(true)
             .css_classes(["text-14-label"])
             .build();
 
@@ -104,9 +126,18 @@ impl SimpleComponent for ConnectBluetoothPage {
                 IconInputOutputMessage::InputChange(text) => Message::PasswordChange(text),
             });
 
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
         root.append(&header);
         root.append(&code_input_label);
         root.append(code_input.widget());
+        root.append(&error_label);
 
         let footer = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
@@ -145,12 +176,21 @@ impl SimpleComponent for ConnectBluetoothPage {
         
         root.append(&footer);
 
-        let model = ConnectBluetoothPage { settings: init };
+        let model = ConnectBluetoothPage {
+            settings: init,
+            request: None,
+            code: String::new(),
+            error: None,
+        };
 
         let widgets = ConnectBluetoothPageWidgets { 
+            header_label: enter_password_label,
+            code_input_label,
             code_input,
+            error_label,
             back_button,
             submit_button,
+            rendered_request: None,
          };
 
         ComponentParts { model, widgets }
@@ -161,15 +201,116 @@ impl SimpleComponent for ConnectBluetoothPage {
         match message {
             Message::MenuItemPressed(key) => {},
             Message::BackPressed => {
-                let _ = sender.output(Message::BackPressed);
+                // leaving the page declines, BlueZ would wait for an answer
+                match self.request.take() {
+                    Some(request) => {
+                        self.reply(request, PairingReply::Reject);
+                        let _ = sender.output(Message::Closed);
+                    }
+                    None => {
+                        let _ = sender.output(Message::BackPressed);
+                    }
+                }
             },
             Message::HomeIconPressed => {
                 sender.output(Message::HomeIconPressed);
             },
-            Message::PasswordChange(text) => {}
-            Message::SubmitPressed => {}
+            Message::PasswordChange(text) => {
+                self.code = text;
+            }
+            Message::SubmitPressed => {
+                let request = match &self.request {
+                    Some(request) => request,
+                    None => return,
+                };
+                let reply = match parse_code(&request.kind, &self.code) {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return;
+                    }
+                };
+                if let Some(request) = self.request.take() {
+                    self.reply(request, reply);
+                }
+                let _ = sender.output(Message::Closed);
+            }
+            Message::SetRequest(request) => {
+                // a request the page replaces is declined
+                if let Some(previous) = self.request.take() {
+                    self.reply(previous, PairingReply::Reject);
+                }
+                self.request = Some(request);
+                self.code = String::new();
+                self.error = None;
+            }
+            Message::RequestCancelled(id) => {
+                if self.request.as_ref().is_some_and(|request| request.id == id) {
+                    self.request = None;
+                    let _ = sender.output(Message::Closed);
+                }
+            }
+            Message::Closed => {}
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets.error_label.set_visible(self.error.is_some());
+        widgets
+            .error_label
+            .set_label(self.error.as_deref().unwrap_or(""));
+
+        if widgets.rendered_request == self.request {
+            return;
+        }
+        if let Some(request) = &self.request {
+            widgets
+                .header_label
+                .set_label(&format!("Pair with '{}'", request.device));
+            let hint = match request.kind {
+                PairingKind::PinCode => "Enter the PIN code of the device here",
+                _ => "Enter the 6 digit code shown on the device here",
+            };
+            widgets.code_input_label.set_label(hint);
+            // start every request with an empty code
+            widgets.code_input.emit(IconInputInputMessage::Clear);
         }
+        widgets.rendered_request = self.request.clone();
+    }
+}
+
+impl ConnectBluetoothPage {
+    /// Answers the request off the main thread
+    fn reply(&self, request: PairingRequest, reply: PairingReply) {
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        thread::spawn(move || {
+            if let Err(e) = bluetooth_backend.reply_pairing(request.id, reply) {
+                error!("error while answering pairing request {} {}", request.id, e);
+            }
+        });
     }
+}
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+/// Reads the typed code as the answer the request asks for
+fn parse_code(kind: &PairingKind, code: &str) -> Result<PairingReply, String> {
+    let code = code.trim();
+    match kind {
+        PairingKind::Passkey => {
+            let digits: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+            match digits.parse::<u32>() {
+                Ok(passkey) if digits.chars().all(|c| c.is_ascii_digit()) && passkey <= MAX_PASSKEY => {
+                    Ok(PairingReply::Passkey(passkey))
+                }
+                _ => Err("the code must be 6 digits".to_string()),
+            }
+        }
+        PairingKind::PinCode => match (1..=MAX_PIN_CODE_LENGTH).contains(&code.len()) {
+            true => Ok(PairingReply::PinCode(code.to_string())),
+            false => Err(format!(
+                "the PIN code must be 1 to {} characters",
+                MAX_PIN_CODE_LENGTH
+            )),
+        },
+        _ => Ok(PairingReply::Accept),
+    }
 }
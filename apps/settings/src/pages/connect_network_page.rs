@@ -1,37 +1,59 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
-    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+    Component, ComponentController, ComponentParts, ComponentSender, RelmWidgetExt,
+    SimpleComponent, Controller,
 };
 
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
+use crate::{
+    backends::network::{
+        NetworkBackendHandle, WifiConnectFailure, WifiConnectRequest, WifiConnectState,
+        WifiNetwork, WifiSecurity,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
 use custom_widgets::{
     icon_input_password::{
         IconInputPassword, IconInputPasswordCss, InitSettings as IconInputPasswordSettings, OutputMessage as IconInputPasswordOutputMessage,
     },
     icon_button::{
         IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
-    } 
+    }
 };
 
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
 }
 
 //Model
 pub struct ConnectNetworkPage {
     settings: Settings,
+    network: Option<WifiNetwork>,
+    /// Counts the networks set, selecting the same one again starts
+    /// over with an empty password too
+    selection: u32,
+    password: String,
+    connect_state: Option<WifiConnectState>,
+    is_connecting: bool,
 }
 
 //Widgets
 pub struct ConnectNetworkPageWidgets {
+    header_label: gtk::Label,
+    password_box: gtk::Box,
     password_input: Controller<IconInputPassword>,
+    status_label: gtk::Label,
     back_button: Controller<IconButton>,
+    connect_button: Controller<IconButton>,
+    rendered_selection: u32,
 }
 
 //Messages
@@ -41,6 +63,10 @@ pub enum Message {
     BackPressed,
     HomeIconPressed,
     PasswordChange(String),
+    SetNetwork(WifiNetwork),
+    ConnectPressed,
+    ConnectStateChanged(WifiConnectState),
+    Connected,
 }
 
 pub struct SettingItem {
@@ -71,7 +97,7 @@ impl SimpleComponent for ConnectNetworkPage {
         let widget_configs = init.widget_configs.clone();
 
         let enter_password_label = gtk::Label::builder()
-            .label("Enter password for 'Mecha 5g'")
+            .label("")
             .css_classes(["header-title"])
             .build();
 
@@ -82,19 +108,23 @@ impl SimpleComponent for ConnectNetworkPage {
 
         header.append(&enter_password_label);
 
-        let password_input = IconInputPassword::builder()
-            .launch(IconInputPasswordSettings {
-                icon: modules.peek_password.icon.default.to_owned(),
-                toggle_icon: None,
-                placeholder: Option::from("Password".to_string()),
-                css: IconInputPasswordCss::default(),
-            })
-            .forward(sender.input_sender(), |msg| match msg {
-                IconInputPasswordOutputMessage::InputChange(text) => Message::PasswordChange(text),
-            });
+        let password_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+
+        let password_input = password_input(&modules, &sender);
+        password_box.append(password_input.widget());
+
+        let status_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .css_classes(["connect-network-status"])
+            .build();
 
         root.append(&header);
-        root.append(password_input.widget());
+        root.append(&password_box);
+        root.append(&status_label);
 
         let footer = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
@@ -115,13 +145,40 @@ impl SimpleComponent for ConnectNetworkPage {
             });
 
         footer.append(back_button.widget());
+
+        let connect_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.next_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::ConnectPressed,
+            });
+        let connect_button_widget = connect_button.widget();
+        connect_button_widget.set_hexpand(true);
+        connect_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(connect_button_widget);
         root.append(&footer);
 
-        let model = ConnectNetworkPage { settings: init };
+        let model = ConnectNetworkPage {
+            settings: init,
+            network: None,
+            selection: 0,
+            password: String::new(),
+            connect_state: None,
+            is_connecting: false,
+        };
 
-        let widgets = ConnectNetworkPageWidgets { 
+        let widgets = ConnectNetworkPageWidgets {
+            header_label: enter_password_label,
+            password_box,
             password_input,
-            back_button
+            status_label,
+            back_button,
+            connect_button,
+            rendered_selection: 0,
          };
 
         ComponentParts { model, widgets }
@@ -137,9 +194,118 @@ impl SimpleComponent for ConnectNetworkPage {
             Message::HomeIconPressed => {
                 sender.output(Message::HomeIconPressed);
             }
-            Message::PasswordChange(text) => {}
+            Message::PasswordChange(text) => {
+                self.password = text;
+            }
+            Message::SetNetwork(network) => {
+                self.network = Some(network);
+                self.selection = self.selection.wrapping_add(1);
+                self.password = String::new();
+                self.connect_state = None;
+            }
+            Message::ConnectPressed => {
+                if self.is_connecting {
+                    return;
+                }
+                let network = match &self.network {
+                    Some(network) => network.clone(),
+                    None => return,
+                };
+
+                let requires_password =
+                    matches!(network.security, WifiSecurity::WpaPsk | WifiSecurity::Wpa3Sae);
+                if requires_password && !(8..=63).contains(&self.password.len()) {
+                    self.connect_state = Some(WifiConnectState::Failed(
+                        WifiConnectFailure::Other(
+                            "password must be 8 to 63 characters".to_string(),
+                        ),
+                    ));
+                    return;
+                }
+
+                let request = WifiConnectRequest {
+                    ssid: network.ssid,
+                    security: network.security,
+                    password: match requires_password {
+                        true => Some(self.password.clone()),
+                        false => None,
+                    },
+                };
+                self.is_connecting = true;
+                self.connect_state = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    let on_state = |state: WifiConnectState| {
+                        sender.input(Message::ConnectStateChanged(state));
+                    };
+                    if let Err(e) = network_backend.connect_wifi(&request, &on_state) {
+                        error!("error while connecting to {} {}", request.ssid, e);
+                        on_state(WifiConnectState::Failed(WifiConnectFailure::Other(
+                            e.to_string(),
+                        )));
+                    }
+                });
+            }
+            Message::ConnectStateChanged(state) => {
+                match state {
+                    WifiConnectState::Connected => {
+                        self.is_connecting = false;
+                        let _ = sender.output(Message::Connected);
+                    }
+                    WifiConnectState::Failed(_) => {
+                        self.is_connecting = false;
+                    }
+                    _ => (),
+                }
+                self.connect_state = Some(state);
+            }
+            Message::Connected => {}
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        if widgets.rendered_selection != self.selection {
+            if let Some(network) = &self.network {
+                let header = match network.security.is_private() {
+                    true => format!("Enter password for '{}'", network.ssid),
+                    false => format!("Connect to '{}'", network.ssid),
+                };
+                widgets.header_label.set_label(&header);
+                widgets.password_box.set_visible(network.security.is_private());
+            }
+
+            // start every network with an empty password
+            widgets.password_box.remove(widgets.password_input.widget());
+            widgets.password_input = password_input(&self.settings.modules, &sender);
+            widgets.password_box.append(widgets.password_input.widget());
+            widgets.rendered_selection = self.selection;
         }
+
+        let status = match &self.connect_state {
+            Some(state) => state.to_string(),
+            None => String::new(),
+        };
+        widgets.status_label.set_label(&status);
+        widgets.status_label.set_class_active(
+            "connect-network-error",
+            matches!(self.connect_state, Some(WifiConnectState::Failed(_))),
+        );
     }
+}
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+fn password_input(
+    modules: &Modules,
+    sender: &ComponentSender<ConnectNetworkPage>,
+) -> Controller<IconInputPassword> {
+    IconInputPassword::builder()
+        .launch(IconInputPasswordSettings {
+            icon: modules.peek_password.icon.default.to_owned(),
+            toggle_icon: None,
+            placeholder: Option::from("Password".to_string()),
+            css: IconInputPasswordCss::default(),
+        })
+        .forward(sender.input_sender(), |msg| match msg {
+            IconInputPasswordOutputMessage::InputChange(text) => Message::PasswordChange(text),
+        })
 }
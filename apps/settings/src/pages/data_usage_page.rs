@@ -0,0 +1,505 @@
+use std::{cell::RefCell, rc::Rc, thread};
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    gtk::{self, glib},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+
+use crate::{
+    backends::{
+        data_usage::{format_bytes, DataUsageBackendHandle, InterfaceUsage},
+        network::default_route_interface,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
+};
+use tracing::{error, info};
+
+const BYTES_PER_GB: f64 = 1_000_000_000.0;
+const GRAPH_HEIGHT: i32 = 120;
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub data_usage_backend: DataUsageBackendHandle,
+    pub refresh_interval: u32,
+}
+
+//Model
+pub struct DataUsagePage {
+    settings: Settings,
+    usage: Vec<InterfaceUsage>,
+    selected_interface: Option<String>,
+    threshold_text: String,
+    /// Bumped when the threshold of the selected interface is loaded
+    threshold_generation: u32,
+    error: Option<String>,
+}
+
+//Widgets
+pub struct DataUsagePageWidgets {
+    interface_dropdown: gtk::DropDown,
+    rendered_interfaces: Vec<String>,
+    today_received_label: gtk::Label,
+    today_sent_label: gtk::Label,
+    month_received_label: gtk::Label,
+    month_sent_label: gtk::Label,
+    graph: gtk::DrawingArea,
+    /// Daily totals drawn by the graph, oldest first
+    graph_values: Rc<RefCell<Vec<u64>>>,
+    threshold_input: Controller<IconInput>,
+    rendered_generation: u32,
+    warning_label: gtk::Label,
+    error_label: gtk::Label,
+    back_button: Controller<IconButton>,
+    apply_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    BackPressed,
+    RefreshUsage,
+    UsageUpdated(Vec<InterfaceUsage>),
+    InterfaceSelected(u32),
+    ThresholdChange(String),
+    ApplyPressed,
+    ThresholdApplied,
+    RequestFailed(String),
+}
+
+impl SimpleComponent for DataUsagePage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = DataUsagePageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Data Usage")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        // the model is replaced once the interfaces are known
+        let interface_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&[] as &[&str])),
+            gtk::Expression::NONE,
+        );
+        interface_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::InterfaceSelected(dropdown.selected()));
+        }));
+
+        let today_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+        let (today_received_row, today_received_label) = details_row("Received");
+        let (today_sent_row, today_sent_label) = details_row("Sent");
+        today_box.append(&today_received_row);
+        today_box.append(&today_sent_row);
+
+        let month_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+        let (month_received_row, month_received_label) = details_row("Received");
+        let (month_sent_row, month_sent_label) = details_row("Sent");
+        month_box.append(&month_received_row);
+        month_box.append(&month_sent_row);
+
+        let graph_values: Rc<RefCell<Vec<u64>>> = Rc::new(RefCell::new(vec![]));
+        let graph = gtk::DrawingArea::builder()
+            .content_height(GRAPH_HEIGHT)
+            .hexpand(true)
+            .build();
+        graph.set_draw_func(clone!(@strong graph_values => move |_, cr, width, height| {
+            let values = graph_values.borrow();
+            let max = values.iter().copied().max().unwrap_or(0);
+            if values.is_empty() || max == 0 {
+                return;
+            }
+            let bar_width = width as f64 / values.len() as f64;
+            cr.set_source_rgba(0.55, 0.78, 1.0, 1.0);
+            for (index, value) in values.iter().enumerate() {
+                // days with some traffic stay visible next to busy ones
+                let bar_height = match *value {
+                    0 => 0.0,
+                    value => (value as f64 / max as f64 * height as f64).max(2.0),
+                };
+                cr.rectangle(
+                    index as f64 * bar_width + 1.0,
+                    height as f64 - bar_height,
+                    (bar_width - 2.0).max(1.0),
+                    bar_height,
+                );
+            }
+            let _ = cr.fill();
+        }));
+
+        let threshold_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("GB per month, empty to turn off".to_string()),
+                css: IconInputCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::ThresholdChange(text),
+            });
+
+        let warning_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&field_label("Interface"));
+        scrollable_content.append(&interface_dropdown);
+        scrollable_content.append(&warning_label);
+        scrollable_content.append(&field_label("Today"));
+        scrollable_content.append(&today_box);
+        scrollable_content.append(&field_label("This Month"));
+        scrollable_content.append(&month_box);
+        scrollable_content.append(&field_label("Last 30 Days"));
+        scrollable_content.append(&graph);
+        scrollable_content.append(&field_label("Warn At"));
+        scrollable_content.append(threshold_input.widget());
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["footer"])
+        .hexpand(true)
+        .vexpand(true)
+        .valign(gtk::Align::End)
+        .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+
+        let apply_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.next_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::ApplyPressed,
+            });
+        let apply_button_widget = apply_button.widget();
+        apply_button_widget.set_hexpand(true);
+        apply_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(apply_button_widget);
+        root.append(&footer);
+
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshUsage);
+                glib::ControlFlow::Continue
+            }),
+        );
+        sender.input(Message::RefreshUsage);
+
+        let model = DataUsagePage {
+            settings: init,
+            usage: vec![],
+            selected_interface: None,
+            threshold_text: String::new(),
+            threshold_generation: 0,
+            error: None,
+        };
+
+        let widgets = DataUsagePageWidgets {
+            interface_dropdown,
+            rendered_interfaces: vec![],
+            today_received_label,
+            today_sent_label,
+            month_received_label,
+            month_sent_label,
+            graph,
+            graph_values,
+            threshold_input,
+            rendered_generation: 0,
+            warning_label,
+            error_label,
+            back_button,
+            apply_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::RefreshUsage => {
+                // the page samples too, the totals are current while it is open
+                let data_usage_backend = self.settings.data_usage_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = data_usage_backend.sample() {
+                        error!("error while sampling data usage {}", e);
+                    }
+                    match data_usage_backend.usage() {
+                        Ok(usage) => sender.input(Message::UsageUpdated(usage)),
+                        Err(e) => {
+                            error!("error while reading data usage {}", e);
+                            sender.input(Message::RequestFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            Message::UsageUpdated(usage) => {
+                self.usage = usage;
+                let selected_exists = self
+                    .selected_interface
+                    .as_ref()
+                    .map(|selected| self.usage.iter().any(|u| &u.interface == selected))
+                    .unwrap_or(false);
+                if !selected_exists {
+                    // start on the interface the traffic is going out of
+                    let default_interface = default_route_interface()
+                        .filter(|interface| self.usage.iter().any(|u| &u.interface == interface));
+                    let interface = default_interface
+                        .or_else(|| self.usage.first().map(|u| u.interface.clone()));
+                    self.select_interface(interface);
+                }
+            }
+            Message::InterfaceSelected(index) => {
+                let interface = self.usage.get(index as usize).map(|u| u.interface.clone());
+                if interface.is_some() && interface != self.selected_interface {
+                    self.select_interface(interface);
+                }
+            }
+            Message::ThresholdChange(text) => {
+                self.threshold_text = text;
+            }
+            Message::ApplyPressed => {
+                let interface = match &self.selected_interface {
+                    Some(interface) => interface.clone(),
+                    None => return,
+                };
+                let threshold = match parse_threshold(&self.threshold_text) {
+                    Ok(threshold) => threshold,
+                    Err(reason) => {
+                        self.error = Some(reason);
+                        return;
+                    }
+                };
+
+                self.error = None;
+                let data_usage_backend = self.settings.data_usage_backend.clone();
+                thread::spawn(move || {
+                    match data_usage_backend.set_warning_threshold(&interface, threshold) {
+                        Ok(_) => sender.input(Message::ThresholdApplied),
+                        Err(e) => {
+                            error!("error while saving data usage warning {}", e);
+                            sender.input(Message::RequestFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            Message::ThresholdApplied => {
+                sender.input(Message::RefreshUsage);
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let interfaces: Vec<String> = self.usage.iter().map(|u| u.interface.clone()).collect();
+        if widgets.rendered_interfaces != interfaces {
+            let names: Vec<&str> = interfaces.iter().map(|name| name.as_str()).collect();
+            widgets
+                .interface_dropdown
+                .set_model(Some(&gtk::StringList::new(&names)));
+            widgets.rendered_interfaces = interfaces.clone();
+        }
+        if let Some(index) = interfaces
+            .iter()
+            .position(|interface| Some(interface) == self.selected_interface.as_ref())
+        {
+            if widgets.interface_dropdown.selected() != index as u32 {
+                widgets.interface_dropdown.set_selected(index as u32);
+            }
+        }
+
+        let usage = self.selected_usage();
+        let totals = |value: Option<u64>| value.map(format_bytes).unwrap_or("-".to_string());
+        widgets
+            .today_received_label
+            .set_label(&totals(usage.map(|u| u.today.rx_bytes)));
+        widgets
+            .today_sent_label
+            .set_label(&totals(usage.map(|u| u.today.tx_bytes)));
+        widgets
+            .month_received_label
+            .set_label(&totals(usage.map(|u| u.month.rx_bytes)));
+        widgets
+            .month_sent_label
+            .set_label(&totals(usage.map(|u| u.month.tx_bytes)));
+
+        let values: Vec<u64> = usage
+            .map(|u| u.daily.iter().map(|(_, totals)| totals.total()).collect())
+            .unwrap_or_default();
+        if *widgets.graph_values.borrow() != values {
+            *widgets.graph_values.borrow_mut() = values;
+            widgets.graph.queue_draw();
+        }
+
+        let warning = usage.filter(|u| u.over_threshold()).map(|u| {
+            format!(
+                "{} used {} this month, over the {} warning",
+                u.interface,
+                format_bytes(u.month.total()),
+                format_bytes(u.warning_threshold.unwrap_or_default())
+            )
+        });
+        widgets
+            .warning_label
+            .set_label(&warning.clone().unwrap_or_default());
+        widgets.warning_label.set_visible(warning.is_some());
+
+        if widgets.rendered_generation != self.threshold_generation {
+            widgets
+                .threshold_input
+                .emit(IconInputInputMessage::SetText(self.threshold_text.clone()));
+            widgets.rendered_generation = self.threshold_generation;
+        }
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+impl DataUsagePage {
+    fn selected_usage(&self) -> Option<&InterfaceUsage> {
+        let selected = self.selected_interface.as_ref()?;
+        self.usage.iter().find(|u| &u.interface == selected)
+    }
+
+    fn select_interface(&mut self, interface: Option<String>) {
+        self.selected_interface = interface;
+        self.threshold_text = self
+            .selected_usage()
+            .and_then(|u| u.warning_threshold)
+            .map(|threshold| format!("{}", threshold as f64 / BYTES_PER_GB))
+            .unwrap_or_default();
+        self.threshold_generation += 1;
+    }
+}
+
+/// Threshold typed in GB, empty turns the warning off
+fn parse_threshold(text: &str) -> Result<Option<u64>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    match text.parse::<f64>() {
+        Ok(gb) if gb > 0.0 && gb.is_finite() => Ok(Some((gb * BYTES_PER_GB) as u64)),
+        _ => Err("enter the warning in GB, like 2.5".to_string()),
+    }
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
@@ -0,0 +1,347 @@
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    gtk::{self},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+
+use crate::{
+    backends::diagnostics::{
+        format_latency, report_text, DiagnosticStatus, DiagnosticStep, DiagnosticsBackendHandle,
+        DIAGNOSTIC_CHECKS,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::icon_button::{
+    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+};
+use tracing::{error, info};
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub diagnostics_backend: DiagnosticsBackendHandle,
+}
+
+//Model
+pub struct DiagnosticsPage {
+    settings: Settings,
+    steps: Vec<DiagnosticStep>,
+    is_running: bool,
+    report: Option<String>,
+    report_path: Option<String>,
+    /// Bumped on every copy so the view puts the report on the clipboard once
+    copy_generation: u32,
+    error: Option<String>,
+}
+
+//Widgets
+pub struct DiagnosticsPageWidgets {
+    /// Status and detail labels of each check, in `DIAGNOSTIC_CHECKS` order
+    step_labels: Vec<(gtk::Label, gtk::Label)>,
+    run_button: gtk::Button,
+    copy_button: gtk::Button,
+    rendered_copy_generation: u32,
+    report_label: gtk::Label,
+    error_label: gtk::Label,
+    back_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    BackPressed,
+    RunPressed,
+    StepFinished(DiagnosticStep),
+    RunFinished(String, Option<String>),
+    CopyPressed,
+    RequestFailed(String),
+}
+
+impl SimpleComponent for DiagnosticsPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = DiagnosticsPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Diagnostics")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        let steps_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let mut step_labels = vec![];
+        for check in DIAGNOSTIC_CHECKS {
+            let (row, status_label) = details_row(&check.to_string());
+            let detail_label = gtk::Label::builder()
+                .label("")
+                .halign(gtk::Align::Start)
+                .wrap(true)
+                .selectable(true)
+                .visible(false)
+                .css_classes(["settings-item-details-box-row-key"])
+                .build();
+            steps_box.append(&row);
+            steps_box.append(&detail_label);
+            step_labels.push((status_label, detail_label));
+        }
+
+        let actions_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(8)
+            .build();
+
+        let run_button = gtk::Button::builder()
+            .label("Run Checks")
+            .hexpand(true)
+            .css_classes(["diagnostics-action"])
+            .build();
+        run_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::RunPressed);
+        }));
+
+        let copy_button = gtk::Button::builder()
+            .label("Copy Report")
+            .hexpand(true)
+            .sensitive(false)
+            .css_classes(["diagnostics-action"])
+            .build();
+        copy_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::CopyPressed);
+        }));
+
+        actions_row.append(&run_button);
+        actions_row.append(&copy_button);
+
+        let report_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&steps_box);
+        scrollable_content.append(&actions_row);
+        scrollable_content.append(&report_label);
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["footer"])
+        .hexpand(true)
+        .vexpand(true)
+        .valign(gtk::Align::End)
+        .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+        root.append(&footer);
+
+        let model = DiagnosticsPage {
+            settings: init,
+            steps: vec![],
+            is_running: false,
+            report: None,
+            report_path: None,
+            copy_generation: 0,
+            error: None,
+        };
+
+        let widgets = DiagnosticsPageWidgets {
+            step_labels,
+            run_button,
+            copy_button,
+            rendered_copy_generation: 0,
+            report_label,
+            error_label,
+            back_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::RunPressed => {
+                if self.is_running {
+                    return;
+                }
+                self.is_running = true;
+                self.steps.clear();
+                self.report = None;
+                self.report_path = None;
+                self.error = None;
+
+                let diagnostics_backend = self.settings.diagnostics_backend.clone();
+                thread::spawn(move || {
+                    let steps = diagnostics_backend.run(|step| {
+                        sender.input(Message::StepFinished(step.clone()));
+                    });
+                    let interface = diagnostics_backend.interface();
+                    let report = report_text(interface.as_deref(), &steps);
+                    let report_path = match diagnostics_backend.save_report(&report) {
+                        Ok(path) => Some(path.to_string_lossy().to_string()),
+                        Err(e) => {
+                            error!("error while saving diagnostics report {}", e);
+                            sender.input(Message::RequestFailed(e.to_string()));
+                            None
+                        }
+                    };
+                    sender.input(Message::RunFinished(report, report_path));
+                });
+            }
+            Message::StepFinished(step) => {
+                self.steps.push(step);
+            }
+            Message::RunFinished(report, report_path) => {
+                self.is_running = false;
+                self.report = Some(report);
+                self.report_path = report_path;
+            }
+            Message::CopyPressed => {
+                if self.report.is_some() {
+                    self.copy_generation += 1;
+                }
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        for (index, check) in DIAGNOSTIC_CHECKS.iter().enumerate() {
+            let (status_label, detail_label) = &widgets.step_labels[index];
+            let step = self.steps.iter().find(|step| step.check == *check);
+            let status = match step {
+                Some(step) => match (step.status, step.latency) {
+                    (DiagnosticStatus::Passed, Some(latency)) => {
+                        format!("{} · {}", step.status, format_latency(latency))
+                    }
+                    (status, _) => status.to_string(),
+                },
+                // checks still to come while a run is in progress
+                None if self.is_running => "…".to_string(),
+                None => "-".to_string(),
+            };
+            status_label.set_label(&status);
+
+            let detail = step.map(|step| step.detail.clone()).unwrap_or_default();
+            detail_label.set_label(&detail);
+            detail_label.set_visible(!detail.is_empty());
+        }
+
+        widgets.run_button.set_sensitive(!self.is_running);
+        widgets
+            .copy_button
+            .set_sensitive(!self.is_running && self.report.is_some());
+
+        if widgets.rendered_copy_generation != self.copy_generation {
+            if let Some(report) = &self.report {
+                widgets.copy_button.clipboard().set_text(report);
+            }
+            widgets.rendered_copy_generation = self.copy_generation;
+        }
+
+        let report_status = match (&self.report, &self.report_path) {
+            (Some(_), Some(path)) => Some(format!("Report saved to {}", path)),
+            _ => None,
+        };
+        widgets
+            .report_label
+            .set_label(&report_status.clone().unwrap_or_default());
+        widgets.report_label.set_visible(report_status.is_some());
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("-")
+        .justify(gtk::Justification::Right)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
@@ -1,13 +1,17 @@
-use gtk::prelude::*;
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
 use custom_utils::get_image_from_path;
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::display::{format_timeout, AutoBrightness, Backlight, DisplayBackendHandle},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_item::{
-            CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
+            CustomListItem, CustomListItemSettings, InputMessage as CustomListItemInputMessage,
+            Message as CustomListItemMessage,
         },
 };
 
@@ -15,23 +19,44 @@ use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
 };
 
-use tracing::info;
+use tracing::{error, info};
+
+/// Smallest move of the scale that is written, smaller ones are the
+/// scale catching up with the backlight
+const BRIGHTNESS_STEP: f64 = 0.5;
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub display_backend: DisplayBackendHandle,
 }
 
 //Model
 pub struct DisplayPage {
     settings: Settings,
+    backlight: Option<Backlight>,
+    /// Level of the scale, from 0 to 100
+    brightness: f64,
+    /// A level is being written, moves of the scale meanwhile
+    /// are kept in `pending_brightness` and written after it
+    is_writing: bool,
+    pending_brightness: Option<f64>,
+    has_light_sensor: bool,
+    auto_brightness: AutoBrightness,
+    /// Screen off timeout in seconds
+    screen_timeout: Option<u32>,
 }
 
 //Widgets
 pub struct DisplayPageWidgets {
     back_button: Controller<IconButton>,
+    brightness_label: gtk::Label,
+    brightness_scale: gtk::Scale,
+    auto_brightness_row: gtk::Box,
+    auto_brightness_switch: gtk::Switch,
+    screen_off_timeout: Controller<CustomListItem>,
 }
 
 //Messages
@@ -40,6 +65,14 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     ScreenTimeoutOpted,
+    BrightnessChanged(f64),
+    BacklightChanged(Option<Backlight>),
+    BrightnessWritten(Option<Backlight>),
+    /// Whether there is a light sensor, and the state of the switch
+    AutoBrightnessLoaded(bool, AutoBrightness),
+    AutoBrightnessToggled(bool),
+    SetScreenTimeout(u32),
+    RequestFailed(String),
 }
 
 pub struct SettingItem {
@@ -81,13 +114,17 @@ impl SimpleComponent for DisplayPage {
             .build(); 
         header.append(&header_title);
 
-        let brigntness_label = gtk::Label::builder()
-            .label("Brigtness CHECK")
+        // hidden until the backlight is found, not every display has one
+        let brightness_label = gtk::Label::builder()
+            .label("Brightness")
             .halign(gtk::Align::Start)
+            .visible(false)
             .build();
 
-        let brigtness_scale = gtk::Scale::builder()
+        let brightness_scale = gtk::Scale::builder()
             .draw_value(true)
+            .digits(0)
+            .visible(false)
             .adjustment(
                 &gtk::Adjustment::builder()
                     .lower(0.0)
@@ -101,12 +138,36 @@ impl SimpleComponent for DisplayPage {
             .value_pos(gtk::PositionType::Right)
             .css_classes(["custom-scale"])
             .build();
-
+        brightness_scale.connect_value_changed(clone!(@strong sender => move |scale| {
+            sender.input(Message::BrightnessChanged(scale.value()));
+        }));
 
         let brigtness_items = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
 
+        // only shown on devices with an ambient light sensor
+        let auto_brightness_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .visible(false)
+            .css_classes(["settings-item-details-box-row"])
+            .build();
+        let auto_brightness_text = gtk::Label::builder()
+            .label("Automatic brightness")
+            .hexpand(true)
+            .halign(gtk::Align::Start)
+            .css_classes(["custom-switch-text"])
+            .build();
+        let auto_brightness_switch = gtk::Switch::new();
+        auto_brightness_switch.add_css_class("custom-switch");
+        auto_brightness_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::AutoBrightnessToggled(state));
+            glib::Propagation::Proceed
+        }));
+        auto_brightness_row.append(&auto_brightness_text);
+        auto_brightness_row.append(&auto_brightness_switch);
+
         let screen_off_timeout = CustomListItem::builder()
             .launch(CustomListItemSettings {
                 start_icon: None,
@@ -123,7 +184,8 @@ impl SimpleComponent for DisplayPage {
             });
 
         let screen_off_timeout_widget = screen_off_timeout.widget();
-        brigtness_items.append(&brigtness_scale);
+        brigtness_items.append(&brightness_scale);
+        brigtness_items.append(&auto_brightness_row);
         brigtness_items.append(screen_off_timeout_widget);
         // brigtness_items.append(&screen_off_timeout_widget.clone());
 
@@ -132,7 +194,7 @@ impl SimpleComponent for DisplayPage {
         let scrollable_content = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
-        scrollable_content.append(&brigntness_label);
+        scrollable_content.append(&brightness_label);
         scrollable_content.append(&brigtness_items);
 
         let scrolled_window = gtk::ScrolledWindow::builder()
@@ -165,10 +227,25 @@ impl SimpleComponent for DisplayPage {
 
         root.append(&footer);
 
-        let model = DisplayPage { settings: init };
+        let mut model = DisplayPage {
+            settings: init,
+            backlight: None,
+            brightness: 50.0,
+            is_writing: false,
+            pending_brightness: None,
+            has_light_sensor: false,
+            auto_brightness: AutoBrightness::default(),
+            screen_timeout: None,
+        };
+        model.load_auto_brightness(sender.clone());
 
         let widgets = DisplayPageWidgets {
-            back_button
+            back_button,
+            brightness_label,
+            brightness_scale,
+            auto_brightness_row,
+            auto_brightness_switch,
+            screen_off_timeout,
         };
 
         ComponentParts { model, widgets }
@@ -184,8 +261,125 @@ impl SimpleComponent for DisplayPage {
             Message::ScreenTimeoutOpted => {
                 let _ = sender.output(Message::ScreenTimeoutOpted);
             }
+            Message::BrightnessChanged(brightness) => {
+                if self.backlight.is_none()
+                    || (brightness - self.brightness).abs() < BRIGHTNESS_STEP
+                {
+                    return;
+                }
+                self.brightness = brightness;
+                // dragging the scale moves it faster than logind answers
+                match self.is_writing {
+                    true => self.pending_brightness = Some(brightness),
+                    false => self.write_brightness(sender, brightness),
+                }
+            }
+            Message::BacklightChanged(backlight) => {
+                // a level being written would pull the scale back
+                if !self.is_writing {
+                    if let Some(backlight) = &backlight {
+                        self.brightness = backlight.percent();
+                    }
+                }
+                self.backlight = backlight;
+            }
+            Message::BrightnessWritten(backlight) => {
+                self.is_writing = false;
+                match self.pending_brightness.take() {
+                    Some(brightness) => self.write_brightness(sender, brightness),
+                    None => sender.input(Message::BacklightChanged(backlight)),
+                }
+            }
+            Message::AutoBrightnessLoaded(has_light_sensor, auto_brightness) => {
+                self.has_light_sensor = has_light_sensor;
+                self.auto_brightness = auto_brightness;
+            }
+            Message::AutoBrightnessToggled(enabled) => {
+                // the switch also emits when synced from the model
+                if self.auto_brightness.enabled == enabled {
+                    return;
+                }
+                self.auto_brightness.enabled = enabled;
+                let display_backend = self.settings.display_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = display_backend.set_auto_brightness(enabled) {
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::SetScreenTimeout(timeout) => {
+                self.screen_timeout = Some(timeout);
+            }
+            Message::RequestFailed(e) => {
+                error!("error while setting brightness {}", e);
+                self.is_writing = false;
+                self.pending_brightness = None;
+                if let Some(backlight) = &self.backlight {
+                    self.brightness = backlight.percent();
+                }
+                // the switch may have moved ahead of the file
+                self.load_auto_brightness(sender);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let has_backlight = self.backlight.is_some();
+        widgets.brightness_label.set_visible(has_backlight);
+        widgets.brightness_scale.set_visible(has_backlight);
+        widgets
+            .auto_brightness_row
+            .set_visible(has_backlight && self.has_light_sensor);
+        widgets
+            .auto_brightness_switch
+            .set_active(self.auto_brightness.enabled);
+
+        if let Some(timeout) = self.screen_timeout {
+            widgets
+                .screen_off_timeout
+                .emit(CustomListItemInputMessage::SetValue(format_timeout(timeout)));
+        }
+        if (widgets.brightness_scale.value() - self.brightness).abs() >= BRIGHTNESS_STEP {
+            widgets.brightness_scale.set_value(self.brightness);
+        }
+    }
+}
+
+impl DisplayPage {
+    /// Writes the level off the main thread and reads back the
+    /// backlight, the raw level it landed on may be coarser
+    fn write_brightness(&mut self, sender: ComponentSender<Self>, brightness: f64) {
+        self.is_writing = true;
+        let display_backend = self.settings.display_backend.clone();
+        let is_auto = self.auto_brightness.enabled;
+        thread::spawn(move || {
+            if let Err(e) = display_backend.set_percent(brightness) {
+                sender.input(Message::RequestFailed(e.to_string()));
+                return;
+            }
+            // the backlight keeps following the light from the new level
+            if is_auto {
+                if let Err(e) = display_backend.move_curve_to(brightness) {
+                    error!("error while moving the brightness curve {}", e);
+                }
+            }
+            let backlight = display_backend.backlight().unwrap_or(None);
+            sender.input(Message::BrightnessWritten(backlight));
+        });
+    }
+
+    fn load_auto_brightness(&self, sender: ComponentSender<Self>) {
+        let display_backend = self.settings.display_backend.clone();
+        thread::spawn(move || {
+            let has_light_sensor = display_backend.light_sensor().is_some();
+            let auto_brightness = match display_backend.auto_brightness() {
+                Ok(auto_brightness) => auto_brightness,
+                Err(e) => {
+                    error!("error while reading auto brightness {}", e);
+                    AutoBrightness::default()
+                }
+            };
+            sender.input(Message::AutoBrightnessLoaded(has_light_sensor, auto_brightness));
+        });
+    }
 }
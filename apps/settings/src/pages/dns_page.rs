@@ -1,35 +1,72 @@
-use gtk::prelude::*;
+use std::{net::IpAddr, thread};
+
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 
 use crate::{
+    backends::dns::{
+        parse_dns_server, parse_search_domains, DnsBackendHandle, DnsConfig, DnsMode, DnsOverTls,
+    },
     settings::{LayoutSettings, Modules, WidgetConfigs},
-    widgets::custom_list_item::{
-            CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
-        },
 };
-use custom_widgets::icon_button::{
-    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
 };
-use tracing::info;
+use tracing::{error, info};
+
+/// DNS over TLS options in the order they are listed in the dropdown
+const DNS_OVER_TLS_OPTIONS: [DnsOverTls; 3] =
+    [DnsOverTls::Off, DnsOverTls::Opportunistic, DnsOverTls::Strict];
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub dns_backend: DnsBackendHandle,
 }
 
 //Model
 pub struct DNSPage {
     settings: Settings,
+    config: DnsConfig,
+    /// Bumped when a config is loaded so the inputs are filled once
+    config_generation: u32,
+    /// Bumped when a server is added so the server input is cleared
+    servers_added: u32,
+    new_server: String,
+    search_domains: String,
+    status: Option<String>,
+    error: Option<String>,
+    is_applying: bool,
 }
 
 //Widgets
 pub struct DNSPageWidgets {
+    auto_switch: gtk::Switch,
+    servers_list: gtk::Box,
+    rendered_servers: Option<(DnsMode, Vec<IpAddr>)>,
+    add_server_box: gtk::Box,
+    new_server_input: Controller<IconInput>,
+    add_server_button: Controller<IconButton>,
+    rendered_servers_added: u32,
+    search_domains_box: gtk::Box,
+    search_domains_input: Controller<IconInput>,
+    rendered_generation: u32,
+    dns_over_tls_dropdown: gtk::DropDown,
+    status_label: gtk::Label,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
+    apply_button: Controller<IconButton>,
 }
 
 //Messages
@@ -37,13 +74,19 @@ pub struct DNSPageWidgets {
 pub enum Message {
     MenuItemPressed(String),
     BackPressed,
-    DNSModePressed,
-}
-
-pub struct SettingItem {
-    text: String,
-    start_icon: Option<String>,
-    end_icon: Option<String>,
+    RefreshConfig,
+    ConfigLoaded(DnsConfig),
+    LoadFailed(String),
+    AutoToggled(bool),
+    NewServerChange(String),
+    AddServerPressed,
+    MoveServerUp(usize),
+    RemoveServer(usize),
+    SearchDomainsChange(String),
+    DnsOverTlsChanged(u32),
+    ApplyPressed,
+    ApplyFailed(String),
+    ConfigApplied,
 }
 
 impl SimpleComponent for DNSPage {
@@ -85,41 +128,125 @@ impl SimpleComponent for DNSPage {
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        let dns_mode = CustomListItem::builder()
-            .launch(CustomListItemSettings {
-                start_icon: None,
-                text: "Mode".to_string(),
-                value: "Auto".to_owned(),
-                end_icon: widget_configs.menu_item.end_icon.clone(),
+        let auto_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .css_classes(["settings-item-details-box-row", "add-network-hidden-row"])
+            .build();
+
+        let auto_text = gtk::Label::builder()
+            .label("Automatic DNS")
+            .hexpand(true)
+            .halign(gtk::Align::Start)
+            .css_classes(["custom-switch-text"])
+            .build();
+
+        let auto_switch = gtk::Switch::new();
+        auto_switch.set_active(true);
+        auto_switch.add_css_class("custom-switch");
+        auto_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::AutoToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        auto_row.append(&auto_text);
+        auto_row.append(&auto_switch);
+
+        let servers_list = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        // manual servers are added at the end of the list and can be moved up
+        let add_server_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .visible(false)
+            .build();
+
+        let new_server_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("1.1.1.1 or 2606:4700:4700::1111".to_string()),
+                css: IconInputCss::default(),
             })
-            .forward(sender.input_sender(), |msg| {
-                info!("DNS PAGE msg is {:?}", msg);
-                match msg {
-                    CustomListItemMessage::WidgetClicked => Message::DNSModePressed,
-                }
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::NewServerChange(text),
+            });
+
+        let add_server_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.add_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::AddServerPressed,
             });
 
-        let dns_mode_widget = dns_mode.widget();
+        add_server_box.append(new_server_input.widget());
+        add_server_box.append(add_server_button.widget());
 
-        //
-        let dns_server_1 = CustomListItem::builder()
-            .launch(CustomListItemSettings {
-                start_icon: None,
-                text: "DNS Server 1".to_string(),
-                value: "192.168.1.1".to_owned(),
-                end_icon: widget_configs.menu_item.end_icon.clone(),
+        let search_domains_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+
+        let search_domains_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("example.com, corp.local".to_string()),
+                css: IconInputCss::default(),
             })
-            .forward(sender.input_sender(), |msg| {
-                info!("DNS PAGE msg is {:?}", msg);
-                match msg {
-                    CustomListItemMessage::WidgetClicked => Message::DNSModePressed,
-                }
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::SearchDomainsChange(text),
             });
 
-        let dns_server_1_widget = dns_server_1.widget();
+        search_domains_box.append(&field_label("Search Domains"));
+        search_domains_box.append(search_domains_input.widget());
 
-        items_box.append(dns_mode_widget);
-        items_box.append(dns_server_1_widget);
+        let dns_over_tls_names: Vec<&str> = DNS_OVER_TLS_OPTIONS
+            .iter()
+            .map(|option| match option {
+                DnsOverTls::Off => "Off",
+                DnsOverTls::Opportunistic => "Opportunistic",
+                DnsOverTls::Strict => "Strict",
+            })
+            .collect();
+        let dns_over_tls_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&dns_over_tls_names)),
+            gtk::Expression::NONE,
+        );
+        dns_over_tls_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::DnsOverTlsChanged(dropdown.selected()));
+        }));
+
+        let status_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        items_box.append(&auto_row);
+        items_box.append(&field_label("DNS Servers"));
+        items_box.append(&servers_list);
+        items_box.append(&add_server_box);
+        items_box.append(&search_domains_box);
+        items_box.append(&field_label("DNS over TLS"));
+        items_box.append(&dns_over_tls_dropdown);
+        items_box.append(&status_label);
+        items_box.append(&error_label);
 
         root.append(&header);
 
@@ -155,14 +282,55 @@ impl SimpleComponent for DNSPage {
             });
 
         footer.append(back_button.widget());
+
+        let apply_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.next_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::ApplyPressed,
+            });
+        let apply_button_widget = apply_button.widget();
+        apply_button_widget.set_hexpand(true);
+        apply_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(apply_button_widget);
         root.append(&footer);
 
-        let model = DNSPage { settings: init };
+        let model = DNSPage {
+            settings: init,
+            config: DnsConfig::default(),
+            config_generation: 0,
+            servers_added: 0,
+            new_server: String::new(),
+            search_domains: String::new(),
+            status: None,
+            error: None,
+            is_applying: false,
+        };
 
         let widgets = DNSPageWidgets {
-            back_button
+            auto_switch,
+            servers_list,
+            rendered_servers: None,
+            add_server_box,
+            new_server_input,
+            add_server_button,
+            rendered_servers_added: 0,
+            search_domains_box,
+            search_domains_input,
+            rendered_generation: 0,
+            dns_over_tls_dropdown,
+            status_label,
+            error_label,
+            back_button,
+            apply_button,
         };
 
+        sender.input(Message::RefreshConfig);
+
         ComponentParts { model, widgets }
     }
 
@@ -173,11 +341,223 @@ impl SimpleComponent for DNSPage {
             Message::BackPressed => {
                 let _ = sender.output(Message::BackPressed);
             }
-            Message::DNSModePressed => {
-                let _ = sender.output(Message::DNSModePressed);
+            Message::RefreshConfig => {
+                let dns_backend = self.settings.dns_backend.clone();
+                thread::spawn(move || match dns_backend.dns_config() {
+                    Ok(config) => sender.input(Message::ConfigLoaded(config)),
+                    Err(e) => {
+                        error!("error while reading dns config {}", e);
+                        sender.input(Message::LoadFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::ConfigLoaded(config) => {
+                self.search_domains = config.search_domains.join(", ");
+                self.config = config;
+                self.config_generation += 1;
+                self.status = Some(format!("Managed by {}", self.settings.dns_backend.name()));
+                self.error = None;
+            }
+            Message::LoadFailed(reason) => {
+                self.error = Some(reason);
+            }
+            Message::AutoToggled(is_auto) => {
+                // the switch also emits when synced from the loaded config
+                let mode = match is_auto {
+                    true => DnsMode::Auto,
+                    false => DnsMode::Manual,
+                };
+                if self.config.mode != mode {
+                    self.config.mode = mode;
+                    self.error = None;
+                }
+            }
+            Message::NewServerChange(text) => {
+                self.new_server = text;
+            }
+            Message::AddServerPressed => {
+                if self.new_server.trim().is_empty() {
+                    return;
+                }
+                match parse_dns_server(&self.new_server) {
+                    Ok(server) if self.config.servers.contains(&server) => {
+                        self.error = Some(format!("{} is already in the list", server));
+                    }
+                    Ok(server) => {
+                        self.config.servers.push(server);
+                        self.servers_added += 1;
+                        self.error = None;
+                    }
+                    Err(reason) => {
+                        self.error = Some(reason);
+                    }
+                }
+            }
+            Message::MoveServerUp(index) => {
+                if index > 0 && index < self.config.servers.len() {
+                    self.config.servers.swap(index - 1, index);
+                }
+            }
+            Message::RemoveServer(index) => {
+                if index < self.config.servers.len() {
+                    self.config.servers.remove(index);
+                }
+            }
+            Message::SearchDomainsChange(text) => {
+                self.search_domains = text;
+            }
+            Message::DnsOverTlsChanged(index) => {
+                if let Some(dns_over_tls) = DNS_OVER_TLS_OPTIONS.get(index as usize) {
+                    self.config.dns_over_tls = *dns_over_tls;
+                }
+            }
+            Message::ApplyPressed => {
+                if self.is_applying {
+                    return;
+                }
+
+                let mut config = self.config.clone();
+                config.search_domains = parse_search_domains(&self.search_domains);
+                if let Err(reason) = config.validate() {
+                    self.error = Some(reason);
+                    return;
+                }
+
+                self.is_applying = true;
+                self.error = None;
+                let dns_backend = self.settings.dns_backend.clone();
+                thread::spawn(move || match dns_backend.set_dns_config(&config) {
+                    Ok(_) => sender.input(Message::ConfigApplied),
+                    Err(e) => {
+                        error!("error while applying dns config {}", e);
+                        sender.input(Message::ApplyFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::ApplyFailed(reason) => {
+                self.is_applying = false;
+                self.error = Some(reason);
+            }
+            Message::ConfigApplied => {
+                self.is_applying = false;
+                sender.input(Message::RefreshConfig);
             }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let is_manual = self.config.mode == DnsMode::Manual;
+        widgets.auto_switch.set_active(!is_manual);
+        widgets.add_server_box.set_visible(is_manual);
+        widgets.search_domains_box.set_visible(is_manual);
+
+        if let Some(index) = DNS_OVER_TLS_OPTIONS
+            .iter()
+            .position(|option| *option == self.config.dns_over_tls)
+        {
+            if widgets.dns_over_tls_dropdown.selected() != index as u32 {
+                widgets.dns_over_tls_dropdown.set_selected(index as u32);
+            }
+        }
+
+        if widgets.rendered_generation != self.config_generation {
+            widgets
+                .search_domains_input
+                .emit(IconInputInputMessage::SetText(self.search_domains.clone()));
+            widgets.rendered_generation = self.config_generation;
+        }
+        if widgets.rendered_servers_added != self.servers_added {
+            widgets.new_server_input.emit(IconInputInputMessage::Clear);
+            widgets.rendered_servers_added = self.servers_added;
+        }
+
+        let servers = Some((self.config.mode, self.config.servers.clone()));
+        if widgets.rendered_servers != servers {
+            while let Some(child) = widgets.servers_list.first_child() {
+                widgets.servers_list.remove(&child);
+            }
+            for (index, server) in self.config.servers.iter().enumerate() {
+                let row = server_row(index, server, is_manual, &sender);
+                widgets.servers_list.append(&row);
+            }
+            if self.config.servers.is_empty() {
+                let row = server_row_label("None");
+                widgets.servers_list.append(&row);
+            }
+            widgets.rendered_servers = servers;
+        }
+
+        let status = self.status.clone().unwrap_or_default();
+        widgets.status_label.set_label(&status);
+        widgets.status_label.set_visible(self.status.is_some());
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+/// Row of the ordered server list, manual servers can be moved up or removed
+fn server_row(
+    index: usize,
+    server: &IpAddr,
+    is_manual: bool,
+    sender: &ComponentSender<DNSPage>,
+) -> gtk::Box {
+    let row = server_row_label(&format!("DNS Server {}", index + 1));
+
+    let value = gtk::Label::builder()
+        .label(&server.to_string())
+        .selectable(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+    row.append(&value);
+
+    if is_manual {
+        let up_button = gtk::Button::builder()
+            .label("▲")
+            .sensitive(index > 0)
+            .css_classes(["dns-server-action"])
+            .build();
+        up_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::MoveServerUp(index));
+        }));
+
+        let remove_button = gtk::Button::builder()
+            .label("✕")
+            .css_classes(["dns-server-action"])
+            .build();
+        remove_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::RemoveServer(index));
+        }));
+
+        row.append(&up_button);
+        row.append(&remove_button);
+    }
+    row
+}
+
+fn server_row_label(text: &str) -> gtk::Box {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(text)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    row.append(&key_label);
+    row
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
 }
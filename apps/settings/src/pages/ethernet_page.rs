@@ -1,10 +1,13 @@
-use gtk::prelude::*;
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 
 use crate::{
+    backends::network::{failure_reason, EthernetLink, NetworkBackendHandle},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_item::{
             CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
@@ -13,22 +16,29 @@ use crate::{
 use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
 };
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub refresh_interval: u32,
 }
 
 //Model
 pub struct EthernetPage {
     settings: Settings,
+    links: Option<Vec<EthernetLink>>,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct EthernetPageWidgets {
+    links_box: gtk::Box,
+    rendered_links: Option<Vec<EthernetLink>>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
 }
 
@@ -38,6 +48,10 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     IPSettingsPressed,
+    RefreshLinks,
+    LinksUpdated(Vec<EthernetLink>),
+    InterfaceToggled(String, bool),
+    RequestFailed(String),
 }
 
 pub struct SettingItem {
@@ -81,33 +95,19 @@ impl SimpleComponent for EthernetPage {
 
         header.append(&header_title);
 
-        let enable_status_box = gtk::Box::builder()
+        // one box per wired interface, rebuilt when the link state changes
+        let links_box = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
-            .css_classes(["settings-item-details-box"])
-            .build();
-
-        let enable_row = gtk::Box::builder()
-            .orientation(gtk::Orientation::Horizontal)
-            .hexpand(true)
-            .css_classes(["settings-item-details-box-row"])
             .build();
 
-        let enable_text = gtk::Label::builder()
-            .label("Enable Ethernet")
-            .hexpand(true)
+        let error_label = gtk::Label::builder()
+            .label("")
             .halign(gtk::Align::Start)
-            .css_classes(["custom-switch-text"])
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
             .build();
 
-        let switch = gtk::Switch::new();
-        switch.set_active(true);
-        let style_context = switch.style_context();
-        style_context.add_class("custom-switch");
-
-        enable_row.append(&enable_text);
-        enable_row.append(&switch);
-        enable_status_box.append(&enable_row);
-
         let items_box = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
         .build();
@@ -135,7 +135,8 @@ impl SimpleComponent for EthernetPage {
         let scrollable_content = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
         .build();
-        scrollable_content.append(&enable_status_box);
+        scrollable_content.append(&links_box);
+        scrollable_content.append(&error_label);
         scrollable_content.append(&items_box);
 
         let scrolled_window = gtk::ScrolledWindow::builder()
@@ -167,9 +168,25 @@ impl SimpleComponent for EthernetPage {
         footer.append(back_button.widget());
         root.append(&footer);
 
-        let model = EthernetPage { settings: init };
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshLinks);
+                glib::ControlFlow::Continue
+            }),
+        );
+        sender.input(Message::RefreshLinks);
+
+        let model = EthernetPage {
+            settings: init,
+            links: None,
+            error: None,
+        };
 
         let widgets = EthernetPageWidgets {
+            links_box,
+            rendered_links: None,
+            error_label,
             back_button
         };
 
@@ -186,8 +203,161 @@ impl SimpleComponent for EthernetPage {
             Message::IPSettingsPressed => {
                 let _ = sender.output(Message::IPSettingsPressed);
             }
+            Message::RefreshLinks => {
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.ethernet_links() {
+                    Ok(links) => sender.input(Message::LinksUpdated(links)),
+                    Err(e) => {
+                        error!("error while reading ethernet links {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::LinksUpdated(links) => {
+                self.links = Some(links);
+            }
+            Message::InterfaceToggled(interface, enabled) => {
+                let link = self
+                    .links
+                    .iter_mut()
+                    .flatten()
+                    .find(|link| link.interface == interface);
+                match link {
+                    Some(link) if link.enabled != enabled => link.enabled = enabled,
+                    _ => return,
+                }
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_ethernet_enabled(&interface, enabled) {
+                        error!("error while setting {} enabled {}", interface, e);
+                        sender.input(Message::RequestFailed(failure_reason(&e)));
+                    }
+                    sender.input(Message::RefreshLinks);
+                });
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        if widgets.rendered_links != self.links {
+            while let Some(child) = widgets.links_box.first_child() {
+                widgets.links_box.remove(&child);
+            }
+            match &self.links {
+                Some(links) if links.is_empty() => {
+                    let empty_label = gtk::Label::builder()
+                        .label("No wired interfaces found")
+                        .halign(gtk::Align::Start)
+                        .css_classes(["connect-network-status"])
+                        .build();
+                    widgets.links_box.append(&empty_label);
+                }
+                Some(links) => {
+                    for link in links {
+                        widgets.links_box.append(&link_box(link, &sender));
+                    }
+                }
+                None => (),
+            }
+            widgets.rendered_links = self.links.clone();
         }
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+/// Switch and link state of one interface
+fn link_box(link: &EthernetLink, sender: &ComponentSender<EthernetPage>) -> gtk::Box {
+    let link_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .css_classes(["settings-item-details-box"])
+        .build();
+
+    let enable_row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let enable_text = gtk::Label::builder()
+        .label(&link.interface)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-switch-text"])
+        .build();
+
+    // set before connecting so rebuilding the list does not toggle the link
+    let switch = gtk::Switch::new();
+    switch.set_active(link.enabled);
+    switch.add_css_class("custom-switch");
+    let interface = link.interface.clone();
+    switch.connect_state_set(clone!(@strong sender => move |_, state| {
+        sender.input(Message::InterfaceToggled(interface.clone(), state));
+        glib::Propagation::Proceed
+    }));
+
+    enable_row.append(&enable_text);
+    enable_row.append(&switch);
+    link_box.append(&enable_row);
+
+    let cable = match link.carrier {
+        Some(true) => "Connected",
+        Some(false) => "Unplugged",
+        None => "Unknown",
+    };
+    let speed = match link.speed {
+        Some(speed) if speed >= 1000 && speed % 1000 == 0 => format!("{} Gb/s", speed / 1000),
+        Some(speed) => format!("{} Mb/s", speed),
+        None => "-".to_string(),
+    };
+    let duplex = match link.duplex.as_deref() {
+        Some("full") => "Full",
+        Some("half") => "Half",
+        _ => "-",
+    };
+
+    for (key, value) in [
+        ("Status", link.operstate.to_string()),
+        ("Cable", cable.to_string()),
+        ("Speed", speed),
+        ("Duplex", duplex.to_string()),
+        ("MAC Address", link.address.clone()),
+    ] {
+        let (row, value_label) = details_row(key);
+        value_label.set_label(&value);
+        link_box.append(&row);
     }
+    link_box
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .selectable(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
 }
@@ -0,0 +1,539 @@
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    gtk::{self, glib},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+
+use crate::{
+    backends::network::{
+        ActiveWifi, HotspotBand, HotspotClient, HotspotConfig, HotspotStatus, NetworkBackendHandle,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
+    icon_input_password::{
+        IconInputPassword, IconInputPasswordCss, InitSettings as IconInputPasswordSettings,
+        InputMessage as IconInputPasswordInputMessage, OutputMessage as IconInputPasswordOutputMessage,
+    },
+};
+use tracing::{error, info};
+
+/// Bands in the order they are listed in the dropdown
+const BAND_OPTIONS: [HotspotBand; 2] = [HotspotBand::Band2_4GHz, HotspotBand::Band5GHz];
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub refresh_interval: u32,
+}
+
+//Model
+pub struct HotspotPage {
+    settings: Settings,
+    config: HotspotConfig,
+    /// Bumped when the saved config is loaded so the inputs are filled once
+    config_generation: u32,
+    status: HotspotStatus,
+    active_wifi: Option<ActiveWifi>,
+    /// State the switch shows, ahead of `status` while starting or stopping
+    hotspot_on: bool,
+    is_busy: bool,
+    error: Option<String>,
+}
+
+//Widgets
+pub struct HotspotPageWidgets {
+    hotspot_switch: gtk::Switch,
+    wifi_warning_label: gtk::Label,
+    form_box: gtk::Box,
+    ssid_input: Controller<IconInput>,
+    password_input: Controller<IconInputPassword>,
+    rendered_generation: u32,
+    band_dropdown: gtk::DropDown,
+    channel_dropdown: gtk::DropDown,
+    rendered_band: Option<HotspotBand>,
+    clients_box: gtk::Box,
+    rendered_clients: Option<Vec<HotspotClient>>,
+    error_label: gtk::Label,
+    back_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    BackPressed,
+    RefreshStatus,
+    ConfigLoaded(HotspotConfig),
+    StatusUpdated(HotspotStatus, Option<ActiveWifi>),
+    SsidChange(String),
+    PasswordChange(String),
+    BandChanged(u32),
+    ChannelChanged(u32),
+    HotspotToggled(bool),
+    RequestFailed(String),
+    HotspotChanged,
+}
+
+impl SimpleComponent for HotspotPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = HotspotPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let modules = init.modules.clone();
+        let layout = init.layout.clone();
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Hotspot")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        let enable_status_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let enable_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .css_classes(["settings-item-details-box-row"])
+            .build();
+
+        let enable_text = gtk::Label::builder()
+            .label("Hotspot")
+            .hexpand(true)
+            .halign(gtk::Align::Start)
+            .css_classes(["custom-switch-text"])
+            .build();
+
+        let hotspot_switch = gtk::Switch::new();
+        hotspot_switch.add_css_class("custom-switch");
+        hotspot_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::HotspotToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        enable_row.append(&enable_text);
+        enable_row.append(&hotspot_switch);
+        enable_status_box.append(&enable_row);
+
+        // the radio can either join a network or host one
+        let wifi_warning_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        let form_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+
+        let ssid_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("Network name".to_string()),
+                css: IconInputCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::SsidChange(text),
+            });
+
+        let password_input = IconInputPassword::builder()
+            .launch(IconInputPasswordSettings {
+                icon: modules.peek_password.icon.default.to_owned(),
+                toggle_icon: None,
+                placeholder: Option::from("At least 8 characters".to_string()),
+                css: IconInputPasswordCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputPasswordOutputMessage::InputChange(text) => Message::PasswordChange(text),
+            });
+
+        let band_names: Vec<String> = BAND_OPTIONS.iter().map(|band| band.to_string()).collect();
+        let band_names: Vec<&str> = band_names.iter().map(|name| name.as_str()).collect();
+        let band_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&band_names)),
+            gtk::Expression::NONE,
+        );
+        band_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::BandChanged(dropdown.selected()));
+        }));
+
+        // the model is replaced with the channels of the selected band
+        let channel_dropdown = gtk::DropDown::new(
+            Some(channel_list(HotspotBand::default())),
+            gtk::Expression::NONE,
+        );
+        channel_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::ChannelChanged(dropdown.selected()));
+        }));
+
+        form_box.append(&field_label("Network Name"));
+        form_box.append(ssid_input.widget());
+        form_box.append(&field_label("Password"));
+        form_box.append(password_input.widget());
+        form_box.append(&field_label("Band"));
+        form_box.append(&band_dropdown);
+        form_box.append(&field_label("Channel"));
+        form_box.append(&channel_dropdown);
+
+        let clients_label = gtk::Label::builder()
+            .label("Connected Devices")
+            .halign(gtk::Align::Start)
+            .build();
+
+        let clients_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&enable_status_box);
+        scrollable_content.append(&wifi_warning_label);
+        scrollable_content.append(&form_box);
+        scrollable_content.append(&clients_label);
+        scrollable_content.append(&clients_box);
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["footer"])
+        .hexpand(true)
+        .vexpand(true)
+        .valign(gtk::Align::End)
+        .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+        root.append(&footer);
+
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshStatus);
+                glib::ControlFlow::Continue
+            }),
+        );
+
+        let network_backend = init.network_backend.clone();
+        let config_sender = sender.clone();
+        thread::spawn(move || match network_backend.hotspot_config() {
+            Ok(config) => config_sender.input(Message::ConfigLoaded(config)),
+            Err(e) => {
+                error!("error while reading hotspot config {}", e);
+                config_sender.input(Message::RequestFailed(e.to_string()));
+            }
+        });
+        sender.input(Message::RefreshStatus);
+
+        let model = HotspotPage {
+            settings: init,
+            config: HotspotConfig::default(),
+            config_generation: 0,
+            status: HotspotStatus::default(),
+            active_wifi: None,
+            hotspot_on: false,
+            is_busy: false,
+            error: None,
+        };
+
+        let widgets = HotspotPageWidgets {
+            hotspot_switch,
+            wifi_warning_label,
+            form_box,
+            ssid_input,
+            password_input,
+            rendered_generation: 0,
+            band_dropdown,
+            channel_dropdown,
+            rendered_band: None,
+            clients_box,
+            rendered_clients: None,
+            error_label,
+            back_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::RefreshStatus => {
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    let status = network_backend.hotspot_status();
+                    let active_wifi = network_backend.active_wifi();
+                    match (status, active_wifi) {
+                        (Ok(status), Ok(active_wifi)) => {
+                            sender.input(Message::StatusUpdated(status, active_wifi))
+                        }
+                        (Err(e), _) | (_, Err(e)) => {
+                            error!("error while reading hotspot status {}", e);
+                            sender.input(Message::RequestFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            Message::ConfigLoaded(config) => {
+                self.config = config;
+                self.config_generation += 1;
+            }
+            Message::StatusUpdated(status, active_wifi) => {
+                // a refresh started before a toggle would undo the switch
+                if !self.is_busy {
+                    self.hotspot_on = status.active;
+                }
+                self.status = status;
+                self.active_wifi = active_wifi;
+            }
+            Message::SsidChange(text) => {
+                self.config.ssid = text;
+            }
+            Message::PasswordChange(text) => {
+                self.config.password = text;
+            }
+            Message::BandChanged(index) => {
+                if let Some(band) = BAND_OPTIONS.get(index as usize) {
+                    if self.config.band != *band {
+                        self.config.band = *band;
+                        self.config.channel = None;
+                    }
+                }
+            }
+            Message::ChannelChanged(index) => {
+                // the first entry is automatic selection
+                self.config.channel = match index {
+                    0 => None,
+                    index => self.config.band.channels().get(index as usize - 1).copied(),
+                };
+            }
+            Message::HotspotToggled(enabled) => {
+                if enabled == self.hotspot_on || self.is_busy {
+                    return;
+                }
+                if enabled {
+                    if let Err(reason) = self.config.validate() {
+                        self.error = Some(reason);
+                        return;
+                    }
+                }
+
+                self.hotspot_on = enabled;
+                self.is_busy = true;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                let config = self.config.clone();
+                thread::spawn(move || {
+                    let result = match enabled {
+                        true => network_backend.start_hotspot(&config),
+                        false => network_backend.stop_hotspot(),
+                    };
+                    match result {
+                        Ok(_) => sender.input(Message::HotspotChanged),
+                        Err(e) => {
+                            error!("error while switching hotspot {}", e);
+                            sender.input(Message::RequestFailed(e.to_string()));
+                            sender.input(Message::HotspotChanged);
+                        }
+                    }
+                });
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+            Message::HotspotChanged => {
+                self.is_busy = false;
+                sender.input(Message::RefreshStatus);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets.hotspot_switch.set_active(self.hotspot_on);
+        widgets.hotspot_switch.set_sensitive(!self.is_busy);
+        // settings are applied when the hotspot starts
+        widgets.form_box.set_sensitive(!self.hotspot_on && !self.is_busy);
+
+        let wifi_warning = match (self.hotspot_on, &self.active_wifi) {
+            (false, Some(active_wifi)) => Some(format!(
+                "Turning on the hotspot disconnects Wi-Fi from {}",
+                active_wifi.ssid
+            )),
+            (true, _) => Some(
+                "Wi-Fi networks cannot be joined while the hotspot is on".to_string(),
+            ),
+            (false, None) => None,
+        };
+        widgets
+            .wifi_warning_label
+            .set_label(&wifi_warning.clone().unwrap_or_default());
+        widgets.wifi_warning_label.set_visible(wifi_warning.is_some());
+
+        if widgets.rendered_generation != self.config_generation {
+            widgets
+                .ssid_input
+                .emit(IconInputInputMessage::SetText(self.config.ssid.clone()));
+            widgets
+                .password_input
+                .emit(IconInputPasswordInputMessage::SetText(self.config.password.clone()));
+            widgets.rendered_generation = self.config_generation;
+        }
+
+        if widgets.rendered_band != Some(self.config.band) {
+            if let Some(index) = BAND_OPTIONS.iter().position(|band| *band == self.config.band) {
+                widgets.band_dropdown.set_selected(index as u32);
+            }
+            widgets
+                .channel_dropdown
+                .set_model(Some(&channel_list(self.config.band)));
+            widgets.rendered_band = Some(self.config.band);
+        }
+        let channel_index = self
+            .config
+            .channel
+            .and_then(|channel| {
+                self.config
+                    .band
+                    .channels()
+                    .iter()
+                    .position(|c| *c == channel)
+            })
+            .map(|index| index as u32 + 1)
+            .unwrap_or(0);
+        if widgets.channel_dropdown.selected() != channel_index {
+            widgets.channel_dropdown.set_selected(channel_index);
+        }
+
+        let clients = Some(self.status.clients.clone());
+        if widgets.rendered_clients != clients {
+            while let Some(child) = widgets.clients_box.first_child() {
+                widgets.clients_box.remove(&child);
+            }
+            for client in self.status.clients.iter() {
+                let (row, value_label) = details_row(&client.mac_address);
+                value_label.set_label(client.ip_address.as_deref().unwrap_or("-"));
+                widgets.clients_box.append(&row);
+            }
+            if self.status.clients.is_empty() {
+                let (row, _) = details_row("No devices connected");
+                widgets.clients_box.append(&row);
+            }
+            widgets.rendered_clients = clients;
+        }
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+/// "Auto" followed by the channels of a band
+fn channel_list(band: HotspotBand) -> gtk::StringList {
+    let mut names = vec!["Auto".to_string()];
+    names.extend(band.channels().iter().map(|channel| channel.to_string()));
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    gtk::StringList::new(&names)
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .selectable(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
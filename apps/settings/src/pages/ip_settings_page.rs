@@ -1,33 +1,46 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::network::{IpConnection, IpFamily, Ipv4Config, Ipv6Config, NetworkBackendHandle},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_item::{
-            CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
+            CustomListItem, CustomListItemSettings, InputMessage as CustomListItemInputMessage,
+            Message as CustomListItemMessage,
         },
 };
 use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
 };
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
 }
 
 //Model
 pub struct IPSettingsPage {
     settings: Settings,
+    connection: Option<IpConnection>,
+    ipv4_config: Option<Ipv4Config>,
+    ipv6_config: Option<Ipv6Config>,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct IPSettingsPageWidgets {
+    header_title: gtk::Label,
+    protocol_mode: Controller<CustomListItem>,
+    ipv6_protocol_mode: Controller<CustomListItem>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
 }
 
@@ -36,7 +49,11 @@ pub struct IPSettingsPageWidgets {
 pub enum Message {
     MenuItemPressed(String),
     BackPressed,
-    ProtocolModes,
+    ProtocolModes(IpFamily),
+    SetConnection(IpConnection),
+    ConfigLoaded(Ipv4Config),
+    Ipv6ConfigLoaded(Ipv6Config),
+    LoadFailed(String),
 }
 
 pub struct SettingItem {
@@ -102,14 +119,28 @@ impl SimpleComponent for IPSettingsPage {
         let protocol_mode = CustomListItem::builder()
             .launch(CustomListItemSettings {
                 start_icon: None,
-                text: "Mode".to_string(),
+                text: "IPv4 Mode".to_string(),
                 value: "Auto [ DHCP ]".to_owned(),
                 end_icon: widget_configs.menu_item.end_icon.clone(),
             })
             .forward(sender.input_sender(), |msg| {
                 info!("IP SETTINGS PAGE msg is {:?}", msg);
                 match msg { 
-                    CustomListItemMessage::WidgetClicked => Message::ProtocolModes,
+                    CustomListItemMessage::WidgetClicked => Message::ProtocolModes(IpFamily::V4),
+                }
+            });
+
+        let ipv6_protocol_mode = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "IPv6 Mode".to_string(),
+                value: "Auto [ SLAAC ]".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("IP SETTINGS PAGE msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::ProtocolModes(IpFamily::V6),
                 }
             });
 
@@ -117,6 +148,16 @@ impl SimpleComponent for IPSettingsPage {
 
 
         ip_items.append(protocol_mode_widget);
+        ip_items.append(ipv6_protocol_mode.widget());
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+        ip_items.append(&error_label);
 
         root.append(&header);
 
@@ -154,9 +195,19 @@ impl SimpleComponent for IPSettingsPage {
         footer.append(back_button.widget());
         root.append(&footer);
 
-        let model = IPSettingsPage { settings: init };
+        let model = IPSettingsPage {
+            settings: init,
+            connection: None,
+            ipv4_config: None,
+            ipv6_config: None,
+            error: None,
+        };
 
         let widgets = IPSettingsPageWidgets {
+            header_title,
+            protocol_mode,
+            ipv6_protocol_mode,
+            error_label,
             back_button
         };
 
@@ -170,11 +221,69 @@ impl SimpleComponent for IPSettingsPage {
             Message::BackPressed => {
                 let _ = sender.output(Message::BackPressed);
             }
-            Message::ProtocolModes => {
-                let _ = sender.output(Message::ProtocolModes);
+            Message::ProtocolModes(family) => {
+                let _ = sender.output(Message::ProtocolModes(family));
+            }
+            Message::SetConnection(connection) => {
+                self.ipv4_config = None;
+                self.ipv6_config = None;
+                self.error = None;
+                self.connection = Some(connection.clone());
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    let configs = network_backend.ipv4_config(&connection).and_then(|ipv4_config| {
+                        Ok((ipv4_config, network_backend.ipv6_config(&connection)?))
+                    });
+                    match configs {
+                        Ok((ipv4_config, ipv6_config)) => {
+                            sender.input(Message::ConfigLoaded(ipv4_config));
+                            sender.input(Message::Ipv6ConfigLoaded(ipv6_config));
+                        }
+                        Err(e) => {
+                            error!("error while reading ip config of {} {}", connection, e);
+                            sender.input(Message::LoadFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            Message::ConfigLoaded(config) => {
+                self.ipv4_config = Some(config);
+            }
+            Message::Ipv6ConfigLoaded(config) => {
+                self.ipv6_config = Some(config);
+            }
+            Message::LoadFailed(reason) => {
+                self.error = Some(reason);
             }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let title = match &self.connection {
+            Some(connection) => format!("IP Settings - {}", connection),
+            None => "IP Settings".to_string(),
+        };
+        widgets.header_title.set_label(&title);
+
+        let mode = match &self.ipv4_config {
+            Some(config) => config.to_string(),
+            None => "-".to_string(),
+        };
+        widgets
+            .protocol_mode
+            .emit(CustomListItemInputMessage::SetValue(mode));
+
+        let ipv6_mode = match &self.ipv6_config {
+            Some(config) => config.to_string(),
+            None => "-".to_string(),
+        };
+        widgets
+            .ipv6_protocol_mode
+            .emit(CustomListItemInputMessage::SetValue(ipv6_mode));
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
 }
@@ -1,35 +1,107 @@
-use gtk::prelude::*;
+use std::{thread, time::Duration};
+
+use gtk::{glib::clone, prelude::*};
 use custom_utils::get_image_from_path;
 use relm4::{
-    gtk::{self},
-    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+    gtk::{self, glib},
+    Component, ComponentController, ComponentParts, ComponentSender, RelmWidgetExt,
+    SimpleComponent, Controller,
 };
 use crate::{
+    backends::{
+        bluetooth::{
+            sort_devices, sort_devices_by_signal, AdapterState, BluetoothBackendHandle,
+            BluetoothDevice, BluetoothEvent, DeviceFilter,
+        },
+        rfkill::{Radio, RfkillBackendHandle, RfkillState},
+    },
     settings::{LayoutSettings, Modules, WidgetConfigs},
-    widgets::custom_bluetooth_item::{
+    widgets::{
+        custom_bluetooth_item::{
             CustomBluetoothItem, CustomBluetoothItemSettings, Message as CustomBluetoothItemMessage,
         },
+        custom_list_item::{
+            CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
+        },
+    },
 };
-use custom_widgets::icon_button::{
-    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
 };
-use tracing::info;
+use tracing::{error, info};
+
+/// Attempts to power the adapter after unblocking the radio, the
+/// adapter only shows up on the bus once the kernel brought it back
+const POWER_ON_ATTEMPTS: u32 = 10;
+const POWER_ON_RETRY_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Discoverable timeouts in seconds in the order they are listed in
+/// the dropdown, 0 stays discoverable until turned off
+const DISCOVERABLE_TIMEOUT_OPTIONS: [u32; 5] = [60, 180, 300, 600, 0];
+
+/// Filters in the order they are listed in the dropdown
+const FILTER_OPTIONS: [DeviceFilter; 3] = [
+    DeviceFilter::All,
+    DeviceFilter::Classic,
+    DeviceFilter::LowEnergy,
+];
+
+/// Longest name a Bluetooth adapter can have, in bytes
+const MAX_ALIAS_LENGTH: usize = 248;
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub rfkill_backend: RfkillBackendHandle,
+    pub bluetooth_backend: BluetoothBackendHandle,
 }
 
 //Model
 pub struct ManageBluetoothPage {
     settings: Settings,
+    rfkill_state: RfkillState,
+    adapter: Option<AdapterState>,
+    devices: Vec<BluetoothDevice>,
+    /// State the switch shows, ahead of the adapter while switching
+    bluetooth_on: bool,
+    is_busy: bool,
+    /// Device being paired, the agent asks for codes meanwhile
+    pairing: Option<BluetoothDevice>,
+    adapter_alias: String,
+    filter: DeviceFilter,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct ManageBluetoothPageWidgets {
     back_button: Controller<IconButton>,
+    received_files_item: Controller<CustomListItem>,
+    bluetooth_switch: gtk::Switch,
+    hardware_label: gtk::Label,
+    scan_button: gtk::Button,
+    adapter_label: gtk::Label,
+    adapter_box: gtk::Box,
+    adapter_alias_input: Controller<IconInput>,
+    discoverable_switch: gtk::Switch,
+    discoverable_timeout_dropdown: gtk::DropDown,
+    pairable_switch: gtk::Switch,
+    filter_dropdown: gtk::DropDown,
+    error_label: gtk::Label,
+    available_devices_label: gtk::Label,
+    available_devices_list: gtk::Box,
+    other_devices_label: gtk::Label,
+    other_devices_list: gtk::Box,
+    device_items: Vec<Controller<CustomBluetoothItem>>,
+    rendered_devices: Option<Vec<BluetoothDevice>>,
+    rendered_alias: Option<String>,
 }
 
 //Messages
@@ -37,9 +109,27 @@ pub struct ManageBluetoothPageWidgets {
 pub enum Message {
     MenuItemPressed(String),
     BackPressed,
-    AvaiableDevicePressed,
-    OtherDevicePressed,
+    AvaiableDevicePressed(BluetoothDevice),
+    OtherDevicePressed(BluetoothDevice),
     HomeIconPressed,
+    BluetoothToggled(bool),
+    BluetoothChanged,
+    RfkillStateChanged(RfkillState),
+    BluetoothEvent(BluetoothEvent),
+    RefreshDevices,
+    DevicesLoaded(Option<AdapterState>, Vec<BluetoothDevice>),
+    ScanPressed,
+    ScanChanged,
+    /// Pairing ended, by device `id`, with the error if it failed
+    PairingFinished(String, Option<String>),
+    AdapterAliasChange(String),
+    RenameAdapterPressed,
+    DiscoverableToggled(bool),
+    DiscoverableTimeoutChanged(u32),
+    PairableToggled(bool),
+    FilterChanged(u32),
+    ReceivedFilesPressed,
+    RequestFailed(String),
 }
 
 pub struct SettingItem {
@@ -106,64 +196,143 @@ impl SimpleComponent for ManageBluetoothPage {
             .build();
 
         let switch = gtk::Switch::new();
-        switch.set_active(true);
+        switch.set_active(false);
         let style_context = switch.style_context();
         style_context.add_class("custom-switch");
+        switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::BluetoothToggled(state));
+            glib::Propagation::Proceed
+        }));
 
         enable_bluetooth_row.append(&enable_network_text);
         enable_bluetooth_row.append(&switch);
         bluetooth_status_box.append(&enable_bluetooth_row);
 
+        // the app cannot undo a hardware switch, only point at it
+        let hardware_label = gtk::Label::builder()
+            .label("Bluetooth is turned off by a hardware switch")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        let scan_button = gtk::Button::builder()
+            .label("Scan")
+            .hexpand(true)
+            .visible(false)
+            .css_classes(["bluetooth-action"])
+            .build();
+        scan_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::ScanPressed);
+        }));
+
+        // how the device shows up to others
+        let adapter_label = gtk::Label::builder()
+            .label("This Device")
+            .css_classes(["list-label"])
+            .halign(gtk::Align::Start)
+            .visible(false)
+            .build();
+
+        let adapter_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+
+        // an empty name goes back to the host name
+        let adapter_alias_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("Device name".to_string()),
+                css: IconInputCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::AdapterAliasChange(text),
+            });
+
+        let rename_button = gtk::Button::builder()
+            .label("Rename")
+            .hexpand(true)
+            .css_classes(["bluetooth-action"])
+            .build();
+        rename_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::RenameAdapterPressed);
+        }));
+
+        let adapter_settings_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let discoverable_switch = switch_row(&adapter_settings_box, "Discoverable");
+        discoverable_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::DiscoverableToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        let timeout_names: Vec<String> = DISCOVERABLE_TIMEOUT_OPTIONS
+            .iter()
+            .map(|timeout| timeout_name(*timeout))
+            .collect();
+        let timeout_names: Vec<&str> = timeout_names.iter().map(|name| name.as_str()).collect();
+        let discoverable_timeout_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&timeout_names)),
+            gtk::Expression::NONE,
+        );
+        discoverable_timeout_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::DiscoverableTimeoutChanged(dropdown.selected()));
+        }));
+        dropdown_row(&adapter_settings_box, "Discoverable for", &discoverable_timeout_dropdown);
+
+        let pairable_switch = switch_row(&adapter_settings_box, "Pairable");
+        pairable_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::PairableToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        adapter_box.append(adapter_alias_input.widget());
+        adapter_box.append(&rename_button);
+        adapter_box.append(&adapter_settings_box);
+
+        // classic or LE only, for hunting down a nearby device
+        let filter_names: Vec<String> = FILTER_OPTIONS.iter().map(|filter| filter.to_string()).collect();
+        let filter_names: Vec<&str> = filter_names.iter().map(|name| name.as_str()).collect();
+        let filter_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&filter_names)),
+            gtk::Expression::NONE,
+        );
+        filter_dropdown.set_visible(false);
+        filter_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::FilterChanged(dropdown.selected()));
+        }));
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
         let available_devices_label = gtk::Label::builder()
         .label("Available Devices")
         .css_classes(["list-label"])
         .halign(gtk::Align::Start)
+        .visible(false)
         .build();
 
+        // paired devices, click on one -> show BluetoothDetails (having forgot connection option)
         let available_devices_list = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        // for not/connected device -> click on info/forward icon -> show BluetoothDetails (having forgot connection option)
-        // for connected device -> show connected icon
-        // for not connected device ( without connected_icon ) -> integrate connection flow (gtk spinner)
-        let available_device_1 = CustomBluetoothItem::builder()
-            .launch(CustomBluetoothItemSettings {
-                name: "Macbook Pro".to_string(),
-                is_connected: true,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                info_i_icon: None,
-                info_arrow_icon: widget_configs.menu_item.end_icon.clone(), // forword arrow
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("available_device_1 msg is {:?}", msg);
-                match msg {
-                    CustomBluetoothItemMessage::WidgetClicked => Message::AvaiableDevicePressed,
-                }
-            });
-
-        let available_device_2 = CustomBluetoothItem::builder()
-            .launch(CustomBluetoothItemSettings {
-                name: "Macbook Air".to_string(),
-                is_connected: false,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                info_i_icon: None,
-                info_arrow_icon: widget_configs.menu_item.end_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomBluetoothItemMessage::WidgetClicked => Message::AvaiableDevicePressed,
-                }
-            });
-
-        available_devices_list.append(available_device_1.widget());
-        available_devices_list.append(available_device_2.widget());
-
         let other_devices_label = gtk::Label::builder()
             .label("Other Devices")
             .css_classes(["list-label"])
             .halign(gtk::Align::Start)
+            .visible(false)
             .build();
 
         // here, device will pair or have pairing request 
@@ -171,39 +340,18 @@ impl SimpleComponent for ManageBluetoothPage {
         .orientation(gtk::Orientation::Vertical)
         .build();
 
-        let other_device_1 = CustomBluetoothItem::builder()
-            .launch(CustomBluetoothItemSettings {
-                name: "Shoaib's Iphone".to_string(),
-                is_connected: false,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                info_i_icon: None,
-                info_arrow_icon: widget_configs.menu_item.end_icon.clone(),
+        // files sent from other devices, also while bluetooth is off
+        let received_files_item = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "Received Files".to_string(),
+                value: "".to_string(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
             })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomBluetoothItemMessage::WidgetClicked => Message::OtherDevicePressed,
-                }
+            .forward(sender.input_sender(), |msg| match msg {
+                CustomListItemMessage::WidgetClicked => Message::ReceivedFilesPressed,
             });
 
-        let other_device_2 = CustomBluetoothItem::builder()
-            .launch(CustomBluetoothItemSettings {
-                name: "Infinity Glide".to_string(),
-                is_connected: false,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                info_i_icon: None,
-                info_arrow_icon: widget_configs.menu_item.end_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomBluetoothItemMessage::WidgetClicked => Message::OtherDevicePressed,
-                }
-            });
-
-        other_devices_list.append(other_device_1.widget());
-        other_devices_list.append(other_device_2.widget());
-
         root.append(&header);
 
         let scrollable_content = gtk::Box::builder()
@@ -211,10 +359,17 @@ impl SimpleComponent for ManageBluetoothPage {
             .build();
         scrollable_content.append(&bluetooth_status_label);
         scrollable_content.append(&bluetooth_status_box);
+        scrollable_content.append(&hardware_label);
+        scrollable_content.append(&scan_button);
+        scrollable_content.append(&error_label);
+        scrollable_content.append(&adapter_label);
+        scrollable_content.append(&adapter_box);
+        scrollable_content.append(&filter_dropdown);
         scrollable_content.append(&available_devices_label);
         scrollable_content.append(&available_devices_list);
         scrollable_content.append(&other_devices_label);
         scrollable_content.append(&other_devices_list);
+        scrollable_content.append(received_files_item.widget());
 
         let scrolled_window = gtk::ScrolledWindow::builder()
             .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
@@ -248,10 +403,41 @@ impl SimpleComponent for ManageBluetoothPage {
 
         root.append(&footer);
 
-        let model = ManageBluetoothPage { settings: init };
+        let model = ManageBluetoothPage {
+            settings: init,
+            rfkill_state: RfkillState::default(),
+            adapter: None,
+            devices: vec![],
+            bluetooth_on: false,
+            is_busy: false,
+            pairing: None,
+            adapter_alias: String::new(),
+            filter: DeviceFilter::default(),
+            error: None,
+        };
+        sender.input(Message::RefreshDevices);
 
         let widgets = ManageBluetoothPageWidgets {
-        back_button
+            back_button,
+            received_files_item,
+            bluetooth_switch: switch,
+            hardware_label,
+            scan_button,
+            adapter_label,
+            adapter_box,
+            adapter_alias_input,
+            discoverable_switch,
+            discoverable_timeout_dropdown,
+            pairable_switch,
+            filter_dropdown,
+            error_label,
+            available_devices_label,
+            available_devices_list,
+            other_devices_label,
+            other_devices_list,
+            device_items: vec![],
+            rendered_devices: None,
+            rendered_alias: None,
         };
 
         ComponentParts { model, widgets }
@@ -264,17 +450,484 @@ impl SimpleComponent for ManageBluetoothPage {
             Message::BackPressed => {
                 let _ = sender.output(Message::BackPressed);
             },
-            Message::AvaiableDevicePressed => {
-                let _ = sender.output(Message::AvaiableDevicePressed);
+            Message::AvaiableDevicePressed(device) => {
+                let _ = sender.output(Message::AvaiableDevicePressed(device));
             },
-            Message::OtherDevicePressed => {
-                let _ = sender.output(Message::OtherDevicePressed);
+            Message::OtherDevicePressed(device) => {
+                if self.pairing.is_some() {
+                    return;
+                }
+                self.pairing = Some(device.clone());
+                self.error = None;
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || {
+                    let error = match bluetooth_backend.pair(&device.id) {
+                        Ok(()) => None,
+                        Err(e) => {
+                            error!("error while pairing {} {}", device.address, e);
+                            Some(e.to_string())
+                        }
+                    };
+                    sender.input(Message::PairingFinished(device.id, error));
+                });
             },
             Message::HomeIconPressed => {
                 let _ = sender.output(Message::HomeIconPressed);
             }
+            Message::BluetoothToggled(enabled) => {
+                // the switch also emits when synced from the adapter state
+                if enabled == self.bluetooth_on
+                    || self.is_busy
+                    || self.rfkill_state.bluetooth.hard_blocked
+                {
+                    return;
+                }
+                self.bluetooth_on = enabled;
+                self.is_busy = true;
+                self.error = None;
+                let rfkill_backend = self.settings.rfkill_backend.clone();
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                let soft_blocked = self.rfkill_state.bluetooth.soft_blocked;
+                thread::spawn(move || {
+                    let result = match enabled {
+                        true => {
+                            if soft_blocked {
+                                if let Err(e) = rfkill_backend.set_blocked(Radio::Bluetooth, false) {
+                                    error!("error while unblocking bluetooth {}", e);
+                                }
+                            }
+                            power_on(&bluetooth_backend)
+                        }
+                        false => bluetooth_backend.set_powered(false),
+                    };
+                    if let Err(e) = result {
+                        error!("error while switching bluetooth {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                    sender.input(Message::BluetoothChanged);
+                });
+            }
+            Message::BluetoothChanged => {
+                self.is_busy = false;
+                self.refresh_devices(sender);
+            }
+            Message::RfkillStateChanged(rfkill_state) => {
+                self.rfkill_state = rfkill_state;
+                if !self.is_busy {
+                    self.bluetooth_on = self.is_on();
+                }
+            }
+            Message::BluetoothEvent(event) => {
+                match event {
+                    BluetoothEvent::AdapterChanged(adapter) => {
+                        let powered = |adapter: &Option<AdapterState>| {
+                            adapter.as_ref().map(|adapter| adapter.powered).unwrap_or(false)
+                        };
+                        // devices are listed again once the adapter comes up
+                        let reload = powered(&adapter) && !powered(&self.adapter);
+                        self.adapter = adapter;
+                        if reload {
+                            self.refresh_devices(sender);
+                        }
+                    }
+                    BluetoothEvent::DeviceChanged(device) => {
+                        match self.devices.iter_mut().find(|known| known.id == device.id) {
+                            Some(known) => *known = device,
+                            None => self.devices.push(device),
+                        }
+                        sort_devices(&mut self.devices);
+                    }
+                    BluetoothEvent::DeviceRemoved(id) => {
+                        self.devices.retain(|device| device.id != id);
+                    }
+                }
+                if !self.is_busy {
+                    self.bluetooth_on = self.is_on();
+                }
+            }
+            Message::RefreshDevices => {
+                self.refresh_devices(sender);
+            }
+            Message::DevicesLoaded(adapter, mut devices) => {
+                sort_devices(&mut devices);
+                self.adapter = adapter;
+                self.devices = devices;
+                if !self.is_busy {
+                    self.bluetooth_on = self.is_on();
+                }
+            }
+            Message::ScanPressed => {
+                let discovering = match self.adapter.as_ref() {
+                    Some(adapter) => adapter.discovering,
+                    None => return,
+                };
+                self.is_busy = true;
+                self.error = None;
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || {
+                    let result = match discovering {
+                        true => bluetooth_backend.stop_discovery(),
+                        false => bluetooth_backend.start_discovery(),
+                    };
+                    if let Err(e) = result {
+                        error!("error while switching discovery {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                    sender.input(Message::ScanChanged);
+                });
+            }
+            Message::ScanChanged => {
+                self.is_busy = false;
+            }
+            Message::PairingFinished(id, error) => {
+                self.pairing = None;
+                if let Some(error) = error.clone() {
+                    self.error = Some(error);
+                }
+                let _ = sender.output(Message::PairingFinished(id, error));
+            }
+            Message::AdapterAliasChange(text) => {
+                self.adapter_alias = text;
+            }
+            Message::RenameAdapterPressed => {
+                let alias = self.adapter_alias.trim().to_string();
+                if alias.len() > MAX_ALIAS_LENGTH {
+                    self.error = Some(format!(
+                        "the name must be at most {} bytes",
+                        MAX_ALIAS_LENGTH
+                    ));
+                    return;
+                }
+                self.error = None;
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_adapter_alias(&alias)
+                });
+            }
+            Message::DiscoverableToggled(discoverable) => {
+                let adapter = match &mut self.adapter {
+                    Some(adapter) => adapter,
+                    None => return,
+                };
+                // the switch also emits when synced from the adapter state
+                if adapter.discoverable == discoverable {
+                    return;
+                }
+                adapter.discoverable = discoverable;
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_discoverable(discoverable)
+                });
+            }
+            Message::DiscoverableTimeoutChanged(index) => {
+                let timeout = match DISCOVERABLE_TIMEOUT_OPTIONS.get(index as usize) {
+                    Some(timeout) => *timeout,
+                    None => return,
+                };
+                let adapter = match &mut self.adapter {
+                    Some(adapter) => adapter,
+                    None => return,
+                };
+                if adapter.discoverable_timeout == timeout {
+                    return;
+                }
+                adapter.discoverable_timeout = timeout;
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_discoverable_timeout(timeout)
+                });
+            }
+            Message::PairableToggled(pairable) => {
+                let adapter = match &mut self.adapter {
+                    Some(adapter) => adapter,
+                    None => return,
+                };
+                if adapter.pairable == pairable {
+                    return;
+                }
+                adapter.pairable = pairable;
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_pairable(pairable)
+                });
+            }
+            Message::FilterChanged(index) => {
+                let filter = match FILTER_OPTIONS.get(index as usize) {
+                    Some(filter) => *filter,
+                    None => return,
+                };
+                if self.filter == filter {
+                    return;
+                }
+                self.filter = filter;
+                let discovering = self
+                    .adapter
+                    .as_ref()
+                    .map(|adapter| adapter.discovering)
+                    .unwrap_or(false);
+                // a running discovery only picks the filter up when restarted
+                self.request(sender, move |bluetooth_backend| {
+                    bluetooth_backend.set_discovery_filter(filter)?;
+                    if discovering {
+                        bluetooth_backend.stop_discovery()?;
+                        bluetooth_backend.start_discovery()?;
+                    }
+                    Ok(())
+                });
+            }
+            Message::ReceivedFilesPressed => {
+                let _ = sender.output(Message::ReceivedFilesPressed);
+            }
+            Message::RequestFailed(e) => {
+                self.error = Some(e);
+                // switches were moved ahead of the adapter
+                self.refresh_devices(sender);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let bluetooth = self.rfkill_state.bluetooth;
+        widgets.bluetooth_switch.set_active(self.bluetooth_on);
+        // a blocked radio hides the adapter, unblocking brings it back
+        widgets.bluetooth_switch.set_sensitive(
+            !bluetooth.hard_blocked
+                && !self.is_busy
+                && (self.adapter.is_some() || bluetooth.soft_blocked),
+        );
+        widgets.hardware_label.set_visible(bluetooth.hard_blocked);
+
+        let is_on = self.is_on();
+        let discovering = self
+            .adapter
+            .as_ref()
+            .map(|adapter| adapter.discovering)
+            .unwrap_or(false);
+        widgets.scan_button.set_visible(is_on);
+        widgets.scan_button.set_sensitive(!self.is_busy);
+        widgets
+            .scan_button
+            .set_label(if discovering { "Stop Scanning" } else { "Scan" });
+
+        widgets.adapter_label.set_visible(is_on);
+        widgets.adapter_box.set_visible(is_on);
+        widgets.filter_dropdown.set_visible(is_on);
+        if let Some(adapter) = &self.adapter {
+            if widgets.rendered_alias.as_ref() != Some(&adapter.alias) {
+                widgets
+                    .adapter_alias_input
+                    .emit(IconInputInputMessage::SetText(adapter.alias.clone()));
+                widgets.rendered_alias = Some(adapter.alias.clone());
+            }
+            widgets.discoverable_switch.set_active(adapter.discoverable);
+            widgets.pairable_switch.set_active(adapter.pairable);
+            let timeout_index = DISCOVERABLE_TIMEOUT_OPTIONS
+                .iter()
+                .position(|timeout| *timeout == adapter.discoverable_timeout);
+            if let Some(index) = timeout_index {
+                if widgets.discoverable_timeout_dropdown.selected() != index as u32 {
+                    widgets.discoverable_timeout_dropdown.set_selected(index as u32);
+                }
+            }
+        }
+
+        let status = match (&self.pairing, &self.error) {
+            (Some(device), _) => Some(format!("Pairing with '{}'...", device)),
+            (None, error) => error.clone(),
+        };
+        widgets.error_label.set_visible(status.is_some());
+        widgets
+            .error_label
+            .set_label(status.as_deref().unwrap_or(""));
+        widgets
+            .error_label
+            .set_class_active("connect-network-error", self.pairing.is_none());
+
+        // devices of a powered off adapter cannot be reached, hide them
+        let devices = match is_on {
+            true => Some(
+                self.devices
+                    .iter()
+                    .filter(|device| self.filter.matches(device))
+                    .cloned()
+                    .collect::<Vec<BluetoothDevice>>(),
+            ),
+            false => None,
+        };
+        if widgets.rendered_devices == devices {
+            return;
+        }
+
+        for list in [&widgets.available_devices_list, &widgets.other_devices_list] {
+            while let Some(child) = list.first_child() {
+                list.remove(&child);
+            }
+        }
+        widgets.device_items.clear();
+
+        let widget_configs = &self.settings.widget_configs;
+        let (paired_devices, mut other_devices): (Vec<BluetoothDevice>, Vec<BluetoothDevice>) =
+            devices
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .partition(|device| device.paired);
+        // nearby devices first when looking for a new one
+        sort_devices_by_signal(&mut other_devices);
+        let lists: [(&gtk::Box, &Vec<BluetoothDevice>, fn(BluetoothDevice) -> Message); 2] = [
+            (&widgets.available_devices_list, &paired_devices, Message::AvaiableDevicePressed),
+            (&widgets.other_devices_list, &other_devices, Message::OtherDevicePressed),
+        ];
+        for (list, list_devices, on_click) in lists {
+            for device in list_devices.iter() {
+                let device_item = device_item(widget_configs, device, &sender, on_click);
+                list.append(device_item.widget());
+                widgets.device_items.push(device_item);
+            }
+        }
+
+        widgets
+            .available_devices_label
+            .set_visible(!paired_devices.is_empty());
+        widgets
+            .other_devices_label
+            .set_visible(!other_devices.is_empty());
+        widgets.rendered_devices = devices;
+    }
+}
+
+impl ManageBluetoothPage {
+    /// On only while the radio is unblocked and the adapter powered
+    fn is_on(&self) -> bool {
+        let bluetooth = self.rfkill_state.bluetooth;
+        !bluetooth.soft_blocked
+            && !bluetooth.hard_blocked
+            && self
+                .adapter
+                .as_ref()
+                .map(|adapter| adapter.powered)
+                .unwrap_or(false)
+    }
+
+    /// Runs a call of the backend off the main thread, the adapter
+    /// reports the change through the watcher
+    fn request<F>(&self, sender: ComponentSender<Self>, call: F)
+    where
+        F: FnOnce(&BluetoothBackendHandle) -> anyhow::Result<()> + Send + 'static,
+    {
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        thread::spawn(move || {
+            if let Err(e) = call(&bluetooth_backend) {
+                error!("error while changing bluetooth adapter {}", e);
+                sender.input(Message::RequestFailed(e.to_string()));
+            }
+        });
+    }
+
+    fn refresh_devices(&mut self, sender: ComponentSender<Self>) {
+        let bluetooth_backend = self.settings.bluetooth_backend.clone();
+        thread::spawn(move || {
+            let adapter = match bluetooth_backend.adapter() {
+                Ok(adapter) => adapter,
+                Err(e) => {
+                    error!("error while reading bluetooth adapter {}", e);
+                    None
+                }
+            };
+            let devices = match bluetooth_backend.devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    error!("error while listing bluetooth devices {}", e);
+                    vec![]
+                }
+            };
+            sender.input(Message::DevicesLoaded(adapter, devices));
+        });
+    }
+}
+
+fn power_on(bluetooth_backend: &BluetoothBackendHandle) -> anyhow::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match bluetooth_backend.set_powered(true) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= POWER_ON_ATTEMPTS => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(POWER_ON_RETRY_INTERVAL);
+            }
+        }
+    }
+}
+
+fn device_item(
+    widget_configs: &WidgetConfigs,
+    device: &BluetoothDevice,
+    sender: &ComponentSender<ManageBluetoothPage>,
+    on_click: fn(BluetoothDevice) -> Message,
+) -> Controller<CustomBluetoothItem> {
+    let pressed_device = device.clone();
+    CustomBluetoothItem::builder()
+        .launch(CustomBluetoothItemSettings {
+            name: device.to_string(),
+            detail: device.rssi.map(|rssi| format!("{} dBm", rssi)),
+            is_connected: device.connected,
+            connected_icon: widget_configs.network_item.connected_icon.clone(),
+            info_i_icon: None,
+            info_arrow_icon: widget_configs.menu_item.end_icon.clone(), // forword arrow
+        })
+        .forward(sender.input_sender(), move |msg| {
+            info!("bluetooth item msg is {:?}", msg);
+            match msg {
+                CustomBluetoothItemMessage::WidgetClicked => on_click(pressed_device.clone()),
+            }
+        })
+}
+
+fn timeout_name(timeout: u32) -> String {
+    match timeout {
+        0 => "Until turned off".to_string(),
+        timeout if timeout % 60 == 0 && timeout > 60 => format!("{} minutes", timeout / 60),
+        60 => "1 minute".to_string(),
+        timeout => format!("{} seconds", timeout),
+    }
+}
+
+/// Appends a row with a switch to `container`, returns the switch
+fn switch_row(container: &gtk::Box, text: &str) -> gtk::Switch {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let label = gtk::Label::builder()
+        .label(text)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-switch-text"])
+        .build();
+
+    let switch = gtk::Switch::new();
+    switch.add_css_class("custom-switch");
+
+    row.append(&label);
+    row.append(&switch);
+    container.append(&row);
+    switch
+}
+
+fn dropdown_row(container: &gtk::Box, text: &str, dropdown: &gtk::DropDown) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let label = gtk::Label::builder()
+        .label(text)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+
+    row.append(&label);
+    row.append(dropdown);
+    container.append(&row);
 }
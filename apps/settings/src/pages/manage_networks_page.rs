@@ -1,9 +1,12 @@
-use gtk::prelude::*;
+use std::{thread, time::Duration};
+
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::network::{NetworkBackendHandle, WifiNetwork, WifiNetworkList},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_network_item::{
         CustomNetworkItem, CustomNetworkItemSettings, Message as CustomNetworkItemMessage,
@@ -14,33 +17,49 @@ use custom_widgets::icon_button::{
     InputMessage as IconButtonInputMessage, OutputMessage as IconButtonOutputMessage,
 };
 
-use tracing::info;
+use tracing::{error, info};
+
+/// Time given to the wifi device to finish a scan before reading the results
+const SCAN_SETTLE_TIME: Duration = Duration::from_secs(3);
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub refresh_interval: u32,
 }
 
 //Model
 pub struct ManageNetworksPage {
     settings: Settings,
+    networks: WifiNetworkList,
+    is_scanning: bool,
 }
 
 //Widgets
 pub struct ManageNetworksPageWidgets {
     back_button: Controller<IconButton>,
     submit_button: Controller<IconButton>,
+    scanning_label: gtk::Label,
+    known_networks_label: gtk::Label,
+    known_networks_list: gtk::Box,
+    available_networks_list: gtk::Box,
+    network_items: Vec<Controller<CustomNetworkItem>>,
+    rendered_networks: WifiNetworkList,
 }
 
 //Messages
 #[derive(Debug)]
 pub enum Message {
     BackPressed,
-    KnownNetworkPressed,
-    AvailableNetworkPressed,
+    KnownNetworkPressed(WifiNetwork),
+    AvailableNetworkPressed(WifiNetwork),
     AddNetworkPressed,
+    ScanRequested,
+    RefreshNetworks,
+    NetworksUpdated(WifiNetworkList),
 }
 
 pub struct SettingItem {
@@ -82,55 +101,24 @@ impl SimpleComponent for ManageNetworksPage {
             .build();
         header.append(&header_title);
 
+        let scanning_label = gtk::Label::builder()
+            .label("Scanning...")
+            .css_classes(["list-label"])
+            .halign(gtk::Align::Start)
+            .visible(false)
+            .build();
+
         let known_networks_label = gtk::Label::builder()
             .label("Known Networks")
             .css_classes(["list-label"])
             .halign(gtk::Align::Start)
+            .visible(false)
             .build();
 
         let known_networks_list = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        let known_network_1 = CustomNetworkItem::builder()
-            .launch(CustomNetworkItemSettings {
-                name: "Actonate 5g".to_string(),
-                is_connected: true,
-                is_private: true,
-                strength: 80,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                private_icon: widget_configs.network_item.private_icon.clone(),
-                strength_icon: widget_configs.network_item.wifi_100_icon.clone(),
-                info_icon: widget_configs.network_item.info_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("Actonate 5g - info click- msg is {:?}", msg);
-                match msg {
-                    CustomNetworkItemMessage::WidgetClicked => Message::KnownNetworkPressed,
-                }
-            });
-
-        let known_network_2 = CustomNetworkItem::builder()
-            .launch(CustomNetworkItemSettings {
-                name: "Actonate 2g".to_string(),
-                is_connected: false,
-                is_private: true,
-                strength: 80,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                private_icon: widget_configs.network_item.private_icon.clone(),
-                strength_icon: widget_configs.network_item.wifi_100_icon.clone(),
-                info_icon: widget_configs.network_item.info_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomNetworkItemMessage::WidgetClicked => Message::KnownNetworkPressed,
-                }
-            });
-
-        known_networks_list.append(known_network_1.widget());
-        known_networks_list.append(known_network_2.widget());
-
         let available_networks_label = gtk::Label::builder()
             .label("Available Networks")
             .css_classes(["list-label"])
@@ -141,50 +129,12 @@ impl SimpleComponent for ManageNetworksPage {
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        let available_network_1 = CustomNetworkItem::builder()
-            .launch(CustomNetworkItemSettings {
-                name: "Mecha 5g".to_string(),
-                is_connected: false,
-                is_private: true,
-                strength: 80,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                private_icon: widget_configs.network_item.private_icon.clone(),
-                strength_icon: widget_configs.network_item.wifi_100_icon.clone(),
-                info_icon: widget_configs.network_item.info_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomNetworkItemMessage::WidgetClicked => Message::AvailableNetworkPressed,
-                }
-            });
-
-        let available_network_2 = CustomNetworkItem::builder()
-            .launch(CustomNetworkItemSettings {
-                name: "Mecha 5g".to_string(),
-                is_connected: false,
-                is_private: false,
-                strength: 80,
-                connected_icon: widget_configs.network_item.connected_icon.clone(),
-                private_icon: widget_configs.network_item.private_icon.clone(),
-                strength_icon: widget_configs.network_item.wifi_100_icon.clone(),
-                info_icon: widget_configs.network_item.info_icon.clone(),
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomNetworkItemMessage::WidgetClicked => Message::AvailableNetworkPressed,
-                }
-            });
-
-        available_networks_list.append(available_network_1.widget());
-        available_networks_list.append(available_network_2.widget());
-
         root.append(&header);
 
         let scrollable_content = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
+        scrollable_content.append(&scanning_label);
         scrollable_content.append(&known_networks_label);
         scrollable_content.append(&known_networks_list);
         scrollable_content.append(&available_networks_label);
@@ -196,6 +146,13 @@ impl SimpleComponent for ManageNetworksPage {
             .min_content_height(360)
             .child(&scrollable_content)
             .build();
+
+        // pull to refresh
+        scrolled_window.connect_edge_overshot(clone!(@strong sender => move |_, position| {
+            if position == gtk::PositionType::Top {
+                sender.input(Message::ScanRequested);
+            }
+        }));
         root.append(&scrolled_window);
 
         let footer = gtk::Box::builder()
@@ -235,11 +192,30 @@ impl SimpleComponent for ManageNetworksPage {
 
         root.append(&footer);
 
-        let model = ManageNetworksPage { settings: init };
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshNetworks);
+                glib::ControlFlow::Continue
+            }),
+        );
+
+        let mut model = ManageNetworksPage {
+            settings: init,
+            networks: WifiNetworkList::default(),
+            is_scanning: false,
+        };
+        model.scan(sender.clone());
 
         let widgets = ManageNetworksPageWidgets {
             back_button,
             submit_button,
+            scanning_label,
+            known_networks_label,
+            known_networks_list,
+            available_networks_list,
+            network_items: vec![],
+            rendered_networks: WifiNetworkList::default(),
         };
 
         ComponentParts { model, widgets }
@@ -251,17 +227,121 @@ impl SimpleComponent for ManageNetworksPage {
             Message::BackPressed => {
                 let _ = sender.output(Message::BackPressed);
             }
-            Message::KnownNetworkPressed => {
-                let _ = sender.output(Message::KnownNetworkPressed);
+            Message::KnownNetworkPressed(network) => {
+                let _ = sender.output(Message::KnownNetworkPressed(network));
             }
-            Message::AvailableNetworkPressed => {
-                let _ = sender.output(Message::AvailableNetworkPressed);
+            Message::AvailableNetworkPressed(network) => {
+                let _ = sender.output(Message::AvailableNetworkPressed(network));
             }
             Message::AddNetworkPressed => {
                 let _ = sender.output(Message::AddNetworkPressed);
             }
+            Message::ScanRequested => {
+                if !self.is_scanning {
+                    self.scan(sender);
+                }
+            }
+            Message::RefreshNetworks => {
+                if !self.is_scanning {
+                    self.refresh_networks(sender);
+                }
+            }
+            Message::NetworksUpdated(networks) => {
+                self.networks = networks;
+                self.is_scanning = false;
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets.scanning_label.set_visible(self.is_scanning);
+
+        if widgets.rendered_networks == self.networks {
+            return;
+        }
+
+        for list in [&widgets.known_networks_list, &widgets.available_networks_list] {
+            while let Some(child) = list.first_child() {
+                list.remove(&child);
+            }
         }
+        widgets.network_items.clear();
+
+        let widget_configs = &self.settings.widget_configs;
+        for network in self.networks.known.iter() {
+            let network_item =
+                network_item(widget_configs, network, &sender, Message::KnownNetworkPressed);
+            widgets.known_networks_list.append(network_item.widget());
+            widgets.network_items.push(network_item);
+        }
+
+        for network in self.networks.available.iter() {
+            let network_item =
+                network_item(widget_configs, network, &sender, Message::AvailableNetworkPressed);
+            widgets.available_networks_list.append(network_item.widget());
+            widgets.network_items.push(network_item);
+        }
+
+        widgets
+            .known_networks_label
+            .set_visible(!self.networks.known.is_empty());
+        widgets.rendered_networks = self.networks.clone();
     }
+}
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+impl ManageNetworksPage {
+    fn scan(&mut self, sender: ComponentSender<Self>) {
+        self.is_scanning = true;
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || {
+            // a scan can be refused right after a previous one, still show the last results
+            if let Err(e) = network_backend.request_wifi_scan() {
+                error!("error while requesting wifi scan {}", e);
+            }
+            thread::sleep(SCAN_SETTLE_TIME);
+            let networks = match network_backend.wifi_network_list() {
+                Ok(networks) => networks,
+                Err(e) => {
+                    error!("error while fetching wifi networks {}", e);
+                    WifiNetworkList::default()
+                }
+            };
+            sender.input(Message::NetworksUpdated(networks));
+        });
+    }
+
+    fn refresh_networks(&self, sender: ComponentSender<Self>) {
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || match network_backend.wifi_network_list() {
+            Ok(networks) => sender.input(Message::NetworksUpdated(networks)),
+            Err(e) => error!("error while fetching wifi networks {}", e),
+        });
+    }
+}
+
+fn network_item(
+    widget_configs: &WidgetConfigs,
+    network: &WifiNetwork,
+    sender: &ComponentSender<ManageNetworksPage>,
+    on_click: fn(WifiNetwork) -> Message,
+) -> Controller<CustomNetworkItem> {
+    let strength = network.strength as i32;
+    let network = network.clone();
+    CustomNetworkItem::builder()
+        .launch(CustomNetworkItemSettings {
+            name: network.ssid.clone(),
+            is_connected: network.is_connected,
+            is_private: network.security.is_private(),
+            strength,
+            connected_icon: widget_configs.network_item.connected_icon.clone(),
+            private_icon: widget_configs.network_item.private_icon.clone(),
+            strength_icon: widget_configs.network_item.strength_icon(strength),
+            info_icon: widget_configs.network_item.info_icon.clone(),
+        })
+        .forward(sender.input_sender(), move |msg| {
+            info!("{} - info click- msg is {:?}", network.ssid, msg);
+            match msg {
+                CustomNetworkItemMessage::WidgetClicked => on_click(network.clone()),
+            }
+        })
 }
@@ -1,5 +1,6 @@
 pub mod add_network_page;
 pub mod bluetooth_details_page;
+pub mod bluetooth_file_request_page;
 pub mod bluetooth_pair_request_page;
 pub mod connect_bluetooth_page;
 pub mod connect_network_page;
@@ -27,4 +28,10 @@ pub mod ip_settings_page;
 pub mod protocol_modes_page;
 pub mod protocol_details_page;
 pub mod ethernet_page;
-pub mod dns_page;
\ No newline at end of file
+pub mod dns_page;
+pub mod hotspot_page;
+pub mod proxy_page;
+pub mod data_usage_page;
+pub mod diagnostics_page;
+pub mod vpn_page;
+pub mod received_files_page;
\ No newline at end of file
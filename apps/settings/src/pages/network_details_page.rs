@@ -1,28 +1,71 @@
-use gtk::prelude::*;
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
 use relm4::{
-    gtk::{self},
+    gtk::{self, glib},
     Component, ComponentController, ComponentParts, ComponentSender, Controller, SimpleComponent,
 };
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
+use crate::{
+    backends::network::{
+        wifi_channel, IpAddress, MacAddressMode, NetworkBackendHandle, WifiDetails,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
 use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
 };
-use tracing::info;
+use tracing::{error, info};
+
+/// MAC address modes in the order they are listed in the dropdown
+const MAC_ADDRESS_OPTIONS: [MacAddressMode; 3] = [
+    MacAddressMode::Device,
+    MacAddressMode::Random,
+    MacAddressMode::Stable,
+];
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub refresh_interval: u32,
 }
 
 //Model
 pub struct NetworkDetailsPage {
     settings: Settings,
+    ssid: Option<String>,
+    details: Option<WifiDetails>,
+    error: Option<String>,
 }
 
 //Widgets
 pub struct NetworkDetailsPageWidgets {
+    network_name: gtk::Label,
+    network_ssid_value: gtk::Label,
+    bssid_value: gtk::Label,
+    security_value: gtk::Label,
+    frequency_value: gtk::Label,
+    signal_value: gtk::Label,
+    autoconnect_row: gtk::Box,
+    autoconnect_switch: gtk::Switch,
+    mac_address_row: gtk::Box,
+    mac_address_dropdown: gtk::DropDown,
+    metered_row: gtk::Box,
+    metered_switch: gtk::Switch,
+    ip_details_box: gtk::Box,
+    ip_address_value: gtk::Label,
+    subnet_mask_value: gtk::Label,
+    gateway_value: gtk::Label,
+    ipv6_address_row: gtk::Box,
+    ipv6_address_value: gtk::Label,
+    ipv6_link_local_row: gtk::Box,
+    ipv6_link_local_value: gtk::Label,
+    ipv6_gateway_row: gtk::Box,
+    ipv6_gateway_value: gtk::Label,
+    dns_value: gtk::Label,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
     remove_button: Controller<IconButton>,
 }
@@ -33,6 +76,14 @@ pub enum Message {
     BackPressed,
     RemovePressed,
     HomeIconPressed,
+    SetNetwork(String),
+    RefreshDetails,
+    DetailsUpdated(WifiDetails),
+    AutoconnectToggled(bool),
+    MacAddressModeChanged(u32),
+    MeteredToggled(bool),
+    RequestFailed(String),
+    NetworkForgotten,
 }
 
 pub struct SettingItem {
@@ -63,7 +114,7 @@ impl SimpleComponent for NetworkDetailsPage {
         let widget_configs = init.widget_configs.clone();
 
         let network_name = gtk::Label::builder()
-            .label("Actonate 5g")
+            .label("")
             .css_classes(["header-title"])
             .build();
 
@@ -79,173 +130,142 @@ impl SimpleComponent for NetworkDetailsPage {
             .css_classes(["settings-item-details-box"])
             .build();
 
-        let network_ssid_row = gtk::Box::builder()
-            .orientation(gtk::Orientation::Horizontal)
-            .hexpand(true)
-            .css_classes(["settings-item-details-box-row"])
-            .build();
-
-        let network_ssid_key = gtk::Label::builder()
-            .label("Network SSID")
-            .hexpand(true)
-            .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let network_ssid_value = gtk::Label::builder()
-            .label("Actonate 5g")
-            .css_classes(["settings-item-details-box-row-value"])
-            .build();
-
-        network_ssid_row.append(&network_ssid_key);
-        network_ssid_row.append(&network_ssid_value);
+        let (network_ssid_row, network_ssid_value) = details_row("Network SSID");
+        let (bssid_row, bssid_value) = details_row("BSSID");
+        let (security_row, security_value) = details_row("Security");
+        let (frequency_row, frequency_value) = details_row("Frequency");
+        let (signal_row, signal_value) = details_row("Signal");
 
         network_details_box_1.append(&network_ssid_row);
+        network_details_box_1.append(&bssid_row);
+        network_details_box_1.append(&security_row);
+        network_details_box_1.append(&frequency_row);
+        network_details_box_1.append(&signal_row);
 
-        let network_id_row = gtk::Box::builder()
-            .orientation(gtk::Orientation::Horizontal)
-            .hexpand(true)
-            .css_classes(["settings-item-details-box-row"])
-            .build();
-
-        let network_id_key = gtk::Label::builder()
-            .label("Network ID")
-            .hexpand(true)
-            .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let network_id_value = gtk::Label::builder()
-            .label("2")
-            .css_classes(["settings-item-details-box-row-value"])
+        let autoconnect_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
             .build();
 
-        network_id_row.append(&network_id_key);
-        network_id_row.append(&network_id_value);
-
-        network_details_box_1.append(&network_id_row);
-
-        let passphrase_row = gtk::Box::builder()
+        let autoconnect_row = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .hexpand(true)
+            .visible(false)
             .css_classes(["settings-item-details-box-row"])
             .build();
 
-        let passphrase_key = gtk::Label::builder()
-            .label("Passphrase")
+        let autoconnect_text = gtk::Label::builder()
+            .label("Auto-connect")
             .hexpand(true)
             .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let passphrase_value = gtk::Label::builder()
-            .label("WPA2")
-            .css_classes(["settings-item-details-box-row-value"])
+            .css_classes(["custom-switch-text"])
             .build();
 
-        passphrase_row.append(&passphrase_key);
-        passphrase_row.append(&passphrase_value);
+        let autoconnect_switch = gtk::Switch::new();
+        autoconnect_switch.add_css_class("custom-switch");
+        autoconnect_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::AutoconnectToggled(state));
+            glib::Propagation::Proceed
+        }));
 
-        network_details_box_1.append(&passphrase_row);
+        autoconnect_row.append(&autoconnect_text);
+        autoconnect_row.append(&autoconnect_switch);
+        autoconnect_box.append(&autoconnect_row);
 
-        let frequency_row = gtk::Box::builder()
+        let mac_address_row = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .hexpand(true)
+            .visible(false)
             .css_classes(["settings-item-details-box-row"])
             .build();
 
-        let frequency_key = gtk::Label::builder()
-            .label("Frequency")
+        let mac_address_text = gtk::Label::builder()
+            .label("MAC Address")
             .hexpand(true)
             .halign(gtk::Align::Start)
             .css_classes(["settings-item-details-box-row-key"])
             .build();
-        let frequency_value = gtk::Label::builder()
-            .label("5GHz")
-            .css_classes(["settings-item-details-box-row-value"])
-            .build();
-
-        frequency_row.append(&frequency_key);
-        frequency_row.append(&frequency_value);
-
-        network_details_box_1.append(&frequency_row);
-
-        let network_details_box_2 = gtk::Box::builder()
-            .orientation(gtk::Orientation::Vertical)
-            .hexpand(true)
-            .css_classes(["settings-item-details-box"])
-            .build();
 
-        let ip_address_row = gtk::Box::builder()
+        let mac_address_names: Vec<String> = MAC_ADDRESS_OPTIONS.iter().map(|mode| mode.to_string()).collect();
+        let mac_address_names: Vec<&str> = mac_address_names.iter().map(|name| name.as_str()).collect();
+        let mac_address_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&mac_address_names)),
+            gtk::Expression::NONE,
+        );
+        mac_address_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::MacAddressModeChanged(dropdown.selected()));
+        }));
+
+        mac_address_row.append(&mac_address_text);
+        mac_address_row.append(&mac_address_dropdown);
+        autoconnect_box.append(&mac_address_row);
+
+        // tells updates and other background downloads to hold off
+        let metered_row = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .hexpand(true)
+            .visible(false)
             .css_classes(["settings-item-details-box-row"])
             .build();
 
-        let ip_address_key = gtk::Label::builder()
-            .label("IP Address")
+        let metered_text = gtk::Label::builder()
+            .label("Metered connection")
             .hexpand(true)
             .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let ip_address_value = gtk::Label::builder()
-            .label("192.168.203.106")
-            .css_classes(["settings-item-details-box-row-value"])
+            .css_classes(["custom-switch-text"])
             .build();
 
-        ip_address_row.append(&ip_address_key);
-        ip_address_row.append(&ip_address_value);
+        let metered_switch = gtk::Switch::new();
+        metered_switch.add_css_class("custom-switch");
+        metered_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::MeteredToggled(state));
+            glib::Propagation::Proceed
+        }));
 
-        network_details_box_2.append(&ip_address_row);
-
-        let subnet_mask_row = gtk::Box::builder()
-            .orientation(gtk::Orientation::Horizontal)
-            .hexpand(true)
-            .css_classes(["settings-item-details-box-row"])
-            .build();
-
-        let subnet_mask_key = gtk::Label::builder()
-            .label("Subnet Mask")
-            .hexpand(true)
-            .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let subnet_mask_value = gtk::Label::builder()
-            .label("255.255.255.0")
-            .css_classes(["settings-item-details-box-row-value"])
-            .build();
+        metered_row.append(&metered_text);
+        metered_row.append(&metered_switch);
+        autoconnect_box.append(&metered_row);
 
-        subnet_mask_row.append(&subnet_mask_key);
-        subnet_mask_row.append(&subnet_mask_value);
-
-        network_details_box_2.append(&subnet_mask_row);
-
-        let gateway_row = gtk::Box::builder()
-            .orientation(gtk::Orientation::Horizontal)
+        let ip_details_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
             .hexpand(true)
-            .css_classes(["settings-item-details-box-row"])
+            .visible(false)
+            .css_classes(["settings-item-details-box"])
             .build();
 
-        let gateway_key = gtk::Label::builder()
-            .label("Gateway")
-            .hexpand(true)
+        let (ip_address_row, ip_address_value) = details_row("IP Address");
+        let (subnet_mask_row, subnet_mask_value) = details_row("Subnet Mask");
+        let (gateway_row, gateway_value) = details_row("Gateway");
+        let (ipv6_address_row, ipv6_address_value) = details_row("IPv6 Address");
+        let (ipv6_link_local_row, ipv6_link_local_value) = details_row("IPv6 Link-local");
+        let (ipv6_gateway_row, ipv6_gateway_value) = details_row("IPv6 Gateway");
+        let (dns_row, dns_value) = details_row("DNS");
+
+        ip_details_box.append(&ip_address_row);
+        ip_details_box.append(&subnet_mask_row);
+        ip_details_box.append(&gateway_row);
+        ip_details_box.append(&ipv6_address_row);
+        ip_details_box.append(&ipv6_link_local_row);
+        ip_details_box.append(&ipv6_gateway_row);
+        ip_details_box.append(&dns_row);
+
+        let error_label = gtk::Label::builder()
+            .label("")
             .halign(gtk::Align::Start)
-            .css_classes(["settings-item-details-box-row-key"])
-            .build();
-        let gateway_value = gtk::Label::builder()
-            .label("192.168.0.1")
-            .css_classes(["settings-item-details-box-row-value"])
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
             .build();
 
-        gateway_row.append(&gateway_key);
-        gateway_row.append(&gateway_value);
-
-        network_details_box_2.append(&gateway_row);
-
         root.append(&header);
 
         let scrollable_content = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
         scrollable_content.append(&network_details_box_1);
-        scrollable_content.append(&network_details_box_2);
+        scrollable_content.append(&autoconnect_box);
+        scrollable_content.append(&ip_details_box);
+        scrollable_content.append(&error_label);
 
         let scrolled_window = gtk::ScrolledWindow::builder()
             .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
@@ -274,6 +294,7 @@ impl SimpleComponent for NetworkDetailsPage {
             });
         footer.append(back_button.widget());
 
+        // forget this network
         let remove_button = IconButton::builder()
             .launch(IconButtonStetings {
                 icon: widget_configs.footer.trash_icon.to_owned(),
@@ -286,14 +307,51 @@ impl SimpleComponent for NetworkDetailsPage {
         let remove_button_widget = remove_button.widget();
         remove_button_widget.set_hexpand(true);
         remove_button_widget.set_halign(gtk::Align::End);
+        remove_button_widget.set_visible(false);
 
         footer.append(remove_button_widget);
         root.append(&footer);
-        root.append(&footer);
 
-        let model = NetworkDetailsPage { settings: init };
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshDetails);
+                glib::ControlFlow::Continue
+            }),
+        );
+
+        let model = NetworkDetailsPage {
+            settings: init,
+            ssid: None,
+            details: None,
+            error: None,
+        };
 
         let widgets = NetworkDetailsPageWidgets {
+            network_name,
+            network_ssid_value,
+            bssid_value,
+            security_value,
+            frequency_value,
+            signal_value,
+            autoconnect_row,
+            autoconnect_switch,
+            mac_address_row,
+            mac_address_dropdown,
+            metered_row,
+            metered_switch,
+            ip_details_box,
+            ip_address_value,
+            subnet_mask_value,
+            gateway_value,
+            ipv6_address_row,
+            ipv6_address_value,
+            ipv6_link_local_row,
+            ipv6_link_local_value,
+            ipv6_gateway_row,
+            ipv6_gateway_value,
+            dns_value,
+            error_label,
             back_button,
             remove_button
         };
@@ -310,9 +368,261 @@ impl SimpleComponent for NetworkDetailsPage {
             Message::HomeIconPressed => {
                 sender.output(Message::HomeIconPressed);
             }
-            Message::RemovePressed => {}
+            Message::SetNetwork(ssid) => {
+                self.ssid = Some(ssid);
+                self.details = None;
+                self.error = None;
+                self.refresh_details(sender);
+            }
+            Message::RefreshDetails => {
+                self.refresh_details(sender);
+            }
+            Message::DetailsUpdated(details) => {
+                // ignore results of a network the page moved away from
+                if self.ssid.as_deref() == Some(details.network.ssid.as_str()) {
+                    self.details = Some(details);
+                }
+            }
+            Message::AutoconnectToggled(autoconnect) => {
+                let (ssid, details) = match (&self.ssid, &mut self.details) {
+                    (Some(ssid), Some(details)) => (ssid.clone(), details),
+                    _ => return,
+                };
+                if details.autoconnect == autoconnect {
+                    return;
+                }
+                details.autoconnect = autoconnect;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_wifi_autoconnect(&ssid, autoconnect) {
+                        error!("error while setting autoconnect of {} {}", ssid, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                        sender.input(Message::RefreshDetails);
+                    }
+                });
+            }
+            Message::MacAddressModeChanged(index) => {
+                let mode = match MAC_ADDRESS_OPTIONS.get(index as usize) {
+                    Some(mode) => *mode,
+                    None => return,
+                };
+                let (ssid, details) = match (&self.ssid, &mut self.details) {
+                    (Some(ssid), Some(details)) => (ssid.clone(), details),
+                    _ => return,
+                };
+                if details.mac_address_mode.is_none() || details.mac_address_mode == Some(mode) {
+                    return;
+                }
+                details.mac_address_mode = Some(mode);
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_wifi_mac_address_mode(&ssid, mode) {
+                        error!("error while setting mac address of {} {}", ssid, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                    sender.input(Message::RefreshDetails);
+                });
+            }
+            Message::MeteredToggled(metered) => {
+                let (ssid, details) = match (&self.ssid, &mut self.details) {
+                    (Some(ssid), Some(details)) => (ssid.clone(), details),
+                    _ => return,
+                };
+                if details.metered.is_none() || details.metered == Some(metered) {
+                    return;
+                }
+                details.metered = Some(metered);
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_wifi_metered(&ssid, metered) {
+                        error!("error while setting metered of {} {}", ssid, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                        sender.input(Message::RefreshDetails);
+                    }
+                });
+            }
+            Message::RemovePressed => {
+                let ssid = match &self.ssid {
+                    Some(ssid) => ssid.clone(),
+                    None => return,
+                };
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.forget_wifi(&ssid) {
+                    Ok(_) => sender.input(Message::NetworkForgotten),
+                    Err(e) => {
+                        error!("error while forgetting {} {}", ssid, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+            Message::NetworkForgotten => {
+                self.ssid = None;
+                self.details = None;
+                let _ = sender.output(Message::NetworkForgotten);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let ssid = self.ssid.clone().unwrap_or_default();
+        widgets.network_name.set_label(&ssid);
+        widgets.network_ssid_value.set_label(&ssid);
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+
+        let details = match &self.details {
+            Some(details) => details,
+            None => {
+                for label in [
+                    &widgets.bssid_value,
+                    &widgets.security_value,
+                    &widgets.frequency_value,
+                    &widgets.signal_value,
+                ] {
+                    label.set_label("");
+                }
+                widgets.autoconnect_row.set_visible(false);
+                widgets.mac_address_row.set_visible(false);
+                widgets.metered_row.set_visible(false);
+                widgets.ip_details_box.set_visible(false);
+                widgets.remove_button.widget().set_visible(false);
+                return;
+            }
+        };
+
+        let network = &details.network;
+        let in_range = !network.bssid.is_empty();
+        widgets.bssid_value.set_label(&network.bssid);
+        widgets.security_value.set_label(&network.security.to_string());
+
+        let frequency = match (in_range, wifi_channel(network.frequency)) {
+            (false, _) => String::new(),
+            (true, Some(channel)) => format!(
+                "{:.1} GHz (channel {})",
+                network.frequency as f32 / 1000.0,
+                channel
+            ),
+            (true, None) => format!("{:.1} GHz", network.frequency as f32 / 1000.0),
+        };
+        widgets.frequency_value.set_label(&frequency);
+
+        let signal = match in_range {
+            true => format!("{}%", network.strength),
+            false => "Out of range".to_string(),
+        };
+        widgets.signal_value.set_label(&signal);
+
+        widgets.autoconnect_row.set_visible(details.is_known);
+        widgets.autoconnect_switch.set_active(details.autoconnect);
+
+        let mac_address_index = details
+            .mac_address_mode
+            .and_then(|mode| MAC_ADDRESS_OPTIONS.iter().position(|m| *m == mode));
+        if let Some(index) = mac_address_index {
+            if widgets.mac_address_dropdown.selected() != index as u32 {
+                widgets.mac_address_dropdown.set_selected(index as u32);
+            }
+        }
+        widgets
+            .mac_address_row
+            .set_visible(details.is_known && mac_address_index.is_some());
+        widgets
+            .metered_switch
+            .set_active(details.metered.unwrap_or_default());
+        widgets
+            .metered_row
+            .set_visible(details.is_known && details.metered.is_some());
+        widgets.remove_button.widget().set_visible(details.is_known);
+
+        match &details.ip {
+            Some(ip) => {
+                let ipv4_address = ip.ipv4_addresses.first();
+                widgets.ip_address_value.set_label(
+                    &ipv4_address
+                        .map(|address| address.address.clone())
+                        .unwrap_or_default(),
+                );
+                widgets.subnet_mask_value.set_label(
+                    &ipv4_address
+                        .and_then(IpAddress::subnet_mask)
+                        .unwrap_or_default(),
+                );
+                widgets
+                    .gateway_value
+                    .set_label(&ip.ipv4_gateway.clone().unwrap_or_default());
+
+                let (link_local, global): (Vec<&IpAddress>, Vec<&IpAddress>) =
+                    ip.ipv6_addresses.iter().partition(|address| address.is_link_local());
+                let join = |addresses: &[&IpAddress]| {
+                    addresses
+                        .iter()
+                        .map(|address| address.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                };
+                widgets.ipv6_address_value.set_label(&join(&global));
+                widgets.ipv6_address_row.set_visible(!global.is_empty());
+                widgets.ipv6_link_local_value.set_label(&join(&link_local));
+                widgets.ipv6_link_local_row.set_visible(!link_local.is_empty());
+                widgets
+                    .ipv6_gateway_value
+                    .set_label(&ip.ipv6_gateway.clone().unwrap_or_default());
+                widgets.ipv6_gateway_row.set_visible(ip.ipv6_gateway.is_some());
+                widgets.dns_value.set_label(&ip.dns.join("\n"));
+                widgets.ip_details_box.set_visible(true);
+            }
+            None => {
+                widgets.ip_details_box.set_visible(false);
+            }
+        }
+    }
+}
+
+impl NetworkDetailsPage {
+    fn refresh_details(&self, sender: ComponentSender<Self>) {
+        let ssid = match &self.ssid {
+            Some(ssid) => ssid.clone(),
+            None => return,
+        };
+
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || match network_backend.wifi_details(&ssid) {
+            Ok(details) => sender.input(Message::DetailsUpdated(details)),
+            Err(e) => error!("error while fetching details of {} {}", ssid, e),
+        });
+    }
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .selectable(true)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
 }
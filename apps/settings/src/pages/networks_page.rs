@@ -1,10 +1,16 @@
+use std::thread;
+
 use gtk::{glib::clone, prelude::*};
 use custom_utils::get_image_from_path;
 use relm4::{
-    gtk::{self, GestureClick},
+    gtk::{self, glib, GestureClick},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::{
+        network::{ConnectionState, NetworkBackendHandle, WifiStatus},
+        rfkill::{RfkillBackendHandle, RfkillState},
+    },
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_item::{
         CustomListItem, CustomListItemSettings, Message as CustomListItemMessage,
@@ -13,23 +19,38 @@ use crate::{
 use custom_widgets::icon_button::{
         IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
     };
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub rfkill_backend: RfkillBackendHandle,
+    pub refresh_interval: u32,
 }
 
 //Model
 pub struct NetworksPage {
     settings: Settings,
+    wifi_status: WifiStatus,
+    rfkill_state: RfkillState,
+    /// State the airplane switch shows until the rfkill events arrive
+    airplane_mode: bool,
+    is_airplane_busy: bool,
 }
 
 //Widgets
 pub struct NetworksPageWidgets {
     back_button: Controller<IconButton>,
+    airplane_switch: gtk::Switch,
+    wifi_switch: gtk::Switch,
+    wifi_hardware_label: gtk::Label,
+    enabled_network_row: gtk::Box,
+    enabled_network_text: gtk::Label,
+    enabled_network_status: gtk::Label,
+    connected_icon_box: gtk::Box,
 }
 
 //Messages
@@ -37,11 +58,24 @@ pub struct NetworksPageWidgets {
 pub enum Message { 
 BackPressed,
     EnableNetworkPressed,
+    ActiveNetworkPressed(String),
     ManageNetworkPressed,
     IpSettingsPressed,
+    ActiveNetworkIpSettingsPressed(String),
     EthernetPressed,
     DNSPressed,
+    HotspotPressed,
+    ProxyPressed,
+    DataUsagePressed,
+    DiagnosticsPressed,
+    VpnPressed,
     HomeIconPressed,
+    WifiToggled(bool),
+    RefreshStatus,
+    StatusUpdated(WifiStatus),
+    AirplaneModeToggled(bool),
+    AirplaneModeChanged,
+    RfkillStateChanged(RfkillState),
 }
 
 
@@ -84,6 +118,35 @@ impl SimpleComponent for NetworksPage {
             .build();
         header.append(&header_title);
 
+        let airplane_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .build();
+
+        let airplane_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .css_classes(["settings-item-details-box-row"])
+            .build();
+
+        let airplane_text = gtk::Label::builder()
+            .label("Airplane Mode")
+            .hexpand(true)
+            .halign(gtk::Align::Start)
+            .css_classes(["custom-switch-text"])
+            .build();
+
+        let airplane_switch = gtk::Switch::new();
+        airplane_switch.add_css_class("custom-switch");
+        airplane_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::AirplaneModeToggled(state));
+            glib::Propagation::Proceed
+        }));
+
+        airplane_row.append(&airplane_text);
+        airplane_row.append(&airplane_switch);
+        airplane_box.append(&airplane_row);
+
         let wifi_label = gtk::Label::builder()
             .label("Wifi")
             .halign(gtk::Align::Start)
@@ -112,36 +175,57 @@ impl SimpleComponent for NetworksPage {
             .build();
 
         let switch = gtk::Switch::new();
-        switch.set_active(true);
+        switch.set_active(false);
         let style_context = switch.style_context();
         style_context.add_class("custom-switch");
+        switch.connect_state_set(clone!(@strong sender => move |_, state| {
+            sender.input(Message::WifiToggled(state));
+            glib::Propagation::Proceed
+        }));
 
         enable_network_row.append(&enable_network_text);
         enable_network_row.append(&switch);
         network_details.append(&enable_network_row);
 
+        // the app cannot undo a hardware switch, only point at it
+        let wifi_hardware_label = gtk::Label::builder()
+            .label("Wi-Fi is turned off by a hardware switch")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
         // when switch is true 
         let enabled_network_row = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
         .hexpand(true)
+        .visible(false)
         .css_classes(["settings-item-details-box-row"])
         .build();
 
         let enabled_network_text = gtk::Label::builder()
-        .label("Actonate Office net1")
+        .label("")
         .hexpand(true)
         .halign(gtk::Align::Start)
         .css_classes(["settings-item-details-box-row-key"])
         .build();
 
+        let enabled_network_status = gtk::Label::builder()
+        .label("")
+        .halign(gtk::Align::End)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
 
-        let connected_icon_box = gtk::Box::builder().hexpand(true).halign(gtk::Align::End).build();
+        let connected_icon_box = gtk::Box::builder().hexpand(true).halign(gtk::Align::End).visible(false).build();
         let connected_icon = get_image_from_path(widget_configs.network_item.connected_icon, &[]);
         connected_icon_box.append(&connected_icon);
 
         let see_details_icon = get_image_from_path(widget_configs.menu_item.end_icon.clone(), &[]);
 
         enabled_network_row.append(&enabled_network_text);
+        enabled_network_row.append(&enabled_network_status);
         enabled_network_row.append(&connected_icon_box);
         enabled_network_row.append(&see_details_icon);
         network_details.append(&enabled_network_row);
@@ -153,7 +237,7 @@ impl SimpleComponent for NetworksPage {
 
         network_click_gesture.connect_released(clone!(@strong sender => move |this, _, _,_| {
             info!("gesture button released is {}", this.current_button());
-            let _ = sender.output(Message::EnableNetworkPressed);
+            sender.input(Message::EnableNetworkPressed);
         }));
         enabled_network_row.add_controller(network_click_gesture);
 
@@ -230,17 +314,99 @@ impl SimpleComponent for NetworksPage {
             });
         let dns_widget = dns.widget();
 
+        let hotspot = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "Hotspot".to_string(),
+                value: "".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::HotspotPressed,
+                }
+            });
+        let hotspot_widget = hotspot.widget();
+
+        let proxy = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "Proxy".to_string(),
+                value: "".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::ProxyPressed,
+                }
+            });
+        let proxy_widget = proxy.widget();
+
+        let data_usage = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "Data Usage".to_string(),
+                value: "".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::DataUsagePressed,
+                }
+            });
+        let data_usage_widget = data_usage.widget();
+
+        let diagnostics = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "Diagnostics".to_string(),
+                value: "".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::DiagnosticsPressed,
+                }
+            });
+        let diagnostics_widget = diagnostics.widget();
+
+        let vpn = CustomListItem::builder()
+            .launch(CustomListItemSettings {
+                start_icon: None,
+                text: "VPN".to_string(),
+                value: "".to_owned(),
+                end_icon: widget_configs.menu_item.end_icon.clone(),
+            })
+            .forward(sender.input_sender(), |msg| {
+                info!("msg is {:?}", msg);
+                match msg {
+                    CustomListItemMessage::WidgetClicked => Message::VpnPressed,
+                }
+            });
+        let vpn_widget = vpn.widget();
+
         others_list_items.append(ethernet_widget);
         others_list_items.append(dns_widget);
+        others_list_items.append(hotspot_widget);
+        others_list_items.append(proxy_widget);
+        others_list_items.append(data_usage_widget);
+        others_list_items.append(vpn_widget);
+        others_list_items.append(diagnostics_widget);
 
         root.append(&header);
 
         let scrollable_content = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
+        scrollable_content.append(&airplane_box);
         scrollable_content.append(&wifi_label);
 
         scrollable_content.append(&network_details);
+        scrollable_content.append(&wifi_hardware_label);
         scrollable_content.append(&wifi_list_items);
         scrollable_content.append(&others_label);
         scrollable_content.append(&others_list_items);
@@ -275,10 +441,33 @@ impl SimpleComponent for NetworksPage {
         footer.append(back_button.widget());
         root.append(&footer);
 
-        let model = NetworksPage { settings: init };
+        // keep the wifi state in sync with changes made outside the app
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshStatus);
+                glib::ControlFlow::Continue
+            }),
+        );
+
+        let model = NetworksPage {
+            settings: init,
+            wifi_status: WifiStatus::default(),
+            rfkill_state: RfkillState::default(),
+            airplane_mode: false,
+            is_airplane_busy: false,
+        };
+        model.refresh_status(sender.clone());
 
         let widgets = NetworksPageWidgets {
-            back_button
+            back_button,
+            airplane_switch,
+            wifi_switch: switch,
+            wifi_hardware_label,
+            enabled_network_row,
+            enabled_network_text,
+            enabled_network_status,
+            connected_icon_box,
         };
 
         ComponentParts { model, widgets }
@@ -291,8 +480,11 @@ impl SimpleComponent for NetworksPage {
             let _ = sender.output(Message::BackPressed);
             },
             Message::EnableNetworkPressed => {
-                let _ = sender.output(Message::EnableNetworkPressed);
+                if let Some(active_wifi) = &self.wifi_status.active {
+                    let _ = sender.output(Message::ActiveNetworkPressed(active_wifi.ssid.clone()));
+                }
             },
+            Message::ActiveNetworkPressed(_) => {}
             Message::ManageNetworkPressed => {
                 let _ = sender.output(Message::ManageNetworkPressed);
                             },
@@ -300,16 +492,121 @@ impl SimpleComponent for NetworksPage {
                 let _ = sender.output(Message::HomeIconPressed);
             }
             Message::IpSettingsPressed => {
-                let _ = sender.output(Message::IpSettingsPressed);
+                // IP settings belong to the profile of the joined network
+                if let Some(active_wifi) = &self.wifi_status.active {
+                    let _ = sender.output(Message::ActiveNetworkIpSettingsPressed(
+                        active_wifi.ssid.clone(),
+                    ));
+                }
             }
+            Message::ActiveNetworkIpSettingsPressed(_) => {}
             Message::EthernetPressed => {
                 let _ = sender.output(Message::EthernetPressed);
             }
             Message::DNSPressed => {
                 let _ = sender.output(Message::DNSPressed);
             }
+            Message::HotspotPressed => {
+                let _ = sender.output(Message::HotspotPressed);
+            }
+            Message::ProxyPressed => {
+                let _ = sender.output(Message::ProxyPressed);
+            }
+            Message::DataUsagePressed => {
+                let _ = sender.output(Message::DataUsagePressed);
+            }
+            Message::DiagnosticsPressed => {
+                let _ = sender.output(Message::DiagnosticsPressed);
+            }
+            Message::VpnPressed => {
+                let _ = sender.output(Message::VpnPressed);
+            }
+            Message::WifiToggled(enabled) => {
+                // the switch also emits when synced from the backend state
+                if enabled == self.wifi_status.enabled || self.rfkill_state.wifi.hard_blocked {
+                    return;
+                }
+                self.wifi_status.enabled = enabled;
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_wifi_enabled(enabled) {
+                        error!("error while setting wifi enabled {}", e);
+                    }
+                    sender.input(Message::RefreshStatus);
+                });
+            }
+            Message::RefreshStatus => {
+                self.refresh_status(sender);
+            }
+            Message::StatusUpdated(wifi_status) => {
+                self.wifi_status = wifi_status;
+            }
+            Message::AirplaneModeToggled(enabled) => {
+                if enabled == self.airplane_mode || self.is_airplane_busy {
+                    return;
+                }
+                self.airplane_mode = enabled;
+                self.is_airplane_busy = true;
+                let rfkill_backend = self.settings.rfkill_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = rfkill_backend.set_airplane_mode(enabled) {
+                        error!("error while setting airplane mode {}", e);
+                    }
+                    sender.input(Message::AirplaneModeChanged);
+                });
+            }
+            Message::AirplaneModeChanged => {
+                self.is_airplane_busy = false;
+                self.airplane_mode = self.rfkill_state.airplane_mode();
+                self.refresh_status(sender);
+            }
+            Message::RfkillStateChanged(rfkill_state) => {
+                self.rfkill_state = rfkill_state;
+                if !self.is_airplane_busy {
+                    self.airplane_mode = rfkill_state.airplane_mode();
+                }
+                self.refresh_status(sender);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets.airplane_switch.set_active(self.airplane_mode);
+        let has_radios = self.rfkill_state.wifi.present || self.rfkill_state.bluetooth.present;
+        widgets
+            .airplane_switch
+            .set_sensitive(has_radios && !self.is_airplane_busy);
+
+        let wifi_hard_blocked = self.rfkill_state.wifi.hard_blocked;
+        widgets.wifi_switch.set_active(self.wifi_status.enabled && !wifi_hard_blocked);
+        widgets.wifi_switch.set_sensitive(!wifi_hard_blocked);
+        widgets.wifi_hardware_label.set_visible(wifi_hard_blocked);
+
+        match &self.wifi_status.active {
+            Some(active_wifi) => {
+                let is_connected = active_wifi.state == ConnectionState::Activated;
+                widgets.enabled_network_text.set_label(&active_wifi.ssid);
+                let status = match is_connected {
+                    true => String::new(),
+                    false => active_wifi.state.to_string(),
+                };
+                widgets.enabled_network_status.set_label(&status);
+                widgets.connected_icon_box.set_visible(is_connected);
+                widgets.enabled_network_row.set_visible(true);
+            }
+            None => {
+                widgets.enabled_network_row.set_visible(false);
+            }
         }
     }
+}
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+impl NetworksPage {
+    fn refresh_status(&self, sender: ComponentSender<Self>) {
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || match network_backend.wifi_status() {
+            Ok(wifi_status) => sender.input(Message::StatusUpdated(wifi_status)),
+            Err(e) => error!("error while fetching wifi status {}", e),
+        });
+    }
 }
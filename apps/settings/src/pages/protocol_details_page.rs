@@ -1,42 +1,95 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     Component, ComponentParts, ComponentSender, SimpleComponent, Controller, ComponentController,
 };
-use crate::settings::{LayoutSettings, Modules, WidgetConfigs};
-use custom_widgets::icon_button::{
-    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+use crate::{
+    backends::network::{
+        IpConnection, IpFamily, Ipv4Config, Ipv6Config, NetworkBackendHandle, StaticIpv4,
+        StaticIpv6,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
 };
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
 }
 
 //Model
 pub struct ProtocolDetailsPage {
     settings: Settings,
+    connection: Option<IpConnection>,
+    family: IpFamily,
+    saved_config: Option<StaticIpv4>,
+    saved_ipv6_config: Option<StaticIpv6>,
+    ip_address: String,
+    subnet_mask: String,
+    gateway: String,
+    dns: String,
+    ipv6_address: String,
+    ipv6_prefix: String,
+    ipv6_gateway: String,
+    ipv6_dns: String,
+    error: Option<String>,
+    is_applying: bool,
 }
 
 //Widgets
 pub struct ProtocolDetailsPageWidgets {
+    header_title: gtk::Label,
+    ipv4_form: gtk::Box,
+    ipv6_form: gtk::Box,
+    ip_address_input: Controller<IconInput>,
+    subnet_mask_input: Controller<IconInput>,
+    gateway_input: Controller<IconInput>,
+    dns_input: Controller<IconInput>,
+    rendered_config: Option<StaticIpv4>,
+    ipv6_address_input: Controller<IconInput>,
+    ipv6_prefix_input: Controller<IconInput>,
+    ipv6_gateway_input: Controller<IconInput>,
+    ipv6_dns_input: Controller<IconInput>,
+    rendered_ipv6_config: Option<StaticIpv6>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
+    apply_button: Controller<IconButton>,
 }
 
 //Messages
 #[derive(Debug)]
-pub enum Message { 
+pub enum Message {
     BackPressed,
     HomeIconPressed,
-}
-
-pub struct SettingItem {
-    text: String,
-    start_icon: Option<String>,
-    end_icon: Option<String>,
+    SetConnection(IpConnection),
+    SetFamily(IpFamily),
+    ConfigLoaded(Ipv4Config),
+    Ipv6ConfigLoaded(Ipv6Config),
+    IpAddressChange(String),
+    SubnetMaskChange(String),
+    GatewayChange(String),
+    DnsChange(String),
+    Ipv6AddressChange(String),
+    Ipv6PrefixChange(String),
+    Ipv6GatewayChange(String),
+    Ipv6DnsChange(String),
+    ApplyPressed,
+    ApplyFailed(String),
+    ConfigApplied(IpConnection),
 }
 
 impl SimpleComponent for ProtocolDetailsPage {
@@ -79,13 +132,6 @@ impl SimpleComponent for ProtocolDetailsPage {
             .css_classes(["settings-item-details-box"])
             .build();
 
-        
-        let details_list2 = gtk::Box::builder()
-            .orientation(gtk::Orientation::Vertical)
-            .css_classes(["settings-item-details-box"])
-            .build();
-
-
         let details_row_1 = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .hexpand(true)
@@ -110,79 +156,55 @@ impl SimpleComponent for ProtocolDetailsPage {
         details_row_1.append(&mode_value);
         details_list1.append(&details_row_1);
 
-        let details_row_2 = gtk::Box::builder()
-        .orientation(gtk::Orientation::Horizontal)
-        .hexpand(true)
-        .css_classes(["settings-item-details-box-row"])
-        .build();
-
-        let ip_address_label = gtk::Label::builder()
-        .label("IP Address")
-        .hexpand(true)
-        .halign(gtk::Align::Start)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
-
-        let ip_address_value = gtk::Label::builder()
-        .label("192.160.12.1")
-        .hexpand(true)
-        .halign(gtk::Align::End)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
-
-        details_row_2.append(&ip_address_label);
-        details_row_2.append(&ip_address_value);
-        details_list2.append(&details_row_2);
-
-
-        let details_row_3 = gtk::Box::builder()
-        .orientation(gtk::Orientation::Horizontal)
-        .hexpand(true)
-        .css_classes(["settings-item-details-box-row"])
-        .build();
-
-        let subnet_mask_label = gtk::Label::builder()
-        .label("Subnet Mask")
-        .hexpand(true)
-        .halign(gtk::Align::Start)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
-
-        let subnet_mask_value = gtk::Label::builder()
-        .label("255.255.255.0")
-        .hexpand(true)
-        .halign(gtk::Align::End)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
-
-        details_row_3.append(&subnet_mask_label);
-        details_row_3.append(&subnet_mask_value);
-        details_list2.append(&details_row_3);
-
-
-        let details_row_4 = gtk::Box::builder()
-        .orientation(gtk::Orientation::Horizontal)
-        .hexpand(true)
-        .css_classes(["settings-item-details-box-row"])
-        .build();
-
-        let gateway_label = gtk::Label::builder()
-        .label("Gateway")
-        .hexpand(true)
-        .halign(gtk::Align::Start)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
-
-        let gateway_value = gtk::Label::builder()
-        .label("None")
-        .hexpand(true)
-        .halign(gtk::Align::End)
-        .css_classes(["settings-item-details-box-row-key"])
-        .build();
+        let ip_address_input = text_input("192.168.1.20", &sender, Message::IpAddressChange);
+        let subnet_mask_input = text_input("255.255.255.0 or /24", &sender, Message::SubnetMaskChange);
+        let gateway_input = text_input("192.168.1.1", &sender, Message::GatewayChange);
+        let dns_input = text_input("1.1.1.1, 8.8.8.8", &sender, Message::DnsChange);
 
-        details_row_4.append(&gateway_label);
-        details_row_4.append(&gateway_value);
-        details_list2.append(&details_row_4);
+        let ipv4_form = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        ipv4_form.append(&field_label("IP Address"));
+        ipv4_form.append(ip_address_input.widget());
+        ipv4_form.append(&field_label("Subnet Mask"));
+        ipv4_form.append(subnet_mask_input.widget());
+        ipv4_form.append(&field_label("Gateway"));
+        ipv4_form.append(gateway_input.widget());
+        ipv4_form.append(&field_label("DNS Servers"));
+        ipv4_form.append(dns_input.widget());
+
+        let ipv6_address_input = text_input("2001:db8::20", &sender, Message::Ipv6AddressChange);
+        let ipv6_prefix_input = text_input("64", &sender, Message::Ipv6PrefixChange);
+        let ipv6_gateway_input = text_input("fe80::1 (optional)", &sender, Message::Ipv6GatewayChange);
+        let ipv6_dns_input = text_input("2606:4700:4700::1111", &sender, Message::Ipv6DnsChange);
+
+        let ipv6_form = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+        ipv6_form.append(&field_label("IPv6 Address"));
+        ipv6_form.append(ipv6_address_input.widget());
+        ipv6_form.append(&field_label("Prefix Length"));
+        ipv6_form.append(ipv6_prefix_input.widget());
+        ipv6_form.append(&field_label("Gateway"));
+        ipv6_form.append(ipv6_gateway_input.widget());
+        ipv6_form.append(&field_label("DNS Servers"));
+        ipv6_form.append(ipv6_dns_input.widget());
+
+        let form = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        form.append(&ipv4_form);
+        form.append(&ipv6_form);
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+        form.append(&error_label);
 
         root.append(&header);
 
@@ -191,7 +213,7 @@ impl SimpleComponent for ProtocolDetailsPage {
             .build();
 
         scrollable_content.append(&details_list1);
-        scrollable_content.append(&details_list2);
+        scrollable_content.append(&form);
 
         let scrolled_window = gtk::ScrolledWindow::builder()
             .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
@@ -209,7 +231,7 @@ impl SimpleComponent for ProtocolDetailsPage {
         .valign(gtk::Align::End)
         .build();
 
-        
+
         let back_button = IconButton::builder()
             .launch(IconButtonStetings {
                 icon: widget_configs.footer.back_icon.to_owned(),
@@ -221,12 +243,58 @@ impl SimpleComponent for ProtocolDetailsPage {
             });
 
         footer.append(back_button.widget());
+
+        let apply_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.next_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::ApplyPressed,
+            });
+        let apply_button_widget = apply_button.widget();
+        apply_button_widget.set_hexpand(true);
+        apply_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(apply_button_widget);
         root.append(&footer);
 
-        let model = ProtocolDetailsPage { settings: init };
+        let model = ProtocolDetailsPage {
+            settings: init,
+            connection: None,
+            family: IpFamily::V4,
+            saved_config: None,
+            saved_ipv6_config: None,
+            ip_address: String::new(),
+            subnet_mask: String::new(),
+            gateway: String::new(),
+            dns: String::new(),
+            ipv6_address: String::new(),
+            ipv6_prefix: String::new(),
+            ipv6_gateway: String::new(),
+            ipv6_dns: String::new(),
+            error: None,
+            is_applying: false,
+        };
 
         let widgets = ProtocolDetailsPageWidgets {
-            back_button
+            header_title,
+            ipv4_form,
+            ipv6_form,
+            ip_address_input,
+            subnet_mask_input,
+            gateway_input,
+            dns_input,
+            rendered_config: None,
+            ipv6_address_input,
+            ipv6_prefix_input,
+            ipv6_gateway_input,
+            ipv6_dns_input,
+            rendered_ipv6_config: None,
+            error_label,
+            back_button,
+            apply_button,
         };
 
         ComponentParts { model, widgets }
@@ -241,8 +309,209 @@ impl SimpleComponent for ProtocolDetailsPage {
             Message::HomeIconPressed => {
                 let _ = sender.output(Message::HomeIconPressed);
             }
+            Message::SetConnection(connection) => {
+                self.connection = Some(connection.clone());
+                self.saved_config = None;
+                self.saved_ipv6_config = None;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    match network_backend.ipv4_config(&connection) {
+                        Ok(config) => sender.input(Message::ConfigLoaded(config)),
+                        Err(e) => error!("error while reading ipv4 config of {} {}", connection, e),
+                    }
+                    match network_backend.ipv6_config(&connection) {
+                        Ok(config) => sender.input(Message::Ipv6ConfigLoaded(config)),
+                        Err(e) => error!("error while reading ipv6 config of {} {}", connection, e),
+                    }
+                });
+            }
+            Message::SetFamily(family) => {
+                self.family = family;
+                self.error = None;
+            }
+            Message::ConfigLoaded(config) => {
+                // switching from DHCP starts from empty fields
+                if let Ipv4Config::Static(config) = config {
+                    self.saved_config = Some(config);
+                }
+            }
+            Message::Ipv6ConfigLoaded(config) => {
+                if let Ipv6Config::Static(config) = config {
+                    self.saved_ipv6_config = Some(config);
+                }
+            }
+            Message::IpAddressChange(text) => {
+                self.ip_address = text;
+            }
+            Message::SubnetMaskChange(text) => {
+                self.subnet_mask = text;
+            }
+            Message::GatewayChange(text) => {
+                self.gateway = text;
+            }
+            Message::DnsChange(text) => {
+                self.dns = text;
+            }
+            Message::Ipv6AddressChange(text) => {
+                self.ipv6_address = text;
+            }
+            Message::Ipv6PrefixChange(text) => {
+                self.ipv6_prefix = text;
+            }
+            Message::Ipv6GatewayChange(text) => {
+                self.ipv6_gateway = text;
+            }
+            Message::Ipv6DnsChange(text) => {
+                self.ipv6_dns = text;
+            }
+            Message::ApplyPressed => {
+                let connection = match &self.connection {
+                    Some(connection) => connection.clone(),
+                    None => return,
+                };
+                if self.is_applying {
+                    return;
+                }
+
+                match self.family {
+                    IpFamily::V4 => self.apply_ipv4(connection, sender),
+                    IpFamily::V6 => self.apply_ipv6(connection, sender),
+                }
+            }
+            Message::ApplyFailed(reason) => {
+                self.is_applying = false;
+                self.error = Some(reason);
+            }
+            Message::ConfigApplied(connection) => {
+                self.is_applying = false;
+                let _ = sender.output(Message::ConfigApplied(connection));
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        // fill the fields once per loaded config so edits are not overwritten
+        if widgets.rendered_config != self.saved_config {
+            if let Some(config) = &self.saved_config {
+                let dns: Vec<String> = config.dns.iter().map(|server| server.to_string()).collect();
+                let fields = [
+                    (&widgets.ip_address_input, config.address.to_string()),
+                    (&widgets.subnet_mask_input, config.netmask().to_string()),
+                    (&widgets.gateway_input, config.gateway.to_string()),
+                    (&widgets.dns_input, dns.join(", ")),
+                ];
+                for (input, text) in fields {
+                    input.emit(IconInputInputMessage::SetText(text));
+                }
+            }
+            widgets.rendered_config = self.saved_config.clone();
+        }
+        if widgets.rendered_ipv6_config != self.saved_ipv6_config {
+            if let Some(config) = &self.saved_ipv6_config {
+                let dns: Vec<String> = config.dns.iter().map(|server| server.to_string()).collect();
+                let gateway = config.gateway.map(|gateway| gateway.to_string()).unwrap_or_default();
+                let fields = [
+                    (&widgets.ipv6_address_input, config.address.to_string()),
+                    (&widgets.ipv6_prefix_input, config.prefix.to_string()),
+                    (&widgets.ipv6_gateway_input, gateway),
+                    (&widgets.ipv6_dns_input, dns.join(", ")),
+                ];
+                for (input, text) in fields {
+                    input.emit(IconInputInputMessage::SetText(text));
+                }
+            }
+            widgets.rendered_ipv6_config = self.saved_ipv6_config.clone();
         }
+
+        widgets
+            .header_title
+            .set_label(&format!("{} Settings", self.family));
+        widgets.ipv4_form.set_visible(self.family == IpFamily::V4);
+        widgets.ipv6_form.set_visible(self.family == IpFamily::V6);
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
     }
+}
+
+impl ProtocolDetailsPage {
+    fn apply_ipv4(&mut self, connection: IpConnection, sender: ComponentSender<Self>) {
+        let config = match StaticIpv4::parse(
+            &self.ip_address,
+            &self.subnet_mask,
+            &self.gateway,
+            &self.dns,
+        ) {
+            Ok(config) => Ipv4Config::Static(config),
+            Err(reason) => {
+                self.error = Some(reason);
+                return;
+            }
+        };
+
+        self.is_applying = true;
+        self.error = None;
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || match network_backend.apply_ipv4_config(&connection, &config) {
+            Ok(_) => sender.input(Message::ConfigApplied(connection)),
+            Err(e) => {
+                error!("error while applying static ipv4 on {} {}", connection, e);
+                sender.input(Message::ApplyFailed(e.to_string()));
+            }
+        });
+    }
+
+    fn apply_ipv6(&mut self, connection: IpConnection, sender: ComponentSender<Self>) {
+        let config = match StaticIpv6::parse(
+            &self.ipv6_address,
+            &self.ipv6_prefix,
+            &self.ipv6_gateway,
+            &self.ipv6_dns,
+        ) {
+            Ok(config) => Ipv6Config::Static(config),
+            Err(reason) => {
+                self.error = Some(reason);
+                return;
+            }
+        };
+
+        self.is_applying = true;
+        self.error = None;
+        let network_backend = self.settings.network_backend.clone();
+        thread::spawn(move || match network_backend.apply_ipv6_config(&connection, &config) {
+            Ok(_) => sender.input(Message::ConfigApplied(connection)),
+            Err(e) => {
+                error!("error while applying static ipv6 on {} {}", connection, e);
+                sender.input(Message::ApplyFailed(e.to_string()));
+            }
+        });
+    }
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+fn text_input(
+    placeholder: &str,
+    sender: &ComponentSender<ProtocolDetailsPage>,
+    on_change: fn(String) -> Message,
+) -> Controller<IconInput> {
+    IconInput::builder()
+        .launch(IconInputSettings {
+            clear_icon: None,
+            icon: None,
+            placeholder: Option::from(placeholder.to_string()),
+            css: IconInputCss::default(),
+        })
+        .forward(sender.input_sender(), move |msg| match msg {
+            IconInputOutputMessage::InputChange(text) => on_change(text),
+        })
 }
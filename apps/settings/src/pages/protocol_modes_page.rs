@@ -1,34 +1,61 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::network::{IpConnection, IpFamily, Ipv4Config, Ipv6Config, NetworkBackendHandle},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_radio_button::{
             CustomListRadioButton, CustomListRadioButtonSettings,
+            InputMessage as CustomListRadioButtonInputMessage,
             Message as CustomListRadioButtonMessage,
         },
 };
 use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
 };
-use tracing::info;
+use tracing::{error, info};
+
+/// IPv6 modes in the order they are listed, static is configured on the details page
+const IPV6_MODES: [(&str, Option<Ipv6Config>); 5] = [
+    ("Automatic [ SLAAC ]", Some(Ipv6Config::Auto)),
+    ("DHCPv6", Some(Ipv6Config::Dhcp)),
+    ("Static", None),
+    ("Link-local only", Some(Ipv6Config::LinkLocal)),
+    ("Disabled", Some(Ipv6Config::Disabled)),
+];
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
 }
 
 //Model
 pub struct ProtocolModesPage {
     settings: Settings,
+    connection: Option<IpConnection>,
+    family: IpFamily,
+    ipv4_config: Option<Ipv4Config>,
+    ipv6_config: Option<Ipv6Config>,
+    error: Option<String>,
+    is_applying: bool,
 }
 
 //Widgets
 pub struct ProtocolModesPageWidgets {
+    header_title: gtk::Label,
+    ipv4_modes: gtk::Box,
+    ipv6_modes: gtk::Box,
+    ipv6_mode_buttons: Vec<Controller<CustomListRadioButton>>,
+    auto_mode: Controller<CustomListRadioButton>,
+    static_mode: Controller<CustomListRadioButton>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
 }
 
@@ -39,6 +66,13 @@ pub enum Message {
     StaticModePressed,
     BackPressed,
     HomeIconPressed,
+    SetConnection(IpConnection),
+    SetFamily(IpFamily),
+    ConfigLoaded(Ipv4Config),
+    Ipv6ConfigLoaded(Ipv6Config),
+    Ipv6ModePressed(usize),
+    ApplyFailed(String),
+    ConfigApplied(IpConnection),
 }
 
 pub struct SettingItem {
@@ -120,8 +154,54 @@ impl SimpleComponent for ProtocolModesPage {
         let auto_mode_widget = auto_mode.widget();
         let static_mode_widget = static_mode.widget();
 
-        screen_off_timeout_items.append(auto_mode_widget);
-        screen_off_timeout_items.append(static_mode_widget);
+        let ipv4_modes = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        ipv4_modes.append(auto_mode_widget);
+        ipv4_modes.append(static_mode_widget);
+
+        let ipv6_modes = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+        let ipv6_mode_buttons: Vec<Controller<CustomListRadioButton>> = IPV6_MODES
+            .iter()
+            .enumerate()
+            .map(|(index, (text, mode))| {
+                let description_text = match mode {
+                    Some(_) => None,
+                    None => Some("<span foreground='red'>*</span> Specifying IPv6 Address and Prefix Length is mandatory".to_string()),
+                };
+                let button = CustomListRadioButton::builder()
+                    .launch(CustomListRadioButtonSettings {
+                        text: text.to_string(),
+                        active_icon: widget_configs.radio_item.active_icon.clone(),
+                        inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
+                        is_active: index == 0,
+                        description_text,
+                    })
+                    .forward(sender.input_sender(), move |msg| {
+                        info!("msg is {:?}", msg);
+                        match msg {
+                            CustomListRadioButtonMessage::WidgetClicked => Message::Ipv6ModePressed(index),
+                        }
+                    });
+                ipv6_modes.append(button.widget());
+                button
+            })
+            .collect();
+
+        screen_off_timeout_items.append(&ipv4_modes);
+        screen_off_timeout_items.append(&ipv6_modes);
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+        screen_off_timeout_items.append(&error_label);
 
         root.append(&header);
 
@@ -160,9 +240,24 @@ impl SimpleComponent for ProtocolModesPage {
         footer.append(back_button.widget());
         root.append(&footer);
 
-        let model = ProtocolModesPage { settings: init };
+        let model = ProtocolModesPage {
+            settings: init,
+            connection: None,
+            family: IpFamily::V4,
+            ipv4_config: None,
+            ipv6_config: None,
+            error: None,
+            is_applying: false,
+        };
 
         let widgets = ProtocolModesPageWidgets {
+            header_title,
+            ipv4_modes,
+            ipv6_modes,
+            ipv6_mode_buttons,
+            auto_mode,
+            static_mode,
+            error_label,
             back_button
         };
 
@@ -179,13 +274,126 @@ impl SimpleComponent for ProtocolModesPage {
                 let _ = sender.output(Message::StaticModePressed);
             },
             Message::AutoModePressed => {
-                let _ = sender.output(Message::AutoModePressed);
+                let connection = match &self.connection {
+                    Some(connection) => connection.clone(),
+                    None => return,
+                };
+                if self.is_applying || self.ipv4_config == Some(Ipv4Config::Auto) {
+                    return;
+                }
+
+                self.is_applying = true;
+                self.error = None;
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    match network_backend.apply_ipv4_config(&connection, &Ipv4Config::Auto) {
+                        Ok(_) => sender.input(Message::ConfigApplied(connection)),
+                        Err(e) => {
+                            error!("error while enabling dhcp on {} {}", connection, e);
+                            sender.input(Message::ApplyFailed(e.to_string()));
+                        }
+                    }
+                });
             },
+            Message::Ipv6ModePressed(index) => {
+                let config = match IPV6_MODES.get(index) {
+                    Some((_, Some(config))) => config.clone(),
+                    Some((_, None)) => {
+                        let _ = sender.output(Message::StaticModePressed);
+                        return;
+                    }
+                    None => return,
+                };
+                let connection = match &self.connection {
+                    Some(connection) => connection.clone(),
+                    None => return,
+                };
+                if self.is_applying || self.ipv6_config.as_ref() == Some(&config) {
+                    return;
+                }
+
+                self.is_applying = true;
+                self.error = None;
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.apply_ipv6_config(&connection, &config) {
+                    Ok(_) => sender.input(Message::ConfigApplied(connection)),
+                    Err(e) => {
+                        error!("error while setting ipv6 {} on {} {}", config, connection, e);
+                        sender.input(Message::ApplyFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::SetFamily(family) => {
+                self.family = family;
+                self.error = None;
+            }
             Message::HomeIconPressed => {
                 let _ = sender.output(Message::HomeIconPressed);
             }
+            Message::SetConnection(connection) => {
+                self.connection = Some(connection.clone());
+                self.ipv4_config = None;
+                self.ipv6_config = None;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    match network_backend.ipv4_config(&connection) {
+                        Ok(config) => sender.input(Message::ConfigLoaded(config)),
+                        Err(e) => error!("error while reading ipv4 config of {} {}", connection, e),
+                    }
+                    match network_backend.ipv6_config(&connection) {
+                        Ok(config) => sender.input(Message::Ipv6ConfigLoaded(config)),
+                        Err(e) => error!("error while reading ipv6 config of {} {}", connection, e),
+                    }
+                });
+            }
+            Message::ConfigLoaded(config) => {
+                self.ipv4_config = Some(config);
+            }
+            Message::Ipv6ConfigLoaded(config) => {
+                self.ipv6_config = Some(config);
+            }
+            Message::ApplyFailed(reason) => {
+                self.is_applying = false;
+                self.error = Some(reason);
+            }
+            Message::ConfigApplied(connection) => {
+                self.is_applying = false;
+                let _ = sender.output(Message::ConfigApplied(connection));
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets
+            .header_title
+            .set_label(&format!("{} Mode", self.family));
+        widgets.ipv4_modes.set_visible(self.family == IpFamily::V4);
+        widgets.ipv6_modes.set_visible(self.family == IpFamily::V6);
+
+        let ipv6_mode_index = IPV6_MODES
+            .iter()
+            .position(|(_, mode)| match (&self.ipv6_config, mode) {
+                (Some(Ipv6Config::Static(_)), None) => true,
+                (Some(config), Some(mode)) => config == mode,
+                _ => false,
+            })
+            .unwrap_or(0);
+        for (index, button) in widgets.ipv6_mode_buttons.iter().enumerate() {
+            button.emit(CustomListRadioButtonInputMessage::SetActive(index == ipv6_mode_index));
+        }
+
+        let is_static = matches!(self.ipv4_config, Some(Ipv4Config::Static(_)));
+        widgets
+            .auto_mode
+            .emit(CustomListRadioButtonInputMessage::SetActive(!is_static));
+        widgets
+            .static_mode
+            .emit(CustomListRadioButtonInputMessage::SetActive(is_static));
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,403 @@
+use std::thread;
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    component::Connector,
+    gtk::{self, glib},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+
+use crate::{
+    backends::network::{parse_bypass_hosts, NetworkBackendHandle, ProxyConfig, ProxyMode},
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
+};
+use tracing::{error, info};
+
+/// Modes in the order they are listed in the dropdown
+const MODE_OPTIONS: [ProxyMode; 3] = [ProxyMode::None, ProxyMode::Manual, ProxyMode::Automatic];
+
+/// The environment file only carries proxy servers, a PAC script is
+/// handed to NetworkManager
+const PAC_NOTE: &str = "Only apps that take the proxy from NetworkManager use the script, \
+    the connect app and command line tools connect directly";
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+}
+
+//Model
+pub struct ProxyPage {
+    settings: Settings,
+    config: ProxyConfig,
+    /// Bumped when the saved config is loaded so the inputs are filled once
+    config_generation: u32,
+    bypass_hosts: String,
+    is_applying: bool,
+    error: Option<String>,
+}
+
+//Widgets
+pub struct ProxyPageWidgets {
+    mode_dropdown: gtk::DropDown,
+    manual_box: gtk::Box,
+    automatic_box: gtk::Box,
+    http_input: Controller<IconInput>,
+    https_input: Controller<IconInput>,
+    socks_input: Controller<IconInput>,
+    bypass_input: Controller<IconInput>,
+    pac_url_input: Controller<IconInput>,
+    rendered_generation: u32,
+    error_label: gtk::Label,
+    back_button: Controller<IconButton>,
+    apply_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    BackPressed,
+    RefreshConfig,
+    ConfigLoaded(ProxyConfig),
+    ModeChanged(u32),
+    HttpChange(String),
+    HttpsChange(String),
+    SocksChange(String),
+    BypassChange(String),
+    PacUrlChange(String),
+    ApplyPressed,
+    RequestFailed(String),
+    ConfigApplied,
+}
+
+impl SimpleComponent for ProxyPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = ProxyPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Proxy")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        let mode_names: Vec<String> = MODE_OPTIONS.iter().map(|mode| mode.to_string()).collect();
+        let mode_names: Vec<&str> = mode_names.iter().map(|name| name.as_str()).collect();
+        let mode_dropdown = gtk::DropDown::new(
+            Some(gtk::StringList::new(&mode_names)),
+            gtk::Expression::NONE,
+        );
+        mode_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(Message::ModeChanged(dropdown.selected()));
+        }));
+
+        let manual_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+
+        let http_input = text_input("proxy.example.com:8080")
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::HttpChange(text),
+            });
+        let https_input = text_input("proxy.example.com:8080")
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::HttpsChange(text),
+            });
+        let socks_input = text_input("proxy.example.com:1080")
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::SocksChange(text),
+            });
+        let bypass_input = text_input("localhost, .local")
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::BypassChange(text),
+            });
+
+        manual_box.append(&field_label("HTTP Proxy"));
+        manual_box.append(http_input.widget());
+        manual_box.append(&field_label("HTTPS Proxy"));
+        manual_box.append(https_input.widget());
+        manual_box.append(&field_label("SOCKS Proxy"));
+        manual_box.append(socks_input.widget());
+        manual_box.append(&field_label("Bypass Hosts"));
+        manual_box.append(bypass_input.widget());
+
+        let automatic_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .visible(false)
+            .build();
+
+        let pac_url_input = text_input("http://example.com/proxy.pac")
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::PacUrlChange(text),
+            });
+
+        automatic_box.append(&field_label("Configuration URL"));
+        automatic_box.append(pac_url_input.widget());
+        let pac_note = gtk::Label::builder()
+            .label(PAC_NOTE)
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .css_classes(["proxy-pac-note"])
+            .build();
+        automatic_box.append(&pac_note);
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&field_label("Mode"));
+        scrollable_content.append(&mode_dropdown);
+        scrollable_content.append(&manual_box);
+        scrollable_content.append(&automatic_box);
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["footer"])
+        .hexpand(true)
+        .vexpand(true)
+        .valign(gtk::Align::End)
+        .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+
+        let apply_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.next_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::ApplyPressed,
+            });
+        let apply_button_widget = apply_button.widget();
+        apply_button_widget.set_hexpand(true);
+        apply_button_widget.set_halign(gtk::Align::End);
+
+        footer.append(apply_button_widget);
+        root.append(&footer);
+
+        sender.input(Message::RefreshConfig);
+
+        let model = ProxyPage {
+            settings: init,
+            config: ProxyConfig::default(),
+            config_generation: 0,
+            bypass_hosts: String::new(),
+            is_applying: false,
+            error: None,
+        };
+
+        let widgets = ProxyPageWidgets {
+            mode_dropdown,
+            manual_box,
+            automatic_box,
+            http_input,
+            https_input,
+            socks_input,
+            bypass_input,
+            pac_url_input,
+            rendered_generation: 0,
+            error_label,
+            back_button,
+            apply_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::RefreshConfig => {
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.proxy_config() {
+                    Ok(config) => sender.input(Message::ConfigLoaded(config)),
+                    Err(e) => {
+                        error!("error while reading proxy config {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::ConfigLoaded(config) => {
+                self.bypass_hosts = config.bypass.join(", ");
+                self.config = config;
+                self.config_generation += 1;
+                self.error = None;
+            }
+            Message::ModeChanged(index) => {
+                if let Some(mode) = MODE_OPTIONS.get(index as usize) {
+                    self.config.mode = *mode;
+                }
+            }
+            Message::HttpChange(text) => {
+                self.config.http = non_empty(text);
+            }
+            Message::HttpsChange(text) => {
+                self.config.https = non_empty(text);
+            }
+            Message::SocksChange(text) => {
+                self.config.socks = non_empty(text);
+            }
+            Message::BypassChange(text) => {
+                self.bypass_hosts = text;
+            }
+            Message::PacUrlChange(text) => {
+                self.config.pac_url = non_empty(text);
+            }
+            Message::ApplyPressed => {
+                if self.is_applying {
+                    return;
+                }
+
+                let mut config = self.config.clone();
+                config.bypass = parse_bypass_hosts(&self.bypass_hosts);
+                if let Err(reason) = config.validate() {
+                    self.error = Some(reason);
+                    return;
+                }
+
+                self.is_applying = true;
+                self.error = None;
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || match network_backend.set_proxy_config(&config) {
+                    Ok(_) => sender.input(Message::ConfigApplied),
+                    Err(e) => {
+                        error!("error while applying proxy config {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::RequestFailed(reason) => {
+                self.is_applying = false;
+                self.error = Some(reason);
+            }
+            Message::ConfigApplied => {
+                self.is_applying = false;
+                sender.input(Message::RefreshConfig);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        if let Some(index) = MODE_OPTIONS.iter().position(|mode| *mode == self.config.mode) {
+            if widgets.mode_dropdown.selected() != index as u32 {
+                widgets.mode_dropdown.set_selected(index as u32);
+            }
+        }
+        widgets
+            .manual_box
+            .set_visible(self.config.mode == ProxyMode::Manual);
+        widgets
+            .automatic_box
+            .set_visible(self.config.mode == ProxyMode::Automatic);
+
+        if widgets.rendered_generation != self.config_generation {
+            let inputs = [
+                (&widgets.http_input, self.config.http.clone()),
+                (&widgets.https_input, self.config.https.clone()),
+                (&widgets.socks_input, self.config.socks.clone()),
+                (&widgets.pac_url_input, self.config.pac_url.clone()),
+                (&widgets.bypass_input, Some(self.bypass_hosts.clone())),
+            ];
+            for (input, text) in inputs {
+                input.emit(IconInputInputMessage::SetText(text.unwrap_or_default()));
+            }
+            widgets.rendered_generation = self.config_generation;
+        }
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+fn text_input(placeholder: &str) -> Connector<IconInput> {
+    IconInput::builder().launch(IconInputSettings {
+        clear_icon: None,
+        icon: None,
+        placeholder: Option::from(placeholder.to_string()),
+        css: IconInputCss::default(),
+    })
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim();
+    match text.is_empty() {
+        true => None,
+        false => Some(text.to_string()),
+    }
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
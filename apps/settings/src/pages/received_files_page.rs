@@ -0,0 +1,452 @@
+use std::{collections::HashMap, thread};
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    gtk::{self},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+use crate::{
+    backends::{
+        bluetooth::{BluetoothBackendHandle, IncomingFile, ReceivedFile, TransferEvent},
+        data_usage::format_bytes,
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::icon_button::{
+    IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+};
+use tracing::{error, info};
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub bluetooth_backend: BluetoothBackendHandle,
+    pub download_dir: String,
+}
+
+/// Accepted file being received, `received` in bytes
+#[derive(Debug, Clone)]
+struct Transfer {
+    file: IncomingFile,
+    received: u64,
+    cancelling: bool,
+}
+
+//Model
+pub struct ReceivedFilesPage {
+    settings: Settings,
+    transfers: Vec<Transfer>,
+    history: Vec<ReceivedFile>,
+    error: Option<String>,
+}
+
+/// Row of a file being received
+struct TransferRow {
+    row: gtk::Box,
+    progress_level: gtk::LevelBar,
+    progress_label: gtk::Label,
+    cancel_button: gtk::Button,
+}
+
+//Widgets
+pub struct ReceivedFilesPageWidgets {
+    back_button: Controller<IconButton>,
+    error_label: gtk::Label,
+    receiving_label: gtk::Label,
+    transfers_list: gtk::Box,
+    transfer_rows: HashMap<u64, TransferRow>,
+    history_list: gtk::Box,
+    empty_label: gtk::Label,
+    rendered_history: Option<Vec<ReceivedFile>>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    MenuItemPressed(String),
+    BackPressed,
+    HomeIconPressed,
+    /// The user accepted an offered file, it is saved from here on
+    FileAccepted(IncomingFile),
+    TransferEvent(TransferEvent),
+    CancelPressed(u64),
+    RefreshHistory,
+    HistoryLoaded(Vec<ReceivedFile>),
+    RequestFailed(String),
+}
+
+impl SimpleComponent for ReceivedFilesPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = ReceivedFilesPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("Received Files")
+            .css_classes(["header-title"])
+            .build();
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+        header.append(&header_title);
+
+        let download_dir_label = gtk::Label::builder()
+            .label(&format!("Files sent over Bluetooth are saved to {}", init.download_dir))
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .css_classes(["bluetooth-pair-request-list-label"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        let receiving_label = gtk::Label::builder()
+            .label("Receiving")
+            .css_classes(["list-label"])
+            .halign(gtk::Align::Start)
+            .visible(false)
+            .build();
+
+        let transfers_list = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .visible(false)
+            .build();
+
+        let history_label = gtk::Label::builder()
+            .label("History")
+            .css_classes(["list-label"])
+            .halign(gtk::Align::Start)
+            .build();
+
+        let history_list = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["settings-item-details-box"])
+            .visible(false)
+            .build();
+
+        let empty_label = gtk::Label::builder()
+            .label("No files received yet")
+            .halign(gtk::Align::Start)
+            .css_classes(["connect-network-status"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&download_dir_label);
+        scrollable_content.append(&error_label);
+        scrollable_content.append(&receiving_label);
+        scrollable_content.append(&transfers_list);
+        scrollable_content.append(&history_label);
+        scrollable_content.append(&history_list);
+        scrollable_content.append(&empty_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["footer"])
+            .hexpand(true)
+            .vexpand(true)
+            .valign(gtk::Align::End)
+            .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+        root.append(&footer);
+
+        let model = ReceivedFilesPage {
+            settings: init,
+            transfers: vec![],
+            history: vec![],
+            error: None,
+        };
+        sender.input(Message::RefreshHistory);
+
+        let widgets = ReceivedFilesPageWidgets {
+            back_button,
+            error_label,
+            receiving_label,
+            transfers_list,
+            transfer_rows: HashMap::new(),
+            history_list,
+            empty_label,
+            rendered_history: None,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::MenuItemPressed(key) => {}
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::HomeIconPressed => {
+                let _ = sender.output(Message::HomeIconPressed);
+            }
+            Message::FileAccepted(file) => {
+                let id = file.id;
+                self.error = None;
+                self.transfers.push(Transfer {
+                    file,
+                    received: 0,
+                    cancelling: false,
+                });
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = bluetooth_backend.reply_file(id, true) {
+                        error!("error while accepting file {} {}", id, e);
+                        sender.input(Message::TransferEvent(TransferEvent::Finished(
+                            id,
+                            Some(e.to_string()),
+                        )));
+                    }
+                });
+            }
+            Message::TransferEvent(event) => match event {
+                TransferEvent::Progress(id, received) => {
+                    if let Some(transfer) = self.transfer_mut(id) {
+                        transfer.received = received;
+                    }
+                }
+                TransferEvent::Finished(id, error) => {
+                    let transfer = match self.transfers.iter().position(|t| t.file.id == id) {
+                        Some(index) => self.transfers.remove(index),
+                        None => return,
+                    };
+                    match error {
+                        Some(e) => self.error = Some(format!("{} - {}", transfer.file.filename, e)),
+                        None => sender.input(Message::RefreshHistory),
+                    }
+                }
+                // offers are answered on the file request page
+                TransferEvent::Requested(_) | TransferEvent::Cancelled(_) => {}
+            },
+            Message::CancelPressed(id) => {
+                match self.transfer_mut(id) {
+                    Some(transfer) if !transfer.cancelling => transfer.cancelling = true,
+                    _ => return,
+                }
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = bluetooth_backend.cancel_transfer(id) {
+                        error!("error while cancelling file {} {}", id, e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::RefreshHistory => {
+                let bluetooth_backend = self.settings.bluetooth_backend.clone();
+                thread::spawn(move || match bluetooth_backend.received_files() {
+                    Ok(history) => sender.input(Message::HistoryLoaded(history)),
+                    Err(e) => {
+                        error!("error while reading received files {}", e);
+                        sender.input(Message::RequestFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::HistoryLoaded(history) => {
+                self.history = history;
+            }
+            Message::RequestFailed(e) => {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        widgets
+            .error_label
+            .set_label(self.error.as_deref().unwrap_or(""));
+        widgets.error_label.set_visible(self.error.is_some());
+
+        // rows are kept while receiving, progress comes in many times a second
+        let ids: Vec<u64> = self.transfers.iter().map(|transfer| transfer.file.id).collect();
+        widgets.transfer_rows.retain(|id, transfer_row| {
+            let is_active = ids.contains(id);
+            if !is_active {
+                widgets.transfers_list.remove(&transfer_row.row);
+            }
+            is_active
+        });
+        for transfer in self.transfers.iter() {
+            let transfer_row = widgets
+                .transfer_rows
+                .entry(transfer.file.id)
+                .or_insert_with(|| {
+                    let transfer_row = transfer_row(&transfer.file, &sender);
+                    widgets.transfers_list.append(&transfer_row.row);
+                    transfer_row
+                });
+            let fraction = match transfer.file.size {
+                Some(size) if size > 0 => (transfer.received as f64 / size as f64).min(1.0),
+                _ => 0.0,
+            };
+            transfer_row.progress_level.set_value(fraction);
+            transfer_row.progress_level.set_visible(transfer.file.size.is_some());
+            transfer_row.progress_label.set_label(&match transfer.file.size {
+                Some(size) => format!("{} of {}", format_bytes(transfer.received), format_bytes(size)),
+                None => format_bytes(transfer.received),
+            });
+            transfer_row.cancel_button.set_sensitive(!transfer.cancelling);
+        }
+        widgets.receiving_label.set_visible(!self.transfers.is_empty());
+        widgets.transfers_list.set_visible(!self.transfers.is_empty());
+
+        if widgets.rendered_history.as_ref() == Some(&self.history) {
+            return;
+        }
+        while let Some(child) = widgets.history_list.first_child() {
+            widgets.history_list.remove(&child);
+        }
+        for received in self.history.iter() {
+            widgets.history_list.append(&history_row(received));
+        }
+        widgets.history_list.set_visible(!self.history.is_empty());
+        widgets.empty_label.set_visible(self.history.is_empty());
+        widgets.rendered_history = Some(self.history.clone());
+    }
+}
+
+impl ReceivedFilesPage {
+    fn transfer_mut(&mut self, id: u64) -> Option<&mut Transfer> {
+        self.transfers.iter_mut().find(|transfer| transfer.file.id == id)
+    }
+}
+
+fn transfer_row(file: &IncomingFile, sender: &ComponentSender<ReceivedFilesPage>) -> TransferRow {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let title_row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .build();
+    let name_label = gtk::Label::builder()
+        .label(&file.filename)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .ellipsize(gtk::pango::EllipsizeMode::Middle)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let cancel_button = gtk::Button::builder()
+        .label("Cancel")
+        .css_classes(["bluetooth-action"])
+        .build();
+    let id = file.id;
+    cancel_button.connect_clicked(clone!(@strong sender => move |_| {
+        sender.input(Message::CancelPressed(id));
+    }));
+    title_row.append(&name_label);
+    title_row.append(&cancel_button);
+
+    let progress_level = gtk::LevelBar::builder()
+        .min_value(0.0)
+        .max_value(1.0)
+        .value(0.0)
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["custom-levelbar"])
+        .build();
+
+    let progress_label = gtk::Label::builder()
+        .label("")
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-item-detail"])
+        .build();
+
+    let sender_label = gtk::Label::builder()
+        .label(&format!("From {}", file.sender))
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-item-detail"])
+        .build();
+
+    row.append(&title_row);
+    row.append(&sender_label);
+    row.append(&progress_level);
+    row.append(&progress_label);
+
+    TransferRow {
+        row,
+        progress_level,
+        progress_label,
+        cancel_button,
+    }
+}
+
+fn history_row(received: &ReceivedFile) -> gtk::Box {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let name_label = gtk::Label::builder()
+        .label(&received.filename)
+        .halign(gtk::Align::Start)
+        .ellipsize(gtk::pango::EllipsizeMode::Middle)
+        .tooltip_text(&received.path)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+
+    let detail_label = gtk::Label::builder()
+        .label(&format!(
+            "{} · {} · {}",
+            received.sender,
+            format_bytes(received.size),
+            received.received_on
+        ))
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-item-detail"])
+        .build();
+
+    row.append(&name_label);
+    row.append(&detail_label);
+    row
+}
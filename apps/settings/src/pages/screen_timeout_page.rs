@@ -1,12 +1,16 @@
+use std::thread;
+
 use gtk::prelude::*;
 use relm4::{
     gtk::{self},
     Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
 };
 use crate::{
+    backends::display::{format_timeout, DisplayBackendHandle, SCREEN_TIMEOUT_OPTIONS},
     settings::{LayoutSettings, Modules, WidgetConfigs},
     widgets::custom_list_radio_button::{
             CustomListRadioButton, CustomListRadioButtonSettings,
+            InputMessage as CustomListRadioButtonInputMessage,
             Message as CustomListRadioButtonMessage,
         },
 };
@@ -14,22 +18,31 @@ use custom_widgets::icon_button::{
     IconButton, IconButtonCss, InitSettings as IconButtonStetings,
     InputMessage as IconButtonInputMessage, OutputMessage as IconButtonOutputMessage,
 };
-use tracing::info;
+use tracing::{error, info};
 
 //Init Settings
 pub struct Settings {
     pub modules: Modules,
     pub layout: LayoutSettings,
     pub widget_configs: WidgetConfigs,
+    pub display_backend: DisplayBackendHandle,
 }
 
 //Model
 pub struct ScreenTimeoutPage {
     settings: Settings,
+    /// Timeout in use, in seconds
+    timeout: Option<u32>,
+    /// Timeout picked on the page, saved on submit
+    selected: Option<u32>,
+    error: Option<String>,
+    is_saving: bool,
 }
 
 //Widgets
 pub struct ScreenTimeoutPageWidgets {
+    timeout_buttons: Vec<Controller<CustomListRadioButton>>,
+    error_label: gtk::Label,
     back_button: Controller<IconButton>,
     submit_button: Controller<IconButton>,
 }
@@ -40,7 +53,14 @@ pub enum Message {
     MenuItemPressed(String),
     BackPressed,
     HomeIconPressed,
-    SubmitPressed
+    SubmitPressed,
+    TimeoutPressed(usize),
+    TimeoutLoaded(u32),
+    /// The timeout in use changed, in seconds, the pages that
+    /// show it are told
+    TimeoutChanged(u32),
+    TimeoutSaved(u32),
+    SaveFailed(String),
 }
 
 pub struct SettingItem {
@@ -88,106 +108,37 @@ impl SimpleComponent for ScreenTimeoutPage {
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        let timeout_10_s = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "10s".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: false,
-                ..Default::default()
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
-
-        let timeout_30_s = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "30s".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: true,
-                ..Default::default()
+        let timeout_buttons: Vec<Controller<CustomListRadioButton>> = SCREEN_TIMEOUT_OPTIONS
+            .iter()
+            .enumerate()
+            .map(|(index, timeout)| {
+                let button = CustomListRadioButton::builder()
+                    .launch(CustomListRadioButtonSettings {
+                        text: format_timeout(*timeout),
+                        active_icon: widget_configs.radio_item.active_icon.clone(),
+                        inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
+                        is_active: false,
+                        ..Default::default()
+                    })
+                    .forward(sender.input_sender(), move |msg| {
+                        info!("msg is {:?}", msg);
+                        match msg {
+                            CustomListRadioButtonMessage::WidgetClicked => Message::TimeoutPressed(index),
+                        }
+                    });
+                screen_off_timeout_items.append(button.widget());
+                button
             })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
-        let timeout_60_s = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "60s".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: false,
-                ..Default::default()
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
+            .collect();
 
-        let timeout_5_m = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "5m".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: false,
-                ..Default::default()
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
-        let timeout_15_m = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "15m".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: false,
-                ..Default::default()
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
-
-        let timeout_30_m = CustomListRadioButton::builder()
-            .launch(CustomListRadioButtonSettings {
-                text: "30m".to_string(),
-                active_icon: widget_configs.radio_item.active_icon.clone(),
-                inactive_icon: widget_configs.radio_item.inactive_icon.clone(),
-                is_active: false,
-                ..Default::default()
-            })
-            .forward(sender.input_sender(), |msg| {
-                info!("msg is {:?}", msg);
-                match msg {
-                    CustomListRadioButtonMessage::WidgetClicked => Message::HomeIconPressed,
-                }
-            });
-
-        let timeout_10_s_widget = timeout_10_s.widget();
-        let timeout_30_s_widget = timeout_30_s.widget();
-        let timeout_60_s_widget = timeout_60_s.widget();
-        let timeout_5_m_widget = timeout_5_m.widget();
-        let timeout_15_m_widget = timeout_15_m.widget();
-        let timeout_30_m_widget = timeout_30_m.widget();
-        screen_off_timeout_items.append(timeout_10_s_widget);
-        screen_off_timeout_items.append(timeout_30_s_widget);
-        screen_off_timeout_items.append(timeout_60_s_widget);
-        screen_off_timeout_items.append(timeout_5_m_widget);
-        screen_off_timeout_items.append(timeout_15_m_widget);
-        screen_off_timeout_items.append(timeout_30_m_widget);
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["screen-timeout-error"])
+            .build();
+        screen_off_timeout_items.append(&error_label);
 
         root.append(&header);
 
@@ -240,9 +191,24 @@ impl SimpleComponent for ScreenTimeoutPage {
         footer.append(submit_button_widget);
         root.append(&footer);
 
-        let model = ScreenTimeoutPage { settings: init };
+        let model = ScreenTimeoutPage {
+            settings: init,
+            timeout: None,
+            selected: None,
+            error: None,
+            is_saving: false,
+        };
+
+        let display_backend = model.settings.display_backend.clone();
+        let load_sender = sender.clone();
+        thread::spawn(move || match display_backend.screen_timeout() {
+            Ok(timeout) => load_sender.input(Message::TimeoutLoaded(timeout)),
+            Err(e) => error!("error while reading screen timeout {}", e),
+        });
 
         let widgets = ScreenTimeoutPageWidgets {
+            timeout_buttons,
+            error_label,
             back_button,
             submit_button,
         };
@@ -255,12 +221,69 @@ impl SimpleComponent for ScreenTimeoutPage {
         match message {
             Message::MenuItemPressed(key) => {}
             Message::BackPressed => {
+                // an unsaved pick is dropped
+                self.selected = self.timeout;
+                self.error = None;
                 let _ = sender.output(Message::BackPressed);
             }
             Message::HomeIconPressed => {}
-            Message::SubmitPressed => {},
+            Message::SubmitPressed => {
+                let timeout = match self.selected {
+                    Some(timeout) => timeout,
+                    None => return,
+                };
+                if self.is_saving {
+                    return;
+                }
+                if self.timeout == Some(timeout) {
+                    let _ = sender.output(Message::BackPressed);
+                    return;
+                }
+
+                self.is_saving = true;
+                self.error = None;
+                let display_backend = self.settings.display_backend.clone();
+                thread::spawn(move || match display_backend.set_screen_timeout(timeout) {
+                    Ok(_) => sender.input(Message::TimeoutSaved(timeout)),
+                    Err(e) => {
+                        error!("error while saving screen timeout {}", e);
+                        sender.input(Message::SaveFailed(e.to_string()));
+                    }
+                });
+            }
+            Message::TimeoutPressed(index) => {
+                if let Some(timeout) = SCREEN_TIMEOUT_OPTIONS.get(index) {
+                    self.selected = Some(*timeout);
+                }
+            }
+            Message::TimeoutLoaded(timeout) => {
+                self.timeout = Some(timeout);
+                self.selected = Some(timeout);
+                let _ = sender.output(Message::TimeoutChanged(timeout));
+            }
+            Message::TimeoutChanged(_) => {}
+            Message::TimeoutSaved(timeout) => {
+                self.is_saving = false;
+                self.timeout = Some(timeout);
+                let _ = sender.output(Message::TimeoutChanged(timeout));
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::SaveFailed(reason) => {
+                self.is_saving = false;
+                self.error = Some(reason);
+            }
         }
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {}
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        for (timeout, button) in SCREEN_TIMEOUT_OPTIONS.iter().zip(widgets.timeout_buttons.iter()) {
+            button.emit(CustomListRadioButtonInputMessage::SetActive(
+                self.selected == Some(*timeout),
+            ));
+        }
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
 }
@@ -0,0 +1,508 @@
+use std::{path::PathBuf, thread};
+
+use gtk::{glib::clone, prelude::*};
+use relm4::{
+    gtk::{self, glib},
+    Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent, Controller,
+};
+
+use crate::{
+    backends::{
+        data_usage::format_bytes,
+        network::{
+            failure_reason, NetworkBackendHandle, WireguardConfig, WireguardProfile,
+            WireguardStatus,
+        },
+    },
+    settings::{LayoutSettings, Modules, WidgetConfigs},
+};
+use custom_widgets::{
+    icon_button::{
+        IconButton, IconButtonCss, InitSettings as IconButtonStetings, OutputMessage as IconButtonOutputMessage,
+    },
+    icon_input::{
+        IconInput, IconInputCss, InitSettings as IconInputSettings,
+        InputMessage as IconInputInputMessage, OutputMessage as IconInputOutputMessage,
+    },
+};
+use tracing::{error, info};
+
+//Init Settings
+pub struct Settings {
+    pub modules: Modules,
+    pub layout: LayoutSettings,
+    pub widget_configs: WidgetConfigs,
+    pub network_backend: NetworkBackendHandle,
+    pub refresh_interval: u32,
+}
+
+//Model
+pub struct VpnPage {
+    settings: Settings,
+    profiles: Vec<(WireguardProfile, WireguardStatus)>,
+    /// Profile being switched or removed, its row is locked until done
+    busy_profile: Option<String>,
+    import_path: String,
+    is_importing: bool,
+    /// Bumped after an import so the path input is cleared once
+    import_generation: u32,
+    notice: Option<String>,
+    error: Option<String>,
+}
+
+//Widgets
+pub struct VpnPageWidgets {
+    profiles_box: gtk::Box,
+    rendered_profiles: Option<(Vec<(WireguardProfile, WireguardStatus)>, Option<String>)>,
+    import_path_input: Controller<IconInput>,
+    rendered_import_generation: u32,
+    import_button: gtk::Button,
+    notice_label: gtk::Label,
+    error_label: gtk::Label,
+    back_button: Controller<IconButton>,
+}
+
+//Messages
+#[derive(Debug)]
+pub enum Message {
+    BackPressed,
+    RefreshProfiles,
+    ProfilesUpdated(Vec<(WireguardProfile, WireguardStatus)>),
+    ImportPathChange(String),
+    ImportPressed,
+    ProfileImported(String),
+    ProfileToggled(String, bool),
+    RemovePressed(String),
+    ProfileChanged,
+    RequestFailed(String),
+}
+
+impl SimpleComponent for VpnPage {
+    type Init = Settings;
+    type Input = Message;
+    type Output = Message;
+    type Root = gtk::Box;
+    type Widgets = VpnPageWidgets;
+
+    fn init_root() -> Self::Root {
+        gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["page-container"])
+            .build()
+    }
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let widget_configs = init.widget_configs.clone();
+
+        let header_title = gtk::Label::builder()
+            .label("VPN")
+            .css_classes(["header-title"])
+            .build();
+
+        let header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["header"])
+            .build();
+
+        header.append(&header_title);
+
+        let profiles_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+
+        let import_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+
+        let import_path_input = IconInput::builder()
+            .launch(IconInputSettings {
+                clear_icon: None,
+                icon: None,
+                placeholder: Option::from("/path/to/profile.conf".to_string()),
+                css: IconInputCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconInputOutputMessage::InputChange(text) => Message::ImportPathChange(text),
+            });
+
+        let import_button = gtk::Button::builder()
+            .label("Import")
+            .hexpand(true)
+            .css_classes(["vpn-action"])
+            .build();
+        import_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(Message::ImportPressed);
+        }));
+
+        import_box.append(&field_label("Import WireGuard Profile"));
+        import_box.append(import_path_input.widget());
+        import_box.append(&import_button);
+
+        let notice_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status"])
+            .build();
+
+        let error_label = gtk::Label::builder()
+            .label("")
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .visible(false)
+            .css_classes(["connect-network-status", "connect-network-error"])
+            .build();
+
+        root.append(&header);
+
+        let scrollable_content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        scrollable_content.append(&profiles_box);
+        scrollable_content.append(&import_box);
+        scrollable_content.append(&notice_label);
+        scrollable_content.append(&error_label);
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never) // Disable horizontal scrolling
+            .min_content_width(360)
+            .min_content_height(360)
+            .child(&scrollable_content)
+            .build();
+        root.append(&scrolled_window);
+
+        let footer = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .css_classes(["footer"])
+        .hexpand(true)
+        .vexpand(true)
+        .valign(gtk::Align::End)
+        .build();
+
+        let back_button = IconButton::builder()
+            .launch(IconButtonStetings {
+                icon: widget_configs.footer.back_icon.to_owned(),
+                toggle_icon: None,
+                css: IconButtonCss::default(),
+            })
+            .forward(sender.input_sender(), |msg| match msg {
+                IconButtonOutputMessage::Clicked => Message::BackPressed,
+            });
+
+        footer.append(back_button.widget());
+        root.append(&footer);
+
+        // handshakes and counters move on while the page is open
+        glib::timeout_add_seconds_local(
+            init.refresh_interval,
+            clone!(@strong sender => move || {
+                sender.input(Message::RefreshProfiles);
+                glib::ControlFlow::Continue
+            }),
+        );
+        sender.input(Message::RefreshProfiles);
+
+        let model = VpnPage {
+            settings: init,
+            profiles: vec![],
+            busy_profile: None,
+            import_path: String::new(),
+            is_importing: false,
+            import_generation: 0,
+            notice: None,
+            error: None,
+        };
+
+        let widgets = VpnPageWidgets {
+            profiles_box,
+            rendered_profiles: None,
+            import_path_input,
+            rendered_import_generation: 0,
+            import_button,
+            notice_label,
+            error_label,
+            back_button,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        info!("Update message is {:?}", message);
+        match message {
+            Message::BackPressed => {
+                let _ = sender.output(Message::BackPressed);
+            }
+            Message::RefreshProfiles => {
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    let profiles = match network_backend.wireguard_profiles() {
+                        Ok(profiles) => profiles,
+                        Err(e) => {
+                            error!("error while reading vpn profiles {}", e);
+                            sender.input(Message::RequestFailed(failure_reason(&e)));
+                            return;
+                        }
+                    };
+                    let mut status_error = None;
+                    let profiles = profiles
+                        .into_iter()
+                        .map(|profile| {
+                            let status = match profile.active {
+                                true => network_backend
+                                    .wireguard_status(&profile.name)
+                                    .unwrap_or_else(|e| {
+                                        error!("error while reading vpn status {}", e);
+                                        status_error = Some(failure_reason(&e));
+                                        WireguardStatus {
+                                            active: true,
+                                            ..Default::default()
+                                        }
+                                    }),
+                                false => WireguardStatus::default(),
+                            };
+                            (profile, status)
+                        })
+                        .collect();
+                    sender.input(Message::ProfilesUpdated(profiles));
+                    // a tunnel without its status would read as no handshake yet
+                    if let Some(reason) = status_error {
+                        sender.input(Message::RequestFailed(reason));
+                    }
+                });
+            }
+            Message::ProfilesUpdated(profiles) => {
+                self.profiles = profiles;
+            }
+            Message::ImportPathChange(text) => {
+                self.import_path = text;
+            }
+            Message::ImportPressed => {
+                let path = self.import_path.trim().to_string();
+                if path.is_empty() || self.is_importing {
+                    return;
+                }
+                self.is_importing = true;
+                self.notice = None;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    let config = match WireguardConfig::from_file(&PathBuf::from(&path)) {
+                        Ok(config) => config,
+                        Err(reason) => {
+                            sender.input(Message::RequestFailed(reason));
+                            sender.input(Message::ProfileChanged);
+                            return;
+                        }
+                    };
+                    match network_backend.import_wireguard_profile(&config) {
+                        Ok(_) => sender.input(Message::ProfileImported(config.name)),
+                        Err(e) => {
+                            error!("error while importing vpn profile {}", e);
+                            sender.input(Message::RequestFailed(failure_reason(&e)));
+                            sender.input(Message::ProfileChanged);
+                        }
+                    }
+                });
+            }
+            Message::ProfileImported(name) => {
+                self.notice = Some(format!("Imported {}", name));
+                self.import_path.clear();
+                self.import_generation += 1;
+                sender.input(Message::ProfileChanged);
+            }
+            Message::ProfileToggled(name, active) => {
+                let is_active = self
+                    .profiles
+                    .iter()
+                    .any(|(profile, _)| profile.name == name && profile.active);
+                if active == is_active || self.busy_profile.is_some() {
+                    return;
+                }
+                self.busy_profile = Some(name.clone());
+                self.notice = None;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.set_wireguard_active(&name, active) {
+                        error!("error while switching vpn {}", e);
+                        sender.input(Message::RequestFailed(failure_reason(&e)));
+                    }
+                    sender.input(Message::ProfileChanged);
+                });
+            }
+            Message::RemovePressed(name) => {
+                if self.busy_profile.is_some() {
+                    return;
+                }
+                self.busy_profile = Some(name.clone());
+                self.notice = None;
+                self.error = None;
+
+                let network_backend = self.settings.network_backend.clone();
+                thread::spawn(move || {
+                    if let Err(e) = network_backend.remove_wireguard_profile(&name) {
+                        error!("error while removing vpn profile {}", e);
+                        sender.input(Message::RequestFailed(failure_reason(&e)));
+                    }
+                    sender.input(Message::ProfileChanged);
+                });
+            }
+            Message::ProfileChanged => {
+                self.busy_profile = None;
+                self.is_importing = false;
+                sender.input(Message::RefreshProfiles);
+            }
+            Message::RequestFailed(reason) => {
+                self.error = Some(reason);
+            }
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
+        let profiles = Some((self.profiles.clone(), self.busy_profile.clone()));
+        if widgets.rendered_profiles != profiles {
+            while let Some(child) = widgets.profiles_box.first_child() {
+                widgets.profiles_box.remove(&child);
+            }
+            for (profile, status) in self.profiles.iter() {
+                let is_busy = self.busy_profile.as_ref() == Some(&profile.name);
+                widgets
+                    .profiles_box
+                    .append(&profile_box(profile, status, is_busy, &sender));
+            }
+            if self.profiles.is_empty() {
+                let empty_box = gtk::Box::builder()
+                    .orientation(gtk::Orientation::Vertical)
+                    .css_classes(["settings-item-details-box"])
+                    .build();
+                let (row, _) = details_row("No VPN profiles");
+                empty_box.append(&row);
+                widgets.profiles_box.append(&empty_box);
+            }
+            widgets.rendered_profiles = profiles;
+        }
+
+        if widgets.rendered_import_generation != self.import_generation {
+            widgets
+                .import_path_input
+                .emit(IconInputInputMessage::SetText(String::new()));
+            widgets.rendered_import_generation = self.import_generation;
+        }
+        widgets.import_button.set_sensitive(!self.is_importing);
+
+        let notice = self.notice.clone().unwrap_or_default();
+        widgets.notice_label.set_label(&notice);
+        widgets.notice_label.set_visible(self.notice.is_some());
+
+        let error = self.error.clone().unwrap_or_default();
+        widgets.error_label.set_label(&error);
+        widgets.error_label.set_visible(self.error.is_some());
+    }
+}
+
+/// Switch, live state and remove button of a profile
+fn profile_box(
+    profile: &WireguardProfile,
+    status: &WireguardStatus,
+    is_busy: bool,
+    sender: &ComponentSender<VpnPage>,
+) -> gtk::Box {
+    let profile_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .css_classes(["settings-item-details-box"])
+        .build();
+
+    let switch_row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let name_label = gtk::Label::builder()
+        .label(&profile.name)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["custom-switch-text"])
+        .build();
+
+    // set before the handler is connected so building the row sends nothing
+    let profile_switch = gtk::Switch::new();
+    profile_switch.add_css_class("custom-switch");
+    profile_switch.set_active(profile.active);
+    profile_switch.set_sensitive(!is_busy);
+    let name = profile.name.clone();
+    profile_switch.connect_state_set(clone!(@strong sender => move |_, state| {
+        sender.input(Message::ProfileToggled(name.clone(), state));
+        glib::Propagation::Proceed
+    }));
+
+    switch_row.append(&name_label);
+    switch_row.append(&profile_switch);
+    profile_box.append(&switch_row);
+
+    let (status_row, status_label) = details_row("Status");
+    status_label.set_label(&status.to_string());
+    profile_box.append(&status_row);
+
+    if profile.active {
+        let (received_row, received_label) = details_row("Received");
+        received_label.set_label(&format_bytes(status.rx_bytes));
+        let (sent_row, sent_label) = details_row("Sent");
+        sent_label.set_label(&format_bytes(status.tx_bytes));
+        profile_box.append(&received_row);
+        profile_box.append(&sent_row);
+    }
+
+    let remove_button = gtk::Button::builder()
+        .label("Remove")
+        .sensitive(!is_busy)
+        .css_classes(["vpn-action"])
+        .build();
+    let name = profile.name.clone();
+    remove_button.connect_clicked(clone!(@strong sender => move |_| {
+        sender.input(Message::RemovePressed(name.clone()));
+    }));
+    profile_box.append(&remove_button);
+
+    profile_box
+}
+
+fn details_row(key: &str) -> (gtk::Box, gtk::Label) {
+    let row = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .hexpand(true)
+        .css_classes(["settings-item-details-box-row"])
+        .build();
+
+    let key_label = gtk::Label::builder()
+        .label(key)
+        .hexpand(true)
+        .halign(gtk::Align::Start)
+        .css_classes(["settings-item-details-box-row-key"])
+        .build();
+    let value_label = gtk::Label::builder()
+        .label("")
+        .justify(gtk::Justification::Right)
+        .css_classes(["settings-item-details-box-row-value"])
+        .build();
+
+    row.append(&key_label);
+    row.append(&value_label);
+    (row, value_label)
+}
+
+fn field_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .css_classes(["add-network-field-label"])
+        .build()
+}
@@ -19,6 +19,8 @@ pub struct LockScreenSettings {
     pub modules: Modules,
     pub widget_configs: WidgetConfigs,
     pub css: CssConfigs,
+    #[serde(default)]
+    pub backends: BackendSettings,
 }
 
 impl Default for LockScreenSettings {
@@ -31,6 +33,7 @@ impl Default for LockScreenSettings {
             modules: Modules::default(),
             css: CssConfigs::default(),
             widget_configs: WidgetConfigs::default(),
+            backends: BackendSettings::default(),
         }
     }
 }
@@ -445,6 +448,9 @@ impl Default for WidgetConfigs {
 pub struct NetworkItemWidgetConfigs {
     pub connected_icon: Option<String>,
     pub private_icon: Option<String>,
+    pub wifi_25_icon: Option<String>,
+    pub wifi_50_icon: Option<String>,
+    pub wifi_75_icon: Option<String>,
     pub wifi_100_icon: Option<String>,
     pub info_icon: Option<String>,
 }
@@ -454,11 +460,28 @@ impl Default for NetworkItemWidgetConfigs {
         Self {
             connected_icon: None,
             private_icon: None,
+            wifi_25_icon: None,
+            wifi_50_icon: None,
+            wifi_75_icon: None,
             wifi_100_icon: None,
             info_icon: None,
         }
     }
 }
+
+impl NetworkItemWidgetConfigs {
+    /// Returns the icon for a signal strength in percent,
+    /// falls back to `wifi_100_icon` when a level is not configured
+    pub fn strength_icon(&self, strength: i32) -> Option<String> {
+        let icon = match strength {
+            i32::MIN..=25 => self.wifi_25_icon.clone(),
+            26..=50 => self.wifi_50_icon.clone(),
+            51..=75 => self.wifi_75_icon.clone(),
+            _ => None,
+        };
+        icon.or(self.wifi_100_icon.clone())
+    }
+}
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct RadioItemWidgetConfigs {
     pub active_icon: Option<String>,
@@ -502,6 +525,364 @@ impl Default for MenuItemWidgetConfigs {
     }
 }
 
+/// # Backend Settings
+///
+/// Part of the settings.yml to control which system services
+/// the pages talk to and how to reach them
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct BackendSettings {
+    #[serde(default)]
+    pub network: NetworkBackendSettings,
+    #[serde(default)]
+    pub dns: DnsBackendSettings,
+    #[serde(default)]
+    pub rfkill: RfkillBackendSettings,
+    #[serde(default)]
+    pub data_usage: DataUsageBackendSettings,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsBackendSettings,
+    #[serde(default)]
+    pub bluetooth: BluetoothBackendSettings,
+    #[serde(default)]
+    pub display: DisplayBackendSettings,
+}
+
+/// D-Bus bus a backend connects to, `session` is useful to run
+/// against a fake service during development
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DBusBusType {
+    #[default]
+    System,
+    Session,
+}
+
+/// Service that manages the network connections on the device
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkServiceType {
+    #[default]
+    NetworkManager,
+    WpaSupplicant,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct NetworkBackendSettings {
+    #[serde(default)]
+    pub service: NetworkServiceType,
+    pub bus: DBusBusType,
+    pub refresh_interval: u32, // Seconds between network state refreshes
+    #[serde(default)]
+    pub wpa_supplicant: WpaSupplicantSettings,
+    #[serde(default)]
+    pub hostapd: HostapdSettings,
+    #[serde(default = "default_sysfs_root")]
+    pub sysfs_root: String, // Root of the sysfs tree link state is read from
+    #[serde(default = "default_proxy_env_file")]
+    pub proxy_env_file: String, // Environment file the device proxy is written to
+    #[serde(default = "default_wireguard_dir")]
+    pub wireguard_dir: String, // Directory wg-quick profiles are kept in without NetworkManager
+}
+
+impl Default for NetworkBackendSettings {
+    fn default() -> Self {
+        Self {
+            service: NetworkServiceType::NetworkManager,
+            bus: DBusBusType::System,
+            refresh_interval: 5,
+            wpa_supplicant: WpaSupplicantSettings::default(),
+            hostapd: HostapdSettings::default(),
+            sysfs_root: default_sysfs_root(),
+            proxy_env_file: default_proxy_env_file(),
+            wireguard_dir: default_wireguard_dir(),
+        }
+    }
+}
+
+fn default_sysfs_root() -> String {
+    String::from("/sys")
+}
+
+fn default_proxy_env_file() -> String {
+    custom_utils::proxy::default_env_file()
+}
+
+fn default_wireguard_dir() -> String {
+    String::from("/etc/wireguard")
+}
+
+/// # wpa_supplicant Settings
+///
+/// Control interface of the wpa_supplicant instance that manages
+/// the Wi-Fi interface, the socket is named after the interface
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct WpaSupplicantSettings {
+    pub ctrl_interface: String, // Path of the interface control socket
+    pub client_dir: String,     // Directory the reply socket is created in
+}
+
+impl Default for WpaSupplicantSettings {
+    fn default() -> Self {
+        Self {
+            ctrl_interface: String::from("/var/run/wpa_supplicant/wlan0"),
+            client_dir: String::from("/tmp"),
+        }
+    }
+}
+
+/// # hostapd Settings
+///
+/// Control interface of the hostapd instance that runs the hotspot
+/// next to wpa_supplicant, NetworkManager starts its own
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct HostapdSettings {
+    pub ctrl_interface: String, // Path of the interface control socket
+}
+
+impl Default for HostapdSettings {
+    fn default() -> Self {
+        Self {
+            ctrl_interface: String::from("/var/run/hostapd/wlan0"),
+        }
+    }
+}
+
+/// Service that resolves names on the device
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsServiceType {
+    #[default]
+    Resolved,
+    ResolvConf,
+}
+
+/// # DNS Backend Settings
+///
+/// `resolved` falls back to writing `resolv_conf_path` when
+/// systemd-resolved is not running, `interface` defaults to the
+/// interface of the default route. Manual servers set through
+/// resolved are kept in the `resolved_drop_in_path` drop-in, the
+/// `--restore-dns` service applies them again at session start
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct DnsBackendSettings {
+    #[serde(default)]
+    pub service: DnsServiceType,
+    pub bus: DBusBusType,
+    pub interface: Option<String>,
+    pub resolv_conf_path: String,
+    #[serde(default = "default_resolved_drop_in_path")]
+    pub resolved_drop_in_path: String,
+}
+
+impl Default for DnsBackendSettings {
+    fn default() -> Self {
+        Self {
+            service: DnsServiceType::Resolved,
+            bus: DBusBusType::System,
+            interface: None,
+            resolv_conf_path: String::from("/etc/resolv.conf"),
+            resolved_drop_in_path: default_resolved_drop_in_path(),
+        }
+    }
+}
+
+fn default_resolved_drop_in_path() -> String {
+    String::from("/etc/systemd/resolved.conf.d/90-mecha-settings.conf")
+}
+
+/// # rfkill Backend Settings
+///
+/// `device_path` can point at a file of recorded events to run
+/// without radios, `saved_radios_path` keeps the radios airplane
+/// mode turns back on
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct RfkillBackendSettings {
+    pub device_path: String,
+    pub saved_radios_path: String,
+}
+
+impl Default for RfkillBackendSettings {
+    fn default() -> Self {
+        Self {
+            device_path: String::from("/dev/rfkill"),
+            saved_radios_path: String::from("/var/lib/mecha/settings/airplane-mode-radios"),
+        }
+    }
+}
+
+/// # Data Usage Backend Settings
+///
+/// Totals are sampled by the app while it is open and by the
+/// `--collect-data-usage` service otherwise, both share `store_path`
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct DataUsageBackendSettings {
+    pub procfs_root: String, // Root of the procfs tree counters are read from
+    pub store_path: String,  // File the daily and monthly totals are kept in
+    pub sample_interval: u32, // Seconds between samples of the collector
+}
+
+impl Default for DataUsageBackendSettings {
+    fn default() -> Self {
+        Self {
+            procfs_root: String::from("/proc"),
+            store_path: String::from("/var/lib/mecha/settings/data-usage.yml"),
+            sample_interval: 60,
+        }
+    }
+}
+
+/// # Diagnostics Backend Settings
+///
+/// Targets of the network checks, point them at local servers to
+/// test the page; the gateway defaults to the one of the default route
+/// read from `procfs_root`
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct DiagnosticsBackendSettings {
+    pub gateway: Option<String>,
+    pub dns_hostname: String,
+    pub https_url: String,
+    pub timeout: u32, // Seconds each check may take
+    pub report_path: String,
+    #[serde(default = "default_procfs_root")]
+    pub procfs_root: String, // Root of the procfs tree the default route is read from
+}
+
+impl Default for DiagnosticsBackendSettings {
+    fn default() -> Self {
+        Self {
+            gateway: None,
+            dns_hostname: String::from("mecha.so"),
+            https_url: String::from("https://mecha.so"),
+            timeout: 5,
+            report_path: String::from("/tmp/network-diagnostics.txt"),
+            procfs_root: default_procfs_root(),
+        }
+    }
+}
+
+fn default_procfs_root() -> String {
+    String::from("/proc")
+}
+
+/// # Bluetooth Backend Settings
+///
+/// BlueZ adapter the pages manage, run the `fake_bluez` example
+/// with `bus: session` to try the pages without a radio. Files sent
+/// to the device are accepted through obexd, which runs on the
+/// session bus of the user
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct BluetoothBackendSettings {
+    pub bus: DBusBusType,
+    pub adapter: String, // Name of the adapter, as in /org/bluez/<adapter>
+    #[serde(default = "default_obex_bus")]
+    pub obex_bus: DBusBusType,
+    #[serde(default = "default_download_dir")]
+    pub download_dir: String, // Directory accepted files are saved in
+    #[serde(default = "default_received_files_path")]
+    pub received_files_path: String, // File the history of received files is kept in
+}
+
+impl Default for BluetoothBackendSettings {
+    fn default() -> Self {
+        Self {
+            bus: DBusBusType::System,
+            adapter: String::from("hci0"),
+            obex_bus: default_obex_bus(),
+            download_dir: default_download_dir(),
+            received_files_path: default_received_files_path(),
+        }
+    }
+}
+
+fn default_obex_bus() -> DBusBusType {
+    DBusBusType::Session
+}
+
+fn default_download_dir() -> String {
+    match env::var("HOME") {
+        Ok(home) => format!("{}/Downloads", home),
+        Err(_) => String::from("/tmp/bluetooth"),
+    }
+}
+
+fn default_received_files_path() -> String {
+    String::from("/var/lib/mecha/settings/bluetooth-received.yml")
+}
+
+/// How the display backend sets the backlight, `logind` lets the
+/// user of the session set it without write access to sysfs
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BrightnessWriter {
+    #[default]
+    Logind,
+    Sysfs,
+}
+
+/// Level of the brightness scale, from 0 to 100, for the light
+/// falling on the sensor in lux
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, PartialEq)]
+pub struct BrightnessCurvePoint {
+    pub lux: f64,
+    pub brightness: f64,
+}
+
+/// # Display Backend Settings
+///
+/// Backlight of the display, from `/sys/class/backlight` under
+/// `sysfs_root`. Point it at a fake tree with `writer: sysfs` to
+/// try the page without a backlight, `device` picks one by name.
+/// Automatic brightness reads the light sensor from
+/// `/bus/iio/devices` under the same root. The screen off timeout
+/// is enforced by the `--idle-daemon` service
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct DisplayBackendSettings {
+    pub sysfs_root: String,
+    pub device: Option<String>,
+    #[serde(default)]
+    pub writer: BrightnessWriter,
+    #[serde(default)]
+    pub light_sensor: Option<String>, // IIO device, defaults to the first that measures illuminance
+    #[serde(default = "default_brightness_curve")]
+    pub brightness_curve: Vec<BrightnessCurvePoint>,
+    #[serde(default = "default_auto_brightness_path")]
+    pub auto_brightness_path: String, // File the switch and the user's offset are kept in
+    #[serde(default = "default_screen_timeout_path")]
+    pub screen_timeout_path: String, // File the screen off timeout is kept in
+}
+
+impl Default for DisplayBackendSettings {
+    fn default() -> Self {
+        Self {
+            sysfs_root: default_sysfs_root(),
+            device: None,
+            writer: BrightnessWriter::Logind,
+            light_sensor: None,
+            brightness_curve: default_brightness_curve(),
+            auto_brightness_path: default_auto_brightness_path(),
+            screen_timeout_path: default_screen_timeout_path(),
+        }
+    }
+}
+
+fn default_brightness_curve() -> Vec<BrightnessCurvePoint> {
+    [(0.0, 5.0), (10.0, 20.0), (100.0, 40.0), (1000.0, 70.0), (10000.0, 100.0)]
+        .iter()
+        .map(|(lux, brightness)| BrightnessCurvePoint {
+            lux: *lux,
+            brightness: *brightness,
+        })
+        .collect()
+}
+
+fn default_auto_brightness_path() -> String {
+    String::from("/var/lib/mecha/settings/auto-brightness.yml")
+}
+
+fn default_screen_timeout_path() -> String {
+    String::from("/var/lib/mecha/settings/screen-timeout.yml")
+}
+
 /// # Reads Settings path from arg
 ///
 /// Reads the `-s` or `--settings` argument for the path
@@ -23,6 +23,8 @@ pub enum InputMessage {
 #[derive(PartialEq, Eq, Hash, Default, Debug, Clone, Serialize, Deserialize)]
 pub struct CustomBluetoothItemSettings {
     pub name: String,
+    /// Shown after the name, like the signal of the device
+    pub detail: Option<String>,
     pub is_connected: bool,
     pub connected_icon: Option<String>,
     pub info_i_icon: Option<String>,
@@ -78,6 +80,15 @@ impl SimpleComponent for CustomBluetoothItem {
         let bluetooth_item_button = gtk::Box::builder().vexpand(false).build();
         bluetooth_item_button.append(&label);
 
+        if let Some(detail) = init.detail.as_ref() {
+            let detail_label = gtk::Label::builder()
+                .valign(gtk::Align::Center)
+                .label(detail)
+                .css_classes(["custom-item-detail"])
+                .build();
+            bluetooth_item_button.append(&detail_label);
+        }
+
         if init.is_connected {
             match init.connected_icon.clone() {
                 Some(icon) => {
@@ -130,6 +141,7 @@ impl SimpleComponent for CustomBluetoothItem {
         let model = CustomBluetoothItem {
             settings: CustomBluetoothItemSettings {
                 name: init.name,
+                detail: init.detail,
                 is_connected: init.is_connected,
                 connected_icon: init.connected_icon,
                 info_i_icon: init.info_i_icon,
@@ -16,6 +16,7 @@ pub enum Message {
 pub enum InputMessage {
     Pressed,
     Released,
+    SetValue(String),
 }
 
 /// Configuration for the password key widget
@@ -37,6 +38,7 @@ pub(crate) struct CustomListItem {
 #[derive(Debug)]
 pub struct CustomListItemWidgets {
     container: gtk::Box,
+    value: gtk::Label,
 }
 
 // #[relm4::factory(pub(crate))]
@@ -138,6 +140,7 @@ impl SimpleComponent for CustomListItem {
 
         let widgets = CustomListItemWidgets {
             container: root.clone(),
+            value,
         };
 
         ComponentParts { widgets, model }
@@ -152,6 +155,9 @@ impl SimpleComponent for CustomListItem {
             InputMessage::Released => {
                 self.is_pressing = false;
             }
+            InputMessage::SetValue(value) => {
+                self.settings.value = value;
+            }
         }
     }
 
@@ -163,5 +169,6 @@ impl SimpleComponent for CustomListItem {
         widgets
             .container
             .set_class_active("custom-list-item-box-focus", self.is_pressing);
+        widgets.value.set_label(&self.settings.value);
     }
 }
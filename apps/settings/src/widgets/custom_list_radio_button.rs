@@ -17,6 +17,7 @@ pub enum Message {
 pub enum InputMessage {
     Pressed,
     Released,
+    SetActive(bool),
 }
 
 /// Configuration for the password key widget
@@ -39,6 +40,10 @@ pub(crate) struct CustomListRadioButton {
 #[derive(Debug)]
 pub struct CustomListRadioButtonWidgets {
     container: gtk::Box,
+    label: gtk::Label,
+    action_button: gtk::Box,
+    icon: Option<gtk::Image>,
+    rendered_active: bool,
 }
 
 // #[relm4::factory(pub(crate))]
@@ -82,22 +87,9 @@ impl SimpleComponent for CustomListRadioButton {
 
         action_button.append(&label);
 
-        match init.is_active {
-            true => match init.active_icon.clone() {
-                Some(icon) => {
-                    let image =
-                        get_image_from_path(Some(icon), &["custom-list-item-box-start-icon"]);
-                    action_button.append(&image);
-                }
-                None => (),
-            },
-            false => match init.inactive_icon.clone() {
-                Some(icon) => {
-                    let image = get_image_from_path(Some(icon), &["custom-list-item-box-end-icon"]);
-                    action_button.append(&image);
-                }
-                None => (),
-            },
+        let icon = radio_icon(&init, init.is_active);
+        if let Some(image) = &icon {
+            action_button.append(image);
         }
 
         root.append(&action_button);
@@ -149,6 +141,10 @@ impl SimpleComponent for CustomListRadioButton {
 
         let widgets = CustomListRadioButtonWidgets {
             container: root.clone(),
+            label,
+            action_button,
+            icon,
+            rendered_active: init.is_active,
         };
 
         ComponentParts { widgets, model }
@@ -160,6 +156,10 @@ impl SimpleComponent for CustomListRadioButton {
             InputMessage::Released => {
                 self.is_active = true;
             }
+            InputMessage::SetActive(is_active) => {
+                self.is_active = is_active;
+                self.settings.is_active = is_active;
+            }
             _ => {}
         }
     }
@@ -172,5 +172,32 @@ impl SimpleComponent for CustomListRadioButton {
         widgets
             .container
             .set_class_active("custom-list-item-box-focus", self.is_active);
+
+        if widgets.rendered_active != self.settings.is_active {
+            if let Some(image) = widgets.icon.take() {
+                widgets.action_button.remove(&image);
+            }
+            widgets.icon = radio_icon(&self.settings, self.settings.is_active);
+            if let Some(image) = &widgets.icon {
+                widgets.action_button.append(image);
+            }
+            widgets
+                .label
+                .set_class_active("custom-list-radio-button-label-active", self.settings.is_active);
+            widgets.rendered_active = self.settings.is_active;
+        }
+    }
+}
+
+fn radio_icon(settings: &CustomListRadioButtonSettings, is_active: bool) -> Option<gtk::Image> {
+    match is_active {
+        true => settings
+            .active_icon
+            .clone()
+            .map(|icon| get_image_from_path(Some(icon), &["custom-list-item-box-start-icon"])),
+        false => settings
+            .inactive_icon
+            .clone()
+            .map(|icon| get_image_from_path(Some(icon), &["custom-list-item-box-end-icon"])),
     }
 }